@@ -0,0 +1,241 @@
+//! Minimal admin HTTP server
+//!
+//! Serves read-only operational endpoints on `--admin-address`. It handles just
+//! enough HTTP/1.1 for `curl` and monitoring probes: one `GET` per connection,
+//! no keep-alive, no request bodies.
+//!
+//! Endpoints:
+//! - `GET /jobs/recent`: JSON array of recently finished jobs, most recent first
+//! - `GET /metrics`: worker metrics in the Prometheus text format
+
+use crate::history::JobHistory;
+use crate::metrics::METRICS;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Semaphore;
+use tracing::{debug, info};
+
+/// Longest request head (request line plus headers) accepted
+const MAX_REQUEST_HEAD_BYTES: usize = 8 * 1024;
+/// Time a client has to send its request before the connection is dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Connections handled at once; further ones wait to be accepted
+const MAX_CONNECTIONS: usize = 64;
+
+const CONTENT_TYPE_JSON: &str = "application/json";
+const CONTENT_TYPE_PROMETHEUS: &str = "text/plain; version=0.0.4";
+
+/// State shared with admin request handlers
+#[derive(Debug, Clone)]
+pub struct AdminState {
+    pub history: Arc<JobHistory>,
+}
+
+/// Accept admin connections until the listener fails
+///
+/// Each connection is handled on its own task so a slow client cannot block
+/// others, up to [`MAX_CONNECTIONS`] at once.
+pub async fn serve(listener: TcpListener, state: AdminState) {
+    if let Ok(address) = listener.local_addr() {
+        info!("Admin server listening on {address}");
+    }
+
+    let connections = Arc::new(Semaphore::new(MAX_CONNECTIONS));
+    loop {
+        // The semaphore is never closed
+        let Ok(permit) = Arc::clone(&connections).acquire_owned().await else {
+            return;
+        };
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                debug!("Admin connection from {peer}");
+                let state = state.clone();
+                tokio::spawn(async move {
+                    let _ = tokio::time::timeout(REQUEST_TIMEOUT, handle(stream, &state)).await;
+                    drop(permit);
+                });
+            }
+            Err(e) => debug!("Admin accept failed: {e}"),
+        }
+    }
+}
+
+async fn handle(stream: TcpStream, state: &AdminState) -> std::io::Result<()> {
+    let (read_half, mut write_half) = stream.into_split();
+    // One byte past the limit shows the head was too large, however it is split into lines
+    let limit = u64::try_from(MAX_REQUEST_HEAD_BYTES).unwrap_or(u64::MAX);
+    let mut reader = BufReader::new(read_half.take(limit + 1));
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+
+    // Drain headers; there is no body to read for GET
+    let mut head_bytes = request_line.len();
+    loop {
+        let mut header = String::new();
+        let n = reader.read_line(&mut header).await?;
+        head_bytes += n;
+        if n == 0 || header == "\r\n" || header == "\n" || head_bytes > MAX_REQUEST_HEAD_BYTES {
+            break;
+        }
+    }
+
+    let (status, content_type, body) = if head_bytes > MAX_REQUEST_HEAD_BYTES {
+        (
+            "431 Request Header Fields Too Large",
+            CONTENT_TYPE_JSON,
+            error_body("request too large"),
+        )
+    } else {
+        route(&request_line, state)
+    };
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    write_half.write_all(response.as_bytes()).await?;
+    write_half.shutdown().await
+}
+
+/// Dispatch a request line to its endpoint, returning the status line, content type and body
+fn route(request_line: &str, state: &AdminState) -> (&'static str, &'static str, String) {
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return (
+            "400 Bad Request",
+            CONTENT_TYPE_JSON,
+            error_body("malformed request"),
+        );
+    };
+    let path = target.split('?').next().unwrap_or(target);
+
+    match (method, path) {
+        ("GET", "/jobs/recent") => (
+            "200 OK",
+            CONTENT_TYPE_JSON,
+            serde_json::to_string(&state.history.recent()).unwrap_or_else(|_| "[]".to_string()),
+        ),
+        ("GET", "/metrics") => ("200 OK", CONTENT_TYPE_PROMETHEUS, METRICS.render()),
+        (_, "/jobs/recent" | "/metrics") => (
+            "405 Method Not Allowed",
+            CONTENT_TYPE_JSON,
+            error_body("method not allowed"),
+        ),
+        _ => ("404 Not Found", CONTENT_TYPE_JSON, error_body("not found")),
+    }
+}
+
+fn error_body(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::JobSummary;
+    use tokio::io::AsyncReadExt;
+
+    async fn start(history: Arc<JobHistory>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(serve(listener, AdminState { history }));
+        address
+    }
+
+    async fn request(address: &str, request: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap().to_string(), body.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_recent_jobs_endpoint_returns_summaries_newest_first() {
+        let history = Arc::new(JobHistory::new(10));
+        for (n, status, failed_task) in [(1, "completed", None), (2, "failed", Some(3))] {
+            history.record(JobSummary {
+                job_id: format!("job-{n}"),
+                plan_id: "plan-1".to_string(),
+                status: status.to_string(),
+                duration_ms: 10,
+                failed_task,
+            });
+        }
+        let address = start(history).await;
+
+        let (status, body) = request(
+            &address,
+            "GET /jobs/recent HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        )
+        .await;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+
+        let jobs: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(jobs[0]["job_id"], "job-2");
+        assert_eq!(jobs[0]["status"], "failed");
+        assert_eq!(jobs[0]["failed_task"], 3);
+        assert_eq!(jobs[1]["job_id"], "job-1");
+        assert!(jobs[1].get("failed_task").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_metrics_endpoint_serves_prometheus_text() {
+        let address = start(Arc::new(JobHistory::new(10))).await;
+
+        let (status, body) = request(&address, "GET /metrics HTTP/1.1\r\n\r\n").await;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(body.contains("# TYPE agw_task_total counter\n"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_routes_and_methods_rejected() {
+        let address = start(Arc::new(JobHistory::new(10))).await;
+
+        let (status, _) = request(&address, "GET /nope HTTP/1.1\r\n\r\n").await;
+        assert_eq!(status, "HTTP/1.1 404 Not Found");
+
+        let (status, _) = request(&address, "POST /jobs/recent HTTP/1.1\r\n\r\n").await;
+        assert_eq!(status, "HTTP/1.1 405 Method Not Allowed");
+    }
+
+    #[tokio::test]
+    async fn test_endless_request_line_rejected_at_limit() {
+        let address = start(Arc::new(JobHistory::new(10))).await;
+
+        // No newline ever arrives; the reply comes without waiting for the timeout
+        let line = format!("GET /{}", "a".repeat(MAX_REQUEST_HEAD_BYTES));
+        let (status, _) = tokio::time::timeout(Duration::from_secs(1), request(&address, &line))
+            .await
+            .unwrap();
+        assert_eq!(status, "HTTP/1.1 431 Request Header Fields Too Large");
+    }
+
+    #[tokio::test]
+    async fn test_connections_beyond_limit_wait() {
+        let address = start(Arc::new(JobHistory::new(10))).await;
+
+        let mut idle = Vec::new();
+        for _ in 0..MAX_CONNECTIONS {
+            idle.push(TcpStream::connect(&address).await.unwrap());
+        }
+        let waiting = tokio::spawn({
+            let address = address.clone();
+            async move { request(&address, "GET /metrics HTTP/1.1\r\n\r\n").await }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!waiting.is_finished());
+
+        // Closing an idle connection frees its slot
+        drop(idle.pop());
+        let (status, _) = tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(status, "HTTP/1.1 200 OK");
+    }
+}
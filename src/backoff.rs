@@ -0,0 +1,163 @@
+//! Delays between retries
+//!
+//! Result posting, reconnecting to AGQ and startup authentication all retry
+//! with a [`Backoff`]: a [`BackoffStrategy`] (chosen with `--retry-backoff`)
+//! applied to a per-operation first delay and cap.
+
+use clap::ValueEnum;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// How the delay grows from one retry to the next
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BackoffStrategy {
+    /// The same delay before every retry
+    Fixed,
+    /// Double the delay after each retry
+    Exponential,
+    /// Exponential, with each delay drawn uniformly from its upper half
+    ExponentialWithJitter,
+    /// Grow the delay along the Fibonacci sequence (1, 1, 2, 3, 5, ...)
+    Fibonacci,
+}
+
+/// A backoff strategy with its first delay and cap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    pub strategy: BackoffStrategy,
+    /// Delay before the first retry
+    pub base: Duration,
+    /// Longest delay between retries
+    pub max: Duration,
+}
+
+impl Backoff {
+    #[must_use]
+    pub const fn new(strategy: BackoffStrategy, base: Duration, max: Duration) -> Self {
+        Self {
+            strategy,
+            base,
+            max,
+        }
+    }
+
+    /// Fixed `delay` between retries
+    #[must_use]
+    pub const fn fixed(delay: Duration) -> Self {
+        Self::new(BackoffStrategy::Fixed, delay, delay)
+    }
+
+    /// Delay before retry `attempt` (0 for the first retry)
+    #[must_use]
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = match self.strategy {
+            BackoffStrategy::Fixed => 1,
+            BackoffStrategy::Exponential | BackoffStrategy::ExponentialWithJitter => {
+                1 << attempt.min(31)
+            }
+            BackoffStrategy::Fibonacci => fibonacci(attempt),
+        };
+        let delay = self.base.saturating_mul(factor).min(self.max);
+        if self.strategy == BackoffStrategy::ExponentialWithJitter {
+            jitter(delay, Uuid::new_v4().as_u64_pair().0)
+        } else {
+            delay
+        }
+    }
+}
+
+/// `attempt`th Fibonacci number starting 1, 1, 2, saturating at `u32::MAX`
+fn fibonacci(attempt: u32) -> u32 {
+    let (mut current, mut next) = (1u32, 1u32);
+    for _ in 0..attempt.min(48) {
+        (current, next) = (next, current.saturating_add(next));
+    }
+    current
+}
+
+/// A delay in `[delay / 2, delay]` picked by `random`
+fn jitter(delay: Duration, random: u64) -> Duration {
+    let nanos = u64::try_from(delay.as_nanos()).unwrap_or(u64::MAX);
+    let half = nanos / 2;
+    let spread = nanos - half;
+    let offset = match spread.checked_add(1) {
+        Some(range) => random % range,
+        None => random,
+    };
+    Duration::from_nanos(half + offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sequence(backoff: Backoff) -> Vec<Duration> {
+        (0..7).map(|attempt| backoff.delay(attempt)).collect()
+    }
+
+    fn millis(values: [u64; 7]) -> Vec<Duration> {
+        values.map(Duration::from_millis).to_vec()
+    }
+
+    #[test]
+    fn test_fixed_sequence() {
+        let backoff = Backoff::fixed(Duration::from_millis(200));
+        assert_eq!(sequence(backoff), millis([200; 7]));
+    }
+
+    #[test]
+    fn test_exponential_sequence() {
+        let backoff = Backoff::new(
+            BackoffStrategy::Exponential,
+            Duration::from_millis(100),
+            Duration::from_millis(3000),
+        );
+        assert_eq!(
+            sequence(backoff),
+            millis([100, 200, 400, 800, 1600, 3000, 3000])
+        );
+        assert_eq!(
+            Backoff::new(BackoffStrategy::Exponential, Duration::MAX, Duration::MAX).delay(40),
+            Duration::MAX
+        );
+    }
+
+    #[test]
+    fn test_fibonacci_sequence() {
+        let backoff = Backoff::new(
+            BackoffStrategy::Fibonacci,
+            Duration::from_millis(100),
+            Duration::from_millis(1000),
+        );
+        assert_eq!(
+            sequence(backoff),
+            millis([100, 100, 200, 300, 500, 800, 1000])
+        );
+        assert_eq!(fibonacci(u32::MAX), u32::MAX);
+    }
+
+    #[test]
+    fn test_exponential_with_jitter_sequence() {
+        let backoff = Backoff::new(
+            BackoffStrategy::ExponentialWithJitter,
+            Duration::from_millis(100),
+            Duration::from_millis(3000),
+        );
+        let upper = millis([100, 200, 400, 800, 1600, 3000, 3000]);
+        for _ in 0..20 {
+            for (delay, upper) in sequence(backoff).into_iter().zip(&upper) {
+                assert!(delay >= *upper / 2 && delay <= *upper, "{delay:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_jitter_bounds() {
+        let delay = Duration::from_nanos(1000);
+        assert_eq!(jitter(delay, 0), Duration::from_nanos(500));
+        assert_eq!(jitter(delay, 500), Duration::from_nanos(1000));
+        assert_eq!(jitter(delay, 501), Duration::from_nanos(500));
+        assert_eq!(jitter(Duration::ZERO, 7), Duration::ZERO);
+        assert!(jitter(Duration::MAX, u64::MAX) <= Duration::MAX);
+    }
+}
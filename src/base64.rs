@@ -0,0 +1,140 @@
+//! Base64 transport of binary task output
+//!
+//! Task output is posted as text, so a task declaring `encode_output_base64`
+//! has its raw stdout bytes base64-encoded (standard alphabet, padded) and
+//! prefixed with [`OUTPUT_MARKER`] instead of being decoded as UTF-8. The
+//! marker lets consumers of a combined result tell which tasks' contributions
+//! to decode; other tasks in the same plan stay plain text.
+
+/// Prefix of a task's stdout when it carries base64-encoded bytes
+pub const OUTPUT_MARKER: &str = "agw:base64:";
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Wire form of binary task output: marker, base64 and a trailing newline
+#[must_use]
+pub fn encode_output(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(OUTPUT_MARKER.len() + bytes.len().div_ceil(3) * 4 + 1);
+    output.push_str(OUTPUT_MARKER);
+    encode_into(bytes, &mut output);
+    output.push('\n');
+    output
+}
+
+/// Raw bytes carried by [`encode_output`]'s wire form (`None` if not in that form)
+#[must_use]
+pub fn decode_output(output: &str) -> Option<Vec<u8>> {
+    let encoded = output.strip_prefix(OUTPUT_MARKER)?;
+    decode(encoded.strip_suffix('\n').unwrap_or(encoded))
+}
+
+/// Base64-encode `bytes`
+#[allow(dead_code)] // Used in tests
+#[must_use]
+pub fn encode(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    encode_into(bytes, &mut output);
+    output
+}
+
+fn encode_into(bytes: &[u8], output: &mut String) {
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let sextets = [
+            b[0] >> 2,
+            (b[0] & 0x03) << 4 | b[1] >> 4,
+            (b[1] & 0x0f) << 2 | b[2] >> 6,
+            b[2] & 0x3f,
+        ];
+        for (i, sextet) in sextets.into_iter().enumerate() {
+            if i <= chunk.len() {
+                output.push(char::from(ALPHABET[usize::from(sextet)]));
+            } else {
+                output.push('=');
+            }
+        }
+    }
+}
+
+/// Decode padded base64 (`None` if it is malformed)
+#[must_use]
+pub fn decode(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.as_bytes();
+    if encoded.len() % 4 != 0 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(encoded.len() / 4 * 3);
+    let quads = encoded.chunks(4);
+    let last = quads.len().saturating_sub(1);
+    for (index, quad) in quads.enumerate() {
+        let padding = quad.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 || (padding > 0 && index != last) {
+            return None;
+        }
+        let mut value = 0u32;
+        for &c in &quad[..4 - padding] {
+            value = value << 6 | u32::from(sextet(c)?);
+        }
+        value <<= 6 * padding;
+        let decoded = value.to_be_bytes();
+        bytes.extend_from_slice(&decoded[1..4 - padding]);
+    }
+    Some(bytes)
+}
+
+fn sextet(c: u8) -> Option<u8> {
+    match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_rfc4648_vectors() {
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (plain, encoded) in vectors {
+            assert_eq!(encode(plain.as_bytes()), encoded);
+            assert_eq!(decode(encoded).unwrap(), plain.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_round_trips_arbitrary_bytes() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode(&encode(&bytes)).unwrap(), bytes);
+        assert_eq!(decode_output(&encode_output(&bytes)).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed() {
+        for invalid in ["Zg=", "Z===", "Zg==Zg==", "Zm9v!A==", "Zm9 v"] {
+            assert!(decode(invalid).is_none(), "{invalid}");
+        }
+        assert!(decode_output("Zm9v\n").is_none());
+    }
+
+    #[test]
+    fn test_output_wire_form() {
+        assert_eq!(encode_output(b"\x00\xff"), "agw:base64:AP8=\n");
+    }
+}
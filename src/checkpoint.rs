@@ -0,0 +1,269 @@
+//! Per-task checkpoints for resuming partially completed plans
+//!
+//! With `--checkpoint-tasks`, each finished task's result is stored under the
+//! job's `task:<n>` result key (`job:<id>:task:<n>` with the default layout).
+//! When a job is executed again, e.g. after a worker crashed mid-plan, leading
+//! tasks with a successful checkpoint are restored instead of re-run.
+//!
+//! Non-idempotent tasks are also marked under `task:<n>:started` before they
+//! run, so a resumed job can tell they may already have had their effect.
+//!
+//! A checkpoint holds the task's full stdout and stderr as the task wrote them:
+//! resumed tasks read them as input, so they cannot be redacted. Checkpointing
+//! is therefore not available together with `--redact-pattern`.
+
+use crate::executor::TaskResult;
+use crate::resp::RespClient;
+use tracing::{debug, warn};
+
+/// Loads and saves task checkpoints for one job
+///
+/// Checkpointing is best-effort: a failed read is treated as a missing
+/// checkpoint and a failed write is logged, never failing the job.
+pub struct CheckpointStore {
+    client: RespClient,
+    job_id: String,
+}
+
+impl CheckpointStore {
+    /// Create a store for `job_id`'s checkpoints
+    #[must_use]
+    pub fn new(client: RespClient, job_id: &str) -> Self {
+        Self {
+            client,
+            job_id: job_id.to_string(),
+        }
+    }
+
+    fn key(&self, task_number: u32) -> String {
+        self.client
+            .result_key(&self.job_id, &format!("task:{task_number}"))
+    }
+
+    /// Load the checkpoint of a task, if one was stored and it succeeded
+    pub async fn load_successful(&mut self, task_number: u32) -> Option<TaskResult> {
+        let key = self.key(task_number);
+        let json = match self.client.get(&key).await {
+            Ok(json) => json?,
+            Err(e) => {
+                warn!("Failed to read checkpoint {key}: {e}");
+                return None;
+            }
+        };
+        match serde_json::from_str::<TaskResult>(&json) {
+            Ok(result) if result.success && result.task_number == task_number => Some(result),
+            Ok(_) => None,
+            Err(e) => {
+                warn!("Ignoring malformed checkpoint {key}: {e}");
+                None
+            }
+        }
+    }
+
+    fn started_key(&self, task_number: u32) -> String {
+        self.client
+            .result_key(&self.job_id, &format!("task:{task_number}:started"))
+    }
+
+    /// Record that a task is about to run
+    pub async fn mark_started(&mut self, task_number: u32) {
+        let key = self.started_key(task_number);
+        match self.client.set(&key, "1").await {
+            Ok(()) => debug!("Stored start marker {key}"),
+            Err(e) => warn!("Failed to store start marker {key}: {e}"),
+        }
+    }
+
+    /// Whether a task may already have run: it was marked started or has a checkpoint
+    ///
+    /// Unlike checkpoint loads, a failed read counts as "may have run", since
+    /// this guards against repeating tasks that must not run twice.
+    pub async fn may_have_run(&mut self, task_number: u32) -> bool {
+        for key in [self.started_key(task_number), self.key(task_number)] {
+            match self.client.get(&key).await {
+                Ok(Some(_)) => return true,
+                Ok(None) => {}
+                Err(e) => {
+                    warn!("Failed to read {key}, assuming task {task_number} may have run: {e}");
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Store a finished task's result
+    pub async fn save(&mut self, result: &TaskResult) {
+        let key = self.key(result.task_number);
+        let json = match serde_json::to_string(result) {
+            Ok(json) => json,
+            Err(e) => {
+                warn!("Failed to serialize checkpoint {key}: {e}");
+                return;
+            }
+        };
+        match self.client.set(&key, &json).await {
+            Ok(()) => debug!("Stored checkpoint {key}"),
+            Err(e) => warn!("Failed to store checkpoint {key}: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::{execute_plan_checkpointed, ExecutionOptions};
+    use crate::mock_agq::MockAgq;
+    use crate::plan::{Plan, Task};
+
+    /// Task 1 fails if it is actually run; task 2 echoes task 1's stdout
+    fn plan() -> Plan {
+        Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![
+                Task {
+                    task_number: 1,
+                    command: "false".to_string(),
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 2,
+                    command: "cat".to_string(),
+                    input_from_task: Some(1),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    async fn run(mock: &MockAgq) -> crate::executor::PlanResult {
+        let client = RespClient::connect(&mock.address).await.unwrap();
+        let mut store = CheckpointStore::new(client, "job-1");
+        execute_plan_checkpointed(
+            "job-1",
+            &plan(),
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+            Some(&mut store),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_resumes_after_successful_checkpoint() {
+        let mock = MockAgq::start(None).await;
+        let checkpoint = TaskResult::new(1, "from checkpoint\n".to_string(), String::new(), 0);
+        mock.set(
+            "job:job-1:task:1",
+            &serde_json::to_string(&checkpoint).unwrap(),
+        );
+
+        let result = run(&mock).await;
+
+        assert!(result.success);
+        assert_eq!(result.task_results[0], checkpoint);
+        assert_eq!(result.task_results[1].stdout, "from checkpoint\n");
+
+        let stored: TaskResult =
+            serde_json::from_str(&mock.get("job:job-1:task:2").unwrap()).unwrap();
+        assert_eq!(stored, result.task_results[1]);
+    }
+
+    /// Task 1 echoes; task 2 is non-idempotent and appends to `marker` when run
+    fn side_effect_plan(marker: &std::path::Path) -> Plan {
+        Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![
+                Task {
+                    task_number: 1,
+                    command: "echo".to_string(),
+                    args: vec!["ready".to_string()],
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 2,
+                    command: "tee".to_string(),
+                    args: vec!["-a".to_string(), marker.to_string_lossy().into_owned()],
+                    input_from_task: Some(1),
+                    idempotent: false,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    async fn run_side_effect(
+        mock: &MockAgq,
+        marker: &std::path::Path,
+        options: &ExecutionOptions,
+    ) -> crate::executor::PlanResult {
+        let client = RespClient::connect(&mock.address).await.unwrap();
+        let mut store = CheckpointStore::new(client, "job-1");
+        execute_plan_checkpointed(
+            "job-1",
+            &side_effect_plan(marker),
+            &serde_json::Value::Null,
+            options,
+            Some(&mut store),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_non_idempotent_task_not_rerun_on_resume() {
+        let mock = MockAgq::start(None).await;
+        let marker = std::env::temp_dir().join(format!("agw-idem-{}", uuid::Uuid::new_v4()));
+
+        // First run: task 2 is marked started, runs once
+        let result = run_side_effect(&mock, &marker, &ExecutionOptions::default()).await;
+        assert!(result.success);
+        assert!(mock.get("job:job-1:task:2:started").is_some());
+        assert_eq!(std::fs::read_to_string(&marker).unwrap(), "ready\n");
+
+        // Crash after task 2 started but before its checkpoint was stored
+        mock.state
+            .lock()
+            .unwrap()
+            .strings
+            .remove("job:job-1:task:2");
+        let result = run_side_effect(&mock, &marker, &ExecutionOptions::default()).await;
+        assert!(!result.success);
+        assert!(result.task_results[1].skipped);
+        assert!(result.task_results[1].stderr.contains("not idempotent"));
+        assert_eq!(std::fs::read_to_string(&marker).unwrap(), "ready\n");
+
+        // --force-reexec runs it again
+        let options = ExecutionOptions {
+            force_reexec: true,
+            ..Default::default()
+        };
+        let result = run_side_effect(&mock, &marker, &options).await;
+        assert!(result.success);
+        assert_eq!(std::fs::read_to_string(&marker).unwrap(), "ready\nready\n");
+        let _ = std::fs::remove_file(&marker);
+    }
+
+    #[tokio::test]
+    async fn test_failed_checkpoint_is_rerun() {
+        let mock = MockAgq::start(None).await;
+        let checkpoint = TaskResult::new(1, "stale\n".to_string(), String::new(), 1);
+        mock.set(
+            "job:job-1:task:1",
+            &serde_json::to_string(&checkpoint).unwrap(),
+        );
+
+        let result = run(&mock).await;
+
+        // Task 1 ran again (and failed), halting the plan
+        assert!(!result.success);
+        assert_eq!(result.task_results.len(), 1);
+        assert_eq!(result.task_results[0].stdout, "");
+        let stored: TaskResult =
+            serde_json::from_str(&mock.get("job:job-1:task:1").unwrap()).unwrap();
+        assert_eq!(stored, result.task_results[0]);
+    }
+}
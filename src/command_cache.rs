@@ -0,0 +1,235 @@
+//! Cached resolution of command names to executable paths
+//!
+//! `Command::new("sort")` searches `PATH` on every spawn, stat-ing each
+//! directory in turn. Hot plans run the same handful of tools over and over, so
+//! the resolved absolute path is cached per command name for a configurable TTL.
+
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Process-wide cache used by the executor
+pub static COMMAND_CACHE: Lazy<CommandCache> = Lazy::new(CommandCache::default);
+
+/// Cache of command name -> resolved executable path
+#[derive(Debug, Default)]
+pub struct CommandCache {
+    inner: Mutex<CacheState>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<String, CacheEntry>,
+    /// Number of PATH searches performed (cache misses)
+    resolutions: u64,
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    path: PathBuf,
+    resolved_at: Instant,
+    /// Search path the entry was resolved against
+    path_var: Option<OsString>,
+}
+
+impl CommandCache {
+    /// Resolve `command` to an executable path, reusing a cached result younger than `ttl`
+    ///
+    /// Commands containing a path separator are returned unchanged, as are
+    /// commands not found on `PATH` (so spawning them reports the usual error).
+    /// A zero `ttl` disables caching.
+    pub fn resolve(&self, command: &str, ttl: Duration) -> PathBuf {
+        self.resolve_at(command, ttl, Instant::now(), std::env::var_os("PATH"))
+    }
+
+    /// Like [`CommandCache::resolve`], but search only `dir` instead of the inherited `PATH`
+    pub fn resolve_in(&self, command: &str, ttl: Duration, dir: &Path) -> PathBuf {
+        self.resolve_at(
+            command,
+            ttl,
+            Instant::now(),
+            Some(dir.as_os_str().to_owned()),
+        )
+    }
+
+    /// Drop the cached entry for `command` (e.g. after the cached path failed to spawn)
+    pub fn invalidate(&self, command: &str) {
+        self.lock().entries.remove(command);
+    }
+
+    /// Number of PATH searches performed so far
+    #[allow(dead_code)] // Used in tests
+    pub fn resolutions(&self) -> u64 {
+        self.lock().resolutions
+    }
+
+    fn resolve_at(
+        &self,
+        command: &str,
+        ttl: Duration,
+        now: Instant,
+        path_var: Option<OsString>,
+    ) -> PathBuf {
+        if ttl.is_zero() || command.contains(std::path::MAIN_SEPARATOR) || command.contains('/') {
+            return PathBuf::from(command);
+        }
+
+        if let Some(entry) = self.lock().entries.get(command) {
+            if entry.path_var == path_var && now.saturating_duration_since(entry.resolved_at) < ttl
+            {
+                return entry.path.clone();
+            }
+        }
+
+        // Search outside the lock; a concurrent miss for the same command just
+        // resolves it twice
+        let resolved = search_path(command, path_var.as_deref());
+
+        let mut state = self.lock();
+        state.resolutions += 1;
+        match resolved {
+            Some(path) => {
+                state.entries.insert(
+                    command.to_string(),
+                    CacheEntry {
+                        path: path.clone(),
+                        resolved_at: now,
+                        path_var,
+                    },
+                );
+                path
+            }
+            None => {
+                state.entries.remove(command);
+                PathBuf::from(command)
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        // The state stays consistent even if a holder panicked
+        self.inner
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// Find the first executable named `command` in the directories of `path_var`
+fn search_path(command: &str, path_var: Option<&OsStr>) -> Option<PathBuf> {
+    std::env::split_paths(path_var?)
+        .map(|dir| dir.join(command))
+        .find(|candidate| is_executable(candidate))
+}
+
+fn is_executable(path: &Path) -> bool {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return false;
+    };
+    if !metadata.is_file() {
+        return false;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o111 != 0
+    }
+    #[cfg(not(unix))]
+    {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path_var() -> Option<OsString> {
+        std::env::var_os("PATH")
+    }
+
+    #[test]
+    fn test_repeated_resolution_uses_cache() {
+        let cache = CommandCache::default();
+        let ttl = Duration::from_secs(60);
+        let now = Instant::now();
+
+        let first = cache.resolve_at("sh", ttl, now, path_var());
+        assert!(first.is_absolute(), "{first:?}");
+        assert_eq!(cache.resolutions(), 1);
+
+        let second = cache.resolve_at("sh", ttl, now + Duration::from_secs(1), path_var());
+        assert_eq!(first, second);
+        assert_eq!(cache.resolutions(), 1);
+    }
+
+    #[test]
+    fn test_cache_entry_expires_after_ttl() {
+        let cache = CommandCache::default();
+        let ttl = Duration::from_secs(60);
+        let now = Instant::now();
+
+        cache.resolve_at("sh", ttl, now, path_var());
+        cache.resolve_at("sh", ttl, now + Duration::from_secs(59), path_var());
+        assert_eq!(cache.resolutions(), 1);
+
+        cache.resolve_at("sh", ttl, now + Duration::from_secs(60), path_var());
+        assert_eq!(cache.resolutions(), 2);
+    }
+
+    #[test]
+    fn test_invalidate_forces_new_search() {
+        let cache = CommandCache::default();
+        let ttl = Duration::from_secs(60);
+        let now = Instant::now();
+
+        cache.resolve_at("sh", ttl, now, path_var());
+        cache.invalidate("sh");
+        cache.resolve_at("sh", ttl, now, path_var());
+        assert_eq!(cache.resolutions(), 2);
+    }
+
+    #[test]
+    fn test_entry_not_reused_for_a_different_search_path() {
+        let cache = CommandCache::default();
+        let ttl = Duration::from_secs(60);
+        let now = Instant::now();
+
+        let inherited = cache.resolve_at("sh", ttl, now, path_var());
+        assert!(inherited.is_absolute(), "{inherited:?}");
+
+        // sh is not in an empty directory, so the cached path must not leak through
+        let empty = std::env::temp_dir().join(format!("agw-empty-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&empty).unwrap();
+        let pinned = cache.resolve_at("sh", ttl, now, Some(empty.clone().into_os_string()));
+        std::fs::remove_dir(&empty).unwrap();
+
+        assert_eq!(pinned, PathBuf::from("sh"));
+        assert_eq!(cache.resolutions(), 2);
+    }
+
+    #[test]
+    fn test_uncacheable_commands_pass_through() {
+        let cache = CommandCache::default();
+        let now = Instant::now();
+
+        // Caching disabled
+        assert_eq!(
+            cache.resolve_at("sh", Duration::ZERO, now, path_var()),
+            PathBuf::from("sh")
+        );
+        // Explicit paths are not searched
+        assert_eq!(
+            cache.resolve_at("/bin/sh", Duration::from_secs(60), now, path_var()),
+            PathBuf::from("/bin/sh")
+        );
+        // Unknown commands are returned as-is so spawning reports the error
+        assert_eq!(
+            cache.resolve_at("agw-no-such-tool", Duration::from_secs(60), now, path_var()),
+            PathBuf::from("agw-no-such-tool")
+        );
+        assert_eq!(cache.resolutions(), 1);
+    }
+}
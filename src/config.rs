@@ -1,10 +1,66 @@
-use clap::Parser;
+use crate::backoff::{Backoff, BackoffStrategy};
+use crate::dependency::DependencyCheck;
+use crate::executor::{
+    ExecutionOptions, ReadMode, ResultSink, UnregisteredTools, DEFAULT_KILL_GRACE,
+    DEFAULT_READ_BUFFER_SIZE,
+};
+use crate::fd_guard::MIN_FDS_PER_JOB;
+use crate::logging::LogRotation;
+use crate::manifest::ConfigSnapshot;
+use crate::plan::{
+    validate_command, JobPriority, SubstitutionLimits, MAX_TIMEOUT_SECS, MIN_TIMEOUT_SECS,
+};
+use crate::redact::Redactor;
+use crate::resp::{
+    ResultKeyTemplate, SessionKeySource, DEFAULT_RESULT_CHUNK_SIZE, MAX_RESULT_VALUE_BYTES,
+};
+use crate::scheduler::FairScheduler;
+use crate::trust::{PlanVerifier, MIN_SIGNING_KEY_LEN};
+use crate::webhook::Webhook;
+use crate::worker::QueueReliability;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use regex::Regex;
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
 use std::time::Duration;
 
+/// Upper bound on `--kill-grace-secs` (24 hours, matching the task timeout limit)
+const MAX_KILL_GRACE_SECS: u64 = 86_400;
+
+/// Upper bound on `--fair-schedule-lookahead` (each inspected job costs a GET per fetch)
+const MAX_FAIR_SCHEDULE_LOOKAHEAD: usize = 256;
+
+/// Upper bound on `--auth-retry`
+const MAX_AUTH_RETRY: u32 = 100;
+
+/// Least estimated entropy (character frequency Shannon entropy times length)
+/// of a session key under `--require-strong-key`
+const MIN_STRONG_KEY_ENTROPY_BITS: f64 = 64.0;
+
+/// Upper bound on `--reconnect-backoff-max-secs`
+const MAX_RECONNECT_BACKOFF_SECS: u64 = 3600;
+
+/// Upper bound on `--idle-ping-secs`
+const MAX_IDLE_PING_SECS: u64 = 3600;
+
+/// Delay between authentication attempts under `--auth-retry`
+/// (the first delay unless `--retry-backoff` is `fixed` or unset)
+pub const AUTH_RETRY_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Longest delay between authentication attempts under `--retry-backoff`
+const AUTH_RETRY_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// First delay between reconnect attempts; grows up to `--reconnect-backoff-max-secs`
+pub const RECONNECT_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
 /// AGW - Agentic Worker for the AGX ecosystem
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 pub struct Config {
+    /// Utility subcommand to run instead of the worker
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// AGQ server address (host:port)
     #[arg(
         short = 'a',
@@ -14,35 +70,406 @@ pub struct Config {
     )]
     pub agq_address: String,
 
+    /// AGQ runs in Redis Cluster mode: follow MOVED/ASK redirects to other nodes
+    /// and use the hash-tagged `{queue}:*` queue names
+    #[arg(long, env = "AGQ_CLUSTER")]
+    pub cluster: bool,
+
+    /// Another node of the AGQ cluster (host:port, as it appears in MOVED/ASK
+    /// replies) that redirects may be followed to (repeatable, or comma-separated
+    /// via the environment); redirects to any other node are refused
+    #[arg(
+        long = "cluster-node",
+        env = "AGQ_CLUSTER_NODES",
+        value_delimiter = ',',
+        requires = "cluster"
+    )]
+    pub cluster_nodes: Vec<String>,
+
     /// Session key for authentication
-    #[arg(short = 'k', long, env = "AGQ_SESSION_KEY")]
-    pub session_key: String,
+    #[arg(
+        short = 'k',
+        long,
+        env = "AGQ_SESSION_KEY",
+        required_unless_present = "session_key_file"
+    )]
+    pub session_key: Option<String>,
+
+    /// File holding the session key; re-read on every authentication attempt
+    #[arg(long, env = "AGQ_SESSION_KEY_FILE", conflicts_with = "session_key")]
+    pub session_key_file: Option<PathBuf>,
+
+    /// Retry a failed AUTH this many times (2s apart) before giving up.
+    /// Unreachable AGQ is always retried; a rejected key only with
+    /// --session-key-file, which is re-read in case the key was rotated
+    #[arg(long, env = "AGW_AUTH_RETRY", default_value_t = 0)]
+    pub auth_retry: u32,
+
+    /// What to do when `--tools` or `--name` (or their environment variables)
+    /// is not valid UTF-8: `reject` it, or convert it `lossy`, replacing
+    /// invalid bytes with U+FFFD
+    #[arg(long, env = "AGW_INVALID_UTF8", value_enum, default_value_t = InvalidUtf8::Reject)]
+    pub invalid_utf8: InvalidUtf8,
+
+    /// Reject weak session keys: a single repeated character, a sequential
+    /// run such as `abcdefgh`, or under 64 bits of estimated entropy
+    #[arg(long, env = "AGW_REQUIRE_STRONG_KEY")]
+    pub require_strong_key: bool,
 
     /// Worker ID (generated if not provided)
     #[arg(short = 'w', long, env = "WORKER_ID")]
     pub worker_id: Option<String>,
 
     /// Worker name for identification (default: auto-generated)
-    #[arg(short = 'n', long, env = "AGW_WORKER_NAME")]
-    pub name: Option<String>,
+    #[arg(short = 'n', long, env = "AGW_WORKER_NAME", value_parser = clap::value_parser!(OsString))]
+    pub name: Option<OsString>,
+
+    /// Build the worker name from a template instead; placeholders are
+    /// {hostname} (short host name), {pid}, {uuid} and {role}
+    #[arg(long, env = "AGW_NAME_TEMPLATE", conflicts_with = "name")]
+    pub name_template: Option<String>,
+
+    /// Role substituted for {role} in --name-template (e.g. gpu, ingest)
+    #[arg(long, env = "AGW_WORKER_ROLE")]
+    pub role: Option<String>,
+
+    /// Consecutive failed AGQ round trips (heartbeat or job fetch) to retry,
+    /// with exponential backoff, before exiting non-zero; 0 exits on the first failure
+    #[arg(long, env = "AGW_MAX_RECONNECT_ATTEMPTS", default_value_t = 5)]
+    pub max_reconnect_attempts: u32,
+
+    /// Cap in seconds on the backoff between reconnect attempts (doubles from 1s)
+    #[arg(long, env = "AGW_RECONNECT_BACKOFF_MAX_SECS", default_value_t = 30)]
+    pub reconnect_backoff_max_secs: u64,
+
+    /// How retry delays grow for result posting, reconnects and startup
+    /// authentication (default: exponential, except fixed 2s for --auth-retry)
+    #[arg(long, env = "AGW_RETRY_BACKOFF", value_enum)]
+    pub retry_backoff: Option<BackoffStrategy>,
 
     /// Heartbeat interval in seconds
     #[arg(long, env = "HEARTBEAT_INTERVAL", default_value = "30")]
     pub heartbeat_interval: u64,
 
+    /// Send a bare PING on a separate connection every N seconds, so NATs and
+    /// firewalls that reap idle connections keep the path to AGQ open (off by default)
+    #[arg(long, env = "AGW_IDLE_PING_SECS")]
+    pub idle_ping_secs: Option<u64>,
+
+    /// Soft cap on file descriptors a job may hold; task spawns are delayed
+    /// while they would exceed it (unlimited when unset)
+    #[arg(long, env = "AGW_MAX_FDS_PER_JOB")]
+    pub max_fds_per_job: Option<usize>,
+
+    /// Shut down gracefully once the worker's own resident memory exceeds this
+    /// many MiB, so an orchestrator can restart it (checked each heartbeat; Linux only)
+    #[arg(long, env = "AGW_MAX_WORKER_RSS_MB")]
+    pub max_worker_rss_mb: Option<u64>,
+
     /// Connection timeout in seconds
     #[arg(long, env = "CONNECTION_TIMEOUT", default_value = "10")]
     pub connection_timeout: u64,
 
     /// Comma-separated list of available tools (e.g., "sort,grep,agx-ocr")
     /// If not provided, tools will be auto-discovered from PATH
-    #[arg(long, env = "WORKER_TOOLS", value_delimiter = ',')]
-    pub tools: Option<Vec<String>>,
+    #[arg(long, env = "WORKER_TOOLS", value_delimiter = ',', value_parser = clap::value_parser!(OsString))]
+    pub tools: Option<Vec<OsString>>,
+
+    /// Only run jobs whose plan commands are all among `--tools`: a job needing
+    /// any other is `requeue`d for a capable worker (the default when given
+    /// without a value) or `fail`ed before anything runs
+    #[arg(
+        long,
+        env = "AGW_ENFORCE_REGISTERED_TOOLS",
+        value_enum,
+        num_args = 0..=1,
+        default_missing_value = "requeue"
+    )]
+    pub enforce_registered_tools: Option<UnregisteredTools>,
+
+    /// Run `<tool> --version` for each registered tool at startup and publish the
+    /// results to `worker:<id>:tool_versions`
+    #[arg(long, env = "COLLECT_TOOL_VERSIONS")]
+    pub collect_tool_versions: bool,
+
+    /// Map a logical tool name to the binary installed on this host, e.g.
+    /// "python=python3" (repeatable, or comma-separated via the environment).
+    /// Workers still advertise the logical name.
+    #[arg(long = "tool-alias", env = "TOOL_ALIASES", value_delimiter = ',', value_parser = parse_tool_alias)]
+    pub tool_aliases: Vec<(String, String)>,
+
+    /// Resolve task commands only from this directory instead of the inherited
+    /// PATH; it also becomes the tasks' PATH. Shell tasks need `sh` in it too.
+    #[arg(long, env = "TOOL_PATH")]
+    pub tool_path: Option<PathBuf>,
+
+    /// Regex whose matches in task stdout/stderr are replaced with `***` before
+    /// results are posted (repeatable)
+    #[arg(long = "redact-pattern", env = "REDACT_PATTERN", value_parser = parse_redact_pattern)]
+    pub redact_patterns: Vec<Regex>,
+
+    /// Regex marking a task's stderr line as a warning: matching lines are moved
+    /// to `job:<id>:warnings` instead of the job's stderr (repeatable)
+    #[arg(long = "warning-pattern", env = "WARNING_PATTERN", value_parser = parse_warning_pattern)]
+    pub warning_patterns: Vec<Regex>,
 
     /// Shutdown timeout in seconds (maximum wait for job completion during shutdown)
-    /// If not specified, waits indefinitely for job completion
+    /// If not specified, waits indefinitely for job completion. Result posts already
+    /// in flight are always awaited, even after the timeout
     #[arg(long, env = "SHUTDOWN_TIMEOUT")]
     pub shutdown_timeout: Option<u64>,
+
+    /// Layout of job result keys; `{job}` is the job ID and `{field}` is
+    /// `stdout`, `stderr`, `status` etc. (e.g. "results/{job}/{field}")
+    #[arg(long, env = "RESULT_KEY_TEMPLATE", default_value = ResultKeyTemplate::DEFAULT)]
+    pub result_key_template: String,
+
+    /// Store stdout/stderr longer than this many bytes as a list of chunks of at
+    /// most this size (`job:<id>:stdout_chunks`), instead of one oversized value
+    #[arg(long, env = "RESULT_CHUNK_SIZE", default_value_t = DEFAULT_RESULT_CHUNK_SIZE)]
+    pub result_chunk_size: usize,
+
+    /// Post job results to the AGQ at this address (host:port) instead of the one
+    /// jobs are fetched from; it is authenticated with the same session key
+    #[arg(long, env = "AGW_RESULT_ADDRESS")]
+    pub result_address: Option<String>,
+
+    /// Most AGQ commands the job connection may have awaiting a reply at once,
+    /// shared by all jobs' result posts (heartbeats are not limited)
+    #[arg(long, env = "AGW_MAX_INFLIGHT_COMMANDS")]
+    pub max_inflight_commands: Option<usize>,
+
+    /// Pause fetching new jobs while `queue:processing` holds more than this many
+    /// jobs (e.g. stuck jobs left by crashed workers with no reaper). The queue is
+    /// shared by every worker, so this caps the backlog of the whole pool, jobs
+    /// other workers are running included, not just this worker's
+    #[arg(long, env = "MAX_PROCESSING_BACKLOG")]
+    pub max_processing_backlog: Option<u64>,
+
+    /// Probe a service tasks depend on before each job fetch, pausing fetching
+    /// (but not heartbeats) while it is unhealthy: tcp://host:port passes when a
+    /// connection is accepted, http://host[:port]/path on a 2xx response
+    #[arg(long, env = "AGW_DEPENDENCY_CHECK", value_parser = DependencyCheck::parse)]
+    pub dependency_check: Option<DependencyCheck>,
+
+    /// Enable fair scheduling: prefer ready jobs whose plans had the smallest
+    /// weighted share of this worker's last N fetched jobs
+    #[arg(long, env = "FAIR_SCHEDULE_WINDOW")]
+    pub fair_schedule_window: Option<usize>,
+
+    /// Fetch from the priority queues `queue:ready:high`, `queue:ready` and
+    /// `queue:ready:low`, draining higher priorities first. Without it only
+    /// `queue:ready` is fetched from, so high and low priority jobs wait for a
+    /// worker that honors priority
+    #[arg(
+        long,
+        env = "AGW_HONOR_PRIORITY",
+        conflicts_with = "fair_schedule_window"
+    )]
+    pub honor_priority: bool,
+
+    /// Number of ready jobs inspected when fair scheduling picks the next job
+    #[arg(long, env = "FAIR_SCHEDULE_LOOKAHEAD", default_value = "16")]
+    pub fair_schedule_lookahead: usize,
+
+    /// Relative fair-scheduling share for a plan, e.g. "reports=3" (repeatable;
+    /// plans not listed have weight 1)
+    #[arg(long = "plan-weight", env = "PLAN_WEIGHTS", value_delimiter = ',', value_parser = parse_plan_weight)]
+    pub plan_weights: Vec<(String, u32)>,
+
+    /// Maximum size in bytes of job input fetched via `input_ref`
+    #[arg(long, env = "MAX_INPUT_BYTES", default_value = "10485760")]
+    pub max_input_bytes: usize,
+
+    /// Fail a job when input substitution leaves one of its tasks with more
+    /// than this many arguments
+    #[arg(long, env = "AGW_MAX_SUBSTITUTED_ARG_COUNT")]
+    pub max_substituted_arg_count: Option<usize>,
+
+    /// Fail a job when input substitution leaves one of its tasks with
+    /// arguments totalling more than this many bytes
+    #[arg(long, env = "AGW_MAX_SUBSTITUTED_TOTAL_BYTES")]
+    pub max_substituted_total_bytes: Option<usize>,
+
+    /// Fail a job when input substitution leaves one of its task arguments
+    /// empty or whitespace-only, which is more likely an input bug than intended
+    #[arg(long, env = "AGW_REJECT_EMPTY_SUBSTITUTED_ARGS")]
+    pub reject_empty_substituted_args: bool,
+
+    /// Check job input against the `{{input.*}}` variables its plan uses:
+    /// warn about input fields no task references, and fail a job with no
+    /// input whose plan needs some. The variables are inferred from task
+    /// arguments; an `input_as_file` task counts as using every field.
+    #[arg(long, env = "AGW_REQUIRE_DECLARED_INPUTS")]
+    pub require_declared_inputs: bool,
+
+    /// Maximum bytes captured per task output stream (stdout/stderr)
+    /// Output beyond the cap is discarded and the task is flagged as truncated.
+    /// Tasks may override this with their own `max_output_bytes`.
+    #[arg(long, env = "MAX_OUTPUT_BYTES")]
+    pub max_output_bytes: Option<usize>,
+
+    /// Maximum lines captured per task output stream, independent of the byte
+    /// cap: later lines are drained and discarded, and a marker noting the
+    /// cut is appended. Enforced in `lines` read mode.
+    #[arg(long, env = "AGW_MAX_OUTPUT_LINES")]
+    pub max_output_lines: Option<usize>,
+
+    /// Hold task outputs larger than this many bytes LZ4-compressed until the
+    /// plan finishes, decompressing them when a downstream task reads them
+    /// (trades CPU for memory in pipelines of big outputs)
+    #[arg(long, env = "AGW_COMPRESS_OUTPUTS_ABOVE")]
+    pub compress_outputs_above: Option<usize>,
+
+    /// How task output is read: `lines` streams line by line (normalizing line
+    /// endings), `chunks` reads large blocks and preserves the exact bytes
+    #[arg(long, env = "READ_MODE", value_enum, default_value = "lines")]
+    pub read_mode: ReadMode,
+
+    /// Buffer size in bytes for reading task output (the chunk size in `chunks` mode)
+    #[arg(long, env = "READ_BUFFER_SIZE", default_value_t = DEFAULT_READ_BUFFER_SIZE)]
+    pub read_buffer_size: usize,
+
+    /// Timeout in seconds for tasks that don't set `timeout_secs` (detached tasks
+    /// excepted); without it such tasks run unbounded
+    #[arg(long, env = "DEFAULT_TASK_TIMEOUT_SECS")]
+    pub default_task_timeout_secs: Option<u32>,
+
+    /// Fail jobs whose plan has a task without an explicit `timeout_secs`
+    /// (detached tasks excepted)
+    #[arg(
+        long,
+        env = "REQUIRE_TIMEOUTS",
+        conflicts_with = "default_task_timeout_secs"
+    )]
+    pub require_timeouts: bool,
+
+    /// Seconds a timed-out task gets to exit after SIGTERM before it is sent
+    /// SIGKILL (Unix; 0 kills immediately)
+    #[arg(long, env = "KILL_GRACE_SECS", default_value_t = DEFAULT_KILL_GRACE.as_secs())]
+    pub kill_grace_secs: u64,
+
+    /// Store each task's result as it finishes so a re-executed job (e.g. after a
+    /// worker crash) skips tasks that already succeeded. Checkpoints hold the
+    /// tasks' unredacted stdout and stderr, so this excludes `--redact-pattern`.
+    #[arg(long, env = "CHECKPOINT_TASKS", conflicts_with = "redact_patterns")]
+    pub checkpoint_tasks: bool,
+
+    /// Delivery guarantee for fetched jobs: `reliable` keeps each job in
+    /// queue:processing until its results are posted (re-run after a crash),
+    /// `at-most-once` pops it outright (lost in a crash, never run twice)
+    #[arg(
+        long,
+        env = "AGW_QUEUE_RELIABILITY",
+        value_enum,
+        default_value_t = QueueReliability::Reliable,
+        conflicts_with = "fair_schedule_window"
+    )]
+    pub queue_reliability: QueueReliability,
+
+    /// On resume, re-run tasks marked `"idempotent": false` even if they may
+    /// already have run (by default they are failed instead)
+    #[arg(long, env = "AGW_FORCE_REEXEC", requires = "checkpoint_tasks")]
+    pub force_reexec: bool,
+
+    /// Monitoring-only mode: fetch and validate jobs, but fail each one with
+    /// "execution disabled on this worker" instead of spawning any process
+    #[arg(long, env = "AGW_NO_EXEC", conflicts_with = "collect_tool_versions")]
+    pub no_exec: bool,
+
+    /// Reject plans containing fields the plan schema doesn't define (e.g. a
+    /// misspelled `timeoutsecs`) instead of silently ignoring them
+    #[arg(long, env = "STRICT_PLAN_PARSING")]
+    pub strict_plan_parsing: bool,
+
+    /// Seconds to cache each command's resolved executable path (0 disables the cache)
+    #[arg(long, env = "COMMAND_CACHE_TTL", default_value = "60")]
+    pub command_cache_ttl: u64,
+
+    /// Warn at startup when local and AGQ wall-clocks differ by more than this many seconds
+    #[arg(long, env = "CLOCK_SKEW_THRESHOLD", default_value = "5")]
+    pub clock_skew_threshold: u64,
+
+    /// DANGEROUS: permit `shell: true` tasks (run via `sh -c`) in plans signed
+    /// with `--shell-signing-key`. Unsigned plans are still rejected.
+    #[arg(long, env = "AGW_ALLOW_SHELL")]
+    pub allow_shell: bool,
+
+    /// Key used to verify `trusted_signature` on plans containing shell tasks
+    #[arg(long, env = "AGW_SHELL_SIGNING_KEY", hide_env_values = true)]
+    pub shell_signing_key: Option<String>,
+
+    /// Also write logs to this file (`<path>.<YYYY-MM-DD[-HH]>` under daily or
+    /// hourly rotation)
+    #[arg(long, env = "AGW_LOG_FILE")]
+    pub log_file: Option<PathBuf>,
+
+    /// When to rotate the log file
+    #[arg(long, env = "AGW_LOG_ROTATION", value_enum, default_value = "never")]
+    pub log_rotation: LogRotation,
+
+    /// Rotate the log file once it reaches this many bytes (with `--log-rotation size`)
+    #[arg(long, env = "AGW_LOG_MAX_BYTES", default_value = "10485760")]
+    pub log_max_bytes: u64,
+
+    /// Most log files kept under rotation, the active one included; older
+    /// ones are deleted
+    #[arg(long, env = "AGW_LOG_MAX_FILES", default_value_t = 5)]
+    pub log_max_files: usize,
+
+    /// Also print each finished plan result as a JSON line on stdout (for
+    /// `agw | jq ...`); console logs move to stderr to keep stdout clean
+    #[arg(long, env = "AGW_EMIT_RESULTS_STDOUT")]
+    pub emit_results_stdout: bool,
+
+    /// After posting a job's results, POST `{job_id, status, duration_ms}` as
+    /// JSON to this http://host[:port]/path URL (retried, never blocking jobs)
+    #[arg(long, env = "AGW_WEBHOOK_URL", value_parser = Webhook::parse)]
+    pub webhook_url: Option<Webhook>,
+
+    /// Record this worker's effective settings (validation, timeouts, tool
+    /// path and aliases) in each job's manifest; keys are never included
+    #[arg(long, env = "AGW_MANIFEST_CONFIG")]
+    pub manifest_config: bool,
+
+    /// Write logs only to `--log-file`, not to the console
+    #[arg(long, env = "AGW_LOG_FILE_ONLY", requires = "log_file")]
+    pub log_file_only: bool,
+
+    /// Serve the admin HTTP endpoints (e.g. `/jobs/recent`) on this address (host:port)
+    #[arg(long, env = "AGW_ADMIN_ADDRESS")]
+    pub admin_address: Option<String>,
+
+    /// Number of recently finished jobs kept for `/jobs/recent`
+    #[arg(long, env = "AGW_JOB_HISTORY_SIZE", default_value = "100")]
+    pub job_history_size: usize,
+}
+
+/// Utility subcommands (the worker runs when none is given)
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Create a job for a plan and push it onto queue:ready (for local testing)
+    Enqueue(EnqueueArgs),
+}
+
+/// Arguments for `agw enqueue`
+#[derive(Args, Debug, Clone)]
+pub struct EnqueueArgs {
+    /// Plan to run (must already be stored in AGQ)
+    #[arg(long)]
+    pub plan_id: String,
+
+    /// Job input as a JSON object
+    #[arg(long, default_value = "{}")]
+    pub input: String,
+
+    /// Job ID (generated if not provided)
+    #[arg(long)]
+    pub job_id: Option<String>,
+
+    /// Job priority: high, normal or low
+    #[arg(long, default_value = "normal")]
+    pub priority: JobPriority,
 }
 
 impl Config {
@@ -56,18 +483,40 @@ impl Config {
         if !self.agq_address.contains(':') {
             anyhow::bail!("AGQ address must be in format host:port");
         }
+        if self
+            .result_address
+            .as_ref()
+            .is_some_and(|address| !address.contains(':'))
+        {
+            anyhow::bail!("Result address must be in format host:port");
+        }
+        if self.cluster_nodes.iter().any(|node| !node.contains(':')) {
+            anyhow::bail!("Cluster nodes must be in format host:port");
+        }
 
         // Validate session key
-        validate_session_key(&self.session_key)?;
+        let session_key = self.session_key_source().load()?;
+        validate_session_key(&session_key)?;
+        if self.require_strong_key {
+            validate_session_key_strength(&session_key)?;
+        }
+        if self.auth_retry > MAX_AUTH_RETRY {
+            anyhow::bail!("Auth retry must not exceed {MAX_AUTH_RETRY}");
+        }
 
         // Validate worker ID if provided
         if let Some(ref id) = self.worker_id {
             validate_worker_id(id)?;
         }
 
+        if self.invalid_utf8 == InvalidUtf8::Reject {
+            require_utf8("WORKER_TOOLS", "--tools", self.tools.iter().flatten())?;
+            require_utf8("AGW_WORKER_NAME", "--name", self.name.iter())?;
+        }
+
         // Validate worker name if provided
-        if let Some(ref name) = self.name {
-            validate_worker_name(name)?;
+        if let Some(name) = self.worker_name() {
+            validate_worker_name(&name)?;
         }
 
         // Validate intervals
@@ -75,10 +524,124 @@ impl Config {
             anyhow::bail!("Heartbeat interval must be greater than 0");
         }
 
+        if self.reconnect_backoff_max_secs > MAX_RECONNECT_BACKOFF_SECS {
+            anyhow::bail!("Reconnect backoff must not exceed {MAX_RECONNECT_BACKOFF_SECS} seconds");
+        }
+
+        if let Some(secs) = self.idle_ping_secs {
+            if secs == 0 || secs > MAX_IDLE_PING_SECS {
+                anyhow::bail!(
+                    "Idle ping interval must be between 1 and {MAX_IDLE_PING_SECS} seconds"
+                );
+            }
+        }
+
+        if let Some(max_fds) = self.max_fds_per_job {
+            if max_fds < MIN_FDS_PER_JOB {
+                anyhow::bail!("Max file descriptors per job must be at least {MIN_FDS_PER_JOB}");
+            }
+        }
+
+        if self.max_worker_rss_mb == Some(0) {
+            anyhow::bail!("Max worker RSS must be greater than 0");
+        }
+
         if self.connection_timeout == 0 {
             anyhow::bail!("Connection timeout must be greater than 0");
         }
 
+        ResultKeyTemplate::parse(&self.result_key_template)?;
+
+        if !(1..=MAX_RESULT_VALUE_BYTES).contains(&self.result_chunk_size) {
+            anyhow::bail!("Result chunk size must be between 1 and {MAX_RESULT_VALUE_BYTES} bytes");
+        }
+
+        if self.max_inflight_commands == Some(0) {
+            anyhow::bail!("Max inflight commands must be greater than 0");
+        }
+
+        if self.max_input_bytes == 0 {
+            anyhow::bail!("Max input bytes must be greater than 0");
+        }
+
+        if self.max_substituted_arg_count == Some(0) {
+            anyhow::bail!("Max substituted arg count must be greater than 0");
+        }
+
+        if self.max_substituted_total_bytes == Some(0) {
+            anyhow::bail!("Max substituted total bytes must be greater than 0");
+        }
+
+        if self.max_output_bytes == Some(0) {
+            anyhow::bail!("Max output bytes must be greater than 0");
+        }
+
+        if self.max_output_lines == Some(0) {
+            anyhow::bail!("Max output lines must be greater than 0");
+        }
+
+        if self.enforce_registered_tools.is_some() && self.tools.is_none() {
+            anyhow::bail!("--enforce-registered-tools requires --tools");
+        }
+
+        if self.fair_schedule_window == Some(0) {
+            anyhow::bail!("Fair schedule window must be greater than 0");
+        }
+
+        if !(1..=MAX_FAIR_SCHEDULE_LOOKAHEAD).contains(&self.fair_schedule_lookahead) {
+            anyhow::bail!(
+                "Fair schedule lookahead must be between 1 and {MAX_FAIR_SCHEDULE_LOOKAHEAD}"
+            );
+        }
+
+        if let Some(timeout) = self.default_task_timeout_secs {
+            if !(MIN_TIMEOUT_SECS..=MAX_TIMEOUT_SECS).contains(&timeout) {
+                anyhow::bail!(
+                    "Default task timeout must be between {MIN_TIMEOUT_SECS} and {MAX_TIMEOUT_SECS} seconds"
+                );
+            }
+        }
+
+        if self.kill_grace_secs > MAX_KILL_GRACE_SECS {
+            anyhow::bail!("Kill grace must not exceed {MAX_KILL_GRACE_SECS} seconds");
+        }
+
+        if self.read_buffer_size == 0 {
+            anyhow::bail!("Read buffer size must be greater than 0");
+        }
+
+        if self.allow_shell {
+            match &self.shell_signing_key {
+                None => anyhow::bail!("--allow-shell requires --shell-signing-key"),
+                Some(key) if key.len() < MIN_SIGNING_KEY_LEN => anyhow::bail!(
+                    "Shell signing key must be at least {MIN_SIGNING_KEY_LEN} characters"
+                ),
+                Some(_) => {}
+            }
+        }
+
+        if self.log_max_bytes == 0 {
+            anyhow::bail!("Log max bytes must be greater than 0");
+        }
+        if self.log_max_files == 0 {
+            anyhow::bail!("Log max files must be greater than 0");
+        }
+
+        if let Some(ref dir) = self.tool_path {
+            if !dir.is_absolute() {
+                anyhow::bail!("Tool path must be an absolute path");
+            }
+            if !dir.is_dir() {
+                anyhow::bail!("Tool path {} is not a directory", dir.display());
+            }
+        }
+
+        if let Some(ref address) = self.admin_address {
+            if !address.contains(':') {
+                anyhow::bail!("Admin address must be in format host:port");
+            }
+        }
+
         Ok(())
     }
 
@@ -100,6 +663,275 @@ impl Config {
     pub fn shutdown_timeout_duration(&self) -> Option<Duration> {
         self.shutdown_timeout.map(Duration::from_secs)
     }
+
+    /// Get the idle ping interval as Duration (`None` when idle pings are off)
+    #[must_use]
+    pub fn idle_ping_duration(&self) -> Option<Duration> {
+        self.idle_ping_secs.map(Duration::from_secs)
+    }
+
+    /// Get the reconnect backoff cap as Duration
+    #[must_use]
+    pub fn reconnect_backoff_max_duration(&self) -> Duration {
+        Duration::from_secs(self.reconnect_backoff_max_secs)
+    }
+
+    /// Backoff between reconnect attempts
+    #[must_use]
+    pub fn reconnect_backoff(&self) -> Backoff {
+        Backoff::new(
+            self.retry_backoff.unwrap_or(BackoffStrategy::Exponential),
+            RECONNECT_INITIAL_BACKOFF,
+            self.reconnect_backoff_max_duration(),
+        )
+    }
+
+    /// Backoff between authentication attempts under `--auth-retry`
+    #[must_use]
+    pub fn auth_backoff(&self) -> Backoff {
+        match self.retry_backoff {
+            None | Some(BackoffStrategy::Fixed) => Backoff::fixed(AUTH_RETRY_BACKOFF),
+            Some(strategy) => Backoff::new(strategy, AUTH_RETRY_BACKOFF, AUTH_RETRY_BACKOFF_MAX),
+        }
+    }
+
+    /// Get clock skew warning threshold as Duration
+    #[must_use]
+    pub fn clock_skew_threshold_duration(&self) -> Duration {
+        Duration::from_secs(self.clock_skew_threshold)
+    }
+
+    /// Nodes `MOVED`/`ASK` redirects may be followed to under `--cluster`: the
+    /// AGQ and result addresses plus every `--cluster-node` (`None` outside
+    /// cluster mode)
+    #[must_use]
+    pub fn redirect_nodes(&self) -> Option<Vec<String>> {
+        self.cluster.then(|| {
+            std::iter::once(&self.agq_address)
+                .chain(&self.result_address)
+                .chain(&self.cluster_nodes)
+                .cloned()
+                .collect()
+        })
+    }
+
+    /// Where to read the session key from
+    #[must_use]
+    pub fn session_key_source(&self) -> SessionKeySource {
+        match (&self.session_key, &self.session_key_file) {
+            (_, Some(path)) => SessionKeySource::File(path.clone()),
+            (key, None) => SessionKeySource::Inline(key.clone().unwrap_or_default()),
+        }
+    }
+
+    /// Build the worker-wide execution options for the executor
+    #[must_use]
+    pub fn execution_options(&self) -> ExecutionOptions {
+        ExecutionOptions {
+            max_output_bytes: self.max_output_bytes,
+            max_output_lines: self.max_output_lines,
+            compress_outputs_above: self.compress_outputs_above,
+            allow_shell: self.allow_shell,
+            command_cache_ttl: Duration::from_secs(self.command_cache_ttl),
+            tool_aliases: self.tool_aliases.iter().cloned().collect(),
+            read_mode: self.read_mode,
+            read_buffer_size: self.read_buffer_size,
+            kill_grace: Duration::from_secs(self.kill_grace_secs),
+            checkpoint_tasks: self.checkpoint_tasks,
+            tool_path: self.tool_path.clone(),
+            redactor: self.redactor(),
+            warning_patterns: self.warning_patterns.clone(),
+            registered_tools: self
+                .enforce_registered_tools
+                .and_then(|_| self.tool_names())
+                .filter(|tools| !tools.is_empty())
+                .map(|tools| tools.into_iter().collect()),
+            unregistered_tools: self.enforce_registered_tools.unwrap_or_default(),
+            max_fds_per_job: self.max_fds_per_job,
+            force_reexec: self.force_reexec,
+            result_sink: self.emit_results_stdout.then(ResultSink::stdout),
+            manifest_config: self.manifest_config.then(|| self.config_snapshot()),
+            progress: None,
+            webhook: self
+                .webhook_url
+                .clone()
+                .map(|webhook| match self.retry_backoff {
+                    Some(strategy) => webhook.with_backoff_strategy(strategy),
+                    None => webhook,
+                }),
+        }
+    }
+
+    /// Settings recorded in job manifests, with paths and aliases redacted
+    #[must_use]
+    pub fn config_snapshot(&self) -> ConfigSnapshot {
+        let redactor = self.redactor();
+        let redact = |text: &str| redactor.redact(text).into_owned();
+        ConfigSnapshot {
+            agw_version: env!("CARGO_PKG_VERSION").to_string(),
+            strict_plan_parsing: self.strict_plan_parsing,
+            require_timeouts: self.require_timeouts,
+            allow_shell: self.allow_shell,
+            max_substituted_arg_count: self.max_substituted_arg_count,
+            max_substituted_total_bytes: self.max_substituted_total_bytes,
+            reject_empty_substituted_args: self.reject_empty_substituted_args,
+            default_task_timeout_secs: self.default_task_timeout_secs,
+            kill_grace_secs: self.kill_grace_secs,
+            shutdown_timeout_secs: self.shutdown_timeout,
+            max_output_bytes: self.max_output_bytes,
+            max_output_lines: self.max_output_lines,
+            checkpoint_tasks: self.checkpoint_tasks,
+            tool_path: self
+                .tool_path
+                .as_ref()
+                .map(|path| redact(&path.to_string_lossy())),
+            tool_aliases: self
+                .tool_aliases
+                .iter()
+                .map(|(logical, binary)| (logical.clone(), redact(binary)))
+                .collect(),
+        }
+    }
+
+    /// `--tools` as strings (invalid UTF-8 converted lossily; see `--invalid-utf8`)
+    #[must_use]
+    pub fn tool_names(&self) -> Option<Vec<String>> {
+        self.tools
+            .as_ref()
+            .map(|tools| tools.iter().map(|tool| lossy(tool)).collect())
+    }
+
+    /// `--name` as a string (invalid UTF-8 converted lossily; see `--invalid-utf8`)
+    #[must_use]
+    pub fn worker_name(&self) -> Option<String> {
+        self.name.as_deref().map(lossy)
+    }
+
+    /// Fair scheduler for the fetch path, present only when `--fair-schedule-window` is set
+    #[must_use]
+    pub fn fair_scheduler(&self) -> Option<FairScheduler> {
+        self.fair_schedule_window
+            .map(|window| FairScheduler::new(window, self.plan_weights.iter().cloned().collect()))
+    }
+
+    /// Checks on task arguments after input substitution
+    #[must_use]
+    pub fn substitution_limits(&self) -> SubstitutionLimits {
+        SubstitutionLimits {
+            max_arg_count: self.max_substituted_arg_count,
+            max_total_bytes: self.max_substituted_total_bytes,
+            reject_empty_args: self.reject_empty_substituted_args,
+        }
+    }
+
+    /// Redactor for posted task output
+    #[must_use]
+    pub fn redactor(&self) -> Redactor {
+        Redactor::new(self.redact_patterns.clone())
+    }
+
+    /// Verifier for trusted plan signatures, present only when shell mode is enabled
+    #[must_use]
+    pub fn plan_verifier(&self) -> Option<PlanVerifier> {
+        if !self.allow_shell {
+            return None;
+        }
+        self.shell_signing_key.as_deref().map(PlanVerifier::new)
+    }
+}
+
+/// Handling of `--tools` and `--name` values that are not valid UTF-8
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum InvalidUtf8 {
+    /// Refuse to start, naming the offending setting
+    Reject,
+    /// Replace invalid bytes with U+FFFD
+    Lossy,
+}
+
+/// Reject any of `values` (from `env_var` / `flag`) that is not valid UTF-8
+///
+/// # Errors
+///
+/// Returns an error naming the environment variable and flag
+fn require_utf8<'a>(
+    env_var: &str,
+    flag: &str,
+    mut values: impl Iterator<Item = &'a OsString>,
+) -> anyhow::Result<()> {
+    if let Some(value) = values.find(|value| value.to_str().is_none()) {
+        anyhow::bail!(
+            "{env_var} ({flag}) is not valid UTF-8: {:?}; fix it or set --invalid-utf8 lossy",
+            value.to_string_lossy()
+        );
+    }
+    Ok(())
+}
+
+fn lossy(value: &OsStr) -> String {
+    value.to_string_lossy().into_owned()
+}
+
+/// Parse a `logical=binary` tool alias
+///
+/// # Errors
+///
+/// Returns an error if the alias is malformed or either side fails command validation
+fn parse_tool_alias(alias: &str) -> anyhow::Result<(String, String)> {
+    let Some((logical, binary)) = alias.split_once('=') else {
+        anyhow::bail!("Tool alias must be in format logical=binary");
+    };
+    let (logical, binary) = (logical.trim(), binary.trim());
+    validate_command(logical).map_err(|e| anyhow::anyhow!("Invalid tool alias name: {e}"))?;
+    validate_command(binary).map_err(|e| anyhow::anyhow!("Invalid tool alias target: {e}"))?;
+    Ok((logical.to_string(), binary.to_string()))
+}
+
+/// Parse a `plan_id=weight` fair-scheduling weight
+///
+/// # Errors
+///
+/// Returns an error if the entry is malformed or the weight is not a positive integer
+fn parse_plan_weight(entry: &str) -> anyhow::Result<(String, u32)> {
+    let Some((plan_id, weight)) = entry.split_once('=') else {
+        anyhow::bail!("Plan weight must be in format plan_id=weight");
+    };
+    let plan_id = plan_id.trim();
+    if plan_id.is_empty() {
+        anyhow::bail!("Plan weight must name a plan");
+    }
+    let weight: u32 = weight
+        .trim()
+        .parse()
+        .map_err(|e| anyhow::anyhow!("Invalid plan weight: {e}"))?;
+    if weight == 0 {
+        anyhow::bail!("Plan weight must be greater than 0");
+    }
+    Ok((plan_id.to_string(), weight))
+}
+
+/// Compile a `--redact-pattern` regex
+///
+/// # Errors
+///
+/// Returns an error if the pattern is empty or not a valid regex
+fn parse_redact_pattern(pattern: &str) -> anyhow::Result<Regex> {
+    if pattern.is_empty() {
+        anyhow::bail!("Redact pattern cannot be empty");
+    }
+    Regex::new(pattern).map_err(|e| anyhow::anyhow!("Invalid redact pattern: {e}"))
+}
+
+/// Compile a `--warning-pattern` regex
+///
+/// # Errors
+///
+/// Returns an error if the pattern is empty or not a valid regex
+fn parse_warning_pattern(pattern: &str) -> anyhow::Result<Regex> {
+    if pattern.is_empty() {
+        anyhow::bail!("Warning pattern cannot be empty");
+    }
+    Regex::new(pattern).map_err(|e| anyhow::anyhow!("Invalid warning pattern: {e}"))
 }
 
 /// Validate session key format
@@ -139,6 +971,50 @@ pub fn validate_session_key(key: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Reject guessable session keys (`--require-strong-key`)
+///
+/// # Errors
+///
+/// Returns an error if the key repeats a single character, is a sequential
+/// run of characters, or has too little estimated entropy
+pub fn validate_session_key_strength(key: &str) -> anyhow::Result<()> {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.windows(2).all(|pair| pair[0] == pair[1]) {
+        anyhow::bail!("Session key is too weak: a single repeated character");
+    }
+    let steps: Vec<i64> = chars
+        .windows(2)
+        .map(|pair| i64::from(u32::from(pair[1])) - i64::from(u32::from(pair[0])))
+        .collect();
+    if steps.iter().all(|&step| step == 1) || steps.iter().all(|&step| step == -1) {
+        anyhow::bail!("Session key is too weak: a sequential run of characters");
+    }
+    let bits = estimated_entropy_bits(&chars);
+    if bits < MIN_STRONG_KEY_ENTROPY_BITS {
+        anyhow::bail!(
+            "Session key is too weak: ~{bits:.0} bits of entropy, at least {MIN_STRONG_KEY_ENTROPY_BITS:.0} required"
+        );
+    }
+    Ok(())
+}
+
+/// Shannon entropy of the key's character frequencies, times its length
+fn estimated_entropy_bits(chars: &[char]) -> f64 {
+    let mut counts = std::collections::HashMap::new();
+    for &c in chars {
+        *counts.entry(c).or_insert(0u32) += 1;
+    }
+    let len = chars.len() as f64;
+    let per_char: f64 = counts
+        .values()
+        .map(|&count| {
+            let p = f64::from(count) / len;
+            -p * p.log2()
+        })
+        .sum();
+    per_char * len
+}
+
 /// Validate worker ID format
 ///
 /// # Errors
@@ -206,6 +1082,50 @@ pub fn validate_worker_name(name: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Values substituted into `--name-template`
+#[derive(Debug, Clone)]
+pub struct NameTemplateVars<'a> {
+    pub hostname: &'a str,
+    pub pid: u32,
+    pub uuid: &'a str,
+    pub role: Option<&'a str>,
+}
+
+/// Resolve a `--name-template` into a worker name
+///
+/// # Errors
+///
+/// Returns an error if the template has an unknown or unterminated
+/// placeholder, uses `{role}` without a role, or resolves to a name rejected
+/// by [`validate_worker_name`]
+pub fn render_name_template(template: &str, vars: &NameTemplateVars<'_>) -> anyhow::Result<String> {
+    let mut name = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            anyhow::bail!("Name template has an unterminated placeholder");
+        };
+        let placeholder = &rest[start + 1..start + len];
+        match placeholder {
+            "hostname" => name.push_str(vars.hostname),
+            "pid" => name.push_str(&vars.pid.to_string()),
+            "uuid" => name.push_str(vars.uuid),
+            "role" => match vars.role {
+                Some(role) => name.push_str(role),
+                None => anyhow::bail!("Name template uses {{role}} but no --role is set"),
+            },
+            other => anyhow::bail!("Name template has unknown placeholder {{{other}}}"),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    name.push_str(rest);
+
+    validate_worker_name(&name)
+        .map_err(|e| anyhow::anyhow!("Name template resolved to {name:?}: {e}"))?;
+    Ok(name)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -217,6 +1137,29 @@ mod tests {
         assert!(validate_session_key("test_key_123").is_ok());
     }
 
+    #[test]
+    fn test_validate_session_key_strength() {
+        assert!(validate_session_key_strength("aaaaaaaa").is_err());
+        assert!(validate_session_key_strength("abcdefghijklmnopqrstuvwxyz").is_err());
+        assert!(validate_session_key_strength("987654321").is_err());
+        assert!(validate_session_key_strength("passwordpassword").is_err());
+        assert!(validate_session_key_strength("k7Qp2Zx9mR4tW1vBn8Ls3Yd6").is_ok());
+
+        // Opt-in: weak keys still pass plain validation
+        assert!(parse(&[]).validate().is_ok());
+        let weak = ["agw", "--session-key", "aaaaaaaa"];
+        assert!(Config::try_parse_from(weak).unwrap().validate().is_ok());
+        let strict = ["agw", "--session-key", "aaaaaaaa", "--require-strong-key"];
+        assert!(Config::try_parse_from(strict).unwrap().validate().is_err());
+        let strong = [
+            "agw",
+            "--session-key",
+            "k7Qp2Zx9mR4tW1vBn8Ls3Yd6",
+            "--require-strong-key",
+        ];
+        assert!(Config::try_parse_from(strong).unwrap().validate().is_ok());
+    }
+
     #[test]
     fn test_validate_session_key_empty() {
         assert!(validate_session_key("").is_err());
@@ -244,6 +1187,655 @@ mod tests {
         assert!(validate_session_key("key`whoami`").is_err());
     }
 
+    fn parse(args: &[&str]) -> Config {
+        let base = ["agw", "--session-key", "test-session-key"];
+        Config::try_parse_from(base.iter().chain(args)).unwrap()
+    }
+
+    #[test]
+    fn test_session_key_file() {
+        let key_file = std::env::temp_dir().join(format!("agw-key-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&key_file, "file-session-key\n").unwrap();
+        let path = key_file.to_str().unwrap();
+
+        let config = Config::try_parse_from(["agw", "--session-key-file", path]).unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            config.session_key_source().load().unwrap(),
+            "file-session-key"
+        );
+
+        // The key file is validated like an inline key
+        std::fs::write(&key_file, "short").unwrap();
+        assert!(config.validate().is_err());
+        std::fs::remove_file(&key_file).unwrap();
+        assert!(config.validate().is_err());
+
+        assert!(Config::try_parse_from(["agw"]).is_err());
+        assert!(Config::try_parse_from([
+            "agw",
+            "--session-key",
+            "test-session-key",
+            "--session-key-file",
+            path
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_auth_retry_bounds() {
+        assert_eq!(parse(&[]).auth_retry, 0);
+        assert!(parse(&["--auth-retry", "5"]).validate().is_ok());
+        let too_many = (MAX_AUTH_RETRY + 1).to_string();
+        assert!(parse(&["--auth-retry", &too_many]).validate().is_err());
+    }
+
+    #[test]
+    fn test_no_exec_excludes_tool_version_probes() {
+        assert!(!parse(&[]).no_exec);
+        assert!(parse(&["--no-exec"]).validate().is_ok());
+        assert!(Config::try_parse_from([
+            "agw",
+            "--session-key",
+            "test-session-key",
+            "--no-exec",
+            "--collect-tool-versions",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_task_timeout_options() {
+        assert_eq!(parse(&[]).default_task_timeout_secs, None);
+        assert!(parse(&["--default-task-timeout-secs", "600"])
+            .validate()
+            .is_ok());
+        assert!(parse(&["--default-task-timeout-secs", "0"])
+            .validate()
+            .is_err());
+        assert!(parse(&["--default-task-timeout-secs", "86401"])
+            .validate()
+            .is_err());
+
+        assert!(parse(&["--require-timeouts"]).require_timeouts);
+        assert!(Config::try_parse_from([
+            "agw",
+            "--session-key",
+            "test-session-key",
+            "--require-timeouts",
+            "--default-task-timeout-secs",
+            "600",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_reconnect_options() {
+        let config = parse(&[]);
+        assert_eq!(config.max_reconnect_attempts, 5);
+        assert_eq!(
+            config.reconnect_backoff_max_duration(),
+            Duration::from_secs(30)
+        );
+
+        assert!(parse(&["--reconnect-backoff-max-secs", "3600"])
+            .validate()
+            .is_ok());
+        assert!(parse(&["--reconnect-backoff-max-secs", "3601"])
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_retry_backoff_option() {
+        let config = parse(&[]);
+        assert_eq!(config.retry_backoff, None);
+        assert_eq!(config.auth_backoff(), Backoff::fixed(AUTH_RETRY_BACKOFF));
+        assert_eq!(
+            config.reconnect_backoff(),
+            Backoff::new(
+                BackoffStrategy::Exponential,
+                RECONNECT_INITIAL_BACKOFF,
+                Duration::from_secs(30)
+            )
+        );
+
+        let config = parse(&["--retry-backoff", "fibonacci"]);
+        assert_eq!(config.retry_backoff, Some(BackoffStrategy::Fibonacci));
+        assert_eq!(config.auth_backoff().strategy, BackoffStrategy::Fibonacci);
+        assert_eq!(config.auth_backoff().max, AUTH_RETRY_BACKOFF_MAX);
+        assert_eq!(
+            config.reconnect_backoff().strategy,
+            BackoffStrategy::Fibonacci
+        );
+        assert_eq!(
+            parse(&["--retry-backoff", "exponential-with-jitter"]).retry_backoff,
+            Some(BackoffStrategy::ExponentialWithJitter)
+        );
+        assert!(Config::try_parse_from([
+            "agw",
+            "--agq-address",
+            "127.0.0.1:6379",
+            "--session-key",
+            "test-session-key-1234567890",
+            "--retry-backoff",
+            "linear",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_idle_ping_option() {
+        assert_eq!(parse(&[]).idle_ping_duration(), None);
+
+        let config = parse(&["--idle-ping-secs", "45"]);
+        assert!(config.validate().is_ok());
+        assert_eq!(config.idle_ping_duration(), Some(Duration::from_secs(45)));
+
+        for bad in ["0", "3601"] {
+            assert!(parse(&["--idle-ping-secs", bad]).validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_max_fds_per_job_option() {
+        assert_eq!(parse(&[]).execution_options().max_fds_per_job, None);
+
+        let config = parse(&["--max-fds-per-job", "64"]);
+        assert!(config.validate().is_ok());
+        assert_eq!(config.execution_options().max_fds_per_job, Some(64));
+
+        assert!(parse(&["--max-fds-per-job", "7"]).validate().is_err());
+    }
+
+    #[test]
+    fn test_checkpoints_exclude_redaction() {
+        assert!(parse(&["--checkpoint-tasks"]).checkpoint_tasks);
+        assert!(!parse(&["--redact-pattern", "secret"]).checkpoint_tasks);
+        let err = Config::try_parse_from([
+            "agw",
+            "--session-key",
+            "test-session-key",
+            "--checkpoint-tasks",
+            "--redact-pattern",
+            "secret",
+        ])
+        .unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn test_force_reexec_requires_checkpointing() {
+        assert!(!parse(&[]).execution_options().force_reexec);
+        assert!(
+            parse(&["--checkpoint-tasks", "--force-reexec"])
+                .execution_options()
+                .force_reexec
+        );
+        assert!(Config::try_parse_from([
+            "agw",
+            "--session-key",
+            "test-session-key",
+            "--force-reexec",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_allow_shell_requires_strong_signing_key() {
+        let config = parse(&["--allow-shell"]);
+        assert!(config.validate().is_err());
+
+        let config = parse(&["--allow-shell", "--shell-signing-key", "short"]);
+        assert!(config.validate().is_err());
+
+        let config = parse(&[
+            "--allow-shell",
+            "--shell-signing-key",
+            "0123456789abcdef0123456789abcdef",
+        ]);
+        assert!(config.validate().is_ok());
+        assert!(config.plan_verifier().is_some());
+        assert!(config.execution_options().allow_shell);
+
+        // A key alone does not enable shell mode
+        let config = parse(&["--shell-signing-key", "0123456789abcdef0123456789abcdef"]);
+        assert!(config.plan_verifier().is_none());
+        assert!(!config.execution_options().allow_shell);
+    }
+
+    #[test]
+    fn test_enqueue_subcommand_parsing() {
+        let config = parse(&[
+            "enqueue",
+            "--plan-id",
+            "p1",
+            "--input",
+            r#"{"path":"/tmp"}"#,
+        ]);
+        let Some(Command::Enqueue(args)) = config.command else {
+            panic!("expected enqueue subcommand");
+        };
+        assert_eq!(args.plan_id, "p1");
+        assert_eq!(args.input, r#"{"path":"/tmp"}"#);
+        assert_eq!(args.job_id, None);
+
+        assert!(parse(&[]).command.is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_invalid_utf8_tools_and_name() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let parse_os = |args: Vec<OsString>| {
+            let base = ["agw", "--session-key", "test-session-key"].map(OsString::from);
+            Config::try_parse_from(base.into_iter().chain(args)).unwrap()
+        };
+        let tools = OsString::from_vec(b"sort,gr\xffep".to_vec());
+
+        // Rejected by default, naming the setting instead of a generic parse failure
+        let config = parse_os(vec!["--tools".into(), tools.clone()]);
+        let err = config.validate().unwrap_err().to_string();
+        assert!(
+            err.contains("WORKER_TOOLS (--tools) is not valid UTF-8"),
+            "{err}"
+        );
+        let config = parse_os(vec!["--name".into(), OsString::from_vec(b"w\xff".to_vec())]);
+        let err = config.validate().unwrap_err().to_string();
+        assert!(
+            err.contains("AGW_WORKER_NAME (--name) is not valid UTF-8"),
+            "{err}"
+        );
+
+        // Converted lossily on request
+        let config = parse_os(vec![
+            "--tools".into(),
+            tools,
+            "--invalid-utf8".into(),
+            "lossy".into(),
+        ]);
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            config.tool_names().unwrap(),
+            ["sort".to_string(), "gr\u{fffd}ep".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_registered_tools_option() {
+        let config = parse(&["--tools", "sort,echo", "--enforce-registered-tools"]);
+        assert!(config.validate().is_ok());
+        let options = config.execution_options();
+        let tools = options.registered_tools.unwrap();
+        assert!(tools.contains("sort") && tools.contains("echo"));
+        assert_eq!(options.unregistered_tools, UnregisteredTools::Requeue);
+
+        let config = parse(&["--tools", "sort", "--enforce-registered-tools", "fail"]);
+        assert_eq!(
+            config.execution_options().unregistered_tools,
+            UnregisteredTools::Fail
+        );
+
+        // Advertising tools alone does not restrict what runs
+        let config = parse(&["--tools", "sort,echo"]);
+        assert!(config.execution_options().registered_tools.is_none());
+        assert!(parse(&[]).execution_options().registered_tools.is_none());
+
+        let err = parse(&["--enforce-registered-tools"])
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("requires --tools"), "{err}");
+    }
+
+    #[test]
+    fn test_tool_alias_parsing() {
+        let config = parse(&[
+            "--tool-alias",
+            "python=python3",
+            "--tool-alias",
+            "awk = gawk",
+        ]);
+        let aliases = config.execution_options().tool_aliases;
+        assert_eq!(aliases["python"], "python3");
+        assert_eq!(aliases["awk"], "gawk");
+
+        for bad in [
+            "python",
+            "=python3",
+            "python=",
+            "python=python3;rm",
+            "py=$(x)",
+        ] {
+            let base = [
+                "agw",
+                "--session-key",
+                "test-session-key",
+                "--tool-alias",
+                bad,
+            ];
+            assert!(
+                Config::try_parse_from(base).is_err(),
+                "{bad:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_warning_pattern_parsing() {
+        let config = parse(&[
+            "--warning-pattern",
+            "^WARN",
+            "--warning-pattern",
+            "deprecated",
+        ]);
+        assert_eq!(config.execution_options().warning_patterns.len(), 2);
+
+        assert!(
+            Config::try_parse_from(["agw", "-k", "test-session-key", "--warning-pattern", ""])
+                .is_err()
+        );
+        assert!(Config::try_parse_from([
+            "agw",
+            "-k",
+            "test-session-key",
+            "--warning-pattern",
+            "("
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_redact_pattern_parsing() {
+        let config = parse(&[
+            "--redact-pattern",
+            r"ghp_\w+",
+            "--redact-pattern",
+            "secret=[^ ]+",
+        ]);
+        assert_eq!(
+            config.redactor().redact("ghp_abc secret=xyz ok"),
+            "*** *** ok"
+        );
+
+        for bad in ["", "(unclosed"] {
+            let base = [
+                "agw",
+                "--session-key",
+                "test-session-key",
+                "--redact-pattern",
+                bad,
+            ];
+            assert!(
+                Config::try_parse_from(base).is_err(),
+                "{bad:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_honor_priority_excludes_fair_scheduling() {
+        assert!(!parse(&[]).honor_priority);
+        assert!(parse(&["--honor-priority"]).honor_priority);
+        assert!(Config::try_parse_from([
+            "agw",
+            "--session-key",
+            "test-session-key",
+            "--honor-priority",
+            "--fair-schedule-window",
+            "50",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_queue_reliability_option() {
+        assert_eq!(parse(&[]).queue_reliability, QueueReliability::Reliable);
+        assert_eq!(
+            parse(&["--queue-reliability", "at-most-once"]).queue_reliability,
+            QueueReliability::AtMostOnce
+        );
+        assert!(Config::try_parse_from([
+            "agw",
+            "--session-key",
+            "test-session-key",
+            "--queue-reliability",
+            "at-most-once",
+            "--fair-schedule-window",
+            "50",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_emit_results_stdout_option() {
+        assert!(parse(&[]).execution_options().result_sink.is_none());
+        assert!(parse(&["--emit-results-stdout"])
+            .execution_options()
+            .result_sink
+            .is_some());
+    }
+
+    #[test]
+    fn test_manifest_config_snapshot() {
+        assert!(parse(&[]).execution_options().manifest_config.is_none());
+
+        let config = parse(&[
+            "--manifest-config",
+            "--require-timeouts",
+            "--tool-path",
+            "/opt/tok_Zx81Qa/bin",
+            "--tool-alias",
+            "python=python3.11",
+            "--shutdown-timeout",
+            "30",
+            "--redact-pattern",
+            r"tok_\w+",
+        ]);
+        let snapshot = config.execution_options().manifest_config.unwrap();
+        assert!(snapshot.require_timeouts);
+        assert!(!snapshot.strict_plan_parsing);
+        assert_eq!(snapshot.shutdown_timeout_secs, Some(30));
+        assert_eq!(snapshot.tool_path.as_deref(), Some("/opt/***/bin"));
+        assert_eq!(snapshot.tool_aliases["python"], "python3.11");
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert!(!json.contains("session"), "{json}");
+        assert!(
+            !json.contains(config.session_key.as_deref().unwrap()),
+            "{json}"
+        );
+        assert!(!json.contains("tok_Zx81Qa"), "{json}");
+    }
+
+    #[test]
+    fn test_max_output_lines_option() {
+        assert_eq!(parse(&[]).execution_options().max_output_lines, None);
+        let config = parse(&["--max-output-lines", "500"]);
+        assert!(config.validate().is_ok());
+        assert_eq!(config.execution_options().max_output_lines, Some(500));
+        assert!(parse(&["--max-output-lines", "0"]).validate().is_err());
+    }
+
+    #[test]
+    fn test_webhook_url_option() {
+        assert!(parse(&[]).execution_options().webhook.is_none());
+        let webhook = parse(&["--webhook-url", "http://hooks:8080/done"])
+            .execution_options()
+            .webhook
+            .unwrap();
+        assert_eq!(webhook.to_string(), "http://hooks:8080/done");
+        assert!(Config::try_parse_from([
+            "agw",
+            "--session-key",
+            "test-session-key",
+            "--webhook-url",
+            "https://hooks/done",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_substitution_limit_options() {
+        assert_eq!(
+            parse(&[]).substitution_limits(),
+            SubstitutionLimits::default()
+        );
+        let config = parse(&[
+            "--max-substituted-arg-count",
+            "64",
+            "--max-substituted-total-bytes",
+            "65536",
+            "--reject-empty-substituted-args",
+        ]);
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            config.substitution_limits(),
+            SubstitutionLimits {
+                max_arg_count: Some(64),
+                max_total_bytes: Some(65536),
+                reject_empty_args: true,
+            }
+        );
+        assert!(parse(&["--max-substituted-arg-count", "0"])
+            .validate()
+            .is_err());
+        assert!(parse(&["--max-substituted-total-bytes", "0"])
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_max_worker_rss_option() {
+        assert_eq!(parse(&[]).max_worker_rss_mb, None);
+        let config = parse(&["--max-worker-rss-mb", "512"]);
+        assert!(config.validate().is_ok());
+        assert_eq!(config.max_worker_rss_mb, Some(512));
+        assert!(parse(&["--max-worker-rss-mb", "0"]).validate().is_err());
+    }
+
+    #[test]
+    fn test_fair_scheduling_options() {
+        assert!(parse(&[]).fair_scheduler().is_none());
+
+        let config = parse(&["--fair-schedule-window", "50", "--plan-weight", "reports=3"]);
+        assert!(config.validate().is_ok());
+        assert_eq!(config.plan_weights, vec![("reports".to_string(), 3)]);
+        assert!(config.fair_scheduler().is_some());
+
+        assert!(parse(&["--fair-schedule-window", "0"]).validate().is_err());
+        assert!(parse(&["--fair-schedule-lookahead", "0"])
+            .validate()
+            .is_err());
+
+        for bad in ["reports", "=3", "reports=0", "reports=-1", "reports=x"] {
+            let base = [
+                "agw",
+                "--session-key",
+                "test-session-key",
+                "--plan-weight",
+                bad,
+            ];
+            assert!(
+                Config::try_parse_from(base).is_err(),
+                "{bad:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_kill_grace_bounded() {
+        assert!(parse(&["--kill-grace-secs", "86400"]).validate().is_ok());
+        assert!(parse(&["--kill-grace-secs", "86401"]).validate().is_err());
+        let near_max = u64::MAX.to_string();
+        assert!(parse(&["--kill-grace-secs", &near_max]).validate().is_err());
+    }
+
+    #[test]
+    fn test_result_chunk_size_bounded() {
+        assert_eq!(parse(&[]).result_chunk_size, DEFAULT_RESULT_CHUNK_SIZE);
+        assert!(parse(&["--result-chunk-size", "1"]).validate().is_ok());
+        assert!(parse(&["--result-chunk-size", "0"]).validate().is_err());
+        let too_large = (MAX_RESULT_VALUE_BYTES + 1).to_string();
+        assert!(parse(&["--result-chunk-size", &too_large])
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_result_address_must_be_host_and_port() {
+        assert_eq!(parse(&[]).result_address, None);
+        let config = parse(&["--result-address", "results:6379"]);
+        assert!(config.validate().is_ok());
+        assert_eq!(config.result_address.as_deref(), Some("results:6379"));
+        assert!(parse(&["--result-address", "results"]).validate().is_err());
+    }
+
+    #[test]
+    fn test_cluster_nodes_follow_configured_addresses() {
+        let config = parse(&[]);
+        assert_eq!(config.redirect_nodes(), None);
+
+        let config = parse(&[
+            "--cluster",
+            "--result-address",
+            "results:6379",
+            "--cluster-node",
+            "10.0.0.2:6379",
+            "--cluster-node",
+            "10.0.0.3:6379",
+        ]);
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            config.redirect_nodes().unwrap(),
+            [
+                "127.0.0.1:6379",
+                "results:6379",
+                "10.0.0.2:6379",
+                "10.0.0.3:6379"
+            ]
+        );
+
+        assert!(parse(&["--cluster", "--cluster-node", "10.0.0.2"])
+            .validate()
+            .is_err());
+        let err = Config::try_parse_from([
+            "agw",
+            "--session-key",
+            "test-session-key",
+            "--cluster-node",
+            "10.0.0.2:6379",
+        ])
+        .unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn test_max_inflight_commands_must_be_positive() {
+        assert_eq!(parse(&[]).max_inflight_commands, None);
+        let config = parse(&["--max-inflight-commands", "4"]);
+        assert!(config.validate().is_ok());
+        assert_eq!(config.max_inflight_commands, Some(4));
+        assert!(parse(&["--max-inflight-commands", "0"]).validate().is_err());
+    }
+
+    #[test]
+    fn test_tool_path_must_be_absolute_directory() {
+        let dir = std::env::temp_dir();
+        let dir = dir.to_str().unwrap();
+        let config = parse(&["--tool-path", dir]);
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            config.execution_options().tool_path,
+            Some(PathBuf::from(dir))
+        );
+
+        assert!(parse(&["--tool-path", "relative/bin"]).validate().is_err());
+        assert!(parse(&["--tool-path", "/agw-no-such-dir"])
+            .validate()
+            .is_err());
+    }
+
     #[test]
     fn test_validate_worker_id_valid() {
         assert!(validate_worker_id("worker-1").is_ok());
@@ -301,6 +1893,61 @@ mod tests {
         assert!(validate_worker_name("worker:1").is_err()); // Colon
     }
 
+    fn template_vars(role: Option<&str>) -> NameTemplateVars<'_> {
+        NameTemplateVars {
+            hostname: "build-host",
+            pid: 4242,
+            uuid: "0f8e2a6c-5b1d-4e0a-9c3b-7d6f1e2a3b4c",
+            role,
+        }
+    }
+
+    #[test]
+    fn test_render_name_template() {
+        let name =
+            render_name_template("{role}-{hostname}-{pid}", &template_vars(Some("gpu"))).unwrap();
+        assert_eq!(name, "gpu-build-host-4242");
+        assert!(validate_worker_name(&name).is_ok());
+
+        let name = render_name_template("agw_{uuid}", &template_vars(None)).unwrap();
+        assert_eq!(name, "agw_0f8e2a6c-5b1d-4e0a-9c3b-7d6f1e2a3b4c");
+        assert_eq!(
+            render_name_template("static-name", &template_vars(None)).unwrap(),
+            "static-name"
+        );
+    }
+
+    #[test]
+    fn test_render_name_template_rejects_invalid_result() {
+        // Resolves to a name validate_worker_name rejects
+        assert!(render_name_template("{hostname}.{pid}", &template_vars(None)).is_err());
+        assert!(render_name_template("{role}", &template_vars(Some("../etc"))).is_err());
+        assert!(render_name_template("{role}", &template_vars(Some(""))).is_err());
+        let err = render_name_template("{uuid}-{uuid}", &template_vars(None)).unwrap_err();
+        assert!(err.to_string().contains("64 characters"), "{err}");
+
+        // Malformed templates
+        assert!(render_name_template("{host}-1", &template_vars(None)).is_err());
+        assert!(render_name_template("worker-{pid", &template_vars(None)).is_err());
+        assert!(render_name_template("{role}-1", &template_vars(None)).is_err());
+    }
+
+    #[test]
+    fn test_name_template_conflicts_with_name() {
+        assert!(Config::try_parse_from([
+            "agw",
+            "--agq-address",
+            "127.0.0.1:6379",
+            "--session-key",
+            "test-session-key-1234567890",
+            "--name",
+            "worker-1",
+            "--name-template",
+            "{hostname}",
+        ])
+        .is_err());
+    }
+
     #[test]
     fn test_validate_worker_name_security() {
         // Path traversal
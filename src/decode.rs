@@ -0,0 +1,130 @@
+//! Incremental UTF-8 decoding of task output
+//!
+//! Output is read from pipes in arbitrary chunks, so a multi-byte character can
+//! be split across two reads. Decoding each chunk on its own would turn both
+//! halves into replacement characters; this decoder carries the incomplete
+//! sequence over to the next chunk instead.
+
+use encoding_rs::{Decoder, UTF_8};
+
+/// Streaming UTF-8 decoder that is lossy only for genuinely invalid bytes
+pub struct Utf8StreamDecoder {
+    decoder: Decoder,
+}
+
+impl Default for Utf8StreamDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Utf8StreamDecoder {
+    /// Create a decoder
+    ///
+    /// Output is always decoded as UTF-8: a leading byte order mark is kept as
+    /// output, and a UTF-16 one does not switch the encoding.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            decoder: UTF_8.new_decoder_without_bom_handling(),
+        }
+    }
+
+    /// Decode the next chunk, appending complete characters to `output`
+    ///
+    /// Trailing bytes of an incomplete sequence are held until the next call.
+    /// Invalid sequences are replaced with U+FFFD.
+    pub fn decode(&mut self, chunk: &[u8], output: &mut String) {
+        self.decode_inner(chunk, output, false);
+    }
+
+    /// Flush the decoder at end of stream
+    ///
+    /// A sequence still incomplete at this point is emitted as U+FFFD.
+    pub fn finish(&mut self, output: &mut String) {
+        self.decode_inner(&[], output, true);
+    }
+
+    fn decode_inner(&mut self, mut chunk: &[u8], output: &mut String, last: bool) {
+        loop {
+            if let Some(needed) = self.decoder.max_utf8_buffer_length(chunk.len()) {
+                output.reserve(needed);
+            }
+            let (result, read, _) = self.decoder.decode_to_string(chunk, output, last);
+            chunk = &chunk[read..];
+            if result == encoding_rs::CoderResult::InputEmpty {
+                break;
+            }
+            // OutputFull: the reservation was insufficient; grow and continue
+            output.reserve(chunk.len().max(4) * 3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multibyte_character_split_across_chunks() {
+        let text = "naïve – 日本 🎉";
+        let bytes = text.as_bytes();
+
+        // Every possible split point, including mid-character ones
+        for split in 0..=bytes.len() {
+            let mut decoder = Utf8StreamDecoder::new();
+            let mut output = String::new();
+            decoder.decode(&bytes[..split], &mut output);
+            decoder.decode(&bytes[split..], &mut output);
+            decoder.finish(&mut output);
+            assert_eq!(output, text, "split at byte {split}");
+        }
+    }
+
+    #[test]
+    fn test_four_byte_character_split_one_byte_at_a_time() {
+        let mut decoder = Utf8StreamDecoder::new();
+        let mut output = String::new();
+        for byte in "🎉".as_bytes() {
+            decoder.decode(std::slice::from_ref(byte), &mut output);
+        }
+        decoder.finish(&mut output);
+        assert_eq!(output, "🎉");
+    }
+
+    #[test]
+    fn test_invalid_and_truncated_sequences_are_replaced() {
+        let mut decoder = Utf8StreamDecoder::new();
+        let mut output = String::new();
+        decoder.decode(b"ok \xff ok ", &mut output);
+        // First two bytes of a three-byte sequence, then end of stream
+        decoder.decode(&"日".as_bytes()[..2], &mut output);
+        decoder.finish(&mut output);
+        assert_eq!(output, "ok \u{FFFD} ok \u{FFFD}");
+    }
+
+    #[test]
+    fn test_utf16_byte_order_mark_decoded_as_lossy_utf8() {
+        let mut decoder = Utf8StreamDecoder::new();
+        let mut output = String::new();
+        decoder.decode(b"\xFF\xFEhi\n", &mut output);
+        decoder.finish(&mut output);
+        assert_eq!(output, "\u{FFFD}\u{FFFD}hi\n");
+
+        let mut decoder = Utf8StreamDecoder::new();
+        let mut output = String::new();
+        decoder.decode(b"\xFE\xFFhi", &mut output);
+        decoder.finish(&mut output);
+        assert_eq!(output, "\u{FFFD}\u{FFFD}hi");
+    }
+
+    #[test]
+    fn test_utf8_byte_order_mark_kept() {
+        let mut decoder = Utf8StreamDecoder::new();
+        let mut output = String::new();
+        decoder.decode(b"\xEF\xBB", &mut output);
+        decoder.decode(b"\xBFhi", &mut output);
+        decoder.finish(&mut output);
+        assert_eq!(output, "\u{FEFF}hi");
+    }
+}
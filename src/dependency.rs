@@ -0,0 +1,280 @@
+//! Health check of an external service the worker's tasks depend on
+//!
+//! With `--dependency-check`, the worker probes the service before each job
+//! fetch and stops taking jobs (while still heartbeating) for as long as the
+//! probe fails, rather than failing every job that reaches the dead database
+//! or API. A `tcp://host:port` check passes when a connection is accepted; an
+//! `http://host[:port]/path` check when the response status is 2xx.
+
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::debug;
+
+/// Longest a single probe may take before the dependency counts as unhealthy
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Most response bytes read looking for the HTTP status line
+const MAX_STATUS_LINE_BYTES: usize = 1024;
+
+/// A `--dependency-check` target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DependencyCheck {
+    /// Healthy when a TCP connection to `address` succeeds
+    Tcp { address: String },
+    /// Healthy when `GET path` on `address` answers with a 2xx status
+    Http {
+        address: String,
+        host: String,
+        path: String,
+    },
+}
+
+impl DependencyCheck {
+    /// Parse a `tcp://host:port` or `http://host[:port][/path]` check
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for other schemes, a missing host or port, or a URL
+    /// containing whitespace
+    pub fn parse(url: &str) -> anyhow::Result<Self> {
+        if url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            anyhow::bail!("Dependency check URL must not contain whitespace");
+        }
+        if let Some(address) = url.strip_prefix("tcp://") {
+            let address = address.trim_end_matches('/');
+            if !has_host_and_port(address) {
+                anyhow::bail!("TCP dependency check must be tcp://host:port");
+            }
+            return Ok(Self::Tcp {
+                address: address.to_string(),
+            });
+        }
+        if let Some(rest) = url.strip_prefix("http://") {
+            let Some((address, host, path)) = split_http_url(rest) else {
+                anyhow::bail!("HTTP dependency check must be http://host[:port][/path]");
+            };
+            return Ok(Self::Http {
+                address,
+                host,
+                path,
+            });
+        }
+        anyhow::bail!("Dependency check must be a tcp:// or http:// URL (https is not supported)")
+    }
+
+    /// Whether the dependency is currently healthy
+    ///
+    /// Any failure, including exceeding `timeout`, counts as unhealthy.
+    pub async fn probe(&self, timeout: Duration) -> bool {
+        let result = tokio::time::timeout(timeout, self.probe_once()).await;
+        match result {
+            Ok(Ok(())) => true,
+            Ok(Err(reason)) => {
+                debug!("Dependency check {self} failed: {reason}");
+                false
+            }
+            Err(_) => {
+                debug!("Dependency check {self} timed out after {timeout:?}");
+                false
+            }
+        }
+    }
+
+    async fn probe_once(&self) -> Result<(), String> {
+        match self {
+            Self::Tcp { address } => TcpStream::connect(address)
+                .await
+                .map(drop)
+                .map_err(|e| e.to_string()),
+            Self::Http {
+                address,
+                host,
+                path,
+            } => {
+                let mut stream = TcpStream::connect(address)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let request =
+                    format!("GET {path} HTTP/1.0\r\nHost: {host}\r\nConnection: close\r\n\r\n");
+                stream
+                    .write_all(request.as_bytes())
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let status = read_status(&mut stream).await?;
+                if (200..300).contains(&status) {
+                    Ok(())
+                } else {
+                    Err(format!("HTTP status {status}"))
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for DependencyCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tcp { address } => write!(f, "tcp://{address}"),
+            Self::Http { host, path, .. } => write!(f, "http://{host}{path}"),
+        }
+    }
+}
+
+fn has_host_and_port(address: &str) -> bool {
+    address
+        .rsplit_once(':')
+        .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+}
+
+/// Split the part of an `http://` URL after the scheme into the address to
+/// connect to (port 80 unless given), the `Host` header and the request path
+///
+/// Returns `None` for a missing host or one with credentials.
+pub(crate) fn split_http_url(rest: &str) -> Option<(String, String, String)> {
+    let (host, path) = match rest.find('/') {
+        Some(slash) => (&rest[..slash], &rest[slash..]),
+        None => (rest, "/"),
+    };
+    if host.is_empty() || host.contains('@') {
+        return None;
+    }
+    let address = if has_host_and_port(host) {
+        host.to_string()
+    } else {
+        format!("{host}:80")
+    };
+    Some((address, host.to_string(), path.to_string()))
+}
+
+/// Read the status code from an HTTP response's status line
+pub(crate) async fn read_status(stream: &mut TcpStream) -> Result<u16, String> {
+    let mut response = Vec::new();
+    let mut buf = [0u8; 256];
+    while !response.contains(&b'\n') && response.len() < MAX_STATUS_LINE_BYTES {
+        let n = stream.read(&mut buf).await.map_err(|e| e.to_string())?;
+        if n == 0 {
+            break;
+        }
+        response.extend_from_slice(&buf[..n]);
+    }
+    let line = String::from_utf8_lossy(&response);
+    let mut parts = line.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some(version), Some(code)) if version.starts_with("HTTP/") => code
+            .parse()
+            .map_err(|_| format!("invalid HTTP status {code:?}")),
+        _ => Err("malformed HTTP response".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU16, Ordering};
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    /// HTTP server answering every request with the current `status`
+    async fn health_server(status: u16) -> (String, Arc<AtomicU16>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let status = Arc::new(AtomicU16::new(status));
+        let current = Arc::clone(&status);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let code = current.load(Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 1024];
+                    let _ = stream.read(&mut buf).await;
+                    let response = format!("HTTP/1.1 {code} Status\r\nContent-Length: 0\r\n\r\n");
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        (address, status)
+    }
+
+    /// An address nothing is listening on
+    async fn closed_address() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            DependencyCheck::parse("tcp://db:5432").unwrap(),
+            DependencyCheck::Tcp {
+                address: "db:5432".to_string()
+            }
+        );
+        assert_eq!(
+            DependencyCheck::parse("http://svc/health").unwrap(),
+            DependencyCheck::Http {
+                address: "svc:80".to_string(),
+                host: "svc".to_string(),
+                path: "/health".to_string(),
+            }
+        );
+        assert_eq!(
+            DependencyCheck::parse("http://svc:8080").unwrap(),
+            DependencyCheck::Http {
+                address: "svc:8080".to_string(),
+                host: "svc:8080".to_string(),
+                path: "/".to_string(),
+            }
+        );
+        assert_eq!(
+            DependencyCheck::parse("http://svc/health")
+                .unwrap()
+                .to_string(),
+            "http://svc/health"
+        );
+
+        for invalid in [
+            "https://svc/health",
+            "svc:5432",
+            "tcp://db",
+            "tcp://:5432",
+            "http:///health",
+            "http://user@svc/health",
+            "http://svc/he alth",
+        ] {
+            assert!(DependencyCheck::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tcp_probe() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let up = DependencyCheck::parse(&format!("tcp://{}", listener.local_addr().unwrap()));
+        assert!(up.unwrap().probe(PROBE_TIMEOUT).await);
+
+        let down = DependencyCheck::parse(&format!("tcp://{}", closed_address().await));
+        assert!(!down.unwrap().probe(PROBE_TIMEOUT).await);
+    }
+
+    #[tokio::test]
+    async fn test_http_probe_requires_2xx() {
+        let (address, status) = health_server(200).await;
+        let check = DependencyCheck::parse(&format!("http://{address}/health")).unwrap();
+        assert!(check.probe(PROBE_TIMEOUT).await);
+
+        status.store(503, Ordering::SeqCst);
+        assert!(!check.probe(PROBE_TIMEOUT).await);
+
+        status.store(204, Ordering::SeqCst);
+        assert!(check.probe(PROBE_TIMEOUT).await);
+    }
+
+    #[tokio::test]
+    async fn test_probe_times_out() {
+        // Accepts connections but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let check =
+            DependencyCheck::parse(&format!("http://{}/health", listener.local_addr().unwrap()))
+                .unwrap();
+        assert!(!check.probe(Duration::from_millis(100)).await);
+    }
+}
@@ -0,0 +1,148 @@
+//! `agw enqueue`: submit a job for local development and testing
+//!
+//! Writes the job metadata the worker's fetch path expects (`job:<id>`) and
+//! pushes the job ID onto the ready queue for its priority (`queue:ready`, or
+//! `queue:ready:high`/`queue:ready:low`; `{queue}:*` under `--cluster`), where
+//! a running worker picks it up.
+//! The worker uses the same path to enqueue a plan's `on_success_enqueue` follow-up.
+
+use crate::config::EnqueueArgs;
+use crate::error::{AgwError, AgwResult};
+use crate::plan::Job;
+use crate::resp::RespClient;
+use tracing::info;
+use uuid::Uuid;
+
+/// Create a job for `args.plan_id` and push it onto the ready queue
+///
+/// Returns the job ID.
+///
+/// # Errors
+///
+/// Returns an error if the input is not a JSON object, the job fails
+/// validation, or a RESP command fails
+pub async fn enqueue(client: &mut RespClient, args: &EnqueueArgs) -> AgwResult<String> {
+    let input: serde_json::Value = serde_json::from_str(&args.input)
+        .map_err(|e| AgwError::InvalidConfig(format!("Invalid --input JSON: {e}")))?;
+    if !input.is_object() {
+        return Err(AgwError::InvalidConfig(
+            "--input must be a JSON object".to_string(),
+        ));
+    }
+
+    let job = Job {
+        job_id: args
+            .job_id
+            .clone()
+            .unwrap_or_else(|| Uuid::new_v4().to_string()),
+        plan_id: args.plan_id.clone(),
+        input,
+        input_ref: None,
+        request_id: None,
+        priority: args.priority,
+        chain_depth: 0,
+        status: "pending".to_string(),
+    };
+    if job.job_id.contains(':') {
+        return Err(AgwError::InvalidConfig(format!(
+            "Job ID cannot contain colons: {}",
+            job.job_id
+        )));
+    }
+
+    push_job(client, &job).await?;
+    Ok(job.job_id)
+}
+
+/// Store a job's metadata and push its ID onto the ready queue for its priority
+///
+/// # Errors
+///
+/// Returns an error if the job fails validation or a RESP command fails
+pub async fn push_job(client: &mut RespClient, job: &Job) -> AgwResult<()> {
+    job.validate()?;
+
+    let job_json = serde_json::to_string(job)
+        .map_err(|e| AgwError::Worker(format!("Failed to serialize job: {e}")))?;
+    client.job_set(&job.job_id, &job_json).await?;
+    let ready = client.queue_keys().ready_for(job.priority);
+    client.lpush(ready, &job.job_id).await?;
+
+    info!("Enqueued job {} for plan {}", job.job_id, job.plan_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_agq::MockAgq;
+    use crate::plan::JobPriority;
+    use crate::resp::QueueKeys;
+
+    const QUEUE_READY: &str = QueueKeys::STANDALONE.ready;
+    const QUEUE_READY_HIGH: &str = QueueKeys::STANDALONE.ready_high;
+
+    fn args(input: &str) -> EnqueueArgs {
+        EnqueueArgs {
+            plan_id: "plan-1".to_string(),
+            input: input.to_string(),
+            job_id: None,
+            priority: JobPriority::Normal,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_writes_job_and_pushes_id() {
+        let mock = MockAgq::start(None).await;
+        let mut client = RespClient::connect(&mock.address).await.unwrap();
+
+        let job_id = enqueue(&mut client, &args(r#"{"path":"/tmp"}"#))
+            .await
+            .unwrap();
+
+        let job = Job::from_json(&mock.get(&format!("job:{job_id}")).unwrap()).unwrap();
+        assert_eq!(job.job_id, job_id);
+        assert_eq!(job.plan_id, "plan-1");
+        assert_eq!(job.input, serde_json::json!({"path": "/tmp"}));
+        assert_eq!(job.status, "pending");
+        assert_eq!(mock.list(QUEUE_READY), vec![job_id.clone()]);
+
+        // The worker's fetch path reads the same key
+        assert_eq!(
+            Job::from_json(&client.job_get(&job_id).await.unwrap().unwrap()).unwrap(),
+            job
+        );
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_pushes_to_priority_queue() {
+        let mock = MockAgq::start(None).await;
+        let mut client = RespClient::connect(&mock.address).await.unwrap();
+
+        let high = EnqueueArgs {
+            priority: JobPriority::High,
+            ..args("{}")
+        };
+        let job_id = enqueue(&mut client, &high).await.unwrap();
+
+        assert_eq!(mock.list(QUEUE_READY_HIGH), vec![job_id.clone()]);
+        assert!(mock.list(QUEUE_READY).is_empty());
+        let job = Job::from_json(&mock.get(&format!("job:{job_id}")).unwrap()).unwrap();
+        assert_eq!(job.priority, JobPriority::High);
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_rejects_invalid_input() {
+        let mock = MockAgq::start(None).await;
+        let mut client = RespClient::connect(&mock.address).await.unwrap();
+
+        assert!(enqueue(&mut client, &args("not json")).await.is_err());
+        assert!(enqueue(&mut client, &args("[1, 2]")).await.is_err());
+        let with_colon = EnqueueArgs {
+            job_id: Some("job:1".to_string()),
+            ..args("{}")
+        };
+        assert!(enqueue(&mut client, &with_colon).await.is_err());
+        assert!(mock.list(QUEUE_READY).is_empty());
+    }
+}
@@ -24,6 +24,31 @@ pub enum AgwError {
     #[error("Executor error: {0}")]
     Executor(String),
 
+    #[error("Executor error: Failed to spawn command '{command}' ({kind}): {source}")]
+    Spawn {
+        /// Command as written in the task
+        command: String,
+        /// Classification of the underlying OS error
+        kind: SpawnFailure,
+        /// The error returned by the OS
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// A job failure whose cause is known, reported as its [`FailureReason`]
+    #[error("Worker error: {message}")]
+    Job {
+        reason: FailureReason,
+        message: String,
+    },
+
+    #[error("Heartbeat failed ({kind}): {message}")]
+    Heartbeat {
+        /// What kind of failure it was, which decides the remediation
+        kind: HeartbeatFailure,
+        message: String,
+    },
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -31,4 +56,191 @@ pub enum AgwError {
     Redis(#[from] redis::RedisError),
 }
 
+impl AgwError {
+    /// Classification of a spawn failure, if this error is one
+    #[must_use]
+    pub fn spawn_failure(&self) -> Option<SpawnFailure> {
+        match self {
+            Self::Spawn { kind, .. } => Some(*kind),
+            _ => None,
+        }
+    }
+
+    /// Why a job failed, if this error tells
+    #[must_use]
+    pub fn failure_reason(&self) -> Option<FailureReason> {
+        match self {
+            Self::Spawn { .. } => Some(FailureReason::SpawnFailed),
+            Self::Job { reason, .. } => Some(*reason),
+            _ => None,
+        }
+    }
+
+    /// This error as a job failure with `reason`, keeping its message
+    #[must_use]
+    pub fn with_reason(self, reason: FailureReason) -> Self {
+        let message = match self {
+            Self::Worker(message) | Self::Job { message, .. } => message,
+            other => other.to_string(),
+        };
+        Self::Job { reason, message }
+    }
+
+    /// Classification of a heartbeat failure, if this error is one
+    #[must_use]
+    pub fn heartbeat_failure(&self) -> Option<HeartbeatFailure> {
+        match self {
+            Self::Heartbeat { kind, .. } => Some(*kind),
+            _ => None,
+        }
+    }
+}
+
+/// Why a task's process could not be spawned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnFailure {
+    /// The executable does not exist (ENOENT)
+    NotFound,
+    /// The executable is not executable by the worker user (EACCES)
+    PermissionDenied,
+    /// A process limit was hit (EAGAIN); retrying may succeed
+    ResourceLimit,
+    /// There was not enough memory to start the process (ENOMEM)
+    OutOfMemory,
+    /// Any other OS error
+    Other,
+}
+
+impl SpawnFailure {
+    /// Classify the error returned by `Command::spawn`
+    #[must_use]
+    pub fn classify(error: &std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::NotFound => Self::NotFound,
+            std::io::ErrorKind::PermissionDenied => Self::PermissionDenied,
+            // EAGAIN maps to WouldBlock
+            std::io::ErrorKind::WouldBlock => Self::ResourceLimit,
+            std::io::ErrorKind::OutOfMemory => Self::OutOfMemory,
+            _ => Self::Other,
+        }
+    }
+
+    /// Whether spawning again later may succeed
+    #[must_use]
+    pub fn is_transient(self) -> bool {
+        self == Self::ResourceLimit
+    }
+
+    /// Conventional errno name for the classification
+    #[must_use]
+    pub fn errno_name(self) -> &'static str {
+        match self {
+            Self::NotFound => "ENOENT",
+            Self::PermissionDenied => "EACCES",
+            Self::ResourceLimit => "EAGAIN",
+            Self::OutOfMemory => "ENOMEM",
+            Self::Other => "other",
+        }
+    }
+}
+
+impl std::fmt::Display for SpawnFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.errno_name())
+    }
+}
+
+/// Why a heartbeat round trip to AGQ failed
+///
+/// Each kind points at a different fix: the network path, the AGQ version, or
+/// the session key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeartbeatFailure {
+    /// The connection was refused, reset or timed out
+    Network,
+    /// AGQ answered with an error or a reply that is not a PING reply
+    Protocol,
+    /// AGQ no longer accepts the session (NOAUTH, or a rejected key on re-auth)
+    AuthExpired,
+}
+
+impl HeartbeatFailure {
+    /// Classify the error returned by the heartbeat's `PING`
+    #[must_use]
+    pub fn classify(error: &redis::RedisError) -> Self {
+        if error.kind() == redis::ErrorKind::AuthenticationFailed
+            || matches!(error.code(), Some("NOAUTH" | "WRONGPASS"))
+        {
+            Self::AuthExpired
+        } else if error.is_io_error() {
+            Self::Network
+        } else {
+            Self::Protocol
+        }
+    }
+
+    /// Label used in logs and the `agw_heartbeat_failures_total` metric
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Network => "network",
+            Self::Protocol => "protocol",
+            Self::AuthExpired => "auth_expired",
+        }
+    }
+}
+
+impl std::fmt::Display for HeartbeatFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Why a job failed, posted as `job:<id>:failure_reason`
+///
+/// A controlled vocabulary, so consumers can alert on and retry jobs without
+/// parsing the free-text stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureReason {
+    /// A task's command could not be started
+    SpawnFailed,
+    /// A task exited with a non-zero code
+    NonZeroExit,
+    /// A task ran past its `timeout_secs` and was stopped
+    Timeout,
+    /// A task was terminated by a signal it was not sent for a timeout
+    Killed,
+    /// The plan, the job's input or a task's output failed validation
+    ValidationError,
+    /// Input or task output could not be substituted into a task's arguments
+    SubstitutionError,
+    /// The job's plan does not exist
+    PlanNotFound,
+    /// The job, or its failing task, was not run
+    Cancelled,
+}
+
+impl FailureReason {
+    /// Code stored in `job:<id>:failure_reason`
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::SpawnFailed => "spawn_failed",
+            Self::NonZeroExit => "non_zero_exit",
+            Self::Timeout => "timeout",
+            Self::Killed => "killed",
+            Self::ValidationError => "validation_error",
+            Self::SubstitutionError => "substitution_error",
+            Self::PlanNotFound => "plan_not_found",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl std::fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 pub type AgwResult<T> = Result<T, AgwError>;
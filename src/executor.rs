@@ -1,15 +1,36 @@
 // Allow module inception - this is a common Rust pattern for protocol clients
 #![allow(clippy::module_name_repetitions)]
 
-use crate::error::{AgwError, AgwResult};
-use crate::plan::{Plan, Task};
+use crate::backoff::{Backoff, BackoffStrategy};
+use crate::base64;
+use crate::checkpoint::CheckpointStore;
+use crate::command_cache::COMMAND_CACHE;
+use crate::decode::Utf8StreamDecoder;
+use crate::error::{AgwError, AgwResult, FailureReason, SpawnFailure};
+use crate::fd_guard::FdGuard;
+use crate::manifest::ConfigSnapshot;
+use crate::metrics::{METRICS, RESULT_FAILURE, RESULT_SUCCESS};
+use crate::output_store::OutputStore;
+use crate::plan::{ExecutionStrategy, JsonFormat, OutputMode, Plan, StdinMode, Task};
+use crate::progress::JobProgress;
+use crate::redact::Redactor;
+use crate::webhook::Webhook;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::process::Stdio;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader, ReadBuf};
 use tokio::process::Command;
 use tracing::{debug, error, info, warn};
 
 /// Result of a single task execution
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TaskResult {
     /// Task number that was executed
     pub task_number: u32,
@@ -21,10 +42,207 @@ pub struct TaskResult {
     pub exit_code: i32,
     /// Whether execution was successful (exit code 0)
     pub success: bool,
+    /// Whether stdout or stderr was truncated at the output cap
+    #[serde(default)]
+    pub output_truncated: bool,
+    /// Whether the task ran longer than its soft deadline
+    #[serde(default)]
+    pub exceeded_soft_deadline: bool,
+    /// Whether the task was not run because its `input_from_task` upstream failed
+    #[serde(default)]
+    pub skipped: bool,
+    /// Program and arguments handed to the OS, after alias resolution and
+    /// substitution, with `--redact-pattern` matches masked (empty if not run)
+    ///
+    /// The program is the resolved absolute path when command caching is on;
+    /// otherwise it is the bare name the OS looks up on `PATH`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub executed_argv: Vec<String>,
+    /// Stderr lines matching a `--warning-pattern`, moved out of `stderr`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// Wall time the task ran, in milliseconds (0 if not run)
+    #[serde(default)]
+    pub duration_ms: u64,
+    /// Whether the task was stopped for running past its `timeout_secs`
+    #[serde(default)]
+    pub timed_out: bool,
+    /// Signal that terminated the task's process (Unix only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal: Option<i32>,
+}
+
+/// Environment variable holding the path of the job input file for `input_as_file` tasks
+pub const INPUT_FILE_ENV: &str = "AGW_INPUT_FILE";
+
+/// Default time a timed-out task gets to exit after SIGTERM before it is killed
+pub const DEFAULT_KILL_GRACE: Duration = Duration::from_secs(5);
+
+/// Delay between attempts of a task with `retries`
+const TASK_RETRY_BACKOFF: Backoff = Backoff::new(
+    BackoffStrategy::Exponential,
+    Duration::from_millis(100),
+    Duration::from_secs(5),
+);
+
+/// Default size of the buffer used to read task output streams
+pub const DEFAULT_READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// What happens to a job whose plan runs a command the worker did not register
+/// (`--enforce-registered-tools`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum UnregisteredTools {
+    /// Hand the job back to its ready queue for a worker that has the tools
+    #[default]
+    Requeue,
+    /// Post the job as errored without running any of it
+    Fail,
+}
+
+/// How task output streams are read
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReadMode {
+    /// Read line by line, normalizing line endings to `\n` (lowest latency)
+    #[default]
+    Lines,
+    /// Read in large chunks, preserving the exact bytes (fewer syscalls for big outputs)
+    Chunks,
+}
+
+/// Worker-wide execution settings applied to every task
+///
+/// Per-task fields on [`Task`] take precedence over these defaults.
+#[derive(Debug, Clone)]
+pub struct ExecutionOptions {
+    /// Cap on captured bytes per output stream (`None` = unlimited)
+    pub max_output_bytes: Option<usize>,
+    /// Task outputs held for downstream tasks are compressed past this many
+    /// bytes (`None` = never)
+    pub compress_outputs_above: Option<usize>,
+    /// Cap on captured lines per output stream in [`ReadMode::Lines`] (`None` = unlimited)
+    pub max_output_lines: Option<usize>,
+    /// Whether shell-mode tasks may run (`--allow-shell`)
+    pub allow_shell: bool,
+    /// How long a command's resolved executable path is cached (zero = no caching)
+    pub command_cache_ttl: Duration,
+    /// Logical tool name -> binary actually spawned on this host
+    pub tool_aliases: HashMap<String, String>,
+    /// How task stdout/stderr are read
+    pub read_mode: ReadMode,
+    /// Buffer size (and chunk size in [`ReadMode::Chunks`]) for reading output
+    pub read_buffer_size: usize,
+    /// Time between SIGTERM and SIGKILL for a timed-out task (zero = kill at once)
+    pub kill_grace: Duration,
+    /// Checkpoint each task's result so a re-executed job resumes where it stopped
+    pub checkpoint_tasks: bool,
+    /// Only directory commands are resolved from, also set as the child's `PATH`
+    /// (`None` = inherited `PATH`)
+    pub tool_path: Option<PathBuf>,
+    /// Masks secrets in the recorded [`TaskResult::executed_argv`]
+    pub redactor: Redactor,
+    /// Stderr lines matching any of these are reported as warnings, not stderr
+    pub warning_patterns: Vec<Regex>,
+    /// Tools this worker registered with AGQ (`--tools`), enforced under
+    /// `--enforce-registered-tools` (`None` = no check)
+    pub registered_tools: Option<HashSet<String>>,
+    /// What happens to jobs whose plan runs a command not in `registered_tools`
+    pub unregistered_tools: UnregisteredTools,
+    /// Soft cap on descriptors a job holds; spawns wait while a task's estimate
+    /// would exceed it (`None` = no cap)
+    pub max_fds_per_job: Option<usize>,
+    /// Re-run non-idempotent tasks on resume even if they may already have run
+    pub force_reexec: bool,
+    /// Where finished plan results are also written as JSON lines
+    /// (`--emit-results-stdout`; `None` = only posted to AGQ)
+    pub result_sink: Option<ResultSink>,
+    /// Worker settings recorded in each job's manifest (`--manifest-config`;
+    /// `None` = not recorded)
+    pub manifest_config: Option<ConfigSnapshot>,
+    /// Records the task being executed, for the heartbeat to post (`None` = not tracked)
+    pub progress: Option<JobProgress>,
+    /// Notified once each job's results are posted (`--webhook-url`)
+    pub webhook: Option<Webhook>,
+}
+
+impl Default for ExecutionOptions {
+    fn default() -> Self {
+        Self {
+            max_output_bytes: None,
+            compress_outputs_above: None,
+            max_output_lines: None,
+            allow_shell: false,
+            command_cache_ttl: Duration::ZERO,
+            tool_aliases: HashMap::new(),
+            read_mode: ReadMode::default(),
+            read_buffer_size: DEFAULT_READ_BUFFER_SIZE,
+            kill_grace: DEFAULT_KILL_GRACE,
+            checkpoint_tasks: false,
+            tool_path: None,
+            redactor: Redactor::default(),
+            warning_patterns: Vec::new(),
+            registered_tools: None,
+            unregistered_tools: UnregisteredTools::default(),
+            max_fds_per_job: None,
+            force_reexec: false,
+            result_sink: None,
+            manifest_config: None,
+            progress: None,
+            webhook: None,
+        }
+    }
+}
+
+/// Line-delimited JSON output of finished [`PlanResult`]s
+///
+/// Each result is written as a single line and flushed, so a consumer such as
+/// `agw --emit-results-stdout | jq` sees it as soon as the job is done.
+#[derive(Clone)]
+pub struct ResultSink {
+    writer: Arc<Mutex<dyn Write + Send>>,
+}
+
+impl std::fmt::Debug for ResultSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResultSink").finish_non_exhaustive()
+    }
+}
+
+impl ResultSink {
+    /// Sink writing to the process's stdout
+    #[must_use]
+    pub fn stdout() -> Self {
+        Self::new(std::io::stdout())
+    }
+
+    /// Sink writing to `writer`
+    #[must_use]
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Arc::new(Mutex::new(writer)),
+        }
+    }
+
+    /// Write `result` as one JSON line
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization or the write fails
+    pub fn emit(&self, result: &PlanResult) -> AgwResult<()> {
+        let mut line = serde_json::to_vec(result)
+            .map_err(|e| AgwError::Worker(format!("Failed to serialize plan result: {e}")))?;
+        line.push(b'\n');
+        let mut writer = self
+            .writer
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        writer.write_all(&line)?;
+        writer.flush()?;
+        Ok(())
+    }
 }
 
 /// Result of entire plan execution
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct PlanResult {
     /// Job ID that was executed
     pub job_id: String,
@@ -46,11 +264,56 @@ impl TaskResult {
             stderr,
             exit_code,
             success: exit_code == 0,
+            output_truncated: false,
+            exceeded_soft_deadline: false,
+            skipped: false,
+            executed_argv: Vec::new(),
+            warnings: Vec::new(),
+            duration_ms: 0,
+            timed_out: false,
+            signal: None,
+        }
+    }
+
+    /// Why the task failed (`None` if it succeeded)
+    #[must_use]
+    pub fn failure_reason(&self) -> Option<FailureReason> {
+        if self.success {
+            None
+        } else if self.skipped {
+            Some(FailureReason::Cancelled)
+        } else if self.timed_out {
+            Some(FailureReason::Timeout)
+        } else if self.signal.is_some() {
+            Some(FailureReason::Killed)
+        } else if self.exit_code != 0 {
+            Some(FailureReason::NonZeroExit)
+        } else {
+            // Exited cleanly but its output was rejected (`expect_json`)
+            Some(FailureReason::ValidationError)
+        }
+    }
+
+    /// Create a failed result for a task that was not run, with `reason` as its stderr
+    #[must_use]
+    pub fn skipped(task_number: u32, reason: &str) -> Self {
+        Self {
+            success: false,
+            skipped: true,
+            ..Self::new(task_number, String::new(), format!("{reason}\n"), -1)
         }
     }
 }
 
 impl PlanResult {
+    /// Why the plan failed: the reason of its first failed task (`None` if it succeeded)
+    #[must_use]
+    pub fn failure_reason(&self) -> Option<FailureReason> {
+        self.task_results
+            .iter()
+            .find_map(TaskResult::failure_reason)
+    }
+
     /// Create a new plan result
     #[must_use]
     pub fn new(job_id: String, plan_id: String, task_results: Vec<TaskResult>) -> Self {
@@ -76,6 +339,33 @@ impl PlanResult {
             .collect::<String>()
     }
 
+    /// Primary result to post for the job, shaped by the plan's `output_mode`
+    ///
+    /// Tasks skipped because their upstream failed produced no output and are
+    /// left out of `last_task_only` and `per_task`.
+    #[must_use]
+    pub fn primary_output(&self, mode: OutputMode) -> String {
+        let executed = self.task_results.iter().filter(|r| !r.skipped);
+        match mode {
+            OutputMode::Concatenated => self.combined_stdout(),
+            OutputMode::LastTaskOnly => executed
+                .last()
+                .map(|r| r.stdout.clone())
+                .unwrap_or_default(),
+            OutputMode::PerTask => {
+                let outputs: serde_json::Map<String, serde_json::Value> = executed
+                    .map(|r| {
+                        (
+                            r.task_number.to_string(),
+                            serde_json::Value::String(r.stdout.clone()),
+                        )
+                    })
+                    .collect();
+                serde_json::Value::Object(outputs).to_string()
+            }
+        }
+    }
+
     /// Combine stderr from all tasks
     ///
     /// Task outputs already contain trailing newlines from command execution,
@@ -88,9 +378,44 @@ impl PlanResult {
             .map(|r| r.stderr.as_str())
             .collect::<String>()
     }
+
+    /// Combine warnings from all tasks, one per line
+    #[must_use]
+    pub fn combined_warnings(&self) -> String {
+        let mut combined = String::new();
+        for warning in self.task_results.iter().flat_map(|r| &r.warnings) {
+            combined.push_str(warning);
+            combined.push('\n');
+        }
+        combined
+    }
+}
+
+/// Execute an entire plan sequentially, without checkpoints
+///
+/// # Errors
+///
+/// Returns an error under the same conditions as [`execute_plan_checkpointed`]
+#[allow(dead_code)] // Used in tests; the worker goes through execute_plan_checkpointed
+pub async fn execute_plan(
+    job_id: &str,
+    plan: &Plan,
+    input: &serde_json::Value,
+    options: &ExecutionOptions,
+) -> AgwResult<PlanResult> {
+    execute_plan_checkpointed(job_id, plan, input, options, None).await
 }
 
-/// Execute an entire plan sequentially
+/// Execute an entire plan sequentially, restoring and storing per-task
+/// checkpoints in `checkpoints`
+///
+/// Leading tasks with a successful checkpoint are not run again: their stored
+/// results are reused (including as stdin for later tasks) and execution
+/// resumes at the first task without one. Every task run is checkpointed.
+///
+/// With [`ExecutionStrategy::HaltOnFailure`] (the default) execution stops at the
+/// first failed task and partial results are returned; with
+/// [`ExecutionStrategy::RunAll`] every task is executed and the plan fails if any did.
 ///
 /// # Errors
 ///
@@ -99,14 +424,32 @@ impl PlanResult {
 /// - IO operations fail while reading/writing stdout/stderr
 /// - Timeout is exceeded
 /// - Process cannot be killed after timeout
+pub async fn execute_plan_checkpointed(
+    job_id: &str,
+    plan: &Plan,
+    input: &serde_json::Value,
+    options: &ExecutionOptions,
+    checkpoints: Option<&mut CheckpointStore>,
+) -> AgwResult<PlanResult> {
+    let mut outputs = OutputStore::new(options.compress_outputs_above);
+    execute_plan_with_outputs(job_id, plan, input, options, checkpoints, &mut outputs).await
+}
+
+/// Execute a plan, holding each finished task's stdout in `previous_outputs`
+/// until the plan is done
 ///
-/// # Panics
-///
-/// This function will not panic under normal conditions. The unwrap at line 111
-/// is safe because `task_results` is guaranteed to be non-empty when we check success.
+/// # Errors
 ///
-/// Note: This function will halt on first failure and return partial results
-pub async fn execute_plan(job_id: &str, plan: &Plan) -> AgwResult<PlanResult> {
+/// Returns an error under the same conditions as [`execute_plan_checkpointed`],
+/// or if a held output fails to decompress
+async fn execute_plan_with_outputs(
+    job_id: &str,
+    plan: &Plan,
+    input: &serde_json::Value,
+    options: &ExecutionOptions,
+    mut checkpoints: Option<&mut CheckpointStore>,
+    previous_outputs: &mut OutputStore,
+) -> AgwResult<PlanResult> {
     info!(
         "Executing plan {} (job {}) with {} tasks",
         plan.plan_id,
@@ -114,34 +457,162 @@ pub async fn execute_plan(job_id: &str, plan: &Plan) -> AgwResult<PlanResult> {
         plan.tasks.len()
     );
 
+    // Stdout moves to `previous_outputs` while the plan runs, and back at the end
     let mut task_results = Vec::new();
-    let mut previous_outputs: std::collections::HashMap<u32, String> =
-        std::collections::HashMap::new();
+    // Tasks that failed or were skipped (only reachable with RunAll)
+    let mut failed_tasks = std::collections::HashSet::new();
+    let mut resuming = checkpoints.is_some();
+    let fd_guard = options.max_fds_per_job.map(FdGuard::new);
 
     for task in &plan.tasks {
+        if resuming {
+            if let Some(store) = checkpoints.as_deref_mut() {
+                if let Some(mut result) = store.load_successful(task.task_number).await {
+                    info!("Task {} restored from checkpoint", task.task_number);
+                    previous_outputs.insert(task.task_number, std::mem::take(&mut result.stdout));
+                    task_results.push(result);
+                    continue;
+                }
+            }
+            resuming = false;
+        }
+
+        // Running on a failed upstream's stdout would silently use partial or empty input
+        let input_tasks = task.input_tasks();
+        if let Some(upstream) = input_tasks
+            .iter()
+            .find(|upstream| failed_tasks.contains(*upstream))
+        {
+            let reason = format!(
+                "Task {} skipped: {} {upstream} failed",
+                task.task_number,
+                task.input_field()
+            );
+            warn!("{reason}");
+            failed_tasks.insert(task.task_number);
+            task_results.push(TaskResult::skipped(task.task_number, &reason));
+            continue;
+        }
+
+        // Same for arguments filled in from a failed upstream's stdout
+        let output_references = task.output_references();
+        if let Some(upstream) = output_references
+            .iter()
+            .find(|upstream| failed_tasks.contains(*upstream))
+        {
+            let reason = format!(
+                "Task {} skipped: arguments reference output of failed task {upstream}",
+                task.task_number
+            );
+            warn!("{reason}");
+            failed_tasks.insert(task.task_number);
+            task_results.push(TaskResult::skipped(task.task_number, &reason));
+            continue;
+        }
+
+        // Resolve {{task.N.stdout}} now that the referenced tasks have run
+        let resolved;
+        let task = if output_references.is_empty() {
+            task
+        } else {
+            let outputs = previous_outputs.outputs_of(&output_references)?;
+            resolved = task
+                .substitute_task_outputs(&outputs)
+                .map_err(|e| e.with_reason(FailureReason::SubstitutionError))?;
+            &resolved
+        };
+
+        // Tasks inherit the plan's environment, their own entries winning
+        let inherited;
+        let task = if plan.env.is_empty() {
+            task
+        } else {
+            inherited = task.inheriting_env(&plan.env);
+            &inherited
+        };
+
         info!("Executing task {}: {}", task.task_number, task.command);
+        if let Some(progress) = &options.progress {
+            progress.task_started(task.task_number);
+        }
+
+        // Get input from previous tasks if specified, concatenated in order
+        // (binary output as its raw bytes); an empty stdin is a pipe closed at once
+        let stdin_input = match task.stdin_mode() {
+            StdinMode::Null => None,
+            StdinMode::Empty => Some(Vec::new()),
+            StdinMode::FromTask => {
+                let mut stdin = Vec::new();
+                for task_num in &input_tasks {
+                    let Some(output) = previous_outputs.get(*task_num)? else {
+                        continue;
+                    };
+                    let binary = plan.tasks.iter().any(|upstream| {
+                        upstream.task_number == *task_num && upstream.encode_output_base64
+                    });
+                    match binary.then(|| base64::decode_output(&output)).flatten() {
+                        Some(bytes) => stdin.extend_from_slice(&bytes),
+                        None => stdin.extend_from_slice(output.as_bytes()),
+                    }
+                }
+                Some(stdin)
+            }
+        };
+
+        // A task that must not run twice is not repeated on resume
+        if !task.idempotent {
+            if let Some(store) = checkpoints.as_deref_mut() {
+                if !options.force_reexec && store.may_have_run(task.task_number).await {
+                    let reason = format!(
+                        "Task {} not re-run: it is not idempotent and may already have run",
+                        task.task_number
+                    );
+                    warn!("{reason} (--force-reexec runs it again)");
+                    failed_tasks.insert(task.task_number);
+                    task_results.push(TaskResult::skipped(task.task_number, &reason));
+                    if plan.execution_strategy == ExecutionStrategy::HaltOnFailure {
+                        break;
+                    }
+                    continue;
+                }
+                store.mark_started(task.task_number).await;
+            }
+        }
+
+        if let Some(guard) = &fd_guard {
+            guard.wait_for(task).await;
+        }
 
-        // Get input from previous task if specified
-        let input = task
-            .input_from_task
-            .and_then(|task_num| previous_outputs.get(&task_num).cloned());
+        match execute_task(task, stdin_input.as_deref(), input, options).await {
+            Ok(mut result) => {
+                if let Some(store) = checkpoints.as_deref_mut() {
+                    store.save(&result).await;
+                }
 
-        match execute_task(task, input.as_deref()).await {
-            Ok(result) => {
                 // Store stdout for potential use by later tasks
-                previous_outputs.insert(task.task_number, result.stdout.clone());
+                previous_outputs.insert(task.task_number, std::mem::take(&mut result.stdout));
 
                 let success = result.success;
                 task_results.push(result);
 
-                // Halt on first failure
                 if !success {
-                    warn!(
-                        "Task {} failed with exit code {}, halting plan execution",
-                        task.task_number,
-                        task_results.last().unwrap().exit_code
-                    );
-                    break;
+                    failed_tasks.insert(task.task_number);
+                    let exit_code = task_results.last().unwrap().exit_code;
+                    match plan.execution_strategy {
+                        ExecutionStrategy::HaltOnFailure => {
+                            warn!(
+                                "Task {} failed with exit code {exit_code}, halting plan execution",
+                                task.task_number
+                            );
+                            break;
+                        }
+                        ExecutionStrategy::RunAll => {
+                            warn!(
+                                "Task {} failed with exit code {exit_code}, continuing with remaining tasks",
+                                task.task_number
+                            );
+                        }
+                    }
                 }
             }
             Err(e) => {
@@ -151,6 +622,17 @@ pub async fn execute_plan(job_id: &str, plan: &Plan) -> AgwResult<PlanResult> {
         }
     }
 
+    for result in &mut task_results {
+        if let Some(stdout) = previous_outputs.take(result.task_number)? {
+            result.stdout = stdout;
+        }
+    }
+    debug!(
+        "Task outputs held for plan {} peaked at {} bytes",
+        plan.plan_id,
+        previous_outputs.peak_bytes()
+    );
+
     let plan_result = PlanResult::new(job_id.to_string(), plan.plan_id.clone(), task_results);
 
     info!(
@@ -163,7 +645,63 @@ pub async fn execute_plan(job_id: &str, plan: &Plan) -> AgwResult<PlanResult> {
     Ok(plan_result)
 }
 
-/// Execute a single task as a subprocess
+/// Execute a single task, running it again up to `task.retries` times while it fails
+///
+/// Each attempt of a task with retries is logged with its timing, so flaky
+/// tasks can be diagnosed. Errors are not retried, except a spawn that hit a
+/// process limit (EAGAIN), which may clear: a missing or non-executable
+/// command will not start on a second try either.
+///
+/// # Errors
+///
+/// Returns an error under the same conditions as [`execute_attempt`]
+async fn execute_task(
+    task: &Task,
+    stdin_input: Option<&[u8]>,
+    job_input: &serde_json::Value,
+    options: &ExecutionOptions,
+) -> AgwResult<TaskResult> {
+    let max_attempts = task.retries.saturating_add(1);
+    let mut attempt = 1;
+    loop {
+        let result = match execute_attempt(task, stdin_input, job_input, options).await {
+            Ok(result) => result,
+            Err(e)
+                if attempt < max_attempts
+                    && e.spawn_failure().is_some_and(SpawnFailure::is_transient) =>
+            {
+                warn!(
+                    task = task.task_number,
+                    attempt, max_attempts, "Task could not be spawned, retrying: {e}"
+                );
+                tokio::time::sleep(TASK_RETRY_BACKOFF.delay(attempt - 1)).await;
+                attempt += 1;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let will_retry = !result.success && attempt < max_attempts;
+        if task.retries > 0 {
+            info!(
+                task = task.task_number,
+                attempt,
+                max_attempts,
+                exit_code = result.exit_code,
+                duration_ms = result.duration_ms,
+                success = result.success,
+                will_retry,
+                "Task attempt finished"
+            );
+        }
+        if !will_retry {
+            return Ok(result);
+        }
+        tokio::time::sleep(TASK_RETRY_BACKOFF.delay(attempt - 1)).await;
+        attempt += 1;
+    }
+}
+
+/// Run a task once as a subprocess
 ///
 /// # Errors
 ///
@@ -172,7 +710,12 @@ pub async fn execute_plan(job_id: &str, plan: &Plan) -> AgwResult<PlanResult> {
 /// - IO operations fail while reading stdout/stderr
 /// - Timeout is exceeded
 /// - Process cannot be killed after timeout
-async fn execute_task(task: &Task, stdin_input: Option<&str>) -> AgwResult<TaskResult> {
+async fn execute_attempt(
+    task: &Task,
+    stdin_input: Option<&[u8]>,
+    job_input: &serde_json::Value,
+    options: &ExecutionOptions,
+) -> AgwResult<TaskResult> {
     debug!("Command: {} with args: {:?}", task.command, task.args);
 
     // Validate command is not empty
@@ -180,8 +723,54 @@ async fn execute_task(task: &Task, stdin_input: Option<&str>) -> AgwResult<TaskR
         return Err(AgwError::Executor("Command cannot be empty".to_string()));
     }
 
+    // Second line of defence: plan validation already rejects untrusted shell tasks
+    if task.shell && !options.allow_shell {
+        return Err(AgwError::Executor(format!(
+            "Task {} uses shell mode but shell execution is disabled",
+            task.task_number
+        )));
+    }
+
+    // A path in the command would bypass the pinned tool directory
+    if options.tool_path.is_some() && !task.shell && task.command.contains('/') {
+        return Err(AgwError::Executor(format!(
+            "Task {} command '{}' must be a bare name when a tool path is set",
+            task.task_number, task.command
+        )));
+    }
+
+    // Removed when dropped, so the file never outlives the task
+    let input_file = if task.input_as_file {
+        Some(InputFile::write(job_input)?)
+    } else {
+        None
+    };
+
+    let started = std::time::Instant::now();
+
+    // Host-specific binary name for the logical tool, if aliased
+    let program = options
+        .tool_aliases
+        .get(&task.command)
+        .map_or(task.command.as_str(), String::as_str);
+    if program != task.command {
+        debug!("Command '{}' aliased to '{program}'", task.command);
+    }
+
     // Spawn the process with piped stdout/stderr
-    let mut child = Command::new(&task.command)
+    let mut command = if task.shell {
+        // sh -c <script> sh <args...>: args are positional parameters, never re-parsed
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(&task.command).arg("sh");
+        command
+    } else {
+        let resolved = match &options.tool_path {
+            Some(dir) => COMMAND_CACHE.resolve_in(program, options.command_cache_ttl, dir),
+            None => COMMAND_CACHE.resolve(program, options.command_cache_ttl),
+        };
+        Command::new(resolved)
+    };
+    command
         .args(&task.args)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
@@ -191,16 +780,54 @@ async fn execute_task(task: &Task, stdin_input: Option<&str>) -> AgwResult<TaskR
             Stdio::null()
         })
         .kill_on_drop(true)
-        .spawn()
-        .map_err(|e| {
-            AgwError::Executor(format!("Failed to spawn command '{}': {}", task.command, e))
-        })?;
+        .envs(&task.env);
+
+    if let Some(file) = &input_file {
+        command.env(INPUT_FILE_ENV, file.path());
+    }
+
+    // Uncached and unresolved names are looked up in the child's PATH, so pin it too
+    if let Some(dir) = &options.tool_path {
+        command.env("PATH", dir);
+    }
+
+    // Ground truth of what runs, taken from the command itself
+    let std_command = command.as_std();
+    let executed_argv: Vec<String> = std::iter::once(std_command.get_program())
+        .chain(std_command.get_args())
+        .map(|arg| options.redactor.redact(&arg.to_string_lossy()).into_owned())
+        .collect();
+    debug!("Task {} argv: {executed_argv:?}", task.task_number);
+
+    if task.detach {
+        let mut result = spawn_detached(task, &mut command, program)?;
+        result.executed_argv = executed_argv;
+        return Ok(result);
+    }
+
+    let mut child = command.spawn().map_err(|source| {
+        // The cached path may have been removed or replaced since it was resolved
+        COMMAND_CACHE.invalidate(program);
+        let kind = SpawnFailure::classify(&source);
+        if program != task.command {
+            warn!(
+                "Aliased command '{}' -> '{program}' failed to spawn ({kind})",
+                task.command
+            );
+        }
+        record_task_metric(task, false);
+        AgwError::Spawn {
+            command: task.command.clone(),
+            kind,
+            source,
+        }
+    })?;
 
     // Write stdin if provided
     if let Some(input) = stdin_input {
         if let Some(mut stdin) = child.stdin.take() {
             stdin
-                .write_all(input.as_bytes())
+                .write_all(input)
                 .await
                 .map_err(|e| AgwError::Executor(format!("Failed to write stdin: {e}")))?;
             stdin
@@ -220,15 +847,49 @@ async fn execute_task(task: &Task, stdin_input: Option<&str>) -> AgwResult<TaskR
         .take()
         .ok_or_else(|| AgwError::Executor("Failed to capture stderr".to_string()))?;
 
-    // Create buffered readers
-    let stdout_reader = BufReader::new(stdout);
-    let stderr_reader = BufReader::new(stderr);
+    // Per-task output cap overrides the worker-wide default
+    let output_limit = task.max_output_bytes.or(options.max_output_bytes);
+    let line_limit = options.max_output_lines;
+    let (mode, buffer_size) = (options.read_mode, options.read_buffer_size);
+
+    // Live copy of stdout for external consumers, if requested and possible
+    let fifo = task
+        .fifo_output
+        .as_deref()
+        .and_then(|path| FifoSink::open(Path::new(path), task.task_number));
+    let stdout = TeeReader::new(stdout, fifo);
 
     // Spawn tasks to read stdout and stderr concurrently
-    let stdout_handle = tokio::spawn(read_stream(stdout_reader));
-    let stderr_handle = tokio::spawn(read_stream(stderr_reader));
+    let stdout_handle = if task.encode_output_base64 {
+        tokio::spawn(read_base64(stdout, buffer_size, output_limit))
+    } else {
+        tokio::spawn(read_output(
+            stdout,
+            mode,
+            buffer_size,
+            output_limit,
+            line_limit,
+        ))
+    };
+    let stderr_handle = tokio::spawn(read_output(
+        stderr,
+        mode,
+        buffer_size,
+        output_limit,
+        line_limit,
+    ));
+
+    // Warn while the task is still running once it passes its soft deadline
+    let soft_deadline_watch = task.soft_deadline_secs.map(|secs| {
+        let task_number = task.task_number;
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_secs(u64::from(secs))).await;
+            warn!("Task {task_number} exceeded soft deadline of {secs}s and is still running");
+        })
+    });
 
     // Wait for process with optional timeout
+    let mut timed_out = false;
     let wait_result = if let Some(timeout_secs) = task.timeout_secs {
         let timeout_duration = std::time::Duration::from_secs(u64::from(timeout_secs));
 
@@ -236,21 +897,13 @@ async fn execute_task(task: &Task, stdin_input: Option<&str>) -> AgwResult<TaskR
             Ok(Ok(status)) => Ok(status),
             Ok(Err(e)) => Err(AgwError::Executor(format!("Process wait failed: {e}"))),
             Err(_) => {
-                // Timeout occurred - kill the process
+                // Timeout occurred - stop the process
                 warn!(
-                    "Task {} exceeded timeout of {}s, killing process",
+                    "Task {} exceeded timeout of {}s, terminating process",
                     task.task_number, timeout_secs
                 );
-                child.kill().await.map_err(|e| {
-                    AgwError::Executor(format!("Failed to kill process after timeout: {e}"))
-                })?;
-
-                // Wait for process to be reaped
-                let status = child.wait().await.map_err(|e| {
-                    AgwError::Executor(format!("Failed to wait for killed process: {e}"))
-                })?;
-
-                Ok(status)
+                timed_out = true;
+                terminate(&mut child, task.task_number, options.kill_grace).await
             }
         }
     } else {
@@ -261,17 +914,48 @@ async fn execute_task(task: &Task, stdin_input: Option<&str>) -> AgwResult<TaskR
             .map_err(|e| AgwError::Executor(format!("Process wait failed: {e}")))
     };
 
+    if let Some(watch) = soft_deadline_watch {
+        watch.abort();
+    }
+
     let status = wait_result?;
+    let elapsed = started.elapsed();
+    let exceeded_soft_deadline = task
+        .soft_deadline_secs
+        .is_some_and(|secs| elapsed > std::time::Duration::from_secs(u64::from(secs)));
 
     // Collect stdout and stderr
-    let stdout_output = stdout_handle
+    let (stdout_output, stdout_truncated) = stdout_handle
         .await
         .map_err(|e| AgwError::Executor(format!("Failed to join stdout task: {e}")))??;
 
-    let stderr_output = stderr_handle
+    let (stderr_output, stderr_truncated) = stderr_handle
         .await
         .map_err(|e| AgwError::Executor(format!("Failed to join stderr task: {e}")))??;
 
+    let (stderr_output, warnings) = split_warnings(stderr_output, &options.warning_patterns);
+    if !warnings.is_empty() {
+        debug!(
+            "Task {} reported {} warning line(s) on stderr",
+            task.task_number,
+            warnings.len()
+        );
+    }
+
+    let output_truncated = stdout_truncated || stderr_truncated;
+    if output_truncated {
+        let limits: Vec<String> = output_limit
+            .map(|bytes| format!("{bytes} bytes"))
+            .into_iter()
+            .chain(line_limit.map(|lines| format!("{lines} lines")))
+            .collect();
+        warn!(
+            "Task {} output truncated at {}",
+            task.task_number,
+            limits.join(" / ")
+        );
+    }
+
     // Get exit code
     let exit_code = status.code().unwrap_or(-1);
 
@@ -283,95 +967,605 @@ async fn execute_task(task: &Task, stdin_input: Option<&str>) -> AgwResult<TaskR
         stderr_output.len()
     );
 
+    let mut result = TaskResult::new(task.task_number, stdout_output, stderr_output, exit_code);
+    // A task that exits cleanly after SIGTERM still did not finish in time
+    result.success = result.success && !timed_out;
+    result.output_truncated = output_truncated;
+    result.exceeded_soft_deadline = exceeded_soft_deadline;
+    result.executed_argv = executed_argv;
+    result.warnings = warnings;
+    result.duration_ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+    result.timed_out = timed_out;
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        result.signal = status.signal();
+    }
+    if task.expect_json && result.success {
+        check_json_output(task, &mut result);
+    }
+    record_task_metric(task, result.success);
+    Ok(result)
+}
+
+/// Count a finished task in `agw_task_total`
+///
+/// Shell tasks are labeled `sh`: their command is a whole script, which would
+/// make an unbounded (and possibly sensitive) label value.
+fn record_task_metric(task: &Task, success: bool) {
+    let command = if task.shell {
+        "sh"
+    } else {
+        task.command.as_str()
+    };
+    let result = if success {
+        RESULT_SUCCESS
+    } else {
+        RESULT_FAILURE
+    };
+    METRICS.task_total.inc(&[command, result]);
+}
+
+/// Start a detached task and return a placeholder result without waiting for it
+///
+/// Output goes to `/dev/null` (nobody would drain a pipe) and the child is not
+/// killed when its handle is dropped; tokio reaps it in the background once it exits.
+fn spawn_detached(task: &Task, command: &mut Command, program: &str) -> AgwResult<TaskResult> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(false);
+
+    let child = command.spawn().map_err(|source| {
+        COMMAND_CACHE.invalidate(program);
+        AgwError::Spawn {
+            command: task.command.clone(),
+            kind: SpawnFailure::classify(&source),
+            source,
+        }
+    })?;
+
+    warn!(
+        "Task {} detached (pid {}); it will not be supervised or killed",
+        task.task_number,
+        child
+            .id()
+            .map_or_else(|| "unknown".to_string(), |pid| pid.to_string())
+    );
     Ok(TaskResult::new(
         task.task_number,
-        stdout_output,
-        stderr_output,
-        exit_code,
+        String::new(),
+        String::new(),
+        0,
     ))
 }
 
-/// Read all lines from a stream asynchronously
-async fn read_stream<R: tokio::io::AsyncRead + Unpin>(reader: BufReader<R>) -> AgwResult<String> {
-    let mut lines = reader.lines();
-    let mut output = String::new();
-
-    loop {
-        match lines.next_line().await {
-            Ok(Some(line)) => {
-                output.push_str(&line);
-                output.push('\n');
+/// Stop a timed-out child and reap it
+///
+/// On Unix the child first gets SIGTERM and `grace` to exit on its own (so it
+/// can flush output and clean up); SIGKILL follows if it is still running.
+/// Elsewhere, or with a zero grace, it is killed at once.
+async fn terminate(
+    child: &mut tokio::process::Child,
+    task_number: u32,
+    grace: Duration,
+) -> AgwResult<std::process::ExitStatus> {
+    #[cfg(unix)]
+    if !grace.is_zero() {
+        if let Some(pid) = child.id().and_then(|pid| libc::pid_t::try_from(pid).ok()) {
+            // SAFETY: kill(2) has no memory-safety preconditions, and the child
+            // has not been reaped yet so `pid` still refers to it
+            if unsafe { libc::kill(pid, libc::SIGTERM) } == 0 {
+                if let Ok(status) = tokio::time::timeout(grace, child.wait()).await {
+                    return status
+                        .map_err(|e| AgwError::Executor(format!("Process wait failed: {e}")));
+                }
+                warn!(
+                    "Task {task_number} still running {}s after SIGTERM, killing process",
+                    grace.as_secs_f64()
+                );
             }
-            Ok(None) => break,
-            Err(e) => return Err(AgwError::Executor(format!("Failed to read line: {e}"))),
         }
     }
+    #[cfg(not(unix))]
+    let _ = (task_number, grace);
 
-    Ok(output)
-}
+    child
+        .kill()
+        .await
+        .map_err(|e| AgwError::Executor(format!("Failed to kill process after timeout: {e}")))?;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    // Wait for process to be reaped
+    child
+        .wait()
+        .await
+        .map_err(|e| AgwError::Executor(format!("Failed to wait for killed process: {e}")))
+}
 
-    #[tokio::test]
-    async fn test_execute_task_plan() {
-        let plan = Plan {
-            plan_id: "plan-456".to_string(),
-            plan_description: None,
-            tasks: vec![Task {
-                task_number: 1,
-                command: "echo".to_string(),
-                args: vec!["hello".to_string()],
-                input_from_task: None,
-                timeout_secs: Some(30),
-            }],
-        };
+/// Fail the task unless its stdout is well-formed JSON, re-formatting it if requested
+fn check_json_output(task: &Task, result: &mut TaskResult) {
+    let value: serde_json::Value = match serde_json::from_str(&result.stdout) {
+        Ok(value) => value,
+        Err(e) => {
+            warn!(
+                "Task {} stdout is not valid JSON, failing task: {e}",
+                task.task_number
+            );
+            result.success = false;
+            result
+                .stderr
+                .push_str(&format!("agw: task stdout is not valid JSON: {e}\n"));
+            return;
+        }
+    };
 
-        let result = execute_plan("job-123", &plan).await.unwrap();
-        assert_eq!(result.job_id, "job-123");
-        assert_eq!(result.plan_id, "plan-456");
-        assert_eq!(result.task_results.len(), 1);
-        assert_eq!(result.task_results[0].stdout.trim(), "hello");
-        assert_eq!(result.task_results[0].exit_code, 0);
-        assert!(result.success);
+    let formatted = match task.json_format {
+        Some(JsonFormat::Pretty) => serde_json::to_string_pretty(&value),
+        Some(JsonFormat::Compact) => serde_json::to_string(&value),
+        None => return,
+    };
+    // Serializing a parsed Value cannot fail
+    if let Ok(mut formatted) = formatted {
+        formatted.push('\n');
+        result.stdout = formatted;
     }
+}
 
-    #[tokio::test]
-    async fn test_execute_multi_step_plan() {
-        let plan = Plan {
-            plan_id: "plan-456".to_string(),
-            plan_description: Some("Multi-step test".to_string()),
-            tasks: vec![
-                Task {
-                    task_number: 1,
-                    command: "echo".to_string(),
-                    args: vec!["line1\nline2\nline3".to_string()],
-                    input_from_task: None,
-                    timeout_secs: Some(30),
-                },
-                Task {
-                    task_number: 2,
-                    command: "wc".to_string(),
-                    args: vec!["-l".to_string()],
-                    input_from_task: Some(1),
-                    timeout_secs: Some(30),
-                },
-            ],
-        };
+/// Job input written to a private temp file for the lifetime of one task
+struct InputFile {
+    path: PathBuf,
+}
 
-        let result = execute_plan("job-123", &plan).await.unwrap();
-        assert_eq!(result.task_results.len(), 2);
-        assert!(result.task_results[0].success);
-        assert!(result.task_results[1].success);
-        assert!(result.success);
+impl InputFile {
+    /// Serialize the job input to a new temp file readable only by the worker user
+    fn write(input: &serde_json::Value) -> AgwResult<Self> {
+        use std::io::Write;
+
+        let json = serde_json::to_vec(input)
+            .map_err(|e| AgwError::Executor(format!("Failed to serialize job input: {e}")))?;
+
+        let path = std::env::temp_dir().join(format!("agw-input-{}.json", uuid::Uuid::new_v4()));
+
+        let mut open_options = std::fs::OpenOptions::new();
+        open_options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            open_options.mode(0o600);
+        }
+
+        // Construct the guard first so a failed write still removes the file
+        let input_file = Self { path };
+        let mut file = open_options
+            .open(&input_file.path)
+            .map_err(|e| AgwError::Executor(format!("Failed to create input file: {e}")))?;
+        file.write_all(&json)
+            .map_err(|e| AgwError::Executor(format!("Failed to write input file: {e}")))?;
+
+        Ok(input_file)
     }
 
-    #[tokio::test]
-    async fn test_execute_plan_with_failure() {
-        let plan = Plan {
-            plan_id: "plan-456".to_string(),
-            plan_description: None,
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for InputFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to remove input file {}: {e}", self.path.display());
+            }
+        }
+    }
+}
+
+/// Non-blocking writer for a task's `fifo_output` named pipe
+///
+/// Writing never waits for the reader: data the pipe has no room for is
+/// dropped, and the sink closes itself once the reader goes away.
+struct FifoSink {
+    file: Option<std::fs::File>,
+    path: PathBuf,
+    task_number: u32,
+    dropped: usize,
+}
+
+impl FifoSink {
+    /// Open the FIFO at `path`, or `None` if it is missing, not a FIFO, or has no reader
+    fn open(path: &Path, task_number: u32) -> Option<Self> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+
+            // Never create or follow anything: only an existing FIFO is written to
+            match std::fs::symlink_metadata(path) {
+                Ok(metadata) if metadata.file_type().is_fifo() => {}
+                Ok(_) => {
+                    warn!(
+                        "Task {task_number} fifo_output {} is not a FIFO, skipping live output",
+                        path.display()
+                    );
+                    return None;
+                }
+                Err(e) => {
+                    warn!(
+                        "Task {task_number} fifo_output {} unavailable, skipping live output: {e}",
+                        path.display()
+                    );
+                    return None;
+                }
+            }
+
+            // O_NONBLOCK makes the open fail with ENXIO instead of blocking when
+            // nobody is reading, and keeps later writes from blocking as well
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .custom_flags(libc::O_NONBLOCK | libc::O_NOFOLLOW)
+                .open(path)
+            {
+                Ok(file) => Some(Self {
+                    file: Some(file),
+                    path: path.to_path_buf(),
+                    task_number,
+                    dropped: 0,
+                }),
+                Err(e) if e.raw_os_error() == Some(libc::ENXIO) => {
+                    debug!(
+                        "Task {task_number} fifo_output {} has no reader, skipping live output",
+                        path.display()
+                    );
+                    None
+                }
+                Err(e) => {
+                    warn!(
+                        "Task {task_number} failed to open fifo_output {}: {e}",
+                        path.display()
+                    );
+                    None
+                }
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            // Rejected by plan validation; nothing to write to here
+            let _ = (path, task_number);
+            None
+        }
+    }
+
+    /// Copy `bytes` to the FIFO without blocking, dropping what does not fit
+    fn write(&mut self, mut bytes: &[u8]) {
+        use std::io::Write;
+
+        let Some(file) = &mut self.file else {
+            return;
+        };
+        while !bytes.is_empty() {
+            match file.write(bytes) {
+                Ok(n) => bytes = &bytes[n..],
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    self.dropped += bytes.len();
+                    return;
+                }
+                Err(e) => {
+                    debug!(
+                        "Task {} fifo_output {} closed: {e}",
+                        self.task_number,
+                        self.path.display()
+                    );
+                    self.file = None;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for FifoSink {
+    fn drop(&mut self) {
+        if self.dropped > 0 {
+            warn!(
+                "Task {} dropped {} bytes of live output to {}: reader too slow",
+                self.task_number,
+                self.dropped,
+                self.path.display()
+            );
+        }
+    }
+}
+
+/// Reader that hands every chunk it reads to an optional [`FifoSink`] as well
+struct TeeReader<R> {
+    inner: R,
+    sink: Option<FifoSink>,
+}
+
+impl<R> TeeReader<R> {
+    fn new(inner: R, sink: Option<FifoSink>) -> Self {
+        Self { inner, sink }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for TeeReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let start = buf.filled().len();
+        let this = &mut *self;
+        let poll = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let (Poll::Ready(Ok(())), Some(sink)) = (&poll, &mut this.sink) {
+            sink.write(&buf.filled()[start..]);
+        }
+        poll
+    }
+}
+
+/// Move the stderr lines matching any of `patterns` out of `stderr`
+///
+/// Returns the remaining stderr and the matching lines without their line endings.
+fn split_warnings(stderr: String, patterns: &[Regex]) -> (String, Vec<String>) {
+    if patterns.is_empty() {
+        return (stderr, Vec::new());
+    }
+
+    let mut kept = String::with_capacity(stderr.len());
+    let mut warnings = Vec::new();
+    for line in stderr.split_inclusive('\n') {
+        let text = line.strip_suffix('\n').unwrap_or(line);
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        if patterns.iter().any(|pattern| pattern.is_match(text)) {
+            warnings.push(text.to_string());
+        } else {
+            kept.push_str(line);
+        }
+    }
+    (kept, warnings)
+}
+
+/// Read an output stream in the configured mode
+async fn read_output<R: AsyncRead + Unpin>(
+    stream: R,
+    mode: ReadMode,
+    buffer_size: usize,
+    limit: Option<usize>,
+    line_limit: Option<usize>,
+) -> AgwResult<(String, bool)> {
+    match mode {
+        ReadMode::Lines => {
+            let reader = BufReader::with_capacity(buffer_size, stream);
+            read_stream(reader, limit, line_limit).await
+        }
+        ReadMode::Chunks => read_chunks(stream, buffer_size, limit).await,
+    }
+}
+
+/// Read a stream in chunks of `chunk_size` bytes, keeping line endings as-is
+///
+/// Truncation and draining behave as in [`read_stream`].
+async fn read_chunks<R: AsyncRead + Unpin>(
+    mut reader: R,
+    chunk_size: usize,
+    limit: Option<usize>,
+) -> AgwResult<(String, bool)> {
+    let mut decoder = Utf8StreamDecoder::new();
+    let mut chunk = vec![0u8; chunk_size.max(1)];
+    let mut output = String::new();
+    let mut truncated = false;
+
+    loop {
+        match reader.read(&mut chunk).await {
+            Ok(0) => break,
+            Ok(n) => {
+                if truncated {
+                    continue;
+                }
+                decoder.decode(&chunk[..n], &mut output);
+                truncated = truncate_to_limit(&mut output, limit);
+            }
+            Err(e) => return Err(AgwError::Executor(format!("Failed to read output: {e}"))),
+        }
+    }
+
+    if !truncated {
+        decoder.finish(&mut output);
+        truncated = truncate_to_limit(&mut output, limit);
+    }
+
+    Ok((output, truncated))
+}
+
+/// Read a stream's raw bytes and return them in base64 wire form
+///
+/// The limit applies to the raw bytes; anything past it is drained and discarded.
+async fn read_base64<R: AsyncRead + Unpin>(
+    mut reader: R,
+    chunk_size: usize,
+    limit: Option<usize>,
+) -> AgwResult<(String, bool)> {
+    let mut chunk = vec![0u8; chunk_size.max(1)];
+    let mut bytes = Vec::new();
+    let mut truncated = false;
+
+    loop {
+        match reader.read(&mut chunk).await {
+            Ok(0) => break,
+            Ok(n) => {
+                if truncated {
+                    continue;
+                }
+                bytes.extend_from_slice(&chunk[..n]);
+                if let Some(limit) = limit.filter(|&limit| bytes.len() > limit) {
+                    bytes.truncate(limit);
+                    truncated = true;
+                }
+            }
+            Err(e) => return Err(AgwError::Executor(format!("Failed to read output: {e}"))),
+        }
+    }
+
+    Ok((base64::encode_output(&bytes), truncated))
+}
+
+/// Cut `output` to at most `limit` bytes at a character boundary, returning whether it was cut
+fn truncate_to_limit(output: &mut String, limit: Option<usize>) -> bool {
+    match limit {
+        Some(limit) if output.len() > limit => {
+            let mut cut = limit;
+            while !output.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            output.truncate(cut);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Marker appended to output cut at `--max-output-lines`
+fn line_limit_marker(line_limit: usize) -> String {
+    format!("[agw: output truncated after {line_limit} lines]\n")
+}
+
+/// Read all lines from a stream asynchronously
+///
+/// When `limit` is set, at most `limit` bytes are kept (cut at a character
+/// boundary) and the rest of the stream is drained and discarded so the child
+/// never blocks on a full pipe. `line_limit` likewise keeps at most that many
+/// lines, followed by [`line_limit_marker`]. Returns the output and whether it
+/// was truncated.
+async fn read_stream<R: AsyncRead + Unpin>(
+    mut reader: BufReader<R>,
+    limit: Option<usize>,
+    line_limit: Option<usize>,
+) -> AgwResult<(String, bool)> {
+    let mut decoder = Utf8StreamDecoder::new();
+    let mut raw_line = Vec::new();
+    let mut output = String::new();
+    let mut truncated = false;
+    let mut lines = 0;
+
+    loop {
+        raw_line.clear();
+        match reader.read_until(b'\n', &mut raw_line).await {
+            Ok(0) => break,
+            Ok(_) => {
+                if truncated {
+                    continue;
+                }
+                if let Some(line_limit) = line_limit.filter(|&line_limit| lines >= line_limit) {
+                    output.push_str(&line_limit_marker(line_limit));
+                    truncated = true;
+                    continue;
+                }
+                lines += 1;
+                // Decode the raw bytes (invalid UTF-8 becomes U+FFFD rather than
+                // failing the task), then normalize the line ending to "\n"
+                decoder.decode(&raw_line, &mut output);
+                if raw_line.ends_with(b"\n") {
+                    output.pop();
+                    if raw_line.ends_with(b"\r\n") {
+                        output.pop();
+                    }
+                } else {
+                    // Final line without a newline: flush any incomplete sequence
+                    decoder.finish(&mut output);
+                }
+                output.push('\n');
+                truncated = truncate_to_limit(&mut output, limit);
+            }
+            Err(e) => return Err(AgwError::Executor(format!("Failed to read line: {e}"))),
+        }
+    }
+
+    Ok((output, truncated))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_execute_task_plan() {
+        let plan = Plan {
+            plan_id: "plan-456".to_string(),
+            plan_description: None,
+            tasks: vec![Task {
+                task_number: 1,
+                command: "echo".to_string(),
+                args: vec!["hello".to_string()],
+                input_from_task: None,
+                timeout_secs: Some(30),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let result = execute_plan(
+            "job-123",
+            &plan,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.job_id, "job-123");
+        assert_eq!(result.plan_id, "plan-456");
+        assert_eq!(result.task_results.len(), 1);
+        assert_eq!(result.task_results[0].stdout.trim(), "hello");
+        assert_eq!(result.task_results[0].exit_code, 0);
+        assert!(result.success);
+    }
+
+    #[tokio::test]
+    async fn test_execute_multi_step_plan() {
+        let plan = Plan {
+            plan_id: "plan-456".to_string(),
+            plan_description: Some("Multi-step test".to_string()),
+            tasks: vec![
+                Task {
+                    task_number: 1,
+                    command: "echo".to_string(),
+                    args: vec!["line1\nline2\nline3".to_string()],
+                    input_from_task: None,
+                    timeout_secs: Some(30),
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 2,
+                    command: "wc".to_string(),
+                    args: vec!["-l".to_string()],
+                    input_from_task: Some(1),
+                    timeout_secs: Some(30),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let result = execute_plan(
+            "job-123",
+            &plan,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.task_results.len(), 2);
+        assert!(result.task_results[0].success);
+        assert!(result.task_results[1].success);
+        assert!(result.success);
+    }
+
+    #[tokio::test]
+    async fn test_execute_plan_with_failure() {
+        let plan = Plan {
+            plan_id: "plan-456".to_string(),
+            plan_description: None,
             tasks: vec![
                 Task {
                     task_number: 1,
@@ -379,6 +1573,7 @@ mod tests {
                     args: vec!["-c".to_string(), "exit 42".to_string()],
                     input_from_task: None,
                     timeout_secs: Some(30),
+                    ..Default::default()
                 },
                 Task {
                     task_number: 2,
@@ -386,11 +1581,20 @@ mod tests {
                     args: vec!["should not run".to_string()],
                     input_from_task: None,
                     timeout_secs: Some(30),
+                    ..Default::default()
                 },
             ],
+            ..Default::default()
         };
 
-        let result = execute_plan("job-123", &plan).await.unwrap();
+        let result = execute_plan(
+            "job-123",
+            &plan,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
         // Should only execute first task
         assert_eq!(result.task_results.len(), 1);
         assert_eq!(result.task_results[0].exit_code, 42);
@@ -399,23 +1603,851 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_execute_plan_with_timeout() {
+    async fn test_task_total_counts_success_and_failure_per_command() {
+        // METRICS is process-wide and tests run concurrently, so compare deltas
+        let count = |command: &str, result: &str| METRICS.task_total.get(&[command, result]);
+        let before = [
+            count("true", RESULT_SUCCESS),
+            count("true", RESULT_FAILURE),
+            count("false", RESULT_SUCCESS),
+            count("false", RESULT_FAILURE),
+        ];
+
+        for command in ["true", "false"] {
+            let task = Task {
+                task_number: 1,
+                command: command.to_string(),
+                timeout_secs: Some(30),
+                ..Default::default()
+            };
+            execute_task(
+                &task,
+                None,
+                &serde_json::Value::Null,
+                &ExecutionOptions::default(),
+            )
+            .await
+            .unwrap();
+        }
+
+        assert!(count("true", RESULT_SUCCESS) > before[0]);
+        assert_eq!(count("true", RESULT_FAILURE), before[1]);
+        assert_eq!(count("false", RESULT_SUCCESS), before[2]);
+        assert!(count("false", RESULT_FAILURE) > before[3]);
+    }
+
+    fn plan_with_failing_middle_task(execution_strategy: ExecutionStrategy) -> Plan {
+        let echo = |task_number: u32, text: &str| Task {
+            task_number,
+            command: "echo".to_string(),
+            args: vec![text.to_string()],
+            timeout_secs: Some(30),
+            ..Default::default()
+        };
+
+        Plan {
+            plan_id: "plan-456".to_string(),
+            tasks: vec![
+                echo(1, "first"),
+                Task {
+                    task_number: 2,
+                    command: "sh".to_string(),
+                    args: vec!["-c".to_string(), "exit 3".to_string()],
+                    timeout_secs: Some(30),
+                    ..Default::default()
+                },
+                echo(3, "third"),
+            ],
+            execution_strategy,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_halt_on_failure_strategy_stops_at_failed_task() {
+        let plan = plan_with_failing_middle_task(ExecutionStrategy::HaltOnFailure);
+
+        let result = execute_plan(
+            "job-123",
+            &plan,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.task_results.len(), 2);
+        assert!(result.task_results[0].success);
+        assert_eq!(result.task_results[1].exit_code, 3);
+        assert!(!result.success);
+    }
+
+    #[tokio::test]
+    async fn test_run_all_strategy_executes_every_task() {
+        let plan = plan_with_failing_middle_task(ExecutionStrategy::RunAll);
+
+        let result = execute_plan(
+            "job-123",
+            &plan,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.task_results.len(), 3);
+        assert!(result.task_results[0].success);
+        assert!(!result.task_results[1].success);
+        assert!(result.task_results[2].success);
+        assert_eq!(result.task_results[2].stdout, "third\n");
+        assert!(!result.success);
+    }
+
+    #[tokio::test]
+    async fn test_task_args_templated_from_earlier_stdout() {
+        let task = |task_number: u32, command: &str, args: &[&str]| Task {
+            task_number,
+            command: command.to_string(),
+            args: args.iter().map(ToString::to_string).collect(),
+            timeout_secs: Some(30),
+            ..Default::default()
+        };
+        let plan = |first: Task| Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![first, task(2, "echo", &["{{task.1.stdout}}", "ready"])],
+            ..Default::default()
+        };
+        let run = |plan: Plan| async move {
+            execute_plan(
+                "job-1",
+                &plan,
+                &serde_json::Value::Null,
+                &ExecutionOptions::default(),
+            )
+            .await
+        };
+
+        let result = run(plan(task(1, "echo", &["report-2026.csv"])))
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert_eq!(result.task_results[1].stdout, "report-2026.csv ready\n");
+
+        // Output that would be rejected as a literal argument is rejected here too
+        let err = run(plan(task(1, "echo", &["a&b"]))).await.unwrap_err();
+        assert!(err.to_string().contains("dangerous"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_executed_argv_records_resolved_substituted_command() {
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![
+                Task {
+                    task_number: 1,
+                    command: "echo".to_string(),
+                    args: vec!["report.csv".to_string()],
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 2,
+                    command: "print".to_string(),
+                    args: vec![
+                        "{{task.1.stdout}}".to_string(),
+                        "--token=tok_s3cr3t".to_string(),
+                    ],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let options = ExecutionOptions {
+            tool_aliases: HashMap::from([("print".to_string(), "echo".to_string())]),
+            command_cache_ttl: Duration::from_secs(60),
+            redactor: Redactor::new(vec![regex::Regex::new(r"tok_\w+").unwrap()]),
+            ..Default::default()
+        };
+
+        let result = execute_plan("job-1", &plan, &serde_json::Value::Null, &options)
+            .await
+            .unwrap();
+        assert!(result.success);
+
+        let argv = &result.task_results[1].executed_argv;
+        assert!(Path::new(&argv[0]).is_absolute(), "{argv:?}");
+        assert!(argv[0].ends_with("/echo"), "{argv:?}");
+        assert_eq!(argv[1..], ["report.csv", "--token=***"]);
+        // The task itself still received the real value
+        assert_eq!(
+            result.task_results[1].stdout,
+            "report.csv --token=tok_s3cr3t\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_all_skips_tasks_whose_upstream_failed() {
+        let mut plan = plan_with_failing_middle_task(ExecutionStrategy::RunAll);
+        let cat_from = |task_number: u32, upstream: u32| Task {
+            task_number,
+            command: "cat".to_string(),
+            input_from_task: Some(upstream),
+            timeout_secs: Some(30),
+            ..Default::default()
+        };
+        // 4 reads the failed task 2, 5 reads the skipped task 4, 6 reads the successful task 1
+        plan.tasks
+            .extend([cat_from(4, 2), cat_from(5, 4), cat_from(6, 1)]);
+
+        let result = execute_plan(
+            "job-123",
+            &plan,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.task_results.len(), 6);
+        assert!(!result.success);
+        assert!(result.task_results[2].success);
+
+        let skipped = &result.task_results[3];
+        assert!(skipped.skipped);
+        assert!(!skipped.success);
+        assert_eq!(skipped.stdout, "");
+        assert_eq!(skipped.stderr, "Task 4 skipped: input_from_task 2 failed\n");
+        assert!(result.task_results[4].skipped);
+        assert_eq!(
+            result.task_results[4].stderr,
+            "Task 5 skipped: input_from_task 4 failed\n"
+        );
+
+        assert!(!result.task_results[5].skipped);
+        assert_eq!(result.task_results[5].stdout, "first\n");
+        assert!(!result.task_results[1].skipped);
+    }
+
+    #[tokio::test]
+    async fn test_shell_task_only_runs_when_allowed() {
+        let plan = Plan {
+            plan_id: "plan-456".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "echo \"$1\" | tr a-z A-Z".to_string(),
+                args: vec!["hello".to_string()],
+                timeout_secs: Some(30),
+                shell: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let denied = execute_plan(
+            "job-123",
+            &plan,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await;
+        assert!(denied.is_err());
+
+        let options = ExecutionOptions {
+            allow_shell: true,
+            ..Default::default()
+        };
+        let result = execute_plan("job-123", &plan, &serde_json::Value::Null, &options)
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert_eq!(result.task_results[0].stdout, "HELLO\n");
+    }
+
+    fn json_task(task_number: u32, output: &str) -> Task {
+        Task {
+            task_number,
+            command: "printf".to_string(),
+            args: vec![output.to_string()],
+            timeout_secs: Some(30),
+            expect_json: true,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_expect_json_valid_output_passes_and_is_reformatted() {
+        let plan = Plan {
+            plan_id: "plan-456".to_string(),
+            tasks: vec![
+                Task {
+                    json_format: Some(JsonFormat::Compact),
+                    ..json_task(1, "{ \"a\": [1, 2],\n  \"b\": null }\n")
+                },
+                Task {
+                    command: "cat".to_string(),
+                    args: vec![],
+                    input_from_task: Some(1),
+                    ..json_task(2, "")
+                },
+            ],
+            ..Default::default()
+        };
+
+        let result = execute_plan(
+            "job-123",
+            &plan,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(result.success);
+        assert_eq!(result.task_results[0].stdout, "{\"a\":[1,2],\"b\":null}\n");
+        assert_eq!(result.task_results[1].stdout, "{\"a\":[1,2],\"b\":null}\n");
+    }
+
+    #[tokio::test]
+    async fn test_expect_json_invalid_output_fails_task() {
+        let plan = Plan {
+            plan_id: "plan-456".to_string(),
+            tasks: vec![json_task(1, "{\"truncated\": "), json_task(2, "{}")],
+            ..Default::default()
+        };
+
+        let result = execute_plan(
+            "job-123",
+            &plan,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(!result.success);
+        assert_eq!(result.task_results.len(), 1);
+        assert_eq!(result.task_results[0].exit_code, 0);
+        assert!(!result.task_results[0].success);
+        assert!(result.task_results[0].stderr.contains("not valid JSON"));
+    }
+
+    #[tokio::test]
+    async fn test_cached_command_resolution_executes() {
+        let plan = Plan {
+            plan_id: "plan-456".to_string(),
+            tasks: (1..=2)
+                .map(|task_number| Task {
+                    task_number,
+                    command: "echo".to_string(),
+                    args: vec![format!("run {task_number}")],
+                    timeout_secs: Some(30),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let options = ExecutionOptions {
+            command_cache_ttl: Duration::from_secs(60),
+            ..Default::default()
+        };
+
+        let result = execute_plan("job-123", &plan, &serde_json::Value::Null, &options)
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert_eq!(result.combined_stdout(), "run 1\nrun 2\n");
+    }
+
+    #[tokio::test]
+    async fn test_aliased_command_spawns_mapped_binary() {
+        let plan = Plan {
+            plan_id: "plan-456".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "agw-logical-echo".to_string(),
+                args: vec!["aliased".to_string()],
+                timeout_secs: Some(30),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        // Without the alias the logical name doesn't exist on this host
+        let unaliased = execute_plan(
+            "job-123",
+            &plan,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await;
+        assert!(unaliased.is_err());
+
+        let options = ExecutionOptions {
+            tool_aliases: HashMap::from([("agw-logical-echo".to_string(), "echo".to_string())]),
+            ..Default::default()
+        };
+        let result = execute_plan("job-123", &plan, &serde_json::Value::Null, &options)
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert_eq!(result.task_results[0].stdout, "aliased\n");
+    }
+
+    #[tokio::test]
+    async fn test_execute_plan_with_timeout() {
+        let plan = Plan {
+            plan_id: "plan-456".to_string(),
+            plan_description: None,
+            tasks: vec![Task {
+                task_number: 1,
+                command: "sleep".to_string(),
+                args: vec!["10".to_string()],
+                input_from_task: None,
+                timeout_secs: Some(1),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let result = execute_plan(
+            "job-123",
+            &plan,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.task_results.len(), 1);
+        assert!(!result.task_results[0].success);
+        assert!(!result.success);
+    }
+
+    /// A shell task that loops until killed, reacting to SIGTERM with `on_term`
+    #[cfg(unix)]
+    fn looping_task(on_term: &str) -> Task {
+        Task {
+            task_number: 1,
+            command: "sh".to_string(),
+            args: vec![
+                "-c".to_string(),
+                format!("trap '{on_term}' TERM; echo started; while :; do sleep 0.1; done"),
+            ],
+            timeout_secs: Some(1),
+            ..Default::default()
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_timeout_sends_sigterm_before_sigkill() {
+        let options = ExecutionOptions {
+            kill_grace: Duration::from_secs(5),
+            ..Default::default()
+        };
+        let started = std::time::Instant::now();
+        let result = execute_task(
+            &looping_task("echo cleaned up; exit 0"),
+            None,
+            &serde_json::Value::Null,
+            &options,
+        )
+        .await
+        .unwrap();
+
+        // The trap ran and the process exited on its own well within the grace period
+        assert_eq!(result.stdout, "started\ncleaned up\n");
+        assert_eq!(result.exit_code, 0);
+        assert!(!result.success, "a timed-out task must not succeed");
+        assert!(started.elapsed() < Duration::from_secs(4));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_timeout_kills_task_ignoring_sigterm_after_grace() {
+        let options = ExecutionOptions {
+            kill_grace: Duration::from_millis(500),
+            ..Default::default()
+        };
+        let result = execute_task(&looping_task(""), None, &serde_json::Value::Null, &options)
+            .await
+            .unwrap();
+
+        assert_eq!(result.stdout, "started\n");
+        assert_eq!(result.exit_code, -1);
+        assert!(!result.success);
+    }
+
+    /// A task that creates `marker` after a short delay
+    #[cfg(unix)]
+    fn delayed_touch(marker: &Path, detach: bool) -> Task {
+        Task {
+            task_number: 1,
+            command: "sh".to_string(),
+            args: vec![
+                "-c".to_string(),
+                "sleep 0.3; touch \"$0\"".to_string(),
+                marker.to_string_lossy().into_owned(),
+            ],
+            detach,
+            ..Default::default()
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_detached_task_returns_without_waiting() {
+        let marker = std::env::temp_dir().join(format!("agw-detach-{}", uuid::Uuid::new_v4()));
+        let started = std::time::Instant::now();
+        let result = execute_task(
+            &delayed_touch(&marker, true),
+            None,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(started.elapsed() < Duration::from_millis(250));
+        assert!(result.success);
+        assert_eq!(result.stdout, "");
+        assert!(!marker.exists());
+
+        // The child keeps running after the task returned
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(marker.exists());
+        std::fs::remove_file(&marker).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_normal_task_killed_when_dropped() {
+        let marker = std::env::temp_dir().join(format!("agw-drop-{}", uuid::Uuid::new_v4()));
+        let task = delayed_touch(&marker, false);
+        let options = ExecutionOptions::default();
+        let execution = execute_task(&task, None, &serde_json::Value::Null, &options);
+
+        // Drop the future while the child is still sleeping
+        assert!(tokio::time::timeout(Duration::from_millis(100), execution)
+            .await
+            .is_err());
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(!marker.exists());
+    }
+
+    /// Create a FIFO in the temp dir
+    #[cfg(unix)]
+    fn make_fifo() -> PathBuf {
+        let path = std::env::temp_dir().join(format!("agw-fifo-{}", uuid::Uuid::new_v4()));
+        let c_path = std::ffi::CString::new(path.to_str().unwrap()).unwrap();
+        // SAFETY: c_path is a valid NUL-terminated string for the duration of the call
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        path
+    }
+
+    #[cfg(unix)]
+    fn fifo_task(path: &Path) -> Task {
+        Task {
+            task_number: 1,
+            command: "printf".to_string(),
+            args: vec!["line 1\\nline 2\\n".to_string()],
+            fifo_output: Some(path.to_string_lossy().into_owned()),
+            ..Default::default()
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_fifo_output_reaches_attached_reader() {
+        use std::io::Read;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let path = make_fifo();
+        // Opening the read end non-blocking attaches the reader without waiting for a writer
+        let mut reader = std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&path)
+            .unwrap();
+
+        let result = execute_task(
+            &fifo_task(&path),
+            None,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let mut live = String::new();
+        reader.read_to_string(&mut live).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(result.success);
+        assert_eq!(live, "line 1\nline 2\n");
+        assert_eq!(result.stdout, "line 1\nline 2\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_fifo_output_without_reader_or_fifo_is_skipped() {
+        // A FIFO nobody reads must not block or fail the task
+        let path = make_fifo();
+        let result = execute_task(
+            &fifo_task(&path),
+            None,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.success);
+        assert_eq!(result.stdout, "line 1\nline 2\n");
+
+        // Neither a regular file nor a missing path is written to or created
+        let file = std::env::temp_dir().join(format!("agw-fifo-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&file, "untouched").unwrap();
+        let missing = std::env::temp_dir().join(format!("agw-fifo-{}", uuid::Uuid::new_v4()));
+        for path in [&file, &missing] {
+            let result = execute_task(
+                &fifo_task(path),
+                None,
+                &serde_json::Value::Null,
+                &ExecutionOptions::default(),
+            )
+            .await
+            .unwrap();
+            assert!(result.success);
+        }
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "untouched");
+        assert!(!missing.exists());
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_only_binary_task_output_is_base64_encoded() {
+        let plan = Plan {
+            plan_id: "plan-bin".to_string(),
+            tasks: vec![
+                Task {
+                    task_number: 1,
+                    command: "printf".to_string(),
+                    args: vec!["\\000\\377ok".to_string()],
+                    encode_output_base64: true,
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 2,
+                    command: "echo".to_string(),
+                    args: vec!["text".to_string()],
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 3,
+                    command: "wc".to_string(),
+                    args: vec!["-c".to_string()],
+                    input_from_task: Some(1),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let result = execute_plan(
+            "job-bin",
+            &plan,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.success);
+
+        // The binary task is encoded behind its marker, the text task is untouched
+        assert_eq!(result.task_results[0].stdout, "agw:base64:AP9vaw==\n");
+        assert_eq!(result.task_results[1].stdout, "text\n");
+        assert!(result
+            .combined_stdout()
+            .starts_with("agw:base64:AP9vaw==\ntext\n"));
+        // Downstream tasks still read the raw bytes
+        assert_eq!(result.task_results[2].stdout.trim(), "4");
+    }
+
+    #[tokio::test]
+    async fn test_binary_output_limit_applies_to_raw_bytes() {
+        let task = Task {
+            task_number: 1,
+            command: "printf".to_string(),
+            args: vec!["\\000\\377ok".to_string()],
+            max_output_bytes: Some(2),
+            encode_output_base64: true,
+            ..Default::default()
+        };
+        let result = execute_task(
+            &task,
+            None,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.output_truncated);
+        assert_eq!(result.stdout, "agw:base64:AP8=\n");
+    }
+
+    #[tokio::test]
+    async fn test_task_failure_reasons() {
+        let task = |command: &str, args: &[&str]| Task {
+            task_number: 1,
+            command: command.to_string(),
+            args: args.iter().map(ToString::to_string).collect(),
+            ..Default::default()
+        };
+        let run = |task: Task| async move {
+            execute_task(
+                &task,
+                None,
+                &serde_json::Value::Null,
+                &ExecutionOptions::default(),
+            )
+            .await
+        };
+        let reason = |task: Task| async move { run(task).await.unwrap().failure_reason() };
+
+        assert_eq!(reason(task("true", &[])).await, None);
+        assert_eq!(
+            reason(task("false", &[])).await,
+            Some(FailureReason::NonZeroExit)
+        );
+        let slow = Task {
+            timeout_secs: Some(1),
+            ..task("sleep", &["10"])
+        };
+        assert_eq!(reason(slow).await, Some(FailureReason::Timeout));
+        #[cfg(unix)]
+        assert_eq!(
+            reason(task("sh", &["-c", "kill -KILL $$"])).await,
+            Some(FailureReason::Killed)
+        );
+        let not_json = Task {
+            expect_json: true,
+            ..task("echo", &["not json"])
+        };
+        assert_eq!(reason(not_json).await, Some(FailureReason::ValidationError));
+        assert_eq!(
+            TaskResult::skipped(1, "upstream failed").failure_reason(),
+            Some(FailureReason::Cancelled)
+        );
+
+        let err = run(task("/nonexistent/agw-test-binary", &[]))
+            .await
+            .unwrap_err();
+        assert_eq!(err.failure_reason(), Some(FailureReason::SpawnFailed));
+    }
+
+    #[tokio::test]
+    async fn test_plan_failure_reasons() {
+        let task = |task_number, command: &str, args: &[&str]| Task {
+            task_number,
+            command: command.to_string(),
+            args: args.iter().map(ToString::to_string).collect(),
+            ..Default::default()
+        };
+        let run = |tasks| async move {
+            let plan = Plan {
+                plan_id: "plan-1".to_string(),
+                tasks,
+                execution_strategy: ExecutionStrategy::RunAll,
+                ..Default::default()
+            };
+            execute_plan(
+                "job-1",
+                &plan,
+                &serde_json::Value::Null,
+                &ExecutionOptions::default(),
+            )
+            .await
+        };
+
+        let result = run(vec![task(1, "echo", &["ok"])]).await.unwrap();
+        assert_eq!(result.failure_reason(), None);
+
+        // The first failed task decides, not the tasks skipped after it
+        let downstream = Task {
+            input_from_task: Some(1),
+            ..task(2, "cat", &[])
+        };
+        let result = run(vec![task(1, "false", &[]), downstream]).await.unwrap();
+        assert!(result.task_results[1].skipped);
+        assert_eq!(result.failure_reason(), Some(FailureReason::NonZeroExit));
+
+        let err = run(vec![
+            task(1, "echo", &["a;b"]),
+            task(2, "echo", &["{{task.1.stdout}}"]),
+        ])
+        .await
+        .unwrap_err();
+        assert_eq!(err.failure_reason(), Some(FailureReason::SubstitutionError));
+    }
+
+    #[tokio::test]
+    async fn test_compressed_intermediate_output_pipes_byte_exact() {
+        use sha2::{Digest, Sha256};
+
         let plan = Plan {
-            plan_id: "plan-456".to_string(),
-            plan_description: None,
-            tasks: vec![Task {
-                task_number: 1,
-                command: "sleep".to_string(),
-                args: vec!["10".to_string()],
-                input_from_task: None,
-                timeout_secs: Some(1),
-            }],
+            plan_id: "plan-1".to_string(),
+            tasks: vec![
+                Task {
+                    task_number: 1,
+                    command: "seq".to_string(),
+                    args: [
+                        "-f",
+                        "intermediate output row %06g of a long pipeline",
+                        "1",
+                        "200000",
+                    ]
+                    .map(String::from)
+                    .to_vec(),
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 2,
+                    command: "sha256sum".to_string(),
+                    input_from_task: Some(1),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
         };
+        let options = ExecutionOptions {
+            compress_outputs_above: Some(1024),
+            ..Default::default()
+        };
+        let input = serde_json::Value::Null;
+        let mut outputs = OutputStore::new(options.compress_outputs_above);
+        let result =
+            execute_plan_with_outputs("job-1", &plan, &input, &options, None, &mut outputs)
+                .await
+                .unwrap();
+        assert!(result.success);
+        let upstream = &result.task_results[0].stdout;
+        assert_eq!(upstream.lines().count(), 200_000);
+        // The downstream task saw exactly the bytes the upstream one produced
+        let digest = format!("{:x}", Sha256::digest(upstream.as_bytes()));
+        assert_eq!(result.task_results[1].stdout, format!("{digest}  -\n"));
+        // Only the compressed upstream output was held while the plan ran
+        assert!(
+            outputs.peak_bytes() < upstream.len() / 2,
+            "held {} of {} bytes",
+            outputs.peak_bytes(),
+            upstream.len()
+        );
 
-        let result = execute_plan("job-123", &plan).await.unwrap();
-        assert_eq!(result.task_results.len(), 1);
-        assert!(!result.task_results[0].success);
-        assert!(!result.success);
+        let mut uncompressed = OutputStore::default();
+        execute_plan_with_outputs("job-2", &plan, &input, &options, None, &mut uncompressed)
+            .await
+            .unwrap();
+        assert!(uncompressed.peak_bytes() >= upstream.len());
     }
 
     #[tokio::test]
@@ -430,6 +2462,7 @@ mod tests {
                     args: vec!["foo\nbar\nfoo".to_string()],
                     input_from_task: None,
                     timeout_secs: Some(30),
+                    ..Default::default()
                 },
                 Task {
                     task_number: 2,
@@ -437,6 +2470,7 @@ mod tests {
                     args: vec![],
                     input_from_task: Some(1),
                     timeout_secs: Some(30),
+                    ..Default::default()
                 },
                 Task {
                     task_number: 3,
@@ -444,11 +2478,20 @@ mod tests {
                     args: vec![],
                     input_from_task: Some(2),
                     timeout_secs: Some(30),
+                    ..Default::default()
                 },
             ],
+            ..Default::default()
         };
 
-        let result = execute_plan("job-123", &plan).await.unwrap();
+        let result = execute_plan(
+            "job-123",
+            &plan,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
         assert_eq!(result.task_results.len(), 3);
         assert!(result.success);
 
@@ -458,6 +2501,207 @@ mod tests {
         assert!(final_output.contains("foo"));
     }
 
+    #[tokio::test]
+    async fn test_execute_plan_merges_stdin_from_multiple_tasks() {
+        let echo = |task_number, lines: &str| Task {
+            task_number,
+            command: "printf".to_string(),
+            args: vec![lines.to_string()],
+            ..Default::default()
+        };
+        let plan = Plan {
+            plan_id: "plan-fan-in".to_string(),
+            tasks: vec![
+                echo(1, "a\\nb\\n"),
+                echo(2, "c\\nd\\ne\\n"),
+                Task {
+                    task_number: 3,
+                    command: "wc".to_string(),
+                    args: vec!["-l".to_string()],
+                    input_from_tasks: vec![1, 2],
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 4,
+                    command: "cat".to_string(),
+                    input_from_tasks: vec![2, 1],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let result = execute_plan(
+            "job-123",
+            &plan,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.success);
+        assert_eq!(result.task_results[2].stdout.trim(), "5");
+        // Concatenated in the order listed
+        assert_eq!(result.task_results[3].stdout, "c\nd\ne\na\nb\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stdin_mode_null_vs_empty() {
+        let probe = |task_number, stdin_mode| {
+            Task {
+            task_number,
+            command: "sh".to_string(),
+            args: vec![
+                "-c".to_string(),
+                "if [ -p /dev/stdin ]; then echo pipe; elif [ -c /dev/stdin ]; then echo null; fi; wc -c"
+                    .to_string(),
+            ],
+            stdin_mode,
+            ..Default::default()
+        }
+        };
+        let plan = Plan {
+            plan_id: "plan-stdin".to_string(),
+            tasks: vec![
+                probe(1, None),
+                probe(2, Some(StdinMode::Null)),
+                probe(3, Some(StdinMode::Empty)),
+            ],
+            ..Default::default()
+        };
+
+        let result = execute_plan(
+            "job-123",
+            &plan,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.success);
+        let outputs: Vec<Vec<&str>> = result
+            .task_results
+            .iter()
+            .map(|task| task.stdout.split_whitespace().collect())
+            .collect();
+        // Both read EOF at once, but only `empty` is an open pipe
+        assert_eq!(outputs[0], ["null", "0"]);
+        assert_eq!(outputs[1], ["null", "0"]);
+        assert_eq!(outputs[2], ["pipe", "0"]);
+    }
+
+    #[tokio::test]
+    async fn test_plan_env_reaches_every_task_and_task_env_wins() {
+        let env = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        };
+        let printenv = |task_number| Task {
+            task_number,
+            command: "printenv".to_string(),
+            args: vec!["LANG".to_string(), "STAGE".to_string()],
+            ..Default::default()
+        };
+        let plan = Plan {
+            plan_id: "plan-env".to_string(),
+            tasks: vec![
+                printenv(1),
+                Task {
+                    env: env(&[("STAGE", "task")]),
+                    ..printenv(2)
+                },
+                printenv(3),
+            ],
+            env: env(&[("LANG", "C"), ("STAGE", "plan")]),
+            ..Default::default()
+        };
+        plan.validate().unwrap();
+
+        let result = execute_plan(
+            "job-123",
+            &plan,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.success);
+        assert_eq!(result.task_results[0].stdout, "C\nplan\n");
+        assert_eq!(result.task_results[1].stdout, "C\ntask\n");
+        assert_eq!(result.task_results[2].stdout, "C\nplan\n");
+    }
+
+    #[tokio::test]
+    async fn test_retried_task_logs_each_attempt() {
+        use crate::test_logs::capture_logs;
+
+        let task = Task {
+            task_number: 1,
+            command: "false".to_string(),
+            retries: 2,
+            ..Default::default()
+        };
+
+        let (_guard, logs) = capture_logs();
+
+        let result = execute_task(
+            &task,
+            None,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert!(!result.success);
+
+        let logs = logs.contents();
+        let attempts: Vec<&str> = logs
+            .lines()
+            .filter(|line| line.contains("Task attempt finished"))
+            .collect();
+        assert_eq!(attempts.len(), 3, "{logs}");
+        for (i, line) in attempts.iter().enumerate() {
+            let attempt = i + 1;
+            assert!(line.contains("task=1"), "{line}");
+            assert!(line.contains(&format!("attempt={attempt}")), "{line}");
+            assert!(line.contains("max_attempts=3"), "{line}");
+            assert!(line.contains("exit_code=1"), "{line}");
+            assert!(line.contains("duration_ms="), "{line}");
+            assert!(
+                line.contains(&format!("will_retry={}", attempt < 3)),
+                "{line}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_task_succeeding_on_retry_reports_last_attempt() {
+        let marker = std::env::temp_dir().join(format!("agw-retry-{}", uuid::Uuid::new_v4()));
+        let task = Task {
+            task_number: 1,
+            // Fails the first time, succeeds once the marker exists
+            command: r#"test -e "$1" || { touch "$1"; exit 3; }; echo ok"#.to_string(),
+            args: vec![marker.display().to_string()],
+            shell: true,
+            retries: 2,
+            ..Default::default()
+        };
+        let options = ExecutionOptions {
+            allow_shell: true,
+            ..ExecutionOptions::default()
+        };
+
+        let result = execute_task(&task, None, &serde_json::Value::Null, &options)
+            .await
+            .unwrap();
+        let _ = std::fs::remove_file(&marker);
+        assert!(result.success);
+        assert_eq!(result.stdout, "ok\n");
+    }
+
     #[tokio::test]
     async fn test_execute_invalid_command() {
         let plan = Plan {
@@ -469,11 +2713,116 @@ mod tests {
                 args: vec![],
                 input_from_task: None,
                 timeout_secs: None,
+                ..Default::default()
             }],
+            ..Default::default()
         };
 
-        let result = execute_plan("job-123", &plan).await;
+        let result = execute_plan(
+            "job-123",
+            &plan,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await;
         assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().spawn_failure(),
+            Some(SpawnFailure::NotFound)
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_tool_path_restricts_command_resolution() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("agw-tool-path-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tool = dir.join("agw-pinned-tool");
+        // Absolute interpreter: the pinned PATH does not contain sh or echo
+        std::fs::write(&tool, "#!/bin/sh\necho \"$PATH\"\n").unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let options = ExecutionOptions {
+            tool_path: Some(dir.clone()),
+            command_cache_ttl: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let task = |command: &str| Task {
+            task_number: 1,
+            command: command.to_string(),
+            ..Default::default()
+        };
+        let run = |task: Task| {
+            let options = options.clone();
+            async move { execute_task(&task, None, &serde_json::Value::Null, &options).await }
+        };
+
+        // Found in the pinned directory, and the child sees only that directory
+        let result = run(task("agw-pinned-tool")).await.unwrap();
+        assert!(result.success, "{result:?}");
+        assert_eq!(result.stdout, format!("{}\n", dir.display()));
+
+        // Present on the inherited PATH but not in the pinned directory
+        let err = run(task("echo")).await.unwrap_err();
+        assert_eq!(err.spawn_failure(), Some(SpawnFailure::NotFound));
+
+        // Paths would bypass the pinned directory entirely
+        let err = run(task("/bin/echo")).await.unwrap_err();
+        assert!(err.to_string().contains("bare name"), "{err}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_permission_denied_spawn_classified_as_eacces() {
+        // A file that exists but is not executable
+        let path = std::env::temp_dir().join(format!("agw-noexec-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "#!/bin/sh\necho hi\n").unwrap();
+
+        let task = Task {
+            task_number: 1,
+            command: path.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        let err = execute_task(
+            &task,
+            None,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(err.spawn_failure(), Some(SpawnFailure::PermissionDenied));
+        assert!(err.to_string().contains("EACCES"), "{err}");
+        assert!(err.to_string().contains("os error 13"), "{err}");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_spawn_failure_classification() {
+        let classify = |errno| SpawnFailure::classify(&std::io::Error::from_raw_os_error(errno));
+        assert_eq!(classify(2), SpawnFailure::NotFound);
+        assert_eq!(classify(13), SpawnFailure::PermissionDenied);
+        assert_eq!(classify(11), SpawnFailure::ResourceLimit);
+        assert_eq!(classify(12), SpawnFailure::OutOfMemory);
+        assert_eq!(classify(12).errno_name(), "ENOMEM");
+        assert_eq!(classify(7), SpawnFailure::Other);
+
+        // Only a process limit is worth spawning again for
+        assert!(SpawnFailure::ResourceLimit.is_transient());
+        for kind in [
+            SpawnFailure::NotFound,
+            SpawnFailure::PermissionDenied,
+            SpawnFailure::OutOfMemory,
+            SpawnFailure::Other,
+        ] {
+            assert!(!kind.is_transient(), "{kind}");
+        }
     }
 
     #[test]
@@ -492,6 +2841,34 @@ mod tests {
         assert_eq!(plan_result.combined_stderr(), "error1\nerror2\nerror3\n");
     }
 
+    #[test]
+    fn test_primary_output_modes() {
+        let task_results = vec![
+            TaskResult::new(1, "output1\n".to_string(), String::new(), 0),
+            TaskResult::new(2, "output2\n".to_string(), "boom\n".to_string(), 1),
+            TaskResult::skipped(3, "upstream failed"),
+        ];
+        let plan_result =
+            PlanResult::new("job-123".to_string(), "plan-456".to_string(), task_results);
+
+        assert_eq!(
+            plan_result.primary_output(OutputMode::Concatenated),
+            "output1\noutput2\n"
+        );
+        assert_eq!(
+            plan_result.primary_output(OutputMode::LastTaskOnly),
+            "output2\n"
+        );
+        assert_eq!(
+            plan_result.primary_output(OutputMode::PerTask),
+            r#"{"1":"output1\n","2":"output2\n"}"#
+        );
+
+        let empty = PlanResult::new("job-123".to_string(), "plan-456".to_string(), vec![]);
+        assert_eq!(empty.primary_output(OutputMode::LastTaskOnly), "");
+        assert_eq!(empty.primary_output(OutputMode::PerTask), "{}");
+    }
+
     #[test]
     fn test_combined_output_empty() {
         let plan_result = PlanResult::new("job-123".to_string(), "plan-456".to_string(), vec![]);
@@ -499,4 +2876,328 @@ mod tests {
         assert_eq!(plan_result.combined_stdout(), "");
         assert_eq!(plan_result.combined_stderr(), "");
     }
+
+    #[tokio::test]
+    async fn test_per_task_output_limits_are_independent() {
+        let plan = Plan {
+            plan_id: "plan-456".to_string(),
+            plan_description: None,
+            tasks: vec![
+                Task {
+                    task_number: 1,
+                    command: "echo".to_string(),
+                    args: vec!["hello world".to_string()],
+                    max_output_bytes: Some(5),
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 2,
+                    command: "echo".to_string(),
+                    args: vec!["hello world".to_string()],
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 3,
+                    command: "echo".to_string(),
+                    args: vec!["a much longer line of output".to_string()],
+                    max_output_bytes: Some(1024),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let options = ExecutionOptions {
+            max_output_bytes: Some(12),
+            ..Default::default()
+        };
+
+        let result = execute_plan("job-123", &plan, &serde_json::Value::Null, &options)
+            .await
+            .unwrap();
+        assert!(result.success, "truncation flags but does not fail tasks");
+
+        // Per-task cap below the global cap
+        assert_eq!(result.task_results[0].stdout, "hello");
+        assert!(result.task_results[0].output_truncated);
+
+        // Global cap applies when the task has none ("hello world\n" is exactly 12 bytes)
+        assert_eq!(result.task_results[1].stdout, "hello world\n");
+        assert!(!result.task_results[1].output_truncated);
+
+        // Per-task cap above the global cap overrides it
+        assert_eq!(
+            result.task_results[2].stdout,
+            "a much longer line of output\n"
+        );
+        assert!(!result.task_results[2].output_truncated);
+    }
+
+    #[test]
+    fn test_split_warnings_moves_matching_lines() {
+        let patterns = [Regex::new("^warning:").unwrap()];
+        let (stderr, warnings) = split_warnings(
+            "warning: slow disk\nerror: boom\r\nwarning: retrying\r\ntrailing".to_string(),
+            &patterns,
+        );
+        assert_eq!(stderr, "error: boom\r\ntrailing");
+        assert_eq!(warnings, ["warning: slow disk", "warning: retrying"]);
+
+        // No patterns: stderr is untouched
+        let (stderr, warnings) = split_warnings("warning: x\n".to_string(), &[]);
+        assert_eq!(stderr, "warning: x\n");
+        assert!(warnings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_read_stream_decodes_character_split_across_reads() {
+        // A reader that hands out one byte per read, splitting every multi-byte character
+        struct OneByteReader(std::io::Cursor<Vec<u8>>);
+
+        impl tokio::io::AsyncRead for OneByteReader {
+            fn poll_read(
+                mut self: std::pin::Pin<&mut Self>,
+                _cx: &mut std::task::Context<'_>,
+                buf: &mut tokio::io::ReadBuf<'_>,
+            ) -> std::task::Poll<std::io::Result<()>> {
+                let mut byte = [0u8; 1];
+                let n = std::io::Read::read(&mut self.0, &mut byte)?;
+                buf.put_slice(&byte[..n]);
+                std::task::Poll::Ready(Ok(()))
+            }
+        }
+
+        let input = "日本語 🎉\r\nbad \u{FFFD}\nlast".as_bytes().to_vec();
+        let reader = BufReader::with_capacity(1, OneByteReader(std::io::Cursor::new(input)));
+        let (output, truncated) = read_stream(reader, None, None).await.unwrap();
+        assert_eq!(output, "日本語 🎉\nbad \u{FFFD}\nlast\n");
+        assert!(!truncated);
+
+        let mut invalid = b"ok \xe6\n".to_vec();
+        invalid.extend_from_slice(b"next");
+        let (output, _) = read_stream(BufReader::new(&invalid[..]), None, None)
+            .await
+            .unwrap();
+        assert_eq!(output, "ok \u{FFFD}\nnext\n");
+    }
+
+    #[tokio::test]
+    async fn test_read_stream_truncates_at_char_boundary() {
+        let input: &[u8] = "h\u{e9}llo\n".as_bytes(); // 'é' is 2 bytes at offset 1
+        let (output, truncated) = read_stream(BufReader::new(input), Some(2), None)
+            .await
+            .unwrap();
+        assert_eq!(output, "h");
+        assert!(truncated);
+
+        let (output, truncated) = read_stream(BufReader::new(input), None, None)
+            .await
+            .unwrap();
+        assert_eq!(output, "h\u{e9}llo\n");
+        assert!(!truncated);
+    }
+
+    #[tokio::test]
+    async fn test_read_stream_truncates_at_line_limit() {
+        let input = (1..=100_000)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let (output, truncated) = read_stream(BufReader::new(input.as_bytes()), None, Some(3))
+            .await
+            .unwrap();
+        assert_eq!(output, "1\n2\n3\n[agw: output truncated after 3 lines]\n");
+        assert!(truncated);
+
+        // Exactly at the limit is not truncated
+        let (output, truncated) = read_stream(BufReader::new(&b"a\nb"[..]), None, Some(2))
+            .await
+            .unwrap();
+        assert_eq!(output, "a\nb\n");
+        assert!(!truncated);
+
+        // The byte cap still applies within the kept lines
+        let (output, truncated) = read_stream(BufReader::new(input.as_bytes()), Some(3), Some(5))
+            .await
+            .unwrap();
+        assert_eq!(output, "1\n2");
+        assert!(truncated);
+    }
+
+    #[tokio::test]
+    async fn test_max_output_lines_truncates_task_output() {
+        let task = Task {
+            task_number: 1,
+            command: "seq".to_string(),
+            args: vec!["1".to_string(), "1000000".to_string()],
+            ..Default::default()
+        };
+        let options = ExecutionOptions {
+            max_output_lines: Some(10),
+            ..Default::default()
+        };
+        let result = execute_task(&task, None, &serde_json::Value::Null, &options)
+            .await
+            .unwrap();
+        assert_eq!(result.exit_code, 0);
+        assert!(result.output_truncated);
+        let lines: Vec<&str> = result.stdout.lines().collect();
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[9], "10");
+        assert_eq!(lines[10], "[agw: output truncated after 10 lines]");
+    }
+
+    #[tokio::test]
+    async fn test_chunk_mode_matches_line_mode_for_large_output() {
+        // ~20 MiB of newline-terminated output with multi-byte characters
+        let mut input = Vec::new();
+        for i in 0..400_000 {
+            input.extend_from_slice(
+                format!("line {i:06} caf\u{e9} \u{1F389} {}\n", "x".repeat(20)).as_bytes(),
+            );
+        }
+
+        let (lines, _) = read_output(
+            &input[..],
+            ReadMode::Lines,
+            DEFAULT_READ_BUFFER_SIZE,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        // An odd chunk size splits characters across reads
+        let (chunks, truncated) = read_output(&input[..], ReadMode::Chunks, 65_537, None, None)
+            .await
+            .unwrap();
+
+        assert!(!truncated);
+        assert_eq!(chunks.len(), input.len());
+        assert_eq!(chunks, lines);
+    }
+
+    #[tokio::test]
+    async fn test_chunk_mode_preserves_exact_bytes() {
+        let input = "a\r\nb\n\nno newline \u{e9}".as_bytes();
+        let (output, truncated) = read_output(input, ReadMode::Chunks, 3, None, None)
+            .await
+            .unwrap();
+        assert_eq!(output.as_bytes(), input);
+        assert!(!truncated);
+
+        let (output, truncated) = read_output(input, ReadMode::Chunks, 3, Some(4), None)
+            .await
+            .unwrap();
+        assert_eq!(output, "a\r\nb");
+        assert!(truncated);
+
+        let task = Task {
+            task_number: 1,
+            command: "printf".to_string(),
+            args: vec!["one\\r\\ntwo".to_string()],
+            ..Default::default()
+        };
+        let options = ExecutionOptions {
+            read_mode: ReadMode::Chunks,
+            ..Default::default()
+        };
+        let result = execute_task(&task, None, &serde_json::Value::Null, &options)
+            .await
+            .unwrap();
+        assert_eq!(result.stdout, "one\r\ntwo");
+    }
+
+    #[tokio::test]
+    async fn test_soft_deadline_flags_but_does_not_fail() {
+        let plan = Plan {
+            plan_id: "plan-456".to_string(),
+            plan_description: None,
+            tasks: vec![
+                Task {
+                    task_number: 1,
+                    command: "sleep".to_string(),
+                    args: vec!["1.2".to_string()],
+                    timeout_secs: Some(10),
+                    soft_deadline_secs: Some(1),
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 2,
+                    command: "echo".to_string(),
+                    args: vec!["fast".to_string()],
+                    soft_deadline_secs: Some(5),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let result = execute_plan(
+            "job-123",
+            &plan,
+            &serde_json::Value::Null,
+            &ExecutionOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.success);
+        assert_eq!(result.task_results.len(), 2);
+
+        assert!(result.task_results[0].success);
+        assert!(result.task_results[0].exceeded_soft_deadline);
+
+        assert!(result.task_results[1].success);
+        assert!(!result.task_results[1].exceeded_soft_deadline);
+    }
+
+    #[tokio::test]
+    async fn test_input_as_file_passes_exact_input_json() {
+        let input = serde_json::json!({
+            "path": "/tmp/data.csv",
+            "options": {"columns": ["a", "b"], "limit": 10}
+        });
+        let plan = Plan {
+            plan_id: "plan-456".to_string(),
+            plan_description: None,
+            tasks: vec![
+                Task {
+                    task_number: 1,
+                    command: "sh".to_string(),
+                    args: vec!["-c".to_string(), "cat \"$AGW_INPUT_FILE\"".to_string()],
+                    input_as_file: true,
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 2,
+                    command: "printenv".to_string(),
+                    args: vec![INPUT_FILE_ENV.to_string()],
+                    input_as_file: true,
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 3,
+                    command: "printenv".to_string(),
+                    args: vec![INPUT_FILE_ENV.to_string()],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let result = execute_plan("job-123", &plan, &input, &ExecutionOptions::default())
+            .await
+            .unwrap();
+
+        let received: serde_json::Value =
+            serde_json::from_str(&result.task_results[0].stdout).unwrap();
+        assert_eq!(received, input);
+
+        // The file is removed once the task finishes
+        let path = result.task_results[1].stdout.trim();
+        assert!(!path.is_empty());
+        assert!(!Path::new(path).exists());
+
+        // Tasks without the option don't see the variable
+        assert!(!result.task_results[2].success);
+    }
 }
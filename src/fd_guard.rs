@@ -0,0 +1,246 @@
+//! Soft limit on file descriptors held on behalf of a job
+//!
+//! Every task spawn opens pipes for its output (and input), plus temp files and
+//! FIFOs when asked to. Output readers of a timed-out or killed task can hold
+//! their pipes a little longer than the task itself, so a job churning through
+//! many such tasks can drive the process towards `EMFILE`. Before each spawn
+//! the executor asks the guard whether the job's open descriptors plus the
+//! task's estimate fit under `--max-fds-per-job`; if not, the spawn is delayed
+//! until enough are released. It is a soft guard: after [`MAX_WAIT`] the task
+//! is spawned anyway rather than stalling the job forever.
+
+use crate::plan::{StdinMode, Task};
+use std::time::Duration;
+use std::time::Instant;
+use tracing::{debug, warn};
+
+/// How often open descriptors are re-counted while a spawn is delayed
+pub const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Longest a spawn is delayed before proceeding regardless
+pub const MAX_WAIT: Duration = Duration::from_secs(30);
+
+/// Smallest accepted `--max-fds-per-job`: enough for the most demanding task
+pub const MIN_FDS_PER_JOB: usize = 8;
+
+/// Counts the process's open descriptors (`None` if it cannot tell)
+pub type FdProbe = Box<dyn Fn() -> Option<usize> + Send + Sync>;
+
+/// Per-job descriptor accounting against a soft limit
+pub struct FdGuard {
+    limit: usize,
+    /// Descriptors already open when the job started (connections, logs, ...)
+    baseline: usize,
+    probe: FdProbe,
+    poll_interval: Duration,
+    max_wait: Duration,
+}
+
+impl std::fmt::Debug for FdGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FdGuard")
+            .field("limit", &self.limit)
+            .field("baseline", &self.baseline)
+            .field("poll_interval", &self.poll_interval)
+            .field("max_wait", &self.max_wait)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FdGuard {
+    /// Guard a job starting now, counting descriptors with [`open_fd_count`]
+    #[must_use]
+    pub fn new(limit: usize) -> Self {
+        Self::with_probe(limit, Box::new(open_fd_count))
+    }
+
+    /// Guard a job starting now, counting descriptors with `probe`
+    #[must_use]
+    pub fn with_probe(limit: usize, probe: FdProbe) -> Self {
+        let baseline = probe().unwrap_or(0);
+        Self {
+            limit,
+            baseline,
+            probe,
+            poll_interval: POLL_INTERVAL,
+            max_wait: MAX_WAIT,
+        }
+    }
+
+    /// Descriptors the job currently holds (`None` if they cannot be counted)
+    #[must_use]
+    pub fn in_use(&self) -> Option<usize> {
+        (self.probe)().map(|open| open.saturating_sub(self.baseline))
+    }
+
+    /// Wait until `task` fits under the limit, or the maximum wait has passed
+    ///
+    /// Returns how long the spawn was delayed (zero if it was not).
+    pub async fn wait_for(&self, task: &Task) -> Duration {
+        let needed = estimated_fds(task);
+        let mut delayed_since: Option<Instant> = None;
+        loop {
+            let in_use = match self.in_use() {
+                Some(in_use) if in_use + needed > self.limit => in_use,
+                _ => break,
+            };
+            let started = *delayed_since.get_or_insert_with(|| {
+                debug!(
+                    "Delaying task {}: needs ~{needed} file descriptors with {in_use} of {} in use",
+                    task.task_number, self.limit
+                );
+                Instant::now()
+            });
+            if started.elapsed() >= self.max_wait {
+                warn!(
+                    "Task {} needs ~{needed} file descriptors with {in_use} of {} in use after {}ms, spawning anyway",
+                    task.task_number,
+                    self.limit,
+                    self.max_wait.as_millis()
+                );
+                break;
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+        delayed_since.map_or(Duration::ZERO, |started| started.elapsed())
+    }
+}
+
+/// Descriptors the worker opens to run `task`
+///
+/// Each pipe counts both ends, since both are open in the worker around the
+/// spawn. Detached tasks get `/dev/null` for everything and hold none.
+#[must_use]
+pub fn estimated_fds(task: &Task) -> usize {
+    if task.detach {
+        return 0;
+    }
+    let mut fds = 4; // stdout and stderr pipes
+    if task.stdin_mode() != StdinMode::Null {
+        fds += 2;
+    }
+    if task.input_as_file {
+        fds += 1;
+    }
+    if task.fifo_output.is_some() {
+        fds += 1;
+    }
+    fds
+}
+
+/// Number of descriptors this process has open, where the OS exposes it
+#[must_use]
+pub fn open_fd_count() -> Option<usize> {
+    let dir = if cfg!(target_os = "linux") {
+        "/proc/self/fd"
+    } else if cfg!(unix) {
+        "/dev/fd"
+    } else {
+        return None;
+    };
+    // The directory handle used for listing shows up in the listing itself
+    let entries = std::fs::read_dir(dir).ok()?;
+    Some(entries.count().saturating_sub(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn task() -> Task {
+        Task {
+            task_number: 1,
+            command: "sort".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Guard whose open-descriptor count is `open` (starting at `baseline`)
+    fn mocked(limit: usize, baseline: usize) -> (FdGuard, Arc<AtomicUsize>) {
+        let open = Arc::new(AtomicUsize::new(baseline));
+        let probe_open = Arc::clone(&open);
+        let mut guard = FdGuard::with_probe(
+            limit,
+            Box::new(move || Some(probe_open.load(Ordering::SeqCst))),
+        );
+        guard.poll_interval = Duration::from_millis(10);
+        guard.max_wait = Duration::from_millis(200);
+        (guard, open)
+    }
+
+    #[test]
+    fn test_estimated_fds() {
+        assert_eq!(estimated_fds(&task()), 4);
+        let piped = Task {
+            input_from_task: Some(1),
+            input_as_file: true,
+            fifo_output: Some("/tmp/out.fifo".to_string()),
+            ..task()
+        };
+        assert_eq!(estimated_fds(&piped), MIN_FDS_PER_JOB);
+        let empty_stdin = Task {
+            stdin_mode: Some(StdinMode::Empty),
+            ..task()
+        };
+        assert_eq!(estimated_fds(&empty_stdin), 6);
+        let detached = Task {
+            detach: true,
+            ..task()
+        };
+        assert_eq!(estimated_fds(&detached), 0);
+    }
+
+    #[test]
+    fn test_in_use_is_relative_to_job_start() {
+        let (guard, open) = mocked(16, 40);
+        assert_eq!(guard.in_use(), Some(0));
+        open.store(46, Ordering::SeqCst);
+        assert_eq!(guard.in_use(), Some(6));
+    }
+
+    #[tokio::test]
+    async fn test_spawn_proceeds_at_once_under_limit() {
+        let (guard, open) = mocked(8, 40);
+        open.store(44, Ordering::SeqCst);
+        assert_eq!(guard.wait_for(&task()).await, Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_delayed_until_descriptors_released() {
+        let (guard, open) = mocked(8, 40);
+        open.store(45, Ordering::SeqCst);
+
+        let release = Arc::clone(&open);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            release.store(42, Ordering::SeqCst);
+        });
+
+        let waited = guard.wait_for(&task()).await;
+        assert!(waited >= Duration::from_millis(50), "{waited:?}");
+        assert!(waited < guard.max_wait, "{waited:?}");
+    }
+
+    #[tokio::test]
+    async fn test_spawn_proceeds_after_max_wait() {
+        let (guard, open) = mocked(8, 40);
+        open.store(100, Ordering::SeqCst);
+        let waited = guard.wait_for(&task()).await;
+        assert!(waited >= guard.max_wait, "{waited:?}");
+        assert!(waited < guard.max_wait * 2, "{waited:?}");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_count_never_delays() {
+        let guard = FdGuard::with_probe(8, Box::new(|| None));
+        assert_eq!(guard.wait_for(&task()).await, Duration::ZERO);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_open_fd_count_available_on_linux() {
+        assert!(open_fd_count().is_some());
+    }
+}
@@ -0,0 +1,97 @@
+//! Bounded in-memory history of recently finished jobs
+//!
+//! Lets operators triage recent failures via the admin endpoint without any
+//! external storage. Only the last `capacity` summaries are kept.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Summary of one finished job
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct JobSummary {
+    pub job_id: String,
+    pub plan_id: String,
+    /// Posted status (`completed`, `failed` or `error`)
+    pub status: String,
+    /// Wall time spent executing the plan, in milliseconds
+    pub duration_ms: u64,
+    /// First task that failed, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failed_task: Option<u32>,
+}
+
+/// Ring buffer of the most recent job summaries
+#[derive(Debug)]
+pub struct JobHistory {
+    capacity: usize,
+    entries: Mutex<VecDeque<JobSummary>>,
+}
+
+impl JobHistory {
+    /// Create a history keeping at most `capacity` summaries (0 keeps none)
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Record a finished job, evicting the oldest summary when full
+    pub fn record(&self, summary: JobSummary) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.lock();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(summary);
+    }
+
+    /// Recorded summaries, most recent first
+    #[must_use]
+    pub fn recent(&self) -> Vec<JobSummary> {
+        self.lock().iter().rev().cloned().collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<JobSummary>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(n: u32) -> JobSummary {
+        JobSummary {
+            job_id: format!("job-{n}"),
+            plan_id: "plan-1".to_string(),
+            status: "completed".to_string(),
+            duration_ms: u64::from(n),
+            failed_task: None,
+        }
+    }
+
+    #[test]
+    fn test_history_evicts_oldest_when_full() {
+        let history = JobHistory::new(3);
+        for n in 1..=5 {
+            history.record(summary(n));
+        }
+
+        let ids: Vec<_> = history.recent().into_iter().map(|s| s.job_id).collect();
+        assert_eq!(ids, vec!["job-5", "job-4", "job-3"]);
+    }
+
+    #[test]
+    fn test_zero_capacity_history_keeps_nothing() {
+        let history = JobHistory::new(0);
+        history.record(summary(1));
+        assert!(history.recent().is_empty());
+    }
+}
@@ -1,7 +1,31 @@
 // Public exports for library usage
+pub mod admin;
+pub mod backoff;
+pub mod base64;
+pub mod checkpoint;
+pub mod command_cache;
 pub mod config;
+pub mod decode;
+pub mod dependency;
+pub mod enqueue;
 pub mod error;
 pub mod executor;
+pub mod fd_guard;
+pub mod history;
+pub mod logging;
+pub mod manifest;
+pub mod memory;
+pub mod metrics;
+#[cfg(test)]
+mod mock_agq;
+pub mod output_store;
 pub mod plan;
+pub mod progress;
+pub mod redact;
 pub mod resp;
+pub mod scheduler;
+#[cfg(test)]
+mod test_logs;
+pub mod trust;
+pub mod webhook;
 pub mod worker;
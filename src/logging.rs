@@ -0,0 +1,308 @@
+//! Log output configuration
+//!
+//! Logs always go to the console unless `--log-file-only` is set; with
+//! `--log-file` they are also written to a file that rotates hourly, daily, or
+//! by size. The console is stdout, or stderr under `--emit-results-stdout` so
+//! that stdout carries nothing but result lines.
+//!
+//! Time-based rotation is `tracing-appender`'s: the active file is named for
+//! its period (`<path>.<YYYY-MM-DD>` daily, `<path>.<YYYY-MM-DD-HH>` hourly),
+//! so a file left from an earlier period is never appended to. It has no size
+//! policy, so size rotation is a small wrapper that shifts `<path>` to
+//! `<path>.1`, `<path>.1` to `<path>.2`, and so on.
+
+use clap::ValueEnum;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::Level;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::{fmt, Layer, Registry};
+
+use crate::config::Config;
+
+/// How the log file is rotated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogRotation {
+    /// Never rotate; the file grows indefinitely
+    Never,
+    /// Start a new file every hour (UTC)
+    Hourly,
+    /// Start a new file every day (UTC)
+    Daily,
+    /// Start a new file once the current one reaches `--log-max-bytes`
+    Size,
+}
+
+/// Build the tracing subscriber described by the configuration
+///
+/// # Errors
+///
+/// Returns an error if the log file cannot be opened
+pub fn build_subscriber(config: &Config) -> io::Result<Box<dyn tracing::Subscriber + Send + Sync>> {
+    let max_level = tracing_subscriber::filter::LevelFilter::from_level(Level::INFO);
+
+    let console = if config.emit_results_stdout {
+        BoxMakeWriter::new(io::stderr)
+    } else {
+        BoxMakeWriter::new(io::stdout)
+    };
+    let console_layer =
+        (!config.log_file_only).then(|| fmt::layer().with_writer(console).with_filter(max_level));
+
+    let file_layer = match &config.log_file {
+        Some(path) => {
+            let writer = file_writer(
+                path,
+                config.log_rotation,
+                config.log_max_bytes,
+                config.log_max_files,
+            )?;
+            Some(
+                fmt::layer()
+                    .with_ansi(false)
+                    .with_writer(writer)
+                    .with_filter(max_level),
+            )
+        }
+        None => None,
+    };
+
+    Ok(Box::new(
+        Registry::default().with(console_layer).with(file_layer),
+    ))
+}
+
+/// Writer for the log file at `path`, keeping at most `max_files` files
+fn file_writer(
+    path: &Path,
+    rotation: LogRotation,
+    max_bytes: u64,
+    max_files: usize,
+) -> io::Result<BoxMakeWriter> {
+    let rotation = match rotation {
+        LogRotation::Size => {
+            let file = SizeRotatingFile::open(path, max_bytes, max_files)?;
+            return Ok(BoxMakeWriter::new(Mutex::new(file)));
+        }
+        LogRotation::Never => Rotation::NEVER,
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+    };
+
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Log file path {} has no file name", path.display()),
+        )
+    };
+    let file_name = path
+        .file_name()
+        .ok_or_else(invalid)?
+        .to_str()
+        .ok_or_else(invalid)?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut builder = RollingFileAppender::builder()
+        .rotation(rotation.clone())
+        .filename_prefix(file_name);
+    if rotation != Rotation::NEVER {
+        builder = builder.max_log_files(max_files);
+    }
+    let appender = builder.build(dir).map_err(io::Error::other)?;
+    Ok(BoxMakeWriter::new(appender))
+}
+
+/// A log file that starts over once it reaches `max_bytes`
+///
+/// The full file becomes `<path>.1`, pushing earlier ones to `<path>.2` and
+/// onwards; with the active file, at most `max_files` files are kept.
+struct SizeRotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    written: u64,
+}
+
+impl SizeRotatingFile {
+    /// Open (appending to) the log file at `path`
+    fn open(path: &Path, max_bytes: u64, max_files: usize) -> io::Result<Self> {
+        let file = open_append(path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes,
+            max_files,
+            file,
+            written,
+        })
+    }
+
+    /// Shift every rotated file one generation back, dropping the oldest
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let generations = self.max_files.saturating_sub(1);
+        if generations == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            for generation in (1..generations).rev() {
+                let from = suffixed(&self.path, generation);
+                if from.exists() {
+                    std::fs::rename(from, suffixed(&self.path, generation + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, suffixed(&self.path, 1))?;
+        }
+        self.file = open_append(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for SizeRotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn suffixed(path: &Path, generation: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{generation}"));
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn temp_log_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("agw-{name}-{}.log", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_log_lines_appear_in_configured_file() {
+        let path = temp_log_path("logging");
+        let config = Config::try_parse_from([
+            "agw",
+            "--session-key",
+            "test-session-key",
+            "--log-file",
+            path.to_str().unwrap(),
+            "--log-file-only",
+        ])
+        .unwrap();
+
+        let subscriber = build_subscriber(&config).unwrap();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("hello from the file logger");
+            tracing::debug!("below the configured level");
+        });
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("hello from the file logger"));
+        assert!(!contents.contains("below the configured level"));
+        assert!(
+            !contents.contains('\u{1b}'),
+            "file output must not contain ANSI codes"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_size_rotation_keeps_generations() {
+        let path = temp_log_path("size");
+        let mut file = SizeRotatingFile::open(&path, 10, 3).unwrap();
+
+        for line in [
+            b"first....\n",
+            b"second...\n",
+            b"third....\n",
+            b"fourth...\n",
+        ] {
+            file.write_all(line).unwrap();
+        }
+        file.flush().unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "fourth...\n");
+        assert_eq!(
+            std::fs::read_to_string(suffixed(&path, 1)).unwrap(),
+            "third....\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(suffixed(&path, 2)).unwrap(),
+            "second...\n"
+        );
+        // Three files in all, so the oldest is gone
+        assert!(!suffixed(&path, 3).exists());
+
+        for file in [path.clone(), suffixed(&path, 1), suffixed(&path, 2)] {
+            std::fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_size_rotation_of_existing_full_file() {
+        let path = temp_log_path("size-existing");
+        std::fs::write(&path, "left over from the last run\n").unwrap();
+
+        let mut file = SizeRotatingFile::open(&path, 10, 2).unwrap();
+        file.write_all(b"new\n").unwrap();
+        file.flush().unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new\n");
+        assert_eq!(
+            std::fs::read_to_string(suffixed(&path, 1)).unwrap(),
+            "left over from the last run\n"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(suffixed(&path, 1)).unwrap();
+    }
+
+    #[test]
+    fn test_daily_rotation_writes_dated_file() {
+        let dir = std::env::temp_dir().join(format!("agw-daily-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("agw.log");
+
+        let writer = file_writer(&path, LogRotation::Daily, 0, 2).unwrap();
+        let subscriber =
+            Registry::default().with(fmt::layer().with_ansi(false).with_writer(writer));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("written to today's file");
+        });
+
+        let names: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(names.len(), 1, "{names:?}");
+        assert!(names[0].starts_with("agw.log."), "{}", names[0]);
+        assert_eq!(names[0].len(), "agw.log.YYYY-MM-DD".len(), "{}", names[0]);
+        let contents = std::fs::read_to_string(dir.join(&names[0])).unwrap();
+        assert!(contents.contains("written to today's file"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
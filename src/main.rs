@@ -1,13 +1,36 @@
 use anyhow::Result;
 use clap::Parser;
-use tracing::{info, Level};
-use tracing_subscriber::FmtSubscriber;
+use tracing::info;
 
+mod admin;
+mod backoff;
+mod base64;
+mod checkpoint;
+mod command_cache;
 mod config;
+mod decode;
+mod dependency;
+mod enqueue;
 mod error;
 mod executor;
+mod fd_guard;
+mod history;
+mod logging;
+mod manifest;
+mod memory;
+mod metrics;
+#[cfg(test)]
+mod mock_agq;
+mod output_store;
 mod plan;
+mod progress;
+mod redact;
 mod resp;
+mod scheduler;
+#[cfg(test)]
+mod test_logs;
+mod trust;
+mod webhook;
 mod worker;
 
 use config::Config;
@@ -15,15 +38,27 @@ use worker::Worker;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize tracing subscriber
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::INFO)
-        .finish();
-    tracing::subscriber::set_global_default(subscriber)?;
-
     // Parse CLI arguments
     let config = Config::parse();
 
+    // Initialize tracing subscriber (stdout and/or rotating log file)
+    let subscriber = logging::build_subscriber(&config)?;
+    tracing::subscriber::set_global_default(subscriber)?;
+
+    if let Some(config::Command::Enqueue(args)) = &config.command {
+        let mut client = resp::RespClient::connect(&config.agq_address).await?;
+        if let Some(nodes) = config.redirect_nodes() {
+            client.enable_cluster_redirects(nodes);
+        }
+        let key_source = config.session_key_source();
+        client
+            .authenticate_with_retry(&key_source, config.auth_retry, &config.auth_backoff())
+            .await?;
+        let job_id = enqueue::enqueue(&mut client, args).await?;
+        println!("{job_id}");
+        return Ok(());
+    }
+
     info!("AGW v{} starting...", env!("CARGO_PKG_VERSION"));
 
     // Create and run worker
@@ -0,0 +1,255 @@
+//! Per-job provenance manifest (`job:<id>:manifest`)
+//!
+//! Records what each executed task actually ran, where its input came from and
+//! a digest of what it produced, so a pipeline run can be reproduced and
+//! checked later. Unlike the result payload it carries no output itself.
+//! With `--manifest-config` it also records the worker settings that shaped
+//! the run, so results from differently configured workers can be told apart.
+
+use crate::executor::{PlanResult, TaskResult};
+use crate::plan::{Plan, Task};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+/// Provenance record for one job
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobManifest {
+    pub job_id: String,
+    pub plan_id: String,
+    /// One entry per executed task, in execution order (skipped tasks are omitted)
+    pub tasks: Vec<ManifestEntry>,
+    /// Settings of the worker that ran the job (`--manifest-config`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<ConfigSnapshot>,
+}
+
+/// Worker settings relevant to how a job was validated and executed
+///
+/// Holds no credentials: session and signing keys are never included, and
+/// paths and aliases pass through the worker's redaction patterns.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigSnapshot {
+    pub agw_version: String,
+    pub strict_plan_parsing: bool,
+    pub require_timeouts: bool,
+    pub allow_shell: bool,
+    pub max_substituted_arg_count: Option<usize>,
+    pub max_substituted_total_bytes: Option<usize>,
+    pub reject_empty_substituted_args: bool,
+    pub default_task_timeout_secs: Option<u32>,
+    pub kill_grace_secs: u64,
+    pub shutdown_timeout_secs: Option<u64>,
+    pub max_output_bytes: Option<usize>,
+    pub max_output_lines: Option<usize>,
+    pub checkpoint_tasks: bool,
+    pub tool_path: Option<String>,
+    /// Logical tool name -> binary spawned on this worker
+    pub tool_aliases: BTreeMap<String, String>,
+}
+
+/// What one task ran and produced
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub task_number: u32,
+    /// Logical command from the plan (before alias resolution)
+    pub command: String,
+    /// Program and arguments handed to the OS, redacted like the task result
+    pub argv: Vec<String>,
+    /// Where the task's input came from
+    pub input: InputSource,
+    /// Hex SHA-256 of the task's captured stdout
+    pub output_sha256: String,
+    /// Wall time the task ran, in milliseconds
+    pub duration_ms: u64,
+    pub exit_code: i32,
+}
+
+/// Source of a task's input
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputSource {
+    /// No stdin and no input file
+    None,
+    /// Stdout of an earlier task, piped to stdin
+    Task(u32),
+    /// Stdout of several earlier tasks, concatenated in order and piped to stdin
+    Tasks(Vec<u32>),
+    /// The job input, written to the file named by `AGW_INPUT_FILE`
+    InputFile,
+}
+
+impl InputSource {
+    fn of(task: &Task) -> Self {
+        match task.input_from_task {
+            Some(upstream) => Self::Task(upstream),
+            None if !task.input_from_tasks.is_empty() => Self::Tasks(task.input_from_tasks.clone()),
+            None if task.input_as_file => Self::InputFile,
+            None => Self::None,
+        }
+    }
+}
+
+impl JobManifest {
+    /// Build the manifest for `result`, an execution of `plan`
+    #[must_use]
+    pub fn build(plan: &Plan, result: &PlanResult) -> Self {
+        let tasks = result
+            .task_results
+            .iter()
+            .filter(|task_result| !task_result.skipped)
+            .filter_map(|task_result| {
+                let task = plan
+                    .tasks
+                    .iter()
+                    .find(|task| task.task_number == task_result.task_number)?;
+                Some(ManifestEntry::new(task, task_result))
+            })
+            .collect();
+        Self {
+            job_id: result.job_id.clone(),
+            plan_id: result.plan_id.clone(),
+            tasks,
+            config: None,
+        }
+    }
+
+    /// Attach the settings of the worker that ran the job
+    #[must_use]
+    pub fn with_config(mut self, config: Option<ConfigSnapshot>) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Serialize to JSON
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization fails
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
+
+impl ManifestEntry {
+    fn new(task: &Task, result: &TaskResult) -> Self {
+        Self {
+            task_number: task.task_number,
+            command: task.command.clone(),
+            argv: result.executed_argv.clone(),
+            input: InputSource::of(task),
+            output_sha256: sha256_hex(result.stdout.as_bytes()),
+            duration_ms: result.duration_ms,
+            exit_code: result.exit_code,
+        }
+    }
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    let digest = Sha256::digest(data);
+    digest
+        .iter()
+        .fold(String::with_capacity(digest.len() * 2), |mut hex, b| {
+            let _ = write!(hex, "{b:02x}");
+            hex
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(task_number: u32, command: &str) -> Task {
+        Task {
+            task_number,
+            command: command.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_manifest_has_one_entry_per_executed_task() {
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![
+                Task {
+                    input_as_file: true,
+                    ..task(1, "cat")
+                },
+                Task {
+                    input_from_task: Some(1),
+                    ..task(2, "sort")
+                },
+                Task {
+                    input_from_task: Some(2),
+                    ..task(3, "uniq")
+                },
+            ],
+            ..Default::default()
+        };
+        let mut first = TaskResult::new(1, "b\na\n".to_string(), String::new(), 0);
+        first.executed_argv = vec!["cat".to_string()];
+        first.duration_ms = 12;
+        let mut second = TaskResult::new(2, String::new(), "boom\n".to_string(), 2);
+        second.executed_argv = vec!["sort".to_string(), "-r".to_string()];
+        let result = PlanResult::new(
+            "job-1".to_string(),
+            "plan-1".to_string(),
+            vec![first, second, TaskResult::skipped(3, "upstream failed")],
+        );
+
+        let manifest = JobManifest::build(&plan, &result);
+        assert_eq!(manifest.job_id, "job-1");
+        assert_eq!(manifest.plan_id, "plan-1");
+        assert_eq!(
+            manifest.tasks,
+            vec![
+                ManifestEntry {
+                    task_number: 1,
+                    command: "cat".to_string(),
+                    argv: vec!["cat".to_string()],
+                    input: InputSource::InputFile,
+                    output_sha256: sha256_hex(b"b\na\n"),
+                    duration_ms: 12,
+                    exit_code: 0,
+                },
+                ManifestEntry {
+                    task_number: 2,
+                    command: "sort".to_string(),
+                    argv: vec!["sort".to_string(), "-r".to_string()],
+                    input: InputSource::Task(1),
+                    output_sha256: sha256_hex(b""),
+                    duration_ms: 0,
+                    exit_code: 2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_manifest_json_shape() {
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![task(1, "echo")],
+            ..Default::default()
+        };
+        let result = PlanResult::new(
+            "job-1".to_string(),
+            "plan-1".to_string(),
+            vec![TaskResult::new(1, String::new(), String::new(), 0)],
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&JobManifest::build(&plan, &result).to_json().unwrap()).unwrap();
+        let entry = &json["tasks"][0];
+        assert_eq!(entry["input"], "none");
+        assert_eq!(
+            entry["output_sha256"],
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        for field in ["task_number", "command", "argv", "duration_ms", "exit_code"] {
+            assert!(entry.get(field).is_some(), "missing {field}");
+        }
+    }
+}
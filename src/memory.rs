@@ -0,0 +1,92 @@
+//! Worker memory self-monitoring
+//!
+//! With `--max-worker-rss-mb`, the worker checks its own resident set size on
+//! every heartbeat and shuts down gracefully once it exceeds the limit, so a
+//! leak is cured by the orchestrator restarting the worker rather than by the
+//! kernel OOM-killing whatever happens to be co-located with it. RSS is read
+//! from `/proc/self/status` and is only available on Linux.
+
+/// Resident set size of this process in bytes (`None` where it cannot be read)
+#[must_use]
+pub fn current_rss_bytes() -> Option<u64> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    parse_vm_rss(&status)
+}
+
+/// Extract `VmRSS` (reported in kB) from the contents of `/proc/<pid>/status`
+fn parse_vm_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let mut fields = line["VmRSS:".len()..].split_whitespace();
+    let kib: u64 = fields.next()?.parse().ok()?;
+    match fields.next() {
+        Some("kB") | None => kib.checked_mul(1024),
+        Some(_) => None,
+    }
+}
+
+/// Checks the worker's RSS against a limit
+#[derive(Debug, Clone, Copy)]
+pub struct RssMonitor {
+    limit_bytes: u64,
+}
+
+impl RssMonitor {
+    /// Monitor against a limit of `limit_mb` mebibytes
+    #[must_use]
+    pub fn new(limit_mb: u64) -> Self {
+        Self {
+            limit_bytes: limit_mb.saturating_mul(1024 * 1024),
+        }
+    }
+
+    /// The current RSS if it is over the limit
+    ///
+    /// Returns `None` when under the limit or when RSS cannot be read.
+    #[must_use]
+    pub fn exceeded(&self) -> Option<u64> {
+        self.check(current_rss_bytes())
+    }
+
+    fn check(&self, rss: Option<u64>) -> Option<u64> {
+        rss.filter(|&rss| rss > self.limit_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_vm_rss() {
+        let status = "Name:\tagw\nVmPeak:\t  204800 kB\nVmRSS:\t   10240 kB\nThreads:\t4\n";
+        assert_eq!(parse_vm_rss(status), Some(10 * 1024 * 1024));
+        assert_eq!(parse_vm_rss("Name:\tagw\n"), None);
+        assert_eq!(parse_vm_rss("VmRSS:\tlots kB\n"), None);
+        assert_eq!(parse_vm_rss("VmRSS:\t10 MB\n"), None);
+    }
+
+    #[test]
+    fn test_threshold() {
+        let monitor = RssMonitor::new(100);
+        assert_eq!(monitor.check(Some(100 * 1024 * 1024)), None);
+        assert_eq!(
+            monitor.check(Some(100 * 1024 * 1024 + 1)),
+            Some(100 * 1024 * 1024 + 1)
+        );
+        assert_eq!(monitor.check(None), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_reads_own_rss_on_linux() {
+        let rss = current_rss_bytes().unwrap();
+        assert!(rss > 0);
+        assert!(RssMonitor::new(1).exceeded().is_some());
+        assert!(RssMonitor::new(u64::MAX / (1024 * 1024))
+            .exceeded()
+            .is_none());
+    }
+}
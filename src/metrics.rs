@@ -0,0 +1,163 @@
+//! Process-wide metrics in the Prometheus text exposition format
+//!
+//! A deliberately small registry: labeled monotonic counters, rendered on the
+//! admin server's `GET /metrics`. Label values are kept in a sorted map so the
+//! output is stable between scrapes.
+
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+
+/// Process-wide metrics updated by the executor
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::default);
+
+/// Label value for tasks that exited successfully
+pub const RESULT_SUCCESS: &str = "success";
+/// Label value for tasks that failed (non-zero exit, timeout, spawn error, ...)
+pub const RESULT_FAILURE: &str = "failure";
+
+/// All metrics exported by the worker
+#[derive(Debug)]
+pub struct Metrics {
+    /// Executed tasks by command and result
+    pub task_total: LabeledCounter,
+    /// Failed heartbeats by failure kind (network, protocol, auth_expired)
+    pub heartbeat_failures_total: LabeledCounter,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            task_total: LabeledCounter::new(
+                "agw_task_total",
+                "Tasks executed, by command and result",
+                &["command", "result"],
+            ),
+            heartbeat_failures_total: LabeledCounter::new(
+                "agw_heartbeat_failures_total",
+                "Failed heartbeats, by failure kind",
+                &["kind"],
+            ),
+        }
+    }
+}
+
+impl Metrics {
+    /// Render every metric in the Prometheus text format
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.task_total.render(&mut out);
+        self.heartbeat_failures_total.render(&mut out);
+        out
+    }
+}
+
+/// Monotonic counter partitioned by a fixed set of labels
+#[derive(Debug)]
+pub struct LabeledCounter {
+    name: &'static str,
+    help: &'static str,
+    label_names: &'static [&'static str],
+    values: Mutex<BTreeMap<Vec<String>, u64>>,
+}
+
+impl LabeledCounter {
+    /// Create a counter with the given label names
+    #[must_use]
+    pub fn new(
+        name: &'static str,
+        help: &'static str,
+        label_names: &'static [&'static str],
+    ) -> Self {
+        Self {
+            name,
+            help,
+            label_names,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Increment the series identified by `labels` (one value per label name)
+    pub fn inc(&self, labels: &[&str]) {
+        debug_assert_eq!(labels.len(), self.label_names.len());
+        let key = labels.iter().map(ToString::to_string).collect();
+        let mut values = self.lock();
+        let value = values.entry(key).or_insert(0);
+        *value = value.saturating_add(1);
+    }
+
+    /// Current value of the series identified by `labels` (0 if never incremented)
+    #[allow(dead_code)] // Used in tests
+    #[must_use]
+    pub fn get(&self, labels: &[&str]) -> u64 {
+        let key: Vec<String> = labels.iter().map(ToString::to_string).collect();
+        self.lock().get(&key).copied().unwrap_or(0)
+    }
+
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} counter", self.name);
+        for (labels, value) in self.lock().iter() {
+            let labels = self
+                .label_names
+                .iter()
+                .zip(labels)
+                .map(|(name, value)| format!("{name}=\"{}\"", escape_label_value(value)))
+                .collect::<Vec<_>>()
+                .join(",");
+            let _ = writeln!(out, "{}{{{labels}}} {value}", self.name);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<Vec<String>, u64>> {
+        self.values
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// Escape a label value per the text exposition format
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labeled_counter_counts_each_series_separately() {
+        let metrics = Metrics::default();
+        metrics.task_total.inc(&["sort", RESULT_SUCCESS]);
+        metrics.task_total.inc(&["sort", RESULT_SUCCESS]);
+        metrics.task_total.inc(&["sort", RESULT_FAILURE]);
+
+        assert_eq!(metrics.task_total.get(&["sort", RESULT_SUCCESS]), 2);
+        assert_eq!(metrics.task_total.get(&["sort", RESULT_FAILURE]), 1);
+        assert_eq!(metrics.task_total.get(&["uniq", RESULT_SUCCESS]), 0);
+    }
+
+    #[test]
+    fn test_render_prometheus_text_format() {
+        let metrics = Metrics::default();
+        metrics.task_total.inc(&["sort", RESULT_SUCCESS]);
+        metrics.task_total.inc(&["we\"ird\\", RESULT_FAILURE]);
+        metrics.heartbeat_failures_total.inc(&["network"]);
+
+        assert_eq!(
+            metrics.render(),
+            "# HELP agw_task_total Tasks executed, by command and result\n\
+             # TYPE agw_task_total counter\n\
+             agw_task_total{command=\"sort\",result=\"success\"} 1\n\
+             agw_task_total{command=\"we\\\"ird\\\\\",result=\"failure\"} 1\n\
+             # HELP agw_heartbeat_failures_total Failed heartbeats, by failure kind\n\
+             # TYPE agw_heartbeat_failures_total counter\n\
+             agw_heartbeat_failures_total{kind=\"network\"} 1\n"
+        );
+    }
+}
@@ -0,0 +1,413 @@
+//! In-process mock AGQ server for tests
+//!
+//! Speaks enough RESP to exercise `RespClient` and the worker job flow without a
+//! real AGQ/Redis instance. State is shared behind a mutex so tests can seed keys
+//! and queues, inspect what the worker wrote, and inject faults (dropped
+//! connections, scripted replies).
+
+#![allow(dead_code)] // Not every helper is used by every test target
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A RESP reply the mock can send
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    Simple(String),
+    Error(String),
+    Integer(i64),
+    Bulk(String),
+    Nil,
+    Array(Vec<Reply>),
+}
+
+impl Reply {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::Simple(s) => out.extend_from_slice(format!("+{s}\r\n").as_bytes()),
+            Self::Error(s) => out.extend_from_slice(format!("-{s}\r\n").as_bytes()),
+            Self::Integer(i) => out.extend_from_slice(format!(":{i}\r\n").as_bytes()),
+            Self::Bulk(s) => {
+                out.extend_from_slice(format!("${}\r\n", s.len()).as_bytes());
+                out.extend_from_slice(s.as_bytes());
+                out.extend_from_slice(b"\r\n");
+            }
+            Self::Nil => out.extend_from_slice(b"$-1\r\n"),
+            Self::Array(items) => {
+                out.extend_from_slice(format!("*{}\r\n", items.len()).as_bytes());
+                for item in items {
+                    item.encode(out);
+                }
+            }
+        }
+    }
+}
+
+/// Shared, inspectable server state
+#[derive(Debug, Default)]
+pub struct MockState {
+    /// Session key required by AUTH (no auth required when `None`)
+    pub session_key: Option<String>,
+    /// String keyspace
+    pub strings: HashMap<String, String>,
+    /// List keyspace (front = head/left)
+    pub lists: HashMap<String, VecDeque<String>>,
+    /// Every command received, in order, across all connections
+    pub commands: Vec<Vec<String>>,
+    /// Connection number (1-based, in accept order) each entry in `commands` arrived on
+    pub command_connections: Vec<usize>,
+    /// Number of accepted connections
+    pub connections: usize,
+    /// Scripted replies consumed before normal handling, keyed by command name
+    pub scripted: HashMap<String, VecDeque<Reply>>,
+    /// Close the connection instead of replying the next N times a command is seen
+    pub drop_on: HashMap<String, usize>,
+    /// Wait this long before replying to a command, keyed by command name
+    pub delays: HashMap<String, Duration>,
+}
+
+/// Handle to a running mock server
+#[derive(Clone)]
+pub struct MockAgq {
+    pub address: String,
+    pub state: Arc<Mutex<MockState>>,
+}
+
+impl MockAgq {
+    /// Start a mock server on an ephemeral localhost port
+    pub async fn start(session_key: Option<&str>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let state = Arc::new(Mutex::new(MockState {
+            session_key: session_key.map(str::to_string),
+            ..MockState::default()
+        }));
+
+        let accept_state = Arc::clone(&state);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let connection = {
+                    let mut state = accept_state.lock().unwrap();
+                    state.connections += 1;
+                    state.connections
+                };
+                tokio::spawn(handle_connection(
+                    stream,
+                    connection,
+                    Arc::clone(&accept_state),
+                ));
+            }
+        });
+
+        Self { address, state }
+    }
+
+    pub fn set(&self, key: &str, value: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .strings
+            .insert(key.to_string(), value.to_string());
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.state.lock().unwrap().strings.get(key).cloned()
+    }
+
+    pub fn push(&self, list: &str, value: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .lists
+            .entry(list.to_string())
+            .or_default()
+            .push_front(value.to_string());
+    }
+
+    pub fn list(&self, list: &str) -> Vec<String> {
+        self.state
+            .lock()
+            .unwrap()
+            .lists
+            .get(list)
+            .map(|l| l.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Queue a scripted reply for the next occurrence of `command`
+    pub fn script(&self, command: &str, reply: Reply) {
+        self.state
+            .lock()
+            .unwrap()
+            .scripted
+            .entry(command.to_uppercase())
+            .or_default()
+            .push_back(reply);
+    }
+
+    /// Drop the connection the next `times` occurrences of `command`
+    pub fn drop_on(&self, command: &str, times: usize) {
+        self.state
+            .lock()
+            .unwrap()
+            .drop_on
+            .insert(command.to_uppercase(), times);
+    }
+
+    /// Delay every reply to `command` by `delay`
+    pub fn delay_on(&self, command: &str, delay: Duration) {
+        self.state
+            .lock()
+            .unwrap()
+            .delays
+            .insert(command.to_uppercase(), delay);
+    }
+
+    /// Count received commands with the given name
+    pub fn count(&self, command: &str) -> usize {
+        self.state
+            .lock()
+            .unwrap()
+            .commands
+            .iter()
+            .filter(|c| c[0] == command.to_uppercase())
+            .count()
+    }
+
+    /// Connection numbers that received the given command, in order
+    pub fn connections_for(&self, command: &str) -> Vec<usize> {
+        let state = self.state.lock().unwrap();
+        state
+            .commands
+            .iter()
+            .zip(&state.command_connections)
+            .filter(|(c, _)| c[0] == command.to_uppercase())
+            .map(|(_, connection)| *connection)
+            .collect()
+    }
+
+    pub fn connections(&self) -> usize {
+        self.state.lock().unwrap().connections
+    }
+}
+
+async fn handle_connection(stream: TcpStream, connection: usize, state: Arc<Mutex<MockState>>) {
+    let (read_half, mut write_half) = stream.into_split();
+    let mut reader = BufReader::new(read_half);
+    let mut authenticated = state.lock().unwrap().session_key.is_none();
+
+    while let Some(args) = read_command(&mut reader).await {
+        if args.is_empty() {
+            continue;
+        }
+        let name = args[0].to_uppercase();
+
+        let reply = {
+            let mut state = state.lock().unwrap();
+            let mut logged = args.clone();
+            logged[0].clone_from(&name);
+            state.commands.push(logged);
+            state.command_connections.push(connection);
+
+            if let Some(remaining) = state.drop_on.get_mut(&name) {
+                if *remaining > 0 {
+                    *remaining -= 1;
+                    return;
+                }
+            }
+
+            if let Some(reply) = state.scripted.get_mut(&name).and_then(VecDeque::pop_front) {
+                Some(reply)
+            } else {
+                execute(&mut state, &name, &args, &mut authenticated)
+            }
+        };
+
+        // Commands with no immediate reply (empty blocking pop) wait briefly, then time out
+        let reply = match reply {
+            Some(reply) => reply,
+            None => {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Reply::Nil
+            }
+        };
+        let delay = state.lock().unwrap().delays.get(&name).copied();
+        if let Some(delay) = delay {
+            tokio::time::sleep(delay).await;
+        }
+
+        let mut out = Vec::new();
+        reply.encode(&mut out);
+        if write_half.write_all(&out).await.is_err() {
+            return;
+        }
+    }
+}
+
+fn execute(
+    state: &mut MockState,
+    name: &str,
+    args: &[String],
+    authenticated: &mut bool,
+) -> Option<Reply> {
+    let arg = |i: usize| args.get(i).cloned().unwrap_or_default();
+
+    if name == "AUTH" {
+        return Some(match &state.session_key {
+            Some(key) if *key == arg(args.len() - 1) => {
+                *authenticated = true;
+                Reply::Simple("OK".to_string())
+            }
+            Some(_) => Reply::Error("ERR invalid session key".to_string()),
+            None => Reply::Simple("OK".to_string()),
+        });
+    }
+
+    // Client library handshake commands (and cluster ASKING) are always accepted
+    if name == "CLIENT" || name == "ASKING" {
+        return Some(Reply::Simple("OK".to_string()));
+    }
+
+    if !*authenticated {
+        return Some(Reply::Error("NOAUTH Authentication required.".to_string()));
+    }
+
+    let reply = match name {
+        "PING" => {
+            if args.len() > 1 {
+                Reply::Bulk(arg(1))
+            } else {
+                Reply::Simple("PONG".to_string())
+            }
+        }
+        "GET" => state
+            .strings
+            .get(&arg(1))
+            .map_or(Reply::Nil, |v| Reply::Bulk(v.clone())),
+        "SET" => {
+            state.strings.insert(arg(1), arg(2));
+            Reply::Simple("OK".to_string())
+        }
+        "DEL" => {
+            let removed = args[1..]
+                .iter()
+                .filter(|k| state.strings.remove(*k).is_some() || state.lists.remove(*k).is_some())
+                .count();
+            Reply::Integer(i64::try_from(removed).unwrap())
+        }
+        "EXISTS" => Reply::Integer(i64::from(
+            state.strings.contains_key(&arg(1)) || state.lists.contains_key(&arg(1)),
+        )),
+        "LPUSH" | "RPUSH" => {
+            let list = state.lists.entry(arg(1)).or_default();
+            for value in &args[2..] {
+                if name == "LPUSH" {
+                    list.push_front(value.clone());
+                } else {
+                    list.push_back(value.clone());
+                }
+            }
+            Reply::Integer(i64::try_from(list.len()).unwrap())
+        }
+        "LLEN" => Reply::Integer(
+            state
+                .lists
+                .get(&arg(1))
+                .map_or(0, |l| i64::try_from(l.len()).unwrap()),
+        ),
+        "LRANGE" => {
+            let list: Vec<String> = state
+                .lists
+                .get(&arg(1))
+                .map(|l| l.iter().cloned().collect())
+                .unwrap_or_default();
+            let len = i64::try_from(list.len()).unwrap();
+            let index = |i: usize| {
+                let i: i64 = arg(i).parse().unwrap_or(0);
+                if i < 0 {
+                    (len + i).max(0)
+                } else {
+                    i
+                }
+            };
+            let (start, stop) = (index(2), index(3).min(len - 1));
+            let range = if start > stop {
+                Vec::new()
+            } else {
+                list[usize::try_from(start).unwrap()..=usize::try_from(stop).unwrap()].to_vec()
+            };
+            Reply::Array(range.into_iter().map(Reply::Bulk).collect())
+        }
+        "LREM" => {
+            let count: i64 = arg(2).parse().unwrap_or(0);
+            let element = arg(3);
+            let mut removed = 0;
+            if let Some(list) = state.lists.get_mut(&arg(1)) {
+                while count == 0 || removed < count.abs() {
+                    let Some(pos) = list.iter().position(|v| *v == element) else {
+                        break;
+                    };
+                    list.remove(pos);
+                    removed += 1;
+                }
+            }
+            Reply::Integer(removed)
+        }
+        "BRPOPLPUSH" | "RPOPLPUSH" => {
+            let value = state.lists.get_mut(&arg(1)).and_then(VecDeque::pop_back)?;
+            state
+                .lists
+                .entry(arg(2))
+                .or_default()
+                .push_front(value.clone());
+            Reply::Bulk(value)
+        }
+        "BRPOP" => {
+            // Keys are checked in order; the last argument is the timeout
+            let keys = &args[1..args.len().saturating_sub(1).max(1)];
+            let (key, value) = keys.iter().find_map(|key| {
+                let value = state.lists.get_mut(key).and_then(VecDeque::pop_back)?;
+                Some((key.clone(), value))
+            })?;
+            Reply::Array(vec![Reply::Bulk(key), Reply::Bulk(value)])
+        }
+        "TIME" => {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap();
+            Reply::Array(vec![
+                Reply::Bulk(now.as_secs().to_string()),
+                Reply::Bulk(now.subsec_micros().to_string()),
+            ])
+        }
+        _ => Reply::Error(format!("ERR unknown command '{name}'")),
+    };
+
+    Some(reply)
+}
+
+/// Read one RESP array-of-bulk-strings command (what redis-rs always sends)
+async fn read_command<R: tokio::io::AsyncRead + Unpin>(
+    reader: &mut BufReader<R>,
+) -> Option<Vec<String>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).await.ok()? == 0 {
+        return None;
+    }
+    let count: usize = line.trim_end().strip_prefix('*')?.parse().ok()?;
+
+    let mut args = Vec::with_capacity(count);
+    for _ in 0..count {
+        line.clear();
+        reader.read_line(&mut line).await.ok()?;
+        let len: usize = line.trim_end().strip_prefix('$')?.parse().ok()?;
+        let mut buf = vec![0u8; len + 2];
+        reader.read_exact(&mut buf).await.ok()?;
+        buf.truncate(len);
+        args.push(String::from_utf8_lossy(&buf).into_owned());
+    }
+    Some(args)
+}
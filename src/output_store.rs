@@ -0,0 +1,216 @@
+//! Outputs of finished tasks, kept for the tasks downstream of them
+//!
+//! Every task's stdout is held until the plan finishes, since a later task may
+//! read it on stdin or through `{{task.N.stdout}}`. The store owns those
+//! outputs while the plan runs and hands them back to the task results once it
+//! is done. In a long pipeline of big outputs that adds up, so with
+//! `--compress-outputs-above` outputs larger than the threshold are held
+//! LZ4-compressed and expanded again only when a downstream task reads them.
+
+use crate::error::{AgwError, AgwResult};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use tracing::debug;
+
+/// Task number -> stdout, compressed past a size threshold
+#[derive(Debug, Default)]
+pub struct OutputStore {
+    /// Outputs larger than this many bytes are compressed (`None` = never)
+    compress_above: Option<usize>,
+    outputs: HashMap<u32, StoredOutput>,
+    /// Bytes currently held for all stored outputs
+    held_bytes: usize,
+    /// Most bytes held at once
+    peak_bytes: usize,
+}
+
+#[derive(Debug)]
+enum StoredOutput {
+    Plain(String),
+    Compressed { block: Vec<u8>, length: usize },
+}
+
+impl StoredOutput {
+    fn held_bytes(&self) -> usize {
+        match self {
+            Self::Plain(output) => output.len(),
+            Self::Compressed { block, .. } => block.len(),
+        }
+    }
+
+    fn into_output(self, task_number: u32) -> AgwResult<String> {
+        match self {
+            Self::Plain(output) => Ok(output),
+            Self::Compressed { block, length } => decompress(task_number, &block, length),
+        }
+    }
+}
+
+impl OutputStore {
+    #[must_use]
+    pub fn new(compress_above: Option<usize>) -> Self {
+        Self {
+            compress_above,
+            ..Self::default()
+        }
+    }
+
+    /// Take ownership of `output` of `task_number`, replacing any earlier one
+    pub fn insert(&mut self, task_number: u32, output: String) {
+        let stored = match self.compress_above {
+            Some(threshold) if output.len() > threshold => {
+                let block = lz4_flex::block::compress(output.as_bytes());
+                // Output that doesn't shrink is kept as it is
+                if block.len() < output.len() {
+                    debug!(
+                        "Task {task_number} output compressed from {} to {} bytes",
+                        output.len(),
+                        block.len()
+                    );
+                    StoredOutput::Compressed {
+                        block,
+                        length: output.len(),
+                    }
+                } else {
+                    StoredOutput::Plain(output)
+                }
+            }
+            _ => StoredOutput::Plain(output),
+        };
+        self.held_bytes += stored.held_bytes();
+        if let Some(replaced) = self.outputs.insert(task_number, stored) {
+            self.held_bytes -= replaced.held_bytes();
+        }
+        self.peak_bytes = self.peak_bytes.max(self.held_bytes);
+    }
+
+    /// Output of `task_number`, decompressed if need be
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a compressed output fails to decompress
+    pub fn get(&self, task_number: u32) -> AgwResult<Option<Cow<'_, str>>> {
+        match self.outputs.get(&task_number) {
+            None => Ok(None),
+            Some(StoredOutput::Plain(output)) => Ok(Some(Cow::Borrowed(output))),
+            Some(StoredOutput::Compressed { block, length }) => {
+                decompress(task_number, block, *length).map(|output| Some(Cow::Owned(output)))
+            }
+        }
+    }
+
+    /// Outputs of `task_numbers` that are stored, decompressed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if one of them fails to decompress
+    pub fn outputs_of(&self, task_numbers: &[u32]) -> AgwResult<HashMap<u32, String>> {
+        let mut outputs = HashMap::new();
+        for &task_number in task_numbers {
+            if let Some(output) = self.get(task_number)? {
+                outputs.insert(task_number, output.into_owned());
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// Remove the output of `task_number`, decompressed, to hand it back
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if it fails to decompress
+    pub fn take(&mut self, task_number: u32) -> AgwResult<Option<String>> {
+        let Some(stored) = self.outputs.remove(&task_number) else {
+            return Ok(None);
+        };
+        self.held_bytes -= stored.held_bytes();
+        stored.into_output(task_number).map(Some)
+    }
+
+    /// Most bytes held at once for stored outputs
+    #[must_use]
+    pub fn peak_bytes(&self) -> usize {
+        self.peak_bytes
+    }
+}
+
+fn decompress(task_number: u32, block: &[u8], length: usize) -> AgwResult<String> {
+    let bytes = lz4_flex::block::decompress(block, length).map_err(|e| {
+        AgwError::Executor(format!(
+            "Stored output of task {task_number} failed to decompress: {e}"
+        ))
+    })?;
+    if bytes.len() != length {
+        return Err(AgwError::Executor(format!(
+            "Stored output of task {task_number} decompressed to {} bytes, expected {length}",
+            bytes.len()
+        )));
+    }
+    String::from_utf8(bytes).map_err(|e| {
+        AgwError::Executor(format!(
+            "Stored output of task {task_number} is not valid UTF-8 after decompression: {e}"
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write;
+
+    fn large_output() -> String {
+        let mut output = String::new();
+        for i in 0..200_000 {
+            writeln!(output, "{i:08},caf\u{e9},\u{1F389},{}", i % 7).unwrap();
+        }
+        output
+    }
+
+    #[test]
+    fn test_large_output_round_trips_compressed() {
+        let output = large_output();
+        let mut store = OutputStore::new(Some(1024));
+        store.insert(1, output.clone());
+        store.insert(2, "small\n".to_string());
+
+        assert!(
+            store.peak_bytes() < output.len() / 2,
+            "{}",
+            store.peak_bytes()
+        );
+        assert_eq!(store.get(1).unwrap().unwrap(), output);
+        assert!(matches!(store.get(2), Ok(Some(Cow::Borrowed("small\n")))));
+        assert!(store.get(3).unwrap().is_none());
+
+        let outputs = store.outputs_of(&[1, 3]).unwrap();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[&1], output);
+
+        assert_eq!(store.take(1).unwrap().unwrap(), output);
+        assert_eq!(store.take(2).unwrap().as_deref(), Some("small\n"));
+        assert!(store.take(1).unwrap().is_none());
+        assert_eq!(store.held_bytes, 0);
+    }
+
+    #[test]
+    fn test_uncompressed_without_threshold() {
+        let output = large_output();
+        let mut store = OutputStore::default();
+        store.insert(1, output.clone());
+        assert_eq!(store.peak_bytes(), output.len());
+        assert!(matches!(store.get(1), Ok(Some(Cow::Borrowed(_)))));
+    }
+
+    #[test]
+    fn test_corrupt_output_is_an_error() {
+        let mut store = OutputStore::new(Some(1024));
+        store.insert(1, large_output());
+        if let Some(StoredOutput::Compressed { block, .. }) = store.outputs.get_mut(&1) {
+            block.truncate(block.len() / 2);
+        }
+        let err = store.get(1).unwrap_err();
+        assert!(err.to_string().contains("Stored output of task 1"), "{err}");
+        assert!(store.outputs_of(&[1]).is_err());
+        assert!(store.take(1).is_err());
+    }
+}
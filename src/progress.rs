@@ -0,0 +1,102 @@
+//! Progress of the running job (`job:<id>:progress`)
+//!
+//! The execution task records which task the job is on; the heartbeat reads
+//! it on every tick and posts it alongside the heartbeat, so monitors see the
+//! worker's liveness and the job's progress refreshed together, without a
+//! timer of their own.
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Progress state shared between a job's execution and the heartbeat
+#[derive(Debug, Clone, Default)]
+pub struct JobProgress(Arc<Mutex<Option<Running>>>);
+
+#[derive(Debug)]
+struct Running {
+    job_id: String,
+    total_tasks: usize,
+    task_number: Option<u32>,
+    started: Instant,
+}
+
+/// What is posted to `job:<id>:progress`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgressSnapshot {
+    pub job_id: String,
+    /// Task currently executing (`None` before the first task starts)
+    pub task_number: Option<u32>,
+    pub total_tasks: usize,
+    /// Time since the job started executing, in milliseconds
+    pub elapsed_ms: u64,
+}
+
+impl JobProgress {
+    /// Record that `job_id`, a plan of `total_tasks` tasks, started executing
+    pub fn start(&self, job_id: &str, total_tasks: usize) {
+        *self.lock() = Some(Running {
+            job_id: job_id.to_string(),
+            total_tasks,
+            task_number: None,
+            started: Instant::now(),
+        });
+    }
+
+    /// Record that the running job moved on to `task_number`
+    pub fn task_started(&self, task_number: u32) {
+        if let Some(running) = self.lock().as_mut() {
+            running.task_number = Some(task_number);
+        }
+    }
+
+    /// Record that the running job finished executing
+    pub fn finish(&self) {
+        *self.lock() = None;
+    }
+
+    /// Progress of the running job (`None` when no job is executing)
+    #[must_use]
+    pub fn snapshot(&self) -> Option<ProgressSnapshot> {
+        self.lock().as_ref().map(|running| ProgressSnapshot {
+            job_id: running.job_id.clone(),
+            task_number: running.task_number,
+            total_tasks: running.total_tasks,
+            elapsed_ms: u64::try_from(running.started.elapsed().as_millis()).unwrap_or(u64::MAX),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<Running>> {
+        // The state is replaced wholesale, so a panic mid-update can't corrupt it
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracks_running_job() {
+        let progress = JobProgress::default();
+        assert!(progress.snapshot().is_none());
+
+        progress.task_started(1);
+        assert!(progress.snapshot().is_none());
+
+        progress.start("job-1", 3);
+        let snapshot = progress.snapshot().unwrap();
+        assert_eq!(snapshot.job_id, "job-1");
+        assert_eq!(snapshot.task_number, None);
+        assert_eq!(snapshot.total_tasks, 3);
+
+        let shared = progress.clone();
+        shared.task_started(2);
+        assert_eq!(progress.snapshot().unwrap().task_number, Some(2));
+
+        progress.finish();
+        assert!(shared.snapshot().is_none());
+    }
+}
@@ -0,0 +1,62 @@
+//! Redaction of secrets from task output
+//!
+//! Tools sometimes echo credentials (e.g. printing a config that contains a
+//! token). Every `--redact-pattern` match in posted stdout/stderr is replaced
+//! with `***` before it leaves the worker.
+
+use regex::Regex;
+use std::borrow::Cow;
+
+/// Replacement text for redacted matches
+pub const REDACTED: &str = "***";
+
+/// Compiled redaction patterns, applied in order
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    patterns: Vec<Regex>,
+}
+
+impl Redactor {
+    /// Create a redactor from already-compiled patterns
+    #[must_use]
+    pub fn new(patterns: Vec<Regex>) -> Self {
+        Self { patterns }
+    }
+
+    /// Replace every pattern match in `text` with [`REDACTED`]
+    ///
+    /// Borrows `text` unchanged when nothing matches.
+    #[must_use]
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for pattern in &self.patterns {
+            if let Cow::Owned(redacted) = pattern.replace_all(&text, REDACTED) {
+                text = Cow::Owned(redacted);
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redactor(patterns: &[&str]) -> Redactor {
+        Redactor::new(patterns.iter().map(|p| Regex::new(p).unwrap()).collect())
+    }
+
+    #[test]
+    fn test_redacts_every_match_of_every_pattern() {
+        let redactor = redactor(&[r"ghp_[A-Za-z0-9]{8,}", r"(?i)password=\S+"]);
+        let text = "token ghp_abcdEFGH1234 and ghp_zzzzzzzz9\nPASSWORD=hunter2 user=alice\n";
+        assert_eq!(redactor.redact(text), "token *** and ***\n*** user=alice\n");
+    }
+
+    #[test]
+    fn test_unmatched_text_is_borrowed_unchanged() {
+        let text = "nothing secret here\n";
+        assert!(matches!(redactor(&[r"ghp_\w+"]).redact(text), Cow::Borrowed(t) if t == text));
+        assert!(matches!(Redactor::default().redact(text), Cow::Borrowed(_)));
+    }
+}
@@ -1,9 +1,18 @@
 // Allow module inception - this is a common Rust pattern for protocol clients
 #![allow(clippy::module_name_repetitions)]
 
-use crate::error::{AgwError, AgwResult};
-use redis::{aio::ConnectionManager, Client, Cmd};
-use tracing::{debug, info};
+use crate::backoff::{Backoff, BackoffStrategy};
+use crate::error::{AgwError, AgwResult, HeartbeatFailure};
+use crate::plan::JobPriority;
+use redis::{
+    aio::ConnectionManager, Client, Cmd, ErrorKind, FromRedisValue, RedisError, RedisResult,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, Semaphore};
+use tracing::{debug, info, warn};
 
 /// RESP client for communicating with AGQ
 ///
@@ -11,9 +20,207 @@ use tracing::{debug, info};
 /// making clones lightweight. This allows workers to spawn plan execution tasks
 /// with their own client instance for result posting, while the main worker
 /// continues to send heartbeats on the original client.
+///
+/// All clones share the same underlying connection, so when `ConnectionManager`
+/// reconnects after a dropped connection every clone sees the new connection.
+/// The new connection is not authenticated, so the session key is kept and
+/// replayed transparently before the failed command is retried once, if it
+/// is safe to run twice.
+///
+/// In cluster mode (see [`RespClient::enable_cluster_redirects`]) `MOVED`/`ASK`
+/// replies naming one of the configured cluster nodes are followed to it.
+/// Connections to redirect targets are opened lazily, authenticated with the
+/// same session key, and shared by all clones. No slot map is kept: every
+/// command is sent to the primary connection first, so keys served by another
+/// node pay one redirect round trip per command.
 #[derive(Clone)]
 pub struct RespClient {
     connection: ConnectionManager,
+    session_key: Option<Arc<str>>,
+    cluster: Option<Arc<ClusterNodes>>,
+    queue_keys: QueueKeys,
+    result_keys: ResultKeyTemplate,
+    result_chunk_size: usize,
+    result_retry_backoff: Backoff,
+    /// Cap on commands awaiting a reply, shared by all clones (`None` = no cap)
+    inflight: Option<Arc<Semaphore>>,
+}
+
+/// Longest stdout/stderr stored as a single value by default
+///
+/// Comfortably below Redis' hard limit on a string value, so the SET never
+/// fails; longer output is stored as a list of chunks instead.
+pub const DEFAULT_RESULT_CHUNK_SIZE: usize = 64 * 1024 * 1024;
+
+/// Attempts to post a job result before giving up
+const RESULT_POST_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry of a failed result post
+const RESULT_POST_INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Redis' hard limit on the size of one string value (512 MiB)
+pub const MAX_RESULT_VALUE_BYTES: usize = 512 * 1024 * 1024;
+
+/// Written to `stdout`/`stderr` when the output is stored in chunks
+///
+/// The chunks are the elements of the `stdout_chunks`/`stderr_chunks` lists, and
+/// the job's `storage` result field is `chunked` rather than `inline`.
+pub const CHUNKED_OUTPUT_MARKER: &str = "agw:chunked";
+
+/// Where the session key comes from
+///
+/// A key read from a file is re-read on every authentication attempt, so a
+/// rotated key is picked up without restarting the worker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionKeySource {
+    /// Key given directly (`--session-key`)
+    Inline(String),
+    /// Key stored in a file (`--session-key-file`); surrounding whitespace is ignored
+    File(PathBuf),
+}
+
+impl SessionKeySource {
+    /// Current session key
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key file cannot be read
+    pub fn load(&self) -> AgwResult<String> {
+        match self {
+            Self::Inline(key) => Ok(key.clone()),
+            Self::File(path) => std::fs::read_to_string(path)
+                .map(|key| key.trim().to_string())
+                .map_err(|e| {
+                    AgwError::InvalidConfig(format!(
+                        "Failed to read session key file {}: {e}",
+                        path.display()
+                    ))
+                }),
+        }
+    }
+}
+
+/// Why an AUTH attempt failed
+struct AuthFailure {
+    error: AgwError,
+    /// AGQ answered and refused the key (as opposed to being unreachable)
+    rejected: bool,
+}
+
+/// Layout of the keys job results are written to
+///
+/// A template such as `results/{job}/{field}` where `{job}` is replaced by the
+/// job ID and `{field}` by `stdout`, `stderr`, `status` etc. Both placeholders are
+/// required so every job and field gets a distinct key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResultKeyTemplate(Arc<str>);
+
+impl ResultKeyTemplate {
+    /// Template matching AGQ's native layout (`job:<id>:stdout` etc.)
+    pub const DEFAULT: &'static str = "job:{job}:{field}";
+
+    /// Parse and validate a template
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template is too long, lacks a placeholder, uses
+    /// an unknown placeholder, or contains characters outside
+    /// `[A-Za-z0-9:/._-]` (which could inject into or collide with other keys)
+    pub fn parse(template: &str) -> AgwResult<Self> {
+        const MAX_TEMPLATE_LEN: usize = 256;
+
+        if template.len() > MAX_TEMPLATE_LEN {
+            return Err(AgwError::InvalidConfig(format!(
+                "Result key template exceeds {MAX_TEMPLATE_LEN} characters"
+            )));
+        }
+
+        for placeholder in ["{job}", "{field}"] {
+            if !template.contains(placeholder) {
+                return Err(AgwError::InvalidConfig(format!(
+                    "Result key template must contain {placeholder}"
+                )));
+            }
+        }
+
+        let literal = template.replace("{job}", "").replace("{field}", "");
+        if let Some(c) = literal
+            .chars()
+            .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, ':' | '/' | '.' | '_' | '-')))
+        {
+            return Err(AgwError::InvalidConfig(format!(
+                "Result key template contains invalid character {c:?}"
+            )));
+        }
+
+        Ok(Self(Arc::from(template)))
+    }
+
+    /// Key for one result field of a job
+    #[must_use]
+    pub fn key(&self, job_id: &str, field: &str) -> String {
+        self.0.replace("{job}", job_id).replace("{field}", field)
+    }
+}
+
+impl Default for ResultKeyTemplate {
+    fn default() -> Self {
+        Self(Arc::from(Self::DEFAULT))
+    }
+}
+
+/// Names of the AGQ job queues
+///
+/// In cluster mode every name carries the `{queue}` hash tag, so all of them
+/// hash to one slot: BRPOPLPUSH/RPOPLPUSH move a job between two queues, which
+/// Redis Cluster refuses (`CROSSSLOT`) for keys in different slots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueKeys {
+    /// Queue that jobs are fetched from
+    pub ready: &'static str,
+    /// Ready queue for high priority jobs (fetched first under `--honor-priority`)
+    pub ready_high: &'static str,
+    /// Ready queue for low priority jobs (fetched last under `--honor-priority`)
+    pub ready_low: &'static str,
+    /// Queue holding jobs while a worker executes them (BRPOPLPUSH target)
+    pub processing: &'static str,
+}
+
+impl QueueKeys {
+    /// Queue names on a single AGQ node
+    pub const STANDALONE: Self = Self {
+        ready: "queue:ready",
+        ready_high: "queue:ready:high",
+        ready_low: "queue:ready:low",
+        processing: "queue:processing",
+    };
+
+    /// Queue names on an AGQ cluster, all in the slot of `{queue}`
+    pub const CLUSTER: Self = Self {
+        ready: "{queue}:ready",
+        ready_high: "{queue}:ready:high",
+        ready_low: "{queue}:ready:low",
+        processing: "{queue}:processing",
+    };
+
+    /// Ready queue a job of the given priority is pushed to
+    #[must_use]
+    pub fn ready_for(&self, priority: JobPriority) -> &'static str {
+        match priority {
+            JobPriority::High => self.ready_high,
+            JobPriority::Normal => self.ready,
+            JobPriority::Low => self.ready_low,
+        }
+    }
+}
+
+/// Maximum number of `MOVED`/`ASK` redirects followed for a single command
+const MAX_REDIRECTS: usize = 5;
+
+/// Cluster nodes redirects may be followed to, and the connections opened to them
+struct ClusterNodes {
+    allowed: HashSet<String>,
+    connections: Mutex<HashMap<String, ConnectionManager>>,
 }
 
 impl RespClient {
@@ -42,7 +249,68 @@ impl RespClient {
 
         info!("Connected to AGQ at {}", address);
 
-        Ok(Self { connection })
+        Ok(Self {
+            connection,
+            session_key: None,
+            cluster: None,
+            queue_keys: QueueKeys::STANDALONE,
+            result_keys: ResultKeyTemplate::default(),
+            result_chunk_size: DEFAULT_RESULT_CHUNK_SIZE,
+            result_retry_backoff: Backoff::new(
+                BackoffStrategy::Exponential,
+                RESULT_POST_INITIAL_BACKOFF,
+                Duration::MAX,
+            ),
+            inflight: None,
+        })
+    }
+
+    /// Write job results to keys laid out by `template` instead of `job:<id>:<field>`
+    pub fn set_result_key_template(&mut self, template: ResultKeyTemplate) {
+        self.result_keys = template;
+    }
+
+    /// Store stdout/stderr longer than `size` bytes as chunks of at most `size` bytes
+    pub fn set_result_chunk_size(&mut self, size: usize) {
+        self.result_chunk_size = size.max(1);
+    }
+
+    /// Grow the delay between result post retries with `strategy` (default exponential)
+    pub fn set_result_retry_backoff(&mut self, strategy: BackoffStrategy) {
+        self.result_retry_backoff.strategy = strategy;
+    }
+
+    /// Allow at most `max` commands awaiting a reply at once across this client
+    /// and its clones; further commands wait for one to finish
+    pub fn set_max_inflight_commands(&mut self, max: usize) {
+        self.inflight = Some(Arc::new(Semaphore::new(max)));
+    }
+
+    /// Key a result field (`stdout`, `stderr`, `status`, ...) of a job is written to
+    #[must_use]
+    pub fn result_key(&self, job_id: &str, field: &str) -> String {
+        self.result_keys.key(job_id, field)
+    }
+
+    /// Names of the job queues on this AGQ
+    #[must_use]
+    pub fn queue_keys(&self) -> QueueKeys {
+        self.queue_keys
+    }
+
+    /// Follow `MOVED`/`ASK` redirects to `nodes`, for AGQ running in Redis
+    /// Cluster mode, and switch to the hash-tagged [`QueueKeys::CLUSTER`] names
+    ///
+    /// Redirects are followed per command rather than by maintaining a slot map,
+    /// so a key that has moved costs one extra round trip each time it is used.
+    /// A redirect to any node not in `nodes` fails the command: the session key
+    /// is only ever sent to configured nodes.
+    pub fn enable_cluster_redirects(&mut self, nodes: impl IntoIterator<Item = String>) {
+        self.cluster = Some(Arc::new(ClusterNodes {
+            allowed: nodes.into_iter().collect(),
+            connections: Mutex::new(HashMap::new()),
+        }));
+        self.queue_keys = QueueKeys::CLUSTER;
     }
 
     /// Authenticate with the AGQ server using session key
@@ -50,45 +318,197 @@ impl RespClient {
     /// # Errors
     ///
     /// Returns an error if authentication fails or receives unexpected response
+    #[allow(dead_code)] // Used in tests
     pub async fn authenticate(&mut self, session_key: &str) -> AgwResult<()> {
+        self.try_authenticate(session_key)
+            .await
+            .map_err(|failure| failure.error)
+    }
+
+    /// Authenticate with the key from `source`, retrying up to `retries` times
+    ///
+    /// Only transient failures are retried, with delays from `backoff`. AGQ being
+    /// unreachable is always transient. A rejected key is transient only when it
+    /// comes from a file, which may be mid-rotation and is re-read before each
+    /// attempt; a rejected inline key can never change, so it fails at once.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key file cannot be read, the failure is permanent,
+    /// or every attempt failed
+    pub async fn authenticate_with_retry(
+        &mut self,
+        source: &SessionKeySource,
+        retries: u32,
+        backoff: &Backoff,
+    ) -> AgwResult<()> {
+        let mut attempt = 0;
+        loop {
+            let key = source.load()?;
+            let failure = match self.try_authenticate(&key).await {
+                Ok(()) => return Ok(()),
+                Err(failure) => failure,
+            };
+
+            let transient = !failure.rejected || matches!(source, SessionKeySource::File(_));
+            if !transient || attempt == retries {
+                return Err(failure.error);
+            }
+            let delay = backoff.delay(attempt);
+            attempt += 1;
+            warn!(
+                "{} (attempt {attempt}/{}), retrying in {delay:?}{}",
+                failure.error,
+                retries + 1,
+                if failure.rejected {
+                    " with the key file re-read"
+                } else {
+                    ""
+                }
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    async fn try_authenticate(&mut self, session_key: &str) -> Result<(), AuthFailure> {
         debug!("Authenticating with AGQ");
 
-        let response: String = Cmd::new()
-            .arg("AUTH")
-            .arg(session_key)
-            .query_async(&mut self.connection)
+        let response = send_auth(&mut self.connection, session_key)
             .await
-            .map_err(|e| AgwError::Authentication(format!("AUTH failed: {e}")))?;
+            .map_err(|e| AuthFailure {
+                rejected: !(e.is_io_error() || e.is_connection_dropped() || e.is_timeout()),
+                error: AgwError::Authentication(format!("AUTH failed: {e}")),
+            })?;
 
         if response != "OK" {
-            return Err(AgwError::Authentication(format!(
-                "Unexpected AUTH response: {response}"
-            )));
+            return Err(AuthFailure {
+                error: AgwError::Authentication(format!("Unexpected AUTH response: {response}")),
+                rejected: true,
+            });
         }
 
+        self.session_key = Some(Arc::from(session_key));
+
         info!("Successfully authenticated with AGQ");
         Ok(())
     }
 
+    /// Execute a command, recovering once from a dropped connection
+    ///
+    /// `ConnectionManager` reconnects in the background when it detects a dropped
+    /// connection, but the command that observed the drop still fails and the new
+    /// connection needs AUTH before it accepts anything else. On such failures this
+    /// re-authenticates with the stored session key and retries the command once.
+    /// A command that may have run before the drop is only retried if it is
+    /// idempotent; otherwise the error is returned for the caller to recover.
+    ///
+    /// In cluster mode, redirect replies are then followed (up to `MAX_REDIRECTS`).
+    /// Under [`RespClient::set_max_inflight_commands`] the whole exchange holds
+    /// one of the shared permits.
+    async fn query<T: FromRedisValue>(&mut self, cmd: &Cmd) -> RedisResult<T> {
+        let _permit = match &self.inflight {
+            // The semaphore is never closed
+            Some(inflight) => inflight.acquire().await.ok(),
+            None => None,
+        };
+        let session_key = self.session_key.clone();
+        let mut result =
+            query_with_reauth(&mut self.connection, session_key.as_deref(), cmd, false).await;
+
+        let Some(cluster) = self.cluster.clone() else {
+            return result;
+        };
+
+        for _ in 0..MAX_REDIRECTS {
+            let redirect = match &result {
+                Err(e) => e
+                    .redirect_node()
+                    .map(|(node, _slot)| (node.to_string(), e.kind() == ErrorKind::Ask)),
+                Ok(_) => None,
+            };
+            let Some((node, ask)) = redirect else {
+                break;
+            };
+
+            debug!(
+                "Following {} redirect to {node}",
+                if ask { "ASK" } else { "MOVED" }
+            );
+            let mut connection =
+                redirect_connection(&cluster, &node, session_key.as_deref()).await?;
+            result = query_with_reauth(&mut connection, session_key.as_deref(), cmd, ask).await;
+        }
+
+        result
+    }
+
     /// Send a heartbeat to AGQ
     ///
     /// # Errors
     ///
-    /// Returns an error if the RESP protocol command fails
+    /// Returns an [`AgwError::Heartbeat`], classified as a network, protocol or
+    /// auth failure, if the `PING` fails or its reply is not a PING reply
     pub async fn heartbeat(&mut self, worker_id: &str) -> AgwResult<()> {
         debug!("Sending heartbeat for worker {worker_id}");
 
-        let response: String = Cmd::new()
-            .arg("PING")
-            .arg(worker_id)
-            .query_async(&mut self.connection)
+        let reply: redis::Value = self
+            .query(Cmd::new().arg("PING").arg(worker_id))
             .await
-            .map_err(|e| AgwError::RespProtocol(format!("PING failed: {e}")))?;
+            .map_err(|e| AgwError::Heartbeat {
+                kind: HeartbeatFailure::classify(&e),
+                message: format!("PING failed: {e}"),
+            })?;
+
+        let response = ping_reply_text(&reply).ok_or_else(|| AgwError::Heartbeat {
+            kind: HeartbeatFailure::Protocol,
+            message: format!("PING failed: unexpected reply {reply:?}"),
+        })?;
 
         debug!("Heartbeat response: {response}");
         Ok(())
     }
 
+    /// Send a bare `PING`, to keep an otherwise idle connection's network path alive
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RESP protocol command fails or the reply is malformed
+    pub async fn ping(&mut self) -> AgwResult<()> {
+        let reply: redis::Value = self
+            .query(Cmd::new().arg("PING"))
+            .await
+            .map_err(|e| AgwError::RespProtocol(format!("PING failed: {e}")))?;
+
+        ping_reply_text(&reply).map(|_| ()).ok_or_else(|| {
+            AgwError::RespProtocol(format!("PING failed: unexpected reply {reply:?}"))
+        })
+    }
+
+    /// Get the AGQ server's wall-clock time via `TIME`
+    ///
+    /// Returns the time as a duration since the Unix epoch.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RESP protocol command fails or the reply is malformed
+    pub async fn server_time(&mut self) -> AgwResult<std::time::Duration> {
+        debug!("Querying AGQ server time");
+
+        // TIME returns [unix seconds, microseconds] as bulk strings
+        let (secs, micros): (u64, u64) = self
+            .query(Cmd::new().arg("TIME"))
+            .await
+            .map_err(|e| AgwError::RespProtocol(format!("TIME failed: {e}")))?;
+
+        if micros >= 1_000_000 {
+            return Err(AgwError::RespProtocol(format!(
+                "Invalid TIME reply: microseconds out of range ({micros})"
+            )));
+        }
+
+        Ok(std::time::Duration::from_secs(secs) + std::time::Duration::from_micros(micros))
+    }
+
     /// Register worker's available tools with AGQ
     ///
     /// Stores the tool list in the `worker:<id>:tools` key as a comma-separated string.
@@ -158,45 +578,75 @@ impl RespClient {
         Ok(())
     }
 
-    /// Blocking pop from queue using BRPOP
+    /// Publish the worker's resolved tool versions to AGQ
     ///
-    /// Blocks until a job is available in the queue or timeout is reached.
-    /// Returns the job data as a JSON string, or None if timeout occurred.
+    /// Stores a JSON object mapping tool name to its `--version` output in the
+    /// `worker:<id>:tool_versions` key.
     ///
-    /// **Note:** This method is deprecated in favor of `brpoplpush()` for reliable
-    /// job processing. Kept for compatibility and potential future use.
+    /// # Errors
+    ///
+    /// Returns an error if the RESP protocol command fails
+    pub async fn register_tool_versions(
+        &mut self,
+        worker_id: &str,
+        versions: &BTreeMap<String, String>,
+    ) -> AgwResult<()> {
+        let key = format!("worker:{worker_id}:tool_versions");
+        let value = serde_json::to_string(versions).map_err(|e| {
+            AgwError::RespProtocol(format!("Failed to serialize tool versions: {e}"))
+        })?;
+
+        info!(
+            "Registering versions for {} tools for worker {worker_id}",
+            versions.len()
+        );
+
+        self.set(&key, &value).await
+    }
+
+    /// Blocking pop from the first non-empty of `queues` using BRPOP
+    ///
+    /// Blocks until a job is available in one of the queues (checked in order)
+    /// or timeout is reached. Returns the job data, or None if timeout occurred.
+    ///
+    /// **Note:** the popped job is in no queue while it runs, so it is lost if
+    /// the worker crashes. Used for at-most-once delivery; prefer
+    /// `brpoplpush()` for reliable job processing.
     ///
     /// # Errors
     ///
-    /// Returns an error if the RESP protocol command fails or queue name doesn't match
-    #[allow(dead_code)]
-    pub async fn brpop(&mut self, queue: &str, timeout: u64) -> AgwResult<Option<String>> {
+    /// Returns an error if the RESP protocol command fails or the reply names a
+    /// queue that was not asked for
+    pub async fn brpop(&mut self, queues: &[&str], timeout: u64) -> AgwResult<Option<String>> {
         debug!(
-            "Blocking pop from queue {} with timeout {}s",
-            queue, timeout
+            "Blocking pop from queues {} with timeout {}s",
+            queues.join(", "),
+            timeout
         );
 
         // BRPOP returns (key, value) tuple or nil on timeout
-        let result: Option<(String, String)> = Cmd::new()
-            .arg("BRPOP")
-            .arg(queue)
-            .arg(timeout)
-            .query_async(&mut self.connection)
+        let result: Option<(String, String)> = self
+            .query(Cmd::new().arg("BRPOP").arg(queues).arg(timeout))
             .await
             .map_err(|e| AgwError::RespProtocol(format!("BRPOP failed: {e}")))?;
 
         if let Some((returned_queue, value)) = result {
-            // Validate that the job came from the expected queue
-            if returned_queue != queue {
+            // Validate that the job came from one of the expected queues
+            if !queues.contains(&returned_queue.as_str()) {
                 return Err(AgwError::RespProtocol(format!(
-                    "Job received from unexpected queue: expected '{queue}', got '{returned_queue}'"
+                    "Job received from unexpected queue: expected one of '{}', got '{returned_queue}'",
+                    queues.join("', '")
                 )));
             }
 
-            debug!("Received job from queue {}: {} bytes", queue, value.len());
+            debug!(
+                "Received job from queue {}: {} bytes",
+                returned_queue,
+                value.len()
+            );
             Ok(Some(value))
         } else {
-            debug!("BRPOP timeout on queue {}", queue);
+            debug!("BRPOP timeout on queues {}", queues.join(", "));
             Ok(None)
         }
     }
@@ -225,12 +675,14 @@ impl RespClient {
         );
 
         // BRPOPLPUSH returns the value directly, or nil on timeout
-        let result: Option<String> = Cmd::new()
-            .arg("BRPOPLPUSH")
-            .arg(source)
-            .arg(destination)
-            .arg(timeout)
-            .query_async(&mut self.connection)
+        let result: Option<String> = self
+            .query(
+                Cmd::new()
+                    .arg("BRPOPLPUSH")
+                    .arg(source)
+                    .arg(destination)
+                    .arg(timeout),
+            )
             .await
             .map_err(|e| AgwError::RespProtocol(format!("BRPOPLPUSH failed: {e}")))?;
 
@@ -248,6 +700,26 @@ impl RespClient {
         }
     }
 
+    /// Atomically move the tail of `source` to the head of `destination` without blocking
+    ///
+    /// The non-blocking counterpart of [`Self::brpoplpush`], for polling several
+    /// queues in turn. Returns `None` if `source` is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RESP protocol command fails
+    pub async fn rpoplpush(
+        &mut self,
+        source: &str,
+        destination: &str,
+    ) -> AgwResult<Option<String>> {
+        debug!("Pop from {} and push to {}", source, destination);
+
+        self.query(Cmd::new().arg("RPOPLPUSH").arg(source).arg(destination))
+            .await
+            .map_err(|e| AgwError::RespProtocol(format!("RPOPLPUSH failed: {e}")))
+    }
+
     /// Remove count occurrences of element from list
     ///
     /// Used to remove successfully completed jobs from the processing queue.
@@ -265,12 +737,8 @@ impl RespClient {
             count, key
         );
 
-        let removed_count: i64 = Cmd::new()
-            .arg("LREM")
-            .arg(key)
-            .arg(count)
-            .arg(element)
-            .query_async(&mut self.connection)
+        let removed_count: i64 = self
+            .query(Cmd::new().arg("LREM").arg(key).arg(count).arg(element))
             .await
             .map_err(|e| AgwError::RespProtocol(format!("LREM failed: {e}")))?;
 
@@ -278,6 +746,90 @@ impl RespClient {
         Ok(removed_count)
     }
 
+    /// Get the length of a list (LLEN)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RESP protocol command fails
+    pub async fn llen(&mut self, key: &str) -> AgwResult<u64> {
+        debug!("Getting length of list {}", key);
+
+        self.query(Cmd::new().arg("LLEN").arg(key))
+            .await
+            .map_err(|e| AgwError::RespProtocol(format!("LLEN failed: {e}")))
+    }
+
+    /// Get a range of list elements (LRANGE); negative indices count from the tail
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RESP protocol command fails
+    pub async fn lrange(&mut self, key: &str, start: i64, stop: i64) -> AgwResult<Vec<String>> {
+        debug!("Reading range {}..={} of list {}", start, stop, key);
+
+        self.query(Cmd::new().arg("LRANGE").arg(key).arg(start).arg(stop))
+            .await
+            .map_err(|e| AgwError::RespProtocol(format!("LRANGE failed: {e}")))
+    }
+
+    /// Push an element onto the head of a list (LPUSH)
+    ///
+    /// Returns the length of the list after the push.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RESP protocol command fails
+    pub async fn lpush(&mut self, key: &str, element: &str) -> AgwResult<i64> {
+        debug!("Pushing element onto list {}", key);
+
+        self.query(Cmd::new().arg("LPUSH").arg(key).arg(element))
+            .await
+            .map_err(|e| AgwError::RespProtocol(format!("LPUSH failed: {e}")))
+    }
+
+    /// Append an element to the tail of a list (RPUSH)
+    ///
+    /// Returns the length of the list after the push.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RESP protocol command fails
+    pub async fn rpush(&mut self, key: &str, element: &str) -> AgwResult<i64> {
+        debug!("Appending element to list {}", key);
+
+        self.query(Cmd::new().arg("RPUSH").arg(key).arg(element))
+            .await
+            .map_err(|e| AgwError::RespProtocol(format!("RPUSH failed: {e}")))
+    }
+
+    /// Delete a key, returning whether it existed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RESP protocol command fails
+    pub async fn del(&mut self, key: &str) -> AgwResult<bool> {
+        debug!("Deleting key: {}", key);
+
+        let removed: i64 = self
+            .query(Cmd::new().arg("DEL").arg(key))
+            .await
+            .map_err(|e| AgwError::RespProtocol(format!("DEL failed: {e}")))?;
+        Ok(removed > 0)
+    }
+
+    /// Get a string value, or `None` if the key doesn't exist
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RESP protocol command fails
+    pub async fn get(&mut self, key: &str) -> AgwResult<Option<String>> {
+        debug!("Getting key: {}", key);
+
+        self.query(Cmd::new().arg("GET").arg(key))
+            .await
+            .map_err(|e| AgwError::RespProtocol(format!("GET failed: {e}")))
+    }
+
     /// Get job metadata from AGQ
     ///
     /// Fetches job information including job_id, plan_id, input data, and status.
@@ -285,40 +837,51 @@ impl RespClient {
     /// # Errors
     ///
     /// Returns an error if the RESP protocol command fails or job doesn't exist
-    pub async fn job_get(&mut self, job_id: &str) -> AgwResult<String> {
+    pub async fn job_get(&mut self, job_id: &str) -> AgwResult<Option<String>> {
         debug!("Fetching job metadata for job_id: {}", job_id);
 
         let job_key = format!("job:{}", job_id);
-        let json: String = Cmd::new()
-            .arg("GET")
-            .arg(&job_key)
-            .query_async(&mut self.connection)
+        let json: Option<String> = self
+            .query(Cmd::new().arg("GET").arg(&job_key))
             .await
             .map_err(|e| AgwError::RespProtocol(format!("JOB.GET failed: {e}")))?;
 
-        debug!("Retrieved job metadata: {} bytes", json.len());
+        if let Some(json) = &json {
+            debug!("Retrieved job metadata: {} bytes", json.len());
+        }
         Ok(json)
     }
 
+    /// Store job metadata in AGQ (the counterpart of [`RespClient::job_get`])
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RESP protocol command fails
+    pub async fn job_set(&mut self, job_id: &str, job_json: &str) -> AgwResult<()> {
+        debug!("Storing job metadata for job_id: {}", job_id);
+
+        self.set(&format!("job:{job_id}"), job_json).await
+    }
+
     /// Get plan from AGQ
     ///
-    /// Fetches plan template including tasks.
+    /// Fetches plan template including tasks, or `None` if the plan doesn't exist.
     ///
     /// # Errors
     ///
-    /// Returns an error if the RESP protocol command fails or plan doesn't exist
-    pub async fn plan_get(&mut self, plan_id: &str) -> AgwResult<String> {
+    /// Returns an error if the RESP protocol command fails
+    pub async fn plan_get(&mut self, plan_id: &str) -> AgwResult<Option<String>> {
         debug!("Fetching plan for plan_id: {}", plan_id);
 
         let plan_key = format!("plan:{}", plan_id);
-        let json: String = Cmd::new()
-            .arg("GET")
-            .arg(&plan_key)
-            .query_async(&mut self.connection)
+        let json: Option<String> = self
+            .query(Cmd::new().arg("GET").arg(&plan_key))
             .await
             .map_err(|e| AgwError::RespProtocol(format!("PLAN.GET failed: {e}")))?;
 
-        debug!("Retrieved plan: {} bytes", json.len());
+        if let Some(json) = &json {
+            debug!("Retrieved plan: {} bytes", json.len());
+        }
         Ok(json)
     }
 
@@ -330,11 +893,8 @@ impl RespClient {
     pub async fn set(&mut self, key: &str, value: &str) -> AgwResult<()> {
         debug!("Setting key: {}", key);
 
-        let response: String = Cmd::new()
-            .arg("SET")
-            .arg(key)
-            .arg(value)
-            .query_async(&mut self.connection)
+        let response: String = self
+            .query(Cmd::new().arg("SET").arg(key).arg(value))
             .await
             .map_err(|e| AgwError::RespProtocol(format!("SET failed: {e}")))?;
 
@@ -350,9 +910,11 @@ impl RespClient {
 
     /// Post job execution results to AGQ with retry logic
     ///
-    /// Stores stdout, stderr, and status for the given job ID.
-    /// Retries up to 3 times with exponential backoff on failure to ensure
-    /// results are not lost due to transient network issues.
+    /// Stores stdout, stderr, and status for the given job ID. A finished job is
+    /// `completed` or `failed` when its tasks ran, and `error` when a task could
+    /// not be started at all (e.g. the command failed to spawn).
+    /// Makes up to 3 attempts, backing off between them (exponentially unless
+    /// set otherwise), so results are not lost due to transient network issues.
     ///
     /// # Errors
     ///
@@ -361,7 +923,7 @@ impl RespClient {
     /// # Panics
     ///
     /// Panics if all retry attempts fail but last_error is None. This should never
-    /// happen in practice since MAX_RETRIES is at least 1, guaranteeing last_error
+    /// happen in practice since RESULT_POST_ATTEMPTS is at least 1, guaranteeing last_error
     /// will be populated.
     pub async fn post_job_result(
         &mut self,
@@ -370,12 +932,9 @@ impl RespClient {
         stderr: &str,
         status: &str,
     ) -> AgwResult<()> {
-        const MAX_RETRIES: u32 = 3;
-        const INITIAL_BACKOFF_MS: u64 = 100;
-
         let mut last_error = None;
 
-        for attempt in 0..MAX_RETRIES {
+        for attempt in 0..RESULT_POST_ATTEMPTS {
             match self
                 .post_job_result_once(job_id, stdout, stderr, status)
                 .await
@@ -383,15 +942,14 @@ impl RespClient {
                 Ok(()) => return Ok(()),
                 Err(e) => {
                     last_error = Some(e);
-                    if attempt < MAX_RETRIES - 1 {
-                        let backoff_ms = INITIAL_BACKOFF_MS * 2_u64.pow(attempt);
+                    if attempt < RESULT_POST_ATTEMPTS - 1 {
+                        let delay = self.result_retry_backoff.delay(attempt);
                         debug!(
-                            "Result posting failed (attempt {}/{}), retrying after {}ms",
+                            "Result posting failed (attempt {}/{}), retrying after {delay:?}",
                             attempt + 1,
-                            MAX_RETRIES,
-                            backoff_ms
+                            RESULT_POST_ATTEMPTS,
                         );
-                        tokio::time::sleep(tokio::time::Duration::from_millis(backoff_ms)).await;
+                        tokio::time::sleep(delay).await;
                     }
                 }
             }
@@ -428,28 +986,67 @@ impl RespClient {
         }
 
         // Validate status is one of the expected values
-        if !matches!(status, "completed" | "failed" | "pending" | "running") {
+        if !matches!(
+            status,
+            "completed" | "failed" | "error" | "pending" | "running"
+        ) {
             return Err(AgwError::RespProtocol(format!(
                 "Invalid job status: {status}"
             )));
         }
 
-        // Set stdout
-        let stdout_key = format!("job:{}:stdout", job_id);
-        self.set(&stdout_key, stdout).await?;
+        // Output too large for one value is stored in chunks rather than failing the SET
+        let chunked =
+            stdout.len() > self.result_chunk_size || stderr.len() > self.result_chunk_size;
+        if chunked {
+            warn!(
+                "Output of job {job_id} exceeds {} bytes, storing it in chunks",
+                self.result_chunk_size
+            );
+        }
 
-        // Set stderr
-        let stderr_key = format!("job:{}:stderr", job_id);
-        self.set(&stderr_key, stderr).await?;
+        for (field, output) in [("stdout", stdout), ("stderr", stderr)] {
+            let key = self.result_key(job_id, field);
+            if chunked {
+                self.post_output_chunks(job_id, field, output).await?;
+                self.set(&key, CHUNKED_OUTPUT_MARKER).await?;
+            } else {
+                self.set(&key, output).await?;
+            }
+        }
+
+        // Set storage mode, so readers know where to find the output
+        let storage_key = self.result_key(job_id, "storage");
+        self.set(&storage_key, if chunked { "chunked" } else { "inline" })
+            .await?;
 
         // Set status
-        let status_key = format!("job:{}:status", job_id);
+        let status_key = self.result_key(job_id, "status");
         self.set(&status_key, status).await?;
 
         info!("Successfully posted results for job {}", job_id);
         Ok(())
     }
 
+    /// Replace the `<field>_chunks` list of a job with `output` split into chunks
+    ///
+    /// Chunks are pushed one per command so no single request approaches the
+    /// server's size limits.
+    async fn post_output_chunks(
+        &mut self,
+        job_id: &str,
+        field: &str,
+        output: &str,
+    ) -> AgwResult<()> {
+        let key = self.result_key(job_id, &format!("{field}_chunks"));
+        // A retried post must not append to the chunks of an earlier attempt
+        self.del(&key).await?;
+        for chunk in split_chunks(output, self.result_chunk_size) {
+            self.rpush(&key, chunk).await?;
+        }
+        Ok(())
+    }
+
     /// Get the underlying connection (for future operations)
     #[allow(dead_code)]
     pub fn connection(&mut self) -> &mut ConnectionManager {
@@ -457,6 +1054,149 @@ impl RespClient {
     }
 }
 
+/// Split `text` into pieces of at most `max_bytes` bytes on UTF-8 boundaries
+///
+/// A character wider than `max_bytes` gets a piece of its own.
+fn split_chunks(text: &str, max_bytes: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let mut end = max_bytes.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+/// Text of a `PING` reply, whatever shape the server sent it in
+///
+/// Servers answer `PING <msg>` with a simple or bulk string, but some configs
+/// (e.g. a connection in subscribe mode) reply with an array whose first element
+/// is the text; any of these counts as a live heartbeat.
+fn ping_reply_text(reply: &redis::Value) -> Option<String> {
+    match reply {
+        redis::Value::Status(text) => Some(text.clone()),
+        redis::Value::Okay => Some("OK".to_string()),
+        redis::Value::Data(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+        redis::Value::Bulk(items) => items.first().and_then(ping_reply_text),
+        redis::Value::Nil | redis::Value::Int(_) => None,
+    }
+}
+
+/// Run a command on `connection`, re-authenticating and retrying once if it was lost
+///
+/// A NOAUTH reply means the command never ran, so it is always retried. A
+/// dropped connection may have lost the reply of a command the server already
+/// ran, so only [`IDEMPOTENT_COMMANDS`] are retried then: running BRPOPLPUSH
+/// or LPUSH twice would claim a second job or push a duplicate.
+async fn query_with_reauth<T: FromRedisValue>(
+    connection: &mut ConnectionManager,
+    session_key: Option<&str>,
+    cmd: &Cmd,
+    asking: bool,
+) -> RedisResult<T> {
+    match send(connection, cmd, asking).await {
+        Err(e)
+            if e.code() == Some("NOAUTH") || (e.is_connection_dropped() && is_idempotent(cmd)) =>
+        {
+            warn!("Lost AGQ connection ({e}), re-authenticating and retrying");
+            if let Some(key) = session_key {
+                send_auth(connection, key).await?;
+            }
+            send(connection, cmd, asking).await
+        }
+        Err(e) if e.is_connection_dropped() => {
+            warn!("Lost AGQ connection ({e}); not retrying a command that may have run");
+            Err(e)
+        }
+        result => result,
+    }
+}
+
+/// Send `cmd`, preceded by `ASKING` when following an `ASK` redirect
+async fn send<T: FromRedisValue>(
+    connection: &mut ConnectionManager,
+    cmd: &Cmd,
+    asking: bool,
+) -> RedisResult<T> {
+    if !asking {
+        return cmd.query_async(connection).await;
+    }
+    // ASKING applies only to the next command on the same connection, so send
+    // both in one pipeline to keep them adjacent
+    redis::pipe()
+        .cmd("ASKING")
+        .ignore()
+        .add_command(cmd.clone())
+        .query_async::<_, (T,)>(connection)
+        .await
+        .map(|(value,)| value)
+}
+
+/// Commands that leave AGQ in the same state however many times they run
+const IDEMPOTENT_COMMANDS: &[&str] = &["GET", "SET", "PING", "LLEN", "LRANGE", "TIME"];
+
+/// Whether `cmd` is one of [`IDEMPOTENT_COMMANDS`]
+fn is_idempotent(cmd: &Cmd) -> bool {
+    matches!(
+        cmd.args_iter().next(),
+        Some(redis::Arg::Simple(name))
+            if IDEMPOTENT_COMMANDS.iter().any(|c| name.eq_ignore_ascii_case(c.as_bytes()))
+    )
+}
+
+/// Get (opening and authenticating on first use) the connection to a redirect target
+async fn redirect_connection(
+    cluster: &ClusterNodes,
+    node: &str,
+    session_key: Option<&str>,
+) -> RedisResult<ConnectionManager> {
+    let mut connections = cluster.connections.lock().await;
+    if let Some(connection) = connections.get(node) {
+        return Ok(connection.clone());
+    }
+
+    // The session key goes to whichever node we connect to, so a redirect
+    // cannot be allowed to pick one
+    if !cluster.allowed.contains(node) {
+        return Err(RedisError::from((
+            ErrorKind::InvalidClientConfig,
+            "Redirect to a node that is not a configured cluster node",
+            node.to_string(),
+        )));
+    }
+    if !is_valid_address(node) {
+        return Err(RedisError::from((
+            ErrorKind::InvalidClientConfig,
+            "Invalid redirect address",
+            node.to_string(),
+        )));
+    }
+
+    info!("Connecting to AGQ cluster node {node}");
+    let mut connection = ConnectionManager::new(Client::open(format!("redis://{node}"))?).await?;
+    if let Some(key) = session_key {
+        send_auth(&mut connection, key).await?;
+    }
+    connections.insert(node.to_string(), connection.clone());
+    Ok(connection)
+}
+
+async fn send_auth(connection: &mut ConnectionManager, session_key: &str) -> RedisResult<String> {
+    Cmd::new()
+        .arg("AUTH")
+        .arg(session_key)
+        .query_async(connection)
+        .await
+}
+
 /// Validate address format (host:port)
 fn is_valid_address(address: &str) -> bool {
     // Must contain exactly one colon
@@ -517,11 +1257,11 @@ mod tests {
     #[test]
     fn test_post_job_result_validates_status() {
         // Valid statuses should be accepted (tested via mock in integration tests)
-        let valid_statuses = vec!["completed", "failed", "pending", "running"];
+        let valid_statuses = vec!["completed", "failed", "error", "pending", "running"];
         for status in valid_statuses {
             assert!(matches!(
                 status,
-                "completed" | "failed" | "pending" | "running"
+                "completed" | "failed" | "error" | "pending" | "running"
             ));
         }
 
@@ -529,7 +1269,7 @@ mod tests {
         let invalid_status = "invalid_status";
         assert!(!matches!(
             invalid_status,
-            "completed" | "failed" | "pending" | "running"
+            "completed" | "failed" | "error" | "pending" | "running"
         ));
     }
 
@@ -546,6 +1286,97 @@ mod tests {
         assert_eq!(status_key, "job:job-123:status");
     }
 
+    #[test]
+    fn test_result_key_template_produces_expected_keys() {
+        let default = ResultKeyTemplate::default();
+        assert_eq!(default.key("job-123", "stdout"), "job:job-123:stdout");
+
+        let template = ResultKeyTemplate::parse("results/{job}/{field}").unwrap();
+        assert_eq!(template.key("job-123", "stdout"), "results/job-123/stdout");
+        assert_eq!(template.key("job-123", "status"), "results/job-123/status");
+    }
+
+    #[test]
+    fn test_result_key_template_rejects_unsafe_templates() {
+        for template in [
+            "results/{job}",
+            "results/{field}",
+            "results {job} {field}",
+            "results/{job}/{field}\r\n",
+            "results/{job}/{field}/{other}",
+            "results/*/{job}/{field}",
+            "$(whoami)/{job}/{field}",
+        ] {
+            assert!(
+                ResultKeyTemplate::parse(template).is_err(),
+                "{template:?} should be rejected"
+            );
+        }
+        assert!(ResultKeyTemplate::parse(&format!("{}{{job}}{{field}}", "a".repeat(300))).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_post_job_result_uses_result_key_template() {
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut client = connected_client(&mock).await;
+        client.set_result_key_template(ResultKeyTemplate::parse("results/{job}/{field}").unwrap());
+
+        client
+            .post_job_result("job-1", "out", "err", "completed")
+            .await
+            .unwrap();
+
+        assert_eq!(mock.get("results/job-1/stdout").as_deref(), Some("out"));
+        assert_eq!(mock.get("results/job-1/stderr").as_deref(), Some("err"));
+        assert_eq!(
+            mock.get("results/job-1/status").as_deref(),
+            Some("completed")
+        );
+        assert_eq!(mock.get("job:job-1:stdout"), None);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_output_stored_in_chunks() {
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut client = connected_client(&mock).await;
+        client.set_result_chunk_size(4);
+
+        // A leftover list from an earlier attempt is replaced, not appended to
+        mock.push("job:job-1:stdout_chunks", "stale");
+        client
+            .post_job_result("job-1", "0123456789", "err", "completed")
+            .await
+            .unwrap();
+
+        assert_eq!(mock.get("job:job-1:storage").as_deref(), Some("chunked"));
+        assert_eq!(
+            mock.get("job:job-1:stdout").as_deref(),
+            Some(CHUNKED_OUTPUT_MARKER)
+        );
+        assert_eq!(mock.list("job:job-1:stdout_chunks"), ["0123", "4567", "89"]);
+        assert_eq!(mock.list("job:job-1:stderr_chunks"), ["err"]);
+        assert_eq!(mock.get("job:job-1:status").as_deref(), Some("completed"));
+
+        // Output within the limit stays inline
+        client
+            .post_job_result("job-2", "0123", "", "completed")
+            .await
+            .unwrap();
+        assert_eq!(mock.get("job:job-2:storage").as_deref(), Some("inline"));
+        assert_eq!(mock.get("job:job-2:stdout").as_deref(), Some("0123"));
+        assert!(mock.list("job:job-2:stdout_chunks").is_empty());
+    }
+
+    #[test]
+    fn test_split_chunks_respects_utf8_boundaries() {
+        assert_eq!(split_chunks("", 4), Vec::<&str>::new());
+        assert_eq!(split_chunks("abcdef", 3), ["abc", "def"]);
+        // "é" is two bytes and must not be split
+        assert_eq!(split_chunks("aéb", 2), ["a", "é", "b"]);
+        // A character wider than the chunk size still makes progress
+        assert_eq!(split_chunks("€x", 1), ["€", "x"]);
+    }
+
     #[test]
     fn test_job_id_validation() {
         // Valid job IDs should pass validation checks
@@ -745,14 +1576,490 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_brpoplpush_timeout_behavior() {
+        // An empty queue times out with no job rather than an error, so the
+        // worker can go back to sending heartbeats
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut client = connected_client(&mock).await;
+
+        let job = client
+            .brpoplpush("queue:ready", "queue:processing", 5)
+            .await
+            .unwrap();
+        assert_eq!(job, None);
+        assert_eq!(mock.count("BRPOPLPUSH"), 1);
+    }
+
+    // ===== Reconnection tests (against the in-process mock AGQ) =====
+
+    use crate::mock_agq::{MockAgq, Reply};
+
+    const SESSION_KEY: &str = "test-session-key";
+
+    async fn connected_client(mock: &MockAgq) -> RespClient {
+        let mut client = RespClient::connect(&mock.address).await.unwrap();
+        client.authenticate(SESSION_KEY).await.unwrap();
+        client
+    }
+
+    #[tokio::test]
+    async fn test_auth_retry_rereads_rotated_key_file() {
+        let mock = MockAgq::start(Some("rotated-session-key")).await;
+        let key_file = std::env::temp_dir().join(format!("agw-key-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&key_file, "stale-session-key\n").unwrap();
+
+        // The new key lands while the client is backing off after a rejection
+        let rotation = {
+            let key_file = key_file.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                std::fs::write(&key_file, "rotated-session-key\n").unwrap();
+            })
+        };
+
+        let mut client = RespClient::connect(&mock.address).await.unwrap();
+        let source = SessionKeySource::File(key_file.clone());
+        client
+            .authenticate_with_retry(&source, 20, &Backoff::fixed(Duration::from_millis(20)))
+            .await
+            .unwrap();
+        rotation.await.unwrap();
+        std::fs::remove_file(&key_file).unwrap();
+
+        assert!(mock.count("AUTH") > 1);
+        client.set("k", "v").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_auth_retry_gives_up() {
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut client = RespClient::connect(&mock.address).await.unwrap();
+
+        // A rejected inline key is permanent: no retries
+        let inline = SessionKeySource::Inline("wrong-session-key".to_string());
+        let err = client
+            .authenticate_with_retry(&inline, 3, &Backoff::fixed(Duration::ZERO))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AgwError::Authentication(_)), "{err}");
+        assert_eq!(mock.count("AUTH"), 1);
+
+        // A key file that never gets the right key is retried, then fails
+        let key_file = std::env::temp_dir().join(format!("agw-key-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&key_file, "wrong-session-key").unwrap();
+        let file = SessionKeySource::File(key_file.clone());
+        assert!(client
+            .authenticate_with_retry(&file, 2, &Backoff::fixed(Duration::ZERO))
+            .await
+            .is_err());
+        std::fs::remove_file(&key_file).unwrap();
+        assert_eq!(mock.count("AUTH"), 1 + 3);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_connection_reauthenticates_and_retries() {
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut client = connected_client(&mock).await;
+
+        mock.drop_on("SET", 1);
+        client.set("some:key", "value").await.unwrap();
+
+        assert_eq!(mock.get("some:key").as_deref(), Some("value"));
+        assert_eq!(
+            mock.count("AUTH"),
+            2,
+            "new connection must be re-authenticated"
+        );
+        assert_eq!(mock.connections(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cloned_client_posts_result_after_reconnect() {
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut client = connected_client(&mock).await;
+        mock.push("queue:ready", "job-raw-1");
+
+        let job_id_raw = client
+            .brpoplpush("queue:ready", "queue:processing", 1)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // The job's own clone observes the reconnect mid-execution
+        let mut job_client = client.clone();
+        mock.drop_on("SET", 1);
+
+        job_client
+            .post_job_result("job-1", "out\n", "", "completed")
+            .await
+            .unwrap();
+        let removed = job_client
+            .lrem("queue:processing", 1, &job_id_raw)
+            .await
+            .unwrap();
+
+        assert_eq!(removed, 1);
+        assert_eq!(mock.get("job:job-1:stdout").as_deref(), Some("out\n"));
+        assert_eq!(mock.get("job:job-1:status").as_deref(), Some("completed"));
+        assert!(mock.list("queue:processing").is_empty());
+
+        // The original client keeps working on the rebound connection
+        client.heartbeat("worker-1").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_non_idempotent_commands_not_retried_after_drop() {
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut client = connected_client(&mock).await;
+        mock.push("queue:processing", "job-raw-2");
+        mock.push("queue:ready", "job-raw-3");
+
+        mock.drop_on("LREM", 1);
+        assert!(client
+            .lrem("queue:processing", 1, "job-raw-2")
+            .await
+            .is_err());
+        mock.drop_on("BRPOPLPUSH", 1);
+        assert!(client
+            .brpoplpush("queue:ready", "queue:processing", 1)
+            .await
+            .is_err());
+        mock.drop_on("LPUSH", 1);
+        assert!(client.lpush("queue:ready", "job-raw-4").await.is_err());
+        assert_eq!(mock.count("LREM"), 1);
+        assert_eq!(mock.count("BRPOPLPUSH"), 1);
+        assert_eq!(mock.count("LPUSH"), 1);
+
+        // The caller's own retry reaches the reconnected, re-authenticated connection
+        let removed = client
+            .lrem("queue:processing", 1, "job-raw-2")
+            .await
+            .unwrap();
+        assert_eq!(removed, 1);
+        assert!(mock.list("queue:processing").is_empty());
+    }
+
+    #[test]
+    fn test_idempotent_commands() {
+        assert!(is_idempotent(Cmd::new().arg("GET").arg("job:1")));
+        assert!(is_idempotent(Cmd::new().arg("set").arg("k").arg("v")));
+        for name in [
+            "BRPOPLPUSH",
+            "RPOPLPUSH",
+            "BRPOP",
+            "LPUSH",
+            "RPUSH",
+            "LREM",
+            "DEL",
+        ] {
+            assert!(!is_idempotent(Cmd::new().arg(name).arg("k")), "{name}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_accepts_ping_reply_shapes() {
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut client = connected_client(&mock).await;
+
+        for reply in [
+            Reply::Simple("PONG".to_string()),
+            Reply::Bulk("worker-1".to_string()),
+            Reply::Array(vec![
+                Reply::Bulk("pong".to_string()),
+                Reply::Bulk("worker-1".to_string()),
+            ]),
+        ] {
+            mock.script("PING", reply.clone());
+            assert!(client.heartbeat("worker-1").await.is_ok(), "{reply:?}");
+        }
+
+        for reply in [Reply::Integer(1), Reply::Nil, Reply::Array(vec![])] {
+            mock.script("PING", reply.clone());
+            assert!(client.heartbeat("worker-1").await.is_err(), "{reply:?}");
+        }
+    }
+
     #[test]
-    fn test_brpoplpush_timeout_behavior() {
-        // Test timeout behavior expectations
-        const TIMEOUT: u64 = 5;
-
-        // Timeout should allow heartbeats to continue
-        assert_eq!(TIMEOUT, 5);
-        assert!(TIMEOUT > 0); // Not blocking forever
-        assert!(TIMEOUT < 60); // Short enough for responsive heartbeats
+    fn test_heartbeat_failure_classification() {
+        use redis::{ErrorKind, RedisError};
+
+        let cases = [
+            (
+                RedisError::from(std::io::Error::from(std::io::ErrorKind::ConnectionReset)),
+                HeartbeatFailure::Network,
+            ),
+            (
+                RedisError::from(std::io::Error::from(std::io::ErrorKind::ConnectionRefused)),
+                HeartbeatFailure::Network,
+            ),
+            (
+                RedisError::from(std::io::Error::from(std::io::ErrorKind::TimedOut)),
+                HeartbeatFailure::Network,
+            ),
+            (
+                RedisError::from((ErrorKind::AuthenticationFailed, "invalid password")),
+                HeartbeatFailure::AuthExpired,
+            ),
+            (
+                RedisError::from((ErrorKind::ResponseError, "unknown command")),
+                HeartbeatFailure::Protocol,
+            ),
+            (
+                RedisError::from((ErrorKind::TypeError, "unexpected reply type")),
+                HeartbeatFailure::Protocol,
+            ),
+        ];
+        for (error, expected) in cases {
+            assert_eq!(HeartbeatFailure::classify(&error), expected, "{error}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_failures_are_categorized() {
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut client = connected_client(&mock).await;
+
+        // Rejected again after the automatic re-authentication
+        for _ in 0..2 {
+            mock.script(
+                "PING",
+                Reply::Error("NOAUTH Authentication required.".to_string()),
+            );
+        }
+        let err = client.heartbeat("worker-1").await.unwrap_err();
+        assert_eq!(err.heartbeat_failure(), Some(HeartbeatFailure::AuthExpired));
+        assert!(err.to_string().contains("(auth_expired)"), "{err}");
+
+        mock.script("PING", Reply::Error("ERR unknown command".to_string()));
+        let err = client.heartbeat("worker-1").await.unwrap_err();
+        assert_eq!(err.heartbeat_failure(), Some(HeartbeatFailure::Protocol));
+
+        mock.script("PING", Reply::Integer(1));
+        let err = client.heartbeat("worker-1").await.unwrap_err();
+        assert_eq!(err.heartbeat_failure(), Some(HeartbeatFailure::Protocol));
+        assert!(err.to_string().contains("unexpected reply"), "{err}");
+
+        mock.drop_on("PING", usize::MAX);
+        let err = client.heartbeat("worker-1").await.unwrap_err();
+        assert_eq!(err.heartbeat_failure(), Some(HeartbeatFailure::Network));
+    }
+
+    #[tokio::test]
+    async fn test_server_time_rejects_malformed_reply() {
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut client = connected_client(&mock).await;
+
+        mock.script(
+            "TIME",
+            Reply::Array(vec![
+                Reply::Bulk("1700000000".to_string()),
+                Reply::Bulk("5000000".to_string()),
+            ]),
+        );
+        assert!(client.server_time().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_moved_redirect_retried_against_indicated_node() {
+        let origin = MockAgq::start(Some(SESSION_KEY)).await;
+        let target = MockAgq::start(Some(SESSION_KEY)).await;
+        target.set("job:job-1:plan", "plan-data");
+
+        let mut client = connected_client(&origin).await;
+        client.enable_cluster_redirects([target.address.clone()]);
+        origin.script(
+            "GET",
+            Reply::Error(format!("MOVED 3999 {}", target.address)),
+        );
+
+        let value: String = client
+            .query(Cmd::new().arg("GET").arg("job:job-1:plan"))
+            .await
+            .unwrap();
+        assert_eq!(value, "plan-data");
+        assert_eq!(target.count("AUTH"), 1);
+        assert_eq!(target.count("GET"), 1);
+        assert_eq!(target.count("ASKING"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_ask_redirect_sends_asking_first() {
+        let origin = MockAgq::start(Some(SESSION_KEY)).await;
+        let target = MockAgq::start(Some(SESSION_KEY)).await;
+
+        let mut client = connected_client(&origin).await;
+        client.enable_cluster_redirects([target.address.clone()]);
+        origin.script("SET", Reply::Error(format!("ASK 3999 {}", target.address)));
+
+        client.set("job:job-1:status", "completed").await.unwrap();
+        assert_eq!(target.get("job:job-1:status").as_deref(), Some("completed"));
+        assert_eq!(origin.get("job:job-1:status"), None);
+        assert_eq!(target.count("ASKING"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_ask_redirect_reauthenticates_after_drop() {
+        let origin = MockAgq::start(Some(SESSION_KEY)).await;
+        let target = MockAgq::start(Some(SESSION_KEY)).await;
+
+        let mut client = connected_client(&origin).await;
+        client.enable_cluster_redirects([target.address.clone()]);
+        origin.script("SET", Reply::Error(format!("ASK 3999 {}", target.address)));
+        target.drop_on("SET", 1);
+
+        client.set("job:job-1:status", "completed").await.unwrap();
+        assert_eq!(target.get("job:job-1:status").as_deref(), Some("completed"));
+        assert_eq!(target.count("AUTH"), 2);
+        assert_eq!(target.count("ASKING"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_redirect_to_unlisted_node_refused() {
+        let origin = MockAgq::start(Some(SESSION_KEY)).await;
+        let target = MockAgq::start(Some(SESSION_KEY)).await;
+
+        let mut client = connected_client(&origin).await;
+        client.enable_cluster_redirects([origin.address.clone()]);
+        origin.script(
+            "GET",
+            Reply::Error(format!("MOVED 3999 {}", target.address)),
+        );
+
+        let err = client
+            .query::<Option<String>>(Cmd::new().arg("GET").arg("key"))
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("not a configured cluster node"),
+            "{err}"
+        );
+        assert_eq!(target.connections(), 0);
+    }
+
+    #[test]
+    fn test_cluster_queue_keys_share_hash_tag() {
+        let cluster = QueueKeys::CLUSTER;
+        for key in [
+            cluster.ready,
+            cluster.ready_high,
+            cluster.ready_low,
+            cluster.processing,
+        ] {
+            assert!(key.starts_with("{queue}:"), "{key}");
+        }
+        assert_eq!(
+            QueueKeys::STANDALONE.ready_for(JobPriority::Low),
+            "queue:ready:low"
+        );
+        assert_eq!(cluster.ready_for(JobPriority::High), "{queue}:ready:high");
+    }
+
+    #[tokio::test]
+    async fn test_redirects_not_followed_without_cluster_mode() {
+        let origin = MockAgq::start(Some(SESSION_KEY)).await;
+        let target = MockAgq::start(Some(SESSION_KEY)).await;
+
+        let mut client = connected_client(&origin).await;
+        origin.script(
+            "GET",
+            Reply::Error(format!("MOVED 3999 {}", target.address)),
+        );
+
+        let result: RedisResult<Option<String>> =
+            client.query(Cmd::new().arg("GET").arg("key")).await;
+        assert!(result.is_err());
+        assert_eq!(target.connections(), 0);
+    }
+
+    /// Names of the complete RESP commands at the start of `buf`, which are removed
+    fn take_command_names(buf: &mut Vec<u8>) -> Vec<String> {
+        let text = std::str::from_utf8(buf).unwrap();
+        let lines: Vec<&str> = text.split_inclusive("\r\n").collect();
+        let (mut names, mut i, mut consumed) = (Vec::new(), 0, 0);
+        while let Some(header) = lines.get(i) {
+            let Some(n) = header
+                .strip_prefix('*')
+                .and_then(|n| n.trim_end().parse::<usize>().ok())
+            else {
+                break;
+            };
+            match lines.get(i + 2 * n) {
+                Some(last) if header.ends_with("\r\n") && last.ends_with("\r\n") => {}
+                _ => break,
+            }
+            names.push(lines[i + 2].trim_end().to_uppercase());
+            consumed += lines[i..=i + 2 * n].iter().map(|l| l.len()).sum::<usize>();
+            i += 1 + 2 * n;
+        }
+        buf.drain(..consumed);
+        names
+    }
+
+    /// Most GETs awaiting a reply while five clones of a client capped at
+    /// `max_inflight` each send one at the same time
+    ///
+    /// The server only replies once the client has gone quiet, so at that point
+    /// everything the client is able to send is outstanding.
+    async fn peak_inflight_gets(max_inflight: Option<usize>) -> usize {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let clients = tokio::spawn(async move {
+            let mut client = RespClient::connect(&address).await.unwrap();
+            if let Some(max) = max_inflight {
+                client.set_max_inflight_commands(max);
+            }
+            let gets: Vec<_> = (0..5)
+                .map(|i| {
+                    let mut client = client.clone();
+                    tokio::spawn(async move { client.get(&format!("key-{i}")).await })
+                })
+                .collect();
+            for get in gets {
+                assert_eq!(get.await.unwrap().unwrap(), None);
+            }
+        });
+
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let (mut received, mut pending) = (Vec::new(), Vec::new());
+        let (mut answered, mut peak) = (0, 0);
+        let mut buf = [0u8; 4096];
+        while answered < 5 {
+            let quiet = Duration::from_millis(200);
+            match tokio::time::timeout(quiet, stream.read(&mut buf)).await {
+                Ok(Ok(n)) if n > 0 => {
+                    received.extend_from_slice(&buf[..n]);
+                    pending.extend(take_command_names(&mut received));
+                    continue;
+                }
+                Ok(_) => panic!("client disconnected"),
+                Err(_) => {}
+            }
+            let gets = pending.iter().filter(|name| *name == "GET").count();
+            peak = peak.max(gets);
+            let mut replies = Vec::new();
+            for name in pending.drain(..) {
+                replies.extend_from_slice(if name == "GET" {
+                    b"$-1\r\n"
+                } else {
+                    b"+OK\r\n"
+                });
+            }
+            stream.write_all(&replies).await.unwrap();
+            answered += gets;
+        }
+        clients.await.unwrap();
+        peak
+    }
+
+    #[tokio::test]
+    async fn test_inflight_commands_never_exceed_cap() {
+        // Uncapped, every clone's command is sent at once
+        assert_eq!(peak_inflight_gets(None).await, 5);
+        assert_eq!(peak_inflight_gets(Some(2)).await, 2);
+        assert_eq!(peak_inflight_gets(Some(1)).await, 1);
     }
 }
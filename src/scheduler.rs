@@ -0,0 +1,153 @@
+//! Weighted fair scheduling across plans
+//!
+//! With a single FIFO queue, one high-volume plan can starve every other plan
+//! served by the same worker. The scheduler remembers which plans the worker
+//! fetched recently and, given a lookahead of ready jobs, picks the one whose
+//! plan has had the smallest weighted share of that recent history.
+
+use std::collections::{HashMap, VecDeque};
+
+/// Tracks recent per-plan fetch counts and chooses the most underserved candidate
+#[derive(Debug, Clone)]
+pub struct FairScheduler {
+    /// Number of recent fetches remembered
+    window: usize,
+    /// Relative share per plan (plans not listed have weight 1)
+    weights: HashMap<String, u32>,
+    /// Plan IDs of recent fetches, oldest first
+    recent: VecDeque<String>,
+    /// Occurrences of each plan ID in `recent`
+    counts: HashMap<String, usize>,
+}
+
+impl FairScheduler {
+    /// Create a scheduler remembering the last `window` fetches
+    #[must_use]
+    pub fn new(window: usize, weights: HashMap<String, u32>) -> Self {
+        Self {
+            window,
+            weights,
+            recent: VecDeque::with_capacity(window),
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Record that a job for `plan_id` was fetched, forgetting the oldest fetch when full
+    pub fn record(&mut self, plan_id: &str) {
+        if self.window == 0 {
+            return;
+        }
+        if self.recent.len() == self.window {
+            if let Some(evicted) = self.recent.pop_front() {
+                if let Some(count) = self.counts.get_mut(&evicted) {
+                    *count -= 1;
+                    if *count == 0 {
+                        self.counts.remove(&evicted);
+                    }
+                }
+            }
+        }
+        *self.counts.entry(plan_id.to_string()).or_default() += 1;
+        self.recent.push_back(plan_id.to_string());
+    }
+
+    /// Number of recent fetches for `plan_id`
+    #[must_use]
+    pub fn recent_count(&self, plan_id: &str) -> usize {
+        self.counts.get(plan_id).copied().unwrap_or(0)
+    }
+
+    /// Choose among candidate jobs, given in queue order (next in line first)
+    ///
+    /// Each candidate is its plan ID, or `None` if it could not be determined;
+    /// such jobs are preferred so they are failed and cleared out promptly.
+    /// The lowest `recent_count / weight` wins, ties going to the earlier
+    /// candidate, so with no history this is plain FIFO.
+    #[must_use]
+    pub fn choose(&self, candidates: &[Option<&str>]) -> Option<usize> {
+        candidates
+            .iter()
+            .enumerate()
+            .min_by(|(a_index, a), (b_index, b)| {
+                let (a_count, a_weight) = self.share(**a);
+                let (b_count, b_weight) = self.share(**b);
+                // Compare a_count / a_weight with b_count / b_weight without division
+                (a_count * b_weight)
+                    .cmp(&(b_count * a_weight))
+                    .then(a_index.cmp(b_index))
+            })
+            .map(|(index, _)| index)
+    }
+
+    /// Recent count and weight of a candidate's plan, widened for comparison
+    fn share(&self, plan_id: Option<&str>) -> (u64, u64) {
+        let Some(plan_id) = plan_id else {
+            return (0, 1);
+        };
+        let weight = self.weights.get(plan_id).copied().unwrap_or(1);
+        (self.recent_count(plan_id) as u64, u64::from(weight))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduler_with_history(history: &[&str]) -> FairScheduler {
+        let mut scheduler = FairScheduler::new(10, HashMap::new());
+        for plan_id in history {
+            scheduler.record(plan_id);
+        }
+        scheduler
+    }
+
+    #[test]
+    fn test_prefers_starved_plan() {
+        let scheduler = scheduler_with_history(&["busy", "busy", "busy", "quiet"]);
+        let candidates = [Some("busy"), Some("busy"), Some("quiet"), Some("new")];
+        assert_eq!(scheduler.choose(&candidates), Some(3));
+        assert_eq!(scheduler.choose(&candidates[..3]), Some(2));
+    }
+
+    #[test]
+    fn test_ties_keep_queue_order() {
+        let scheduler = scheduler_with_history(&[]);
+        assert_eq!(scheduler.choose(&[Some("a"), Some("b")]), Some(0));
+        assert_eq!(scheduler.choose(&[]), None);
+
+        let scheduler = scheduler_with_history(&["a", "b"]);
+        assert_eq!(scheduler.choose(&[Some("b"), Some("a")]), Some(0));
+    }
+
+    #[test]
+    fn test_weights_scale_fair_share() {
+        let mut scheduler = FairScheduler::new(10, HashMap::from([("reports".to_string(), 3)]));
+        for plan_id in ["reports", "reports", "other"] {
+            scheduler.record(plan_id);
+        }
+        // 2/3 for reports is less than 1/1 for other
+        assert_eq!(scheduler.choose(&[Some("other"), Some("reports")]), Some(1));
+
+        scheduler.record("reports");
+        scheduler.record("reports");
+        // 4/3 exceeds 1/1
+        assert_eq!(scheduler.choose(&[Some("reports"), Some("other")]), Some(1));
+    }
+
+    #[test]
+    fn test_unknown_plan_chosen_first() {
+        let scheduler = scheduler_with_history(&["a", "b"]);
+        assert_eq!(scheduler.choose(&[Some("b"), None]), Some(1));
+    }
+
+    #[test]
+    fn test_window_forgets_old_fetches() {
+        let mut scheduler = FairScheduler::new(3, HashMap::new());
+        for plan_id in ["a", "a", "b", "b", "b"] {
+            scheduler.record(plan_id);
+        }
+        assert_eq!(scheduler.recent_count("a"), 0);
+        assert_eq!(scheduler.recent_count("b"), 3);
+        assert_eq!(scheduler.choose(&[Some("b"), Some("a")]), Some(1));
+    }
+}
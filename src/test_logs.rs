@@ -0,0 +1,51 @@
+//! Log capture for tests
+//!
+//! Installs a thread-local `fmt` subscriber that writes into a shared buffer, so
+//! tests can assert on what was logged without touching the global subscriber.
+
+#![allow(dead_code)] // Not every helper is used by every test target
+
+use std::sync::{Arc, Mutex};
+use tracing::subscriber::DefaultGuard;
+use tracing::Level;
+
+/// In-memory writer shared between a subscriber and the test reading it
+#[derive(Clone, Default)]
+pub struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+impl CapturedLogs {
+    /// Everything written so far, as text
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
+
+impl std::io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Capture `INFO` and above on the current thread until the guard is dropped
+pub fn capture_logs() -> (DefaultGuard, CapturedLogs) {
+    capture_logs_at(Level::INFO)
+}
+
+/// Capture `level` and above on the current thread until the guard is dropped
+pub fn capture_logs_at(level: Level) -> (DefaultGuard, CapturedLogs) {
+    let logs = CapturedLogs::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer({
+            let logs = logs.clone();
+            move || logs.clone()
+        })
+        .with_max_level(level)
+        .with_ansi(false)
+        .finish();
+    (tracing::subscriber::set_default(subscriber), logs)
+}
@@ -0,0 +1,138 @@
+//! Trusted plan signatures
+//!
+//! Shell-mode tasks bypass the argv-only execution model, so they only run when
+//! the worker is started with `--allow-shell` *and* the plan carries a valid
+//! `trusted_signature`: a hex-encoded HMAC-SHA256 of the plan, keyed with the
+//! worker's `--shell-signing-key`.
+
+use crate::plan::Plan;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Minimum length of the shell signing key
+pub const MIN_SIGNING_KEY_LEN: usize = 32;
+
+/// Verifies (and, for tooling and tests, produces) trusted plan signatures
+#[derive(Clone)]
+pub struct PlanVerifier {
+    key: Vec<u8>,
+}
+
+impl std::fmt::Debug for PlanVerifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PlanVerifier").finish_non_exhaustive()
+    }
+}
+
+impl PlanVerifier {
+    /// Create a verifier for the given signing key
+    #[must_use]
+    pub fn new(key: &str) -> Self {
+        Self {
+            key: key.as_bytes().to_vec(),
+        }
+    }
+
+    /// Compute the hex signature for a plan (ignoring any existing signature)
+    #[must_use]
+    #[allow(dead_code)] // Used by tests and plan-signing tooling
+    pub fn sign(&self, plan: &Plan) -> String {
+        use std::fmt::Write;
+
+        let tag = self.mac(plan).finalize().into_bytes();
+        tag.iter()
+            .fold(String::with_capacity(tag.len() * 2), |mut hex, b| {
+                let _ = write!(hex, "{b:02x}");
+                hex
+            })
+    }
+
+    /// Check the plan's `trusted_signature` in constant time
+    #[must_use]
+    pub fn verify(&self, plan: &Plan) -> bool {
+        let Some(signature) = plan.trusted_signature.as_deref() else {
+            return false;
+        };
+        let Some(tag) = decode_hex(signature) else {
+            return false;
+        };
+        self.mac(plan).verify_slice(&tag).is_ok()
+    }
+
+    fn mac(&self, plan: &Plan) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(&signing_payload(plan));
+        mac
+    }
+}
+
+/// Bytes covered by the signature: the plan's JSON without the signature itself
+fn signing_payload(plan: &Plan) -> Vec<u8> {
+    let unsigned = Plan {
+        trusted_signature: None,
+        ..plan.clone()
+    };
+    serde_json::to_vec(&unsigned).unwrap_or_default()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::Task;
+
+    const KEY: &str = "0123456789abcdef0123456789abcdef";
+
+    fn plan() -> Plan {
+        Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "echo hi | tr a-z A-Z".to_string(),
+                shell: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_signed_plan_verifies() {
+        let verifier = PlanVerifier::new(KEY);
+        let mut plan = plan();
+        assert!(!verifier.verify(&plan));
+
+        plan.trusted_signature = Some(verifier.sign(&plan));
+        assert!(verifier.verify(&plan));
+    }
+
+    #[test]
+    fn test_tampered_or_foreign_signature_rejected() {
+        let verifier = PlanVerifier::new(KEY);
+        let mut plan = plan();
+        plan.trusted_signature = Some(verifier.sign(&plan));
+
+        plan.tasks[0].command = "rm -rf /tmp/x".to_string();
+        assert!(!verifier.verify(&plan));
+
+        let mut plan = self::plan();
+        plan.trusted_signature =
+            Some(PlanVerifier::new("another-key-another-key-another!").sign(&plan));
+        assert!(!verifier.verify(&plan));
+
+        plan.trusted_signature = Some("not-hex".to_string());
+        assert!(!verifier.verify(&plan));
+    }
+}
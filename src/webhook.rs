@@ -0,0 +1,253 @@
+//! Notification of finished jobs to an external HTTP endpoint
+//!
+//! With `--webhook-url`, once a job's results are posted the worker sends
+//! `POST` with a JSON [`JobNotification`] to the URL. Delivery runs on its own
+//! task, retrying failed attempts with backoff, so a slow or unreachable
+//! endpoint never holds up the next job. Only plain `http://` is supported.
+
+use crate::backoff::{Backoff, BackoffStrategy};
+use crate::dependency::{read_status, split_http_url};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tracing::{debug, warn};
+
+/// Longest a single delivery attempt may take
+pub const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Delivery attempts before a notification is dropped
+pub const MAX_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry; grows up to [`RETRY_BACKOFF_MAX`]
+const RETRY_BACKOFF_BASE: Duration = Duration::from_millis(500);
+
+/// Longest delay between delivery attempts
+const RETRY_BACKOFF_MAX: Duration = Duration::from_secs(5);
+
+/// Body of a webhook request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobNotification {
+    pub job_id: String,
+    /// Status posted for the job: `completed`, `failed` or `error`
+    pub status: String,
+    /// Time the job took to execute, in milliseconds
+    pub duration_ms: u64,
+}
+
+/// A `--webhook-url` endpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Webhook {
+    address: String,
+    host: String,
+    path: String,
+    backoff: Backoff,
+}
+
+impl Webhook {
+    /// Parse an `http://host[:port][/path]` URL
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for other schemes, a missing host or a URL containing
+    /// whitespace
+    pub fn parse(url: &str) -> anyhow::Result<Self> {
+        if url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            anyhow::bail!("Webhook URL must not contain whitespace");
+        }
+        let Some(rest) = url.strip_prefix("http://") else {
+            anyhow::bail!("Webhook URL must be an http:// URL (https is not supported)");
+        };
+        let Some((address, host, path)) = split_http_url(rest) else {
+            anyhow::bail!("Webhook URL must be http://host[:port][/path]");
+        };
+        Ok(Self {
+            address,
+            host,
+            path,
+            backoff: Backoff::new(
+                BackoffStrategy::Exponential,
+                RETRY_BACKOFF_BASE,
+                RETRY_BACKOFF_MAX,
+            ),
+        })
+    }
+
+    /// Grow the delay between delivery attempts by `strategy` (`--retry-backoff`)
+    #[must_use]
+    pub fn with_backoff_strategy(mut self, strategy: BackoffStrategy) -> Self {
+        self.backoff.strategy = strategy;
+        self
+    }
+
+    /// Deliver `notification` on a task of its own
+    pub fn spawn_notify(&self, notification: JobNotification) {
+        let webhook = self.clone();
+        tokio::spawn(async move { webhook.notify(&notification).await });
+    }
+
+    /// Deliver `notification`, retrying up to [`MAX_ATTEMPTS`] times
+    ///
+    /// Returns whether the endpoint accepted it with a 2xx status.
+    pub async fn notify(&self, notification: &JobNotification) -> bool {
+        let body = match serde_json::to_string(notification) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Failed to serialize webhook notification: {e}");
+                return false;
+            }
+        };
+        for attempt in 0..MAX_ATTEMPTS {
+            if attempt > 0 {
+                tokio::time::sleep(self.backoff.delay(attempt - 1)).await;
+            }
+            let result = tokio::time::timeout(ATTEMPT_TIMEOUT, self.post(&body)).await;
+            let reason = match result {
+                Ok(Ok(())) => {
+                    debug!("Webhook notified for job {}", notification.job_id);
+                    return true;
+                }
+                Ok(Err(reason)) => reason,
+                Err(_) => format!("timed out after {ATTEMPT_TIMEOUT:?}"),
+            };
+            warn!(
+                "Webhook for job {} failed (attempt {}/{MAX_ATTEMPTS}): {reason}",
+                notification.job_id,
+                attempt + 1
+            );
+        }
+        false
+    }
+
+    async fn post(&self, body: &str) -> Result<(), String> {
+        let mut stream = TcpStream::connect(&self.address)
+            .await
+            .map_err(|e| e.to_string())?;
+        let request = format!(
+            "POST {} HTTP/1.0\r\nHost: {}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            self.path,
+            self.host,
+            body.len()
+        );
+        stream
+            .write_all(request.as_bytes())
+            .await
+            .map_err(|e| e.to_string())?;
+        let status = read_status(&mut stream).await?;
+        if (200..300).contains(&status) {
+            Ok(())
+        } else {
+            Err(format!("HTTP status {status}"))
+        }
+    }
+}
+
+impl std::fmt::Display for Webhook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "http://{}{}", self.host, self.path)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    /// HTTP server recording each request body, answering with `statuses` in
+    /// turn (the last one repeated)
+    pub async fn webhook_server(statuses: &[u16]) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let received = Arc::clone(&bodies);
+        let statuses = statuses.to_vec();
+        tokio::spawn(async move {
+            let mut served = 0;
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let code = statuses[served.min(statuses.len() - 1)];
+                served += 1;
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                let body = loop {
+                    let n = stream.read(&mut buf).await.unwrap_or(0);
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request).into_owned();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let length = head
+                            .lines()
+                            .find_map(|line| line.strip_prefix("Content-Length: "))
+                            .and_then(|length| length.parse().ok())
+                            .unwrap_or(0);
+                        if body.len() >= length || n == 0 {
+                            break body.to_string();
+                        }
+                    }
+                    if n == 0 {
+                        break String::new();
+                    }
+                };
+                received.lock().unwrap().push(body);
+                let response = format!("HTTP/1.1 {code} Status\r\nContent-Length: 0\r\n\r\n");
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        (address, bodies)
+    }
+
+    fn notification() -> JobNotification {
+        JobNotification {
+            job_id: "job-1".to_string(),
+            status: "completed".to_string(),
+            duration_ms: 1234,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let webhook = Webhook::parse("http://hooks.internal:8080/agw/done").unwrap();
+        assert_eq!(webhook.address, "hooks.internal:8080");
+        assert_eq!(webhook.path, "/agw/done");
+        assert_eq!(webhook.to_string(), "http://hooks.internal:8080/agw/done");
+        assert_eq!(Webhook::parse("http://hooks").unwrap().address, "hooks:80");
+
+        for invalid in [
+            "https://hooks/done",
+            "hooks:8080/done",
+            "http:///done",
+            "http://user@hooks/done",
+            "http://hooks/do ne",
+        ] {
+            assert!(Webhook::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_posts_json_payload() {
+        let (address, bodies) = webhook_server(&[200]).await;
+        let webhook = Webhook::parse(&format!("http://{address}/done")).unwrap();
+        assert!(webhook.notify(&notification()).await);
+
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 1);
+        let payload: JobNotification = serde_json::from_str(&bodies[0]).unwrap();
+        assert_eq!(payload, notification());
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_accepted() {
+        let (address, bodies) = webhook_server(&[503, 500, 204]).await;
+        let webhook = Webhook::parse(&format!("http://{address}/done"))
+            .unwrap()
+            .with_backoff_strategy(BackoffStrategy::Fixed);
+        assert!(webhook.notify(&notification()).await);
+        assert_eq!(bodies.lock().unwrap().len(), 3);
+
+        let (address, bodies) = webhook_server(&[500]).await;
+        let webhook = Webhook::parse(&format!("http://{address}/done")).unwrap();
+        assert!(!webhook.notify(&notification()).await);
+        assert_eq!(bodies.lock().unwrap().len(), MAX_ATTEMPTS as usize);
+    }
+}
@@ -1,20 +1,317 @@
-use crate::config::Config;
-use crate::error::{AgwError, AgwResult};
-use crate::executor;
-use crate::plan::Plan;
-use crate::resp::RespClient;
-use tokio::task::JoinHandle;
-use tracing::{debug, error, info};
+use crate::admin::{self, AdminState};
+use crate::backoff::Backoff;
+use crate::checkpoint::CheckpointStore;
+use crate::config::{render_name_template, Config, NameTemplateVars};
+use crate::dependency::PROBE_TIMEOUT;
+use crate::enqueue;
+use crate::error::{AgwError, AgwResult, FailureReason};
+use crate::executor::{self, ExecutionOptions, PlanResult, TaskResult, UnregisteredTools};
+use crate::history::{JobHistory, JobSummary};
+use crate::manifest::{ConfigSnapshot, JobManifest};
+use crate::memory::RssMonitor;
+use crate::metrics::METRICS;
+use crate::plan::{FollowUp, Job, JobPriority, Plan, MAX_FOLLOW_UP_DEPTH};
+use crate::progress::JobProgress;
+use crate::redact::Redactor;
+use crate::resp::{RespClient, ResultKeyTemplate};
+use crate::scheduler::FairScheduler;
+use crate::webhook::JobNotification;
+use std::collections::{BTreeMap, HashSet};
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::net::TcpListener;
+use tokio::process::Command;
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{debug, error, info, info_span, warn, Instrument, Span};
 use uuid::Uuid;
 
+/// Delivery guarantee for fetched jobs (`--queue-reliability`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum QueueReliability {
+    /// BRPOPLPUSH into `queue:processing`, removed (LREM) once results are
+    /// posted: a job survives a worker crash but may then run twice
+    #[default]
+    Reliable,
+    /// Plain BRPOP with no processing queue: a job lost in a crash is never
+    /// retried, so it runs at most once
+    AtMostOnce,
+}
+
+/// How often the priority queues are polled while all of them are empty
+const PRIORITY_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Pause after handing back a job this worker lacks the tools for, so the job
+/// goes to a capable worker instead of straight back to this one
+const CAPABILITY_REQUEUE_PAUSE: Duration = Duration::from_secs(1);
+
+/// Failure reason posted for every job under `--no-exec`
+const NO_EXEC_REASON: &str = "execution disabled on this worker";
+
+/// How long a tool's `--version` probe may run before it is abandoned
+const TOOL_VERSION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A job fetched from AGQ and ready to execute
+///
+/// Holds the plan with input variables already substituted, plus the raw
+/// queue entry needed to remove the job from the processing queue afterwards.
+#[derive(Debug, Clone)]
+struct PreparedJob {
+    job: Job,
+    plan: Plan,
+    job_id_raw: String,
+    /// Whether the job sits in `queue:processing` until its results are posted
+    in_processing: bool,
+}
+
+/// Connections a job runs with
+#[derive(Clone)]
+struct JobClients {
+    /// Queue and checkpoint operations, on the AGQ the job was fetched from
+    source: RespClient,
+    /// Result posts, on `--result-address` if set
+    results: RespClient,
+}
+
+impl JobClients {
+    /// Both kinds of operation on one connection
+    #[allow(dead_code)] // Used in tests
+    fn shared(client: RespClient) -> Self {
+        Self {
+            results: client.clone(),
+            source: client,
+        }
+    }
+}
+
+/// A job whose plan is executing on a spawned task
+struct RunningJob {
+    handle: JoinHandle<()>,
+    job_id: String,
+    job_id_raw: String,
+    priority: JobPriority,
+    in_processing: bool,
+}
+
+/// Result posts still in flight, awaited before the worker exits
+///
+/// A job's results are posted on their own task rather than the job's, so a
+/// job aborted at the shutdown timeout cannot cut off a post that has already
+/// started; [`PendingPosts::drain`] then waits for it to land.
+#[derive(Clone, Default)]
+struct PendingPosts(Arc<std::sync::Mutex<JoinSet<()>>>);
+
+impl PendingPosts {
+    /// Start posting on a tracked task
+    fn spawn<F>(&self, post: F)
+    where
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let mut posts = self
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        // Reap posts that already landed so the set doesn't grow with every job
+        while posts.try_join_next().is_some() {}
+        posts.spawn(post.in_current_span());
+    }
+
+    /// Wait for every post in flight to finish
+    async fn drain(&self) {
+        let mut posts = std::mem::take(
+            &mut *self
+                .0
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner),
+        );
+        if posts.is_empty() {
+            return;
+        }
+        info!("Waiting for {} result post(s) to finish", posts.len());
+        while let Some(result) = posts.join_next().await {
+            if let Err(e) = result {
+                error!("Result post task panicked during shutdown: {e}");
+            }
+        }
+    }
+}
+
+/// Why the worker's main loop stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShutdownReason {
+    /// A termination signal (`SIGTERM`/`SIGINT`) was received
+    #[cfg_attr(not(unix), allow(dead_code))]
+    Signal(&'static str),
+    /// Heartbeating or fetching failed and the worker gave up
+    Error,
+    /// The worker's RSS exceeded `--max-worker-rss-mb`
+    #[cfg_attr(not(unix), allow(dead_code))]
+    MemoryLimit,
+}
+
+impl std::fmt::Display for ShutdownReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Signal(name) => write!(f, "signal ({name})"),
+            Self::Error => f.write_str("error"),
+            Self::MemoryLimit => f.write_str("memory limit"),
+        }
+    }
+}
+
+/// Jobs and tasks handled since the worker started, for the shutdown report
+#[derive(Debug, Default)]
+struct WorkerStats {
+    jobs_succeeded: AtomicU64,
+    jobs_failed: AtomicU64,
+    tasks_executed: AtomicU64,
+}
+
+impl WorkerStats {
+    /// Count a finished job and the tasks it ran
+    fn record_job(&self, success: bool, tasks: usize) {
+        let jobs = if success {
+            &self.jobs_succeeded
+        } else {
+            &self.jobs_failed
+        };
+        jobs.fetch_add(1, Ordering::Relaxed);
+        self.tasks_executed
+            .fetch_add(u64::try_from(tasks).unwrap_or(u64::MAX), Ordering::Relaxed);
+    }
+}
+
+/// Summary logged once when the worker exits
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ShutdownReport {
+    reason: ShutdownReason,
+    uptime: Duration,
+    jobs_processed: u64,
+    jobs_succeeded: u64,
+    jobs_failed: u64,
+    tasks_executed: u64,
+}
+
+impl ShutdownReport {
+    fn log(&self, worker_id: &str) {
+        info!(
+            worker_id,
+            reason = %self.reason,
+            uptime_secs = self.uptime.as_secs(),
+            jobs_processed = self.jobs_processed,
+            jobs_succeeded = self.jobs_succeeded,
+            jobs_failed = self.jobs_failed,
+            tasks_executed = self.tasks_executed,
+            "Shutdown report"
+        );
+    }
+}
+
+/// Bounded retry of failed AGQ round trips
+///
+/// Dropped connections are re-established by the client on its next command;
+/// this only decides how long to wait before that next command and when to give
+/// up, so an orchestrator can replace a worker that cannot reach AGQ.
+#[derive(Debug)]
+struct ReconnectPolicy {
+    max_attempts: u32,
+    backoff: Backoff,
+    /// Consecutive failures since the last successful round trip
+    failures: u32,
+}
+
+impl ReconnectPolicy {
+    fn new(max_attempts: u32, backoff: Backoff) -> Self {
+        Self {
+            max_attempts,
+            backoff,
+            failures: 0,
+        }
+    }
+
+    /// Record a failure, returning the delay before the next attempt, or `None`
+    /// once all attempts are used up
+    fn next_delay(&mut self) -> Option<Duration> {
+        if self.failures >= self.max_attempts {
+            return None;
+        }
+        let delay = self.backoff.delay(self.failures);
+        self.failures += 1;
+        Some(delay)
+    }
+
+    /// Record a successful round trip
+    fn reset(&mut self) {
+        if self.failures > 0 {
+            info!(
+                "AGQ connection recovered after {} attempt(s)",
+                self.failures
+            );
+            self.failures = 0;
+        }
+    }
+}
+
 /// AGW Worker
+///
+/// Heartbeats use their own connection: `client` (and its clones used for result
+/// posting) multiplex one connection, so a large result write would otherwise
+/// delay the heartbeat reply queued behind it.
 pub struct Worker {
-    config: Config,
+    config: Arc<Config>,
     id: String,
     name: String,
     client: RespClient,
+    heartbeat_client: RespClient,
+    /// Connection results are posted to (the job connection unless `--result-address` is set)
+    result_client: RespClient,
+    /// Fetches jobs; shared with the fetch in flight, if any
+    fetcher: Arc<tokio::sync::Mutex<JobFetcher>>,
+    /// Recently finished jobs, served by the admin endpoint
+    history: Arc<JobHistory>,
+    /// Scrubs secrets from task output before results are posted
+    redactor: Arc<Redactor>,
+    /// Counters for the shutdown report
+    stats: Arc<WorkerStats>,
+    /// Result posts in flight, drained at shutdown
+    pending_posts: PendingPosts,
+    /// When the worker was created
+    started: Instant,
+    /// Retry budget for failed heartbeats and job fetches
+    reconnect: ReconnectPolicy,
+    /// Checks the worker's own RSS against `--max-worker-rss-mb`
+    rss_monitor: Option<RssMonitor>,
+    /// Progress of the running job, posted with each heartbeat
+    progress: JobProgress,
 }
 
+/// Pops jobs off the ready queues and prepares them to run
+///
+/// Kept apart from the rest of the [`Worker`] so that the main loop can keep
+/// one fetch in flight across iterations while it sends heartbeats and
+/// handles signals. Dropping a fetch part way through would strand a job it
+/// had already moved to `queue:processing`, or lose it outright at-most-once.
+struct JobFetcher {
+    config: Arc<Config>,
+    client: RespClient,
+    result_client: RespClient,
+    /// Whether fetching is paused because the processing backlog is too large
+    backlog_paused: bool,
+    /// Whether fetching is paused because `--dependency-check` is failing
+    dependency_paused: bool,
+    redactor: Arc<Redactor>,
+    /// Chooses among ready jobs by plan when fair scheduling is enabled
+    scheduler: Option<FairScheduler>,
+    stats: Arc<WorkerStats>,
+}
+
+/// A fetch kept in flight across main loop iterations
+type JobFetch = Pin<Box<dyn Future<Output = AgwResult<Option<PreparedJob>>> + Send>>;
+
 impl Worker {
     /// Create a new worker instance
     ///
@@ -29,13 +326,29 @@ impl Worker {
             .map_err(|e| AgwError::InvalidConfig(e.to_string()))?;
 
         // Generate or use provided worker ID
+        let instance_uuid = Uuid::new_v4().to_string();
         let worker_id = config
             .worker_id
             .clone()
-            .unwrap_or_else(|| format!("agw-{}", Uuid::new_v4()));
+            .unwrap_or_else(|| format!("agw-{instance_uuid}"));
 
         // Generate or use provided worker name
-        let worker_name = config.name.clone().unwrap_or_else(|| {
+        let templated_name = config
+            .name_template
+            .as_deref()
+            .map(|template| {
+                let hostname = short_hostname().unwrap_or_else(|| "localhost".to_string());
+                let vars = NameTemplateVars {
+                    hostname: &hostname,
+                    pid: std::process::id(),
+                    uuid: &instance_uuid,
+                    role: config.role.as_deref(),
+                };
+                render_name_template(template, &vars)
+            })
+            .transpose()
+            .map_err(|e| AgwError::InvalidConfig(e.to_string()))?;
+        let worker_name = config.worker_name().or(templated_name).unwrap_or_else(|| {
             // Auto-generate name from worker ID (use "worker-" prefix + first 12 chars)
             // This provides uniqueness while being more readable than full UUID
             let short_id = worker_id.chars().take(18).collect::<String>();
@@ -47,37 +360,158 @@ impl Worker {
             worker_id, worker_name
         );
 
-        // Connect to AGQ
+        // Connect to AGQ (job/result connection and dedicated heartbeat connection)
         let mut client = RespClient::connect(&config.agq_address).await?;
+        let mut heartbeat_client = RespClient::connect(&config.agq_address).await?;
+        configure_job_client(&mut client, &config)?;
+        if let Some(nodes) = config.redirect_nodes() {
+            heartbeat_client.enable_cluster_redirects(nodes);
+        }
 
         // Authenticate
-        client.authenticate(&config.session_key).await?;
+        let key_source = config.session_key_source();
+        client
+            .authenticate_with_retry(&key_source, config.auth_retry, &config.auth_backoff())
+            .await?;
+        heartbeat_client
+            .authenticate_with_retry(&key_source, config.auth_retry, &config.auth_backoff())
+            .await?;
+
+        // Results may go to a separate AGQ, authenticated with the same key
+        let result_client = match &config.result_address {
+            Some(address) => {
+                let mut result_client = RespClient::connect(address).await?;
+                configure_job_client(&mut result_client, &config)?;
+                result_client
+                    .authenticate_with_retry(&key_source, config.auth_retry, &config.auth_backoff())
+                    .await?;
+                info!("Posting results to {address}");
+                result_client
+            }
+            None => client.clone(),
+        };
+
+        if let Some(interval) = config.idle_ping_duration() {
+            let mut idle_client = RespClient::connect(&config.agq_address).await?;
+            if let Some(nodes) = config.redirect_nodes() {
+                idle_client.enable_cluster_redirects(nodes);
+            }
+            idle_client
+                .authenticate_with_retry(&key_source, config.auth_retry, &config.auth_backoff())
+                .await?;
+            tokio::spawn(idle_ping(idle_client, interval));
+        }
+
+        // Wall-clock timestamps written to AGQ are only meaningful if clocks agree
+        check_clock_skew(&mut client, config.clock_skew_threshold_duration()).await;
 
         // Register available tools with AGQ
-        let tools = config.tools.clone().unwrap_or_else(|| {
+        let tools = config.tool_names().unwrap_or_else(|| {
             info!("No tools specified, auto-discovery not yet implemented");
             vec![]
         });
 
         if !tools.is_empty() {
             client.register_tools(&worker_id, &tools).await?;
+
+            if config.collect_tool_versions {
+                let versions = collect_tool_versions(
+                    &tools,
+                    config.tool_path.as_deref(),
+                    TOOL_VERSION_TIMEOUT,
+                )
+                .await;
+                client.register_tool_versions(&worker_id, &versions).await?;
+            }
+        }
+
+        let history = Arc::new(JobHistory::new(config.job_history_size));
+        if let Some(ref address) = config.admin_address {
+            let listener = TcpListener::bind(address).await.map_err(|e| {
+                AgwError::Worker(format!("Failed to bind admin server to {address}: {e}"))
+            })?;
+            tokio::spawn(admin::serve(
+                listener,
+                AdminState {
+                    history: Arc::clone(&history),
+                },
+            ));
         }
 
+        let config = Arc::new(config);
+        let redactor = Arc::new(config.redactor());
+        let stats = Arc::new(WorkerStats::default());
+        let rss_monitor = config.max_worker_rss_mb.map(RssMonitor::new);
+        let reconnect =
+            ReconnectPolicy::new(config.max_reconnect_attempts, config.reconnect_backoff());
+        let fetcher = JobFetcher {
+            config: Arc::clone(&config),
+            client: client.clone(),
+            result_client: result_client.clone(),
+            backlog_paused: false,
+            dependency_paused: false,
+            redactor: Arc::clone(&redactor),
+            scheduler: config.fair_scheduler(),
+            stats: Arc::clone(&stats),
+        };
+
         Ok(Self {
             config,
             id: worker_id,
             name: worker_name,
             client,
+            heartbeat_client,
+            result_client,
+            fetcher: Arc::new(tokio::sync::Mutex::new(fetcher)),
+            history,
+            redactor,
+            stats,
+            pending_posts: PendingPosts::default(),
+            started: Instant::now(),
+            reconnect,
+            rss_monitor,
+            progress: JobProgress::default(),
         })
     }
 
-    /// Run the worker main loop
+    /// Run the worker main loop, logging a [`ShutdownReport`] when it ends
     ///
     /// # Errors
     ///
-    /// Returns an error if heartbeat fails, job fetch fails, or connection to AGQ is lost
+    /// Returns an error if heartbeats or job fetches still fail after
+    /// `--max-reconnect-attempts` retries, or once the worker has shut down for
+    /// exceeding `--max-worker-rss-mb` (so it exits non-zero and is restarted)
     pub async fn run(mut self) -> AgwResult<()> {
+        let result = self.run_loop().await;
+        let reason = *result.as_ref().unwrap_or(&ShutdownReason::Error);
+        self.shutdown_report(reason).log(&self.id);
+        match result? {
+            ShutdownReason::MemoryLimit => Err(AgwError::Worker(
+                "Worker exceeded its memory limit and shut down".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Summarize the jobs handled so far
+    fn shutdown_report(&self, reason: ShutdownReason) -> ShutdownReport {
+        let jobs_succeeded = self.stats.jobs_succeeded.load(Ordering::Relaxed);
+        let jobs_failed = self.stats.jobs_failed.load(Ordering::Relaxed);
+        ShutdownReport {
+            reason,
+            uptime: self.started.elapsed(),
+            jobs_processed: jobs_succeeded + jobs_failed,
+            jobs_succeeded,
+            jobs_failed,
+            tasks_executed: self.stats.tasks_executed.load(Ordering::Relaxed),
+        }
+    }
+
+    async fn run_loop(&mut self) -> AgwResult<ShutdownReason> {
         info!("Worker {} starting main loop", self.id);
+        if self.config.no_exec {
+            warn!("Execution disabled (--no-exec): jobs are validated and failed without running");
+        }
 
         // Setup signal handlers for graceful shutdown
         #[cfg(unix)]
@@ -88,42 +522,53 @@ impl Worker {
         let mut sigint = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::interrupt())
             .map_err(|e| AgwError::Worker(format!("Failed to setup SIGINT handler: {e}")))?;
 
-        // Main loop: fetch jobs and send heartbeats
+        // Main loop: fetch jobs and send heartbeats (the first tick completes
+        // immediately, so the initial heartbeat goes out before any fetch)
         let mut heartbeat_interval = tokio::time::interval(self.config.heartbeat_duration());
 
-        // Consume the first tick (which completes immediately) and send initial heartbeat
-        heartbeat_interval.tick().await;
-        self.send_heartbeat().await?;
-
         // Track currently executing job (if any)
-        let mut current_job: Option<JoinHandle<()>> = None;
+        let mut current_job: Option<RunningJob> = None;
 
-        // Shutdown flag (Unix only - Windows doesn't have signal handlers yet)
+        // Fetch in flight (if any), polled across iterations until it completes
+        let mut fetch: Option<JobFetch> = None;
+
+        // Set once shutdown is requested (Unix only - Windows doesn't have signal handlers yet)
         #[cfg(unix)]
-        let mut shutdown_requested = false;
+        let mut shutdown_reason: Option<ShutdownReason> = None;
 
         loop {
-            // Check if shutdown was requested and no job is running (Unix only)
+            // Check if shutdown was requested and no job is running (Unix only).
+            // A fetch in flight is let finish first, and a job it claimed is run.
             #[cfg(unix)]
-            if shutdown_requested && current_job.is_none() {
+            if shutdown_reason.is_some() && current_job.is_none() && fetch.is_none() {
                 info!("Shutdown complete - no jobs running");
                 break;
             }
 
             // Check if current job is complete (non-blocking)
             // If finished, await the handle to detect panics and ensure cleanup
-            if let Some(handle) = current_job.as_mut() {
-                if handle.is_finished() {
+            if let Some(running) = current_job.as_mut() {
+                if running.handle.is_finished() {
                     debug!("Job execution task completed");
                     // Await the handle to catch any panics and ensure proper cleanup
                     // This prevents silently ignoring panicked tasks during normal operation
-                    if let Err(e) = handle.await {
+                    if let Err(e) = (&mut running.handle).await {
                         error!("Job execution task panicked: {e}");
+                        self.progress.finish();
                     }
                     current_job = None;
                 }
             }
 
+            // Start fetching the next job once idle
+            #[cfg(unix)]
+            let idle = current_job.is_none() && shutdown_reason.is_none();
+            #[cfg(not(unix))]
+            let idle = current_job.is_none();
+            if idle && fetch.is_none() {
+                fetch = Some(self.start_fetch());
+            }
+
             // Use tokio::select with biased mode to prioritize heartbeats
             // This prevents DoS when jobs are continuously available
             #[cfg(unix)]
@@ -134,7 +579,7 @@ impl Worker {
                     // Signal handlers - highest priority
                     _ = sigterm.recv() => {
                         info!("Received SIGTERM, initiating graceful shutdown");
-                        shutdown_requested = true;
+                        shutdown_reason = Some(ShutdownReason::Signal("SIGTERM"));
                         if current_job.is_some() {
                             info!("Waiting for current job to complete before shutdown");
                         }
@@ -142,7 +587,7 @@ impl Worker {
 
                     _ = sigint.recv() => {
                         info!("Received SIGINT (Ctrl+C), initiating graceful shutdown");
-                        shutdown_requested = true;
+                        shutdown_reason = Some(ShutdownReason::Signal("SIGINT"));
                         if current_job.is_some() {
                             info!("Waiting for current job to complete before shutdown");
                         }
@@ -153,36 +598,57 @@ impl Worker {
                         match self.send_heartbeat().await {
                             Ok(()) => {
                                 debug!("Heartbeat sent successfully for worker {}", self.id);
+                                self.reconnect.reset();
+                                self.post_progress().await;
                             }
                             Err(e) => {
-                                error!("Failed to send heartbeat: {e}");
-                                return Err(e);
+                                self.recover_connection("Heartbeat", e).await?;
+                                // Retry the heartbeat as soon as the backoff is over
+                                heartbeat_interval.reset_immediately();
+                            }
+                        }
+                        if shutdown_reason.is_none() && self.memory_limit_exceeded() {
+                            shutdown_reason = Some(ShutdownReason::MemoryLimit);
+                            if current_job.is_some() {
+                                info!("Waiting for current job to complete before shutdown");
                             }
                         }
                     }
 
                     // Job fetch and preparation
-                    job_result = self.fetch_and_prepare_job(), if current_job.is_none() && !shutdown_requested => {
+                    job_result = poll_fetch(&mut fetch), if fetch.is_some() => {
+                    fetch = None;
                     match job_result {
-                        Ok(Some((job_id, plan, job_id_raw))) => {
+                        Ok(Some(prepared)) => {
+                            self.reconnect.reset();
                             debug!("Prepared job {} (plan {}) with {} tasks",
-                                job_id, plan.plan_id, plan.tasks.len());
+                                prepared.job.job_id, prepared.plan.plan_id, prepared.plan.tasks.len());
 
-                            // Clone client for the spawned task
-                            let client = self.client.clone();
+                            // Clone clients for the spawned task
+                            let clients = self.job_clients();
+                            let options = self.job_options();
+                            let job_id = prepared.job.job_id.clone();
+                            let job_id_raw = prepared.job_id_raw.clone();
+                            let priority = prepared.job.priority;
+                            let in_processing = prepared.in_processing;
+                            let history = Arc::clone(&self.history);
+                            let redactor = Arc::clone(&self.redactor);
+                            let stats = Arc::clone(&self.stats);
+                            let pending_posts = self.pending_posts.clone();
+                            let span = job_span(&prepared.job);
 
                             // Spawn plan execution on a separate task to allow heartbeats to continue
-                            let plan_handle = tokio::spawn(Self::handle_plan_execution(job_id, plan, job_id_raw, client));
+                            let handle = tokio::spawn(Self::handle_plan_execution(prepared, clients, options, history, redactor, stats, pending_posts).instrument(span));
 
-                            current_job = Some(plan_handle);
+                            current_job = Some(RunningJob { handle, job_id, job_id_raw, priority, in_processing });
                         }
                         Ok(None) => {
-                            // Timeout - continue loop
-                            debug!("Job fetch timeout, continuing...");
+                            // Timeout or rejected job - continue loop
+                            debug!("No job to run, continuing...");
+                            self.reconnect.reset();
                         }
                         Err(e) => {
-                            error!("Failed to fetch and prepare job: {e}");
-                            return Err(e);
+                            self.recover_connection("Job fetch", e).await?;
                         }
                     }
                 }
@@ -200,33 +666,48 @@ impl Worker {
                         match self.send_heartbeat().await {
                             Ok(()) => {
                                 debug!("Heartbeat sent successfully for worker {}", self.id);
+                                self.reconnect.reset();
+                                self.post_progress().await;
                             }
                             Err(e) => {
-                                error!("Failed to send heartbeat: {e}");
-                                return Err(e);
+                                self.recover_connection("Heartbeat", e).await?;
+                                // Retry the heartbeat as soon as the backoff is over
+                                heartbeat_interval.reset_immediately();
                             }
                         }
                     }
 
                     // Job fetch and preparation (no shutdown handling on Windows yet)
-                    job_result = self.fetch_and_prepare_job(), if current_job.is_none() => {
+                    job_result = poll_fetch(&mut fetch), if fetch.is_some() => {
+                        fetch = None;
                         match job_result {
-                            Ok(Some((job_id, plan, job_id_raw))) => {
+                            Ok(Some(prepared)) => {
+                                self.reconnect.reset();
                                 debug!("Prepared job {} (plan {}) with {} tasks",
-                                    job_id, plan.plan_id, plan.tasks.len());
+                                    prepared.job.job_id, prepared.plan.plan_id, prepared.plan.tasks.len());
 
-                                let client = self.client.clone();
+                                let clients = self.job_clients();
+                                let options = self.job_options();
+                                let job_id = prepared.job.job_id.clone();
+                                let job_id_raw = prepared.job_id_raw.clone();
+                                let priority = prepared.job.priority;
+                                let in_processing = prepared.in_processing;
+                                let history = Arc::clone(&self.history);
+                                let redactor = Arc::clone(&self.redactor);
+                                let stats = Arc::clone(&self.stats);
+                                let pending_posts = self.pending_posts.clone();
+                                let span = job_span(&prepared.job);
 
-                                let plan_handle = tokio::spawn(Self::handle_plan_execution(job_id, plan, job_id_raw, client));
+                                let handle = tokio::spawn(Self::handle_plan_execution(prepared, clients, options, history, redactor, stats, pending_posts).instrument(span));
 
-                                current_job = Some(plan_handle);
+                                current_job = Some(RunningJob { handle, job_id, job_id_raw, priority, in_processing });
                             }
                             Ok(None) => {
-                                debug!("Job fetch timeout, continuing...");
+                                debug!("No job to run, continuing...");
+                                self.reconnect.reset();
                             }
                             Err(e) => {
-                                error!("Failed to fetch and prepare job: {e}");
-                                return Err(e);
+                                self.recover_connection("Job fetch", e).await?;
                             }
                         }
                     }
@@ -235,67 +716,202 @@ impl Worker {
         }
 
         // Graceful shutdown: wait for current job to complete if still running
-        if let Some(handle) = current_job {
-            if let Some(timeout) = self.config.shutdown_timeout_duration() {
-                info!(
-                    "Waiting up to {:?} for current job to complete before shutdown",
+        if let Some(running) = current_job {
+            self.finish_running_job(running).await;
+        }
+        self.pending_posts.drain().await;
+
+        info!("Worker {} shutting down gracefully", self.id);
+        #[cfg(unix)]
+        return Ok(shutdown_reason.unwrap_or(ShutdownReason::Error));
+    }
+
+    /// Back off after a failed AGQ round trip, or give up once the retry budget is spent
+    ///
+    /// # Errors
+    ///
+    /// Returns a connection error once `--max-reconnect-attempts` consecutive
+    /// attempts have failed
+    async fn recover_connection(&mut self, operation: &str, e: AgwError) -> AgwResult<()> {
+        let Some(delay) = self.reconnect.next_delay() else {
+            error!("{operation} failed: {e}");
+            return Err(AgwError::Connection(format!(
+                "{operation} failed after {} reconnect attempt(s), giving up: {e}",
+                self.reconnect.max_attempts
+            )));
+        };
+        warn!(
+            "{operation} failed: {e}; reconnect attempt {}/{} in {delay:?}",
+            self.reconnect.failures, self.reconnect.max_attempts
+        );
+        tokio::time::sleep(delay).await;
+        Ok(())
+    }
+
+    /// Wait for the running job during shutdown, requeueing it if the timeout expires
+    async fn finish_running_job(&mut self, running: RunningJob) {
+        let RunningJob {
+            mut handle,
+            job_id,
+            job_id_raw,
+            priority,
+            in_processing,
+        } = running;
+
+        let Some(timeout) = self.config.shutdown_timeout_duration() else {
+            info!("Waiting for current job to complete before shutdown (no timeout)");
+            if let Err(e) = handle.await {
+                error!("Job execution task panicked during shutdown: {e}");
+            }
+            return;
+        };
+
+        info!(
+            "Waiting up to {:?} for current job to complete before shutdown",
+            timeout
+        );
+        match tokio::time::timeout(timeout, &mut handle).await {
+            Ok(Ok(())) => {
+                info!("Job completed successfully before shutdown");
+            }
+            Ok(Err(e)) => {
+                error!("Job execution task panicked during shutdown: {e}");
+            }
+            Err(_) => {
+                error!(
+                    "Job {job_id} did not complete within {:?}, forcing shutdown and requeueing it",
                     timeout
                 );
-                match tokio::time::timeout(timeout, handle).await {
-                    Ok(Ok(())) => {
-                        info!("Job completed successfully before shutdown");
-                    }
-                    Ok(Err(e)) => {
-                        error!("Job execution task panicked during shutdown: {e}");
-                    }
-                    Err(_) => {
-                        error!(
-                            "Job did not complete within {:?}, forcing shutdown. \
-                             Job results may be incomplete.",
-                            timeout
-                        );
-                    }
+
+                // Stop the job (dropping its future kills the child process) so it
+                // cannot post results after another worker has picked it up
+                handle.abort();
+                let _ = handle.await;
+                // A post that already started is let finish, so the status check
+                // below sees it rather than requeueing a job with posted results
+                self.pending_posts.drain().await;
+
+                if !in_processing {
+                    warn!("Job {job_id} dropped unfinished (at-most-once delivery, not requeued)");
+                    return;
                 }
-            } else {
-                info!("Waiting for current job to complete before shutdown (no timeout)");
-                if let Err(e) = handle.await {
-                    error!("Job execution task panicked during shutdown: {e}");
+                let queues = self.client.queue_keys();
+                let ready = queues.ready_for(priority);
+                match requeue_abandoned_job(
+                    &mut self.client,
+                    &mut self.result_client,
+                    &job_id,
+                    &job_id_raw,
+                    ready,
+                )
+                .await
+                {
+                    Ok(true) => info!("Requeued job {job_id} to {ready}"),
+                    Ok(false) => {}
+                    Err(e) => error!(
+                        "Failed to requeue job {job_id}; it remains in {}: {e}",
+                        queues.processing
+                    ),
                 }
             }
         }
-
-        info!("Worker {} shutting down gracefully", self.id);
-        Ok(())
     }
+}
 
+impl JobFetcher {
     /// Fetch and prepare a job for execution
     ///
     /// New workflow (AGQ #46):
-    /// 1. Pop job_id from queue (BRPOPLPUSH for reliability)
+    /// 1. Pop job_id from queue (BRPOPLPUSH for reliability, BRPOP for at-most-once)
     /// 2. Fetch job metadata (JOB.GET)
     /// 3. Fetch plan template (PLAN.GET)
     /// 4. Substitute input variables in tasks
     ///
-    /// Returns (job_id, plan_with_substituted_inputs, job_json) tuple
+    /// Returns the job with its plan's input variables substituted. A job whose
+    /// metadata or plan is invalid is failed (or discarded) and `Ok(None)` is returned.
     ///
     /// # Errors
     ///
-    /// Returns an error if fetching fails, JSON is invalid, or validation fails
-    async fn fetch_and_prepare_job(&mut self) -> AgwResult<Option<(String, Plan, String)>> {
-        use crate::plan::Job;
-
-        const QUEUE_READY: &str = "queue:ready";
-        const QUEUE_PROCESSING: &str = "queue:processing";
+    /// Returns an error if communicating with AGQ fails
+    async fn fetch_and_prepare_job(&mut self) -> AgwResult<Option<PreparedJob>> {
         const TIMEOUT: u64 = 5; // 5 second timeout to allow heartbeats
+        let queues = self.client.queue_keys();
 
-        // Step 1: Pop job_id from queue
-        match self
-            .client
-            .brpoplpush(QUEUE_READY, QUEUE_PROCESSING, TIMEOUT)
-            .await?
-        {
+        // Step 0: Don't pile more work onto a backlog of stuck jobs
+        if let Some(max_backlog) = self.config.max_processing_backlog {
+            let backlog = self.client.llen(queues.processing).await?;
+            if backlog > max_backlog {
+                if !self.backlog_paused {
+                    warn!(
+                        "{} holds {backlog} jobs (limit {max_backlog}), \
+                         pausing job fetching until it drains",
+                        queues.processing
+                    );
+                    self.backlog_paused = true;
+                }
+                // Wait as long as a fetch would have blocked, then check again
+                tokio::time::sleep(Duration::from_secs(TIMEOUT)).await;
+                return Ok(None);
+            }
+            if self.backlog_paused {
+                info!(
+                    "{} drained to {backlog} jobs, resuming job fetching",
+                    queues.processing
+                );
+                self.backlog_paused = false;
+            }
+        }
+
+        // Don't take jobs whose tasks would fail against a dead dependency
+        if let Some(check) = &self.config.dependency_check {
+            if !check.probe(PROBE_TIMEOUT).await {
+                if !self.dependency_paused {
+                    warn!(
+                        "Dependency {check} is unhealthy, pausing job fetching until it recovers"
+                    );
+                    self.dependency_paused = true;
+                }
+                tokio::time::sleep(Duration::from_secs(TIMEOUT)).await;
+                return Ok(None);
+            }
+            if self.dependency_paused {
+                info!("Dependency {check} recovered, resuming job fetching");
+                self.dependency_paused = false;
+            }
+        }
+
+        // Step 1: Pop job_id from queue (or pick one by plan when scheduling
+        // fairly, or by priority when honoring it)
+        let popped = if self.at_most_once() {
+            let ready: &[&str] = if self.config.honor_priority {
+                &[queues.ready_high, queues.ready, queues.ready_low]
+            } else {
+                &[queues.ready]
+            };
+            self.client.brpop(ready, TIMEOUT).await?
+        } else if self.scheduler.is_some() {
+            self.claim_fair_job(TIMEOUT).await?
+        } else if self.config.honor_priority {
+            self.claim_priority_job(TIMEOUT).await?
+        } else {
+            self.client
+                .brpoplpush(queues.ready, queues.processing, TIMEOUT)
+                .await?
+        };
+        match popped {
             Some(job_id_raw) => {
-                info!("Received job_id from queue (moved to processing)");
+                if self.at_most_once() {
+                    info!("Received job_id from queue");
+                } else {
+                    info!("Received job_id from queue (moved to processing)");
+                }
+
+                // A blank entry names no job; fetching it would only fail confusingly
+                if job_id_raw.trim().is_empty() {
+                    warn!("Discarding empty job id {job_id_raw:?} popped from the queue");
+                    self.release_job(&job_id_raw).await?;
+                    return Ok(None);
+                }
 
                 // Step 2: Get job metadata
                 let job_json = self.client.job_get(&job_id_raw).await.map_err(|e| {
@@ -305,18 +921,50 @@ impl Worker {
                     ))
                 })?;
 
-                let job = Job::from_json(&job_json).map_err(|e| {
-                    AgwError::Worker(format!(
-                        "Failed to parse job JSON for '{}': {}",
-                        job_id_raw, e
-                    ))
-                })?;
+                // A queued id whose metadata expired or was never written names
+                // no job to run or post results for
+                let Some(job_json) = job_json else {
+                    error!("Discarding job '{job_id_raw}': no metadata at job:{job_id_raw}");
+                    self.stats.record_job(false, 0);
+                    self.release_job(&job_id_raw).await?;
+                    return Ok(None);
+                };
 
-                job.validate().map_err(|e| {
-                    AgwError::Worker(format!("Job validation failed for '{}': {}", job.job_id, e))
-                })?;
+                // A malformed job has no trustworthy job_id to post results under,
+                // so it is only dropped from the processing queue
+                let mut job = match parse_job(&job_id_raw, &job_json) {
+                    Ok(job) => job,
+                    Err(e) => {
+                        error!("Discarding job '{job_id_raw}': {e}");
+                        self.stats.record_job(false, 0);
+                        self.release_job(&job_id_raw).await?;
+                        return Ok(None);
+                    }
+                };
+
+                info!(
+                    "Fetched job {} (plan_id: {}, request_id: {})",
+                    job.job_id,
+                    job.plan_id,
+                    job.request_id.as_deref().unwrap_or("none")
+                );
+                if let Some(scheduler) = self.scheduler.as_mut() {
+                    scheduler.record(&job.plan_id);
+                }
 
-                info!("Fetched job {} (plan_id: {})", job.job_id, job.plan_id);
+                // Step 2b: Resolve input passed by reference; a missing or
+                // invalid reference fails only this job
+                if let Some(input_ref) = job.input_ref.clone() {
+                    let raw = self.client.get(&input_ref).await?;
+                    match parse_input_ref(&input_ref, raw, self.config.max_input_bytes) {
+                        Ok(input) => job.input = input,
+                        Err(e) => {
+                            self.reject_job(&job, &job_id_raw, FailureReason::ValidationError, &e)
+                                .await?;
+                            return Ok(None);
+                        }
+                    }
+                }
 
                 // Step 3: Get plan template
                 let plan_json = self.client.plan_get(&job.plan_id).await.map_err(|e| {
@@ -325,50 +973,220 @@ impl Worker {
                         job.plan_id, job.job_id, e
                     ))
                 })?;
+                let Some(plan_json) = plan_json else {
+                    let reason = format!("Plan '{}' does not exist", job.plan_id);
+                    self.reject_job(&job, &job_id_raw, FailureReason::PlanNotFound, &reason)
+                        .await?;
+                    return Ok(None);
+                };
 
-                let mut plan = Plan::from_json(&plan_json).map_err(|e| {
-                    AgwError::Worker(format!(
-                        "Failed to parse plan JSON for '{}': {}",
-                        job.plan_id, e
-                    ))
-                })?;
+                // Step 4: Validate the plan and substitute input variables.
+                // An invalid plan fails only this job, not the worker.
+                match prepare_plan(&job, &plan_json, &self.config) {
+                    Ok(_) if self.config.no_exec => {
+                        let failure = FailureReason::Cancelled;
+                        self.reject_job(&job, &job_id_raw, failure, &NO_EXEC_REASON)
+                            .await?;
+                        Ok(None)
+                    }
+                    Ok(plan) => Ok(Some(PreparedJob {
+                        job,
+                        plan,
+                        job_id_raw,
+                        in_processing: !self.at_most_once(),
+                    })),
+                    Err(e) => {
+                        let failure = e.failure_reason().unwrap_or(FailureReason::ValidationError);
+                        self.reject_job(&job, &job_id_raw, failure, &e).await?;
+                        Ok(None)
+                    }
+                }
+            }
+            None => Ok(None),
+        }
+    }
 
-                plan.validate().map_err(|e| {
-                    AgwError::Worker(format!(
-                        "Plan validation failed for '{}': {}",
-                        plan.plan_id, e
-                    ))
-                })?;
+    /// Fail a fetched job that cannot run, posting `reason` as its stderr
+    async fn reject_job(
+        &mut self,
+        job: &Job,
+        job_id_raw: &str,
+        failure: FailureReason,
+        reason: &(dyn std::fmt::Display + Sync),
+    ) -> AgwResult<()> {
+        let job_id = &job.job_id;
+        error!("Failing job {job_id} ({failure}): {reason}");
+        self.stats.record_job(false, 0);
+        post_request_id(&mut self.result_client, job_id, job.request_id.as_deref()).await?;
+        post_failure_reason(&mut self.result_client, job_id, Some(failure)).await?;
+        self.result_client
+            .post_job_result(
+                job_id,
+                "",
+                &self.redactor.redact(&reason.to_string()),
+                "failed",
+            )
+            .await?;
+        self.release_job(job_id_raw).await
+    }
 
-                info!(
-                    "Fetched plan {} with {} tasks",
-                    plan.plan_id,
-                    plan.tasks.len()
-                );
+    /// Whether jobs are fetched with at-most-once delivery
+    fn at_most_once(&self) -> bool {
+        self.config.queue_reliability == QueueReliability::AtMostOnce
+    }
 
-                // Step 4: Substitute input variables in tasks
-                let mut substituted_tasks = Vec::new();
-                for task in &plan.tasks {
-                    let substituted_task = task.substitute_input(&job.input).map_err(|e| {
-                        AgwError::Worker(format!(
-                            "Failed to substitute input variables for task {} in job '{}': {}",
-                            task.task_number, job.job_id, e
-                        ))
-                    })?;
-                    substituted_tasks.push(substituted_task);
-                }
+    /// Drop a finished job from `queue:processing` (nothing to do at-most-once)
+    async fn release_job(&mut self, job_id_raw: &str) -> AgwResult<()> {
+        if !self.at_most_once() {
+            let processing = self.client.queue_keys().processing;
+            self.client.lrem(processing, 1, job_id_raw).await?;
+        }
+        Ok(())
+    }
+
+    /// Claim the ready job whose plan is most underserved, moving it to processing
+    ///
+    /// Inspects up to `--fair-schedule-lookahead` jobs at the consuming end of
+    /// the ready queue and asks the scheduler to pick one by plan. With nothing
+    /// to choose between, this blocks for the next job like a plain fetch.
+    /// Returns `None` if another worker claimed the chosen job first.
+    async fn claim_fair_job(&mut self, timeout: u64) -> AgwResult<Option<String>> {
+        let queues = self.client.queue_keys();
+        let lookahead = i64::try_from(self.config.fair_schedule_lookahead).unwrap_or(i64::MAX);
+        let mut candidates = self.client.lrange(queues.ready, -lookahead, -1).await?;
+        if candidates.len() <= 1 {
+            return self
+                .client
+                .brpoplpush(queues.ready, queues.processing, timeout)
+                .await;
+        }
+        // Jobs are consumed from the tail, so the last element is next in line
+        candidates.reverse();
+
+        let mut plan_ids = Vec::with_capacity(candidates.len());
+        for job_id_raw in &candidates {
+            let plan_id = self
+                .client
+                .get(&format!("job:{job_id_raw}"))
+                .await?
+                .and_then(|json| Job::from_json(&json).ok())
+                .map(|job| job.plan_id);
+            plan_ids.push(plan_id);
+        }
 
-                plan.tasks = substituted_tasks;
+        let Some(scheduler) = self.scheduler.as_ref() else {
+            return Ok(None);
+        };
+        let plan_refs: Vec<_> = plan_ids.iter().map(Option::as_deref).collect();
+        let Some(index) = scheduler.choose(&plan_refs) else {
+            return Ok(None);
+        };
+        let job_id_raw = &candidates[index];
+        if index > 0 {
+            debug!(
+                "Fair scheduling picked job {job_id_raw} (plan {}) over {} earlier job(s)",
+                plan_ids[index].as_deref().unwrap_or("unknown"),
+                index
+            );
+        }
+
+        // Push to processing before removing from ready, so the job is always in
+        // at least one queue even if the worker dies in between
+        self.client.lpush(queues.processing, job_id_raw).await?;
+        if self.client.lrem(queues.ready, 1, job_id_raw).await? == 0 {
+            debug!("Job {job_id_raw} was claimed by another worker");
+            self.client.lrem(queues.processing, 1, job_id_raw).await?;
+            return Ok(None);
+        }
+        Ok(Some(job_id_raw.clone()))
+    }
 
-                Ok(Some((job.job_id, plan, job_id_raw)))
+    /// Claim the next job from the highest priority ready queue that has one
+    ///
+    /// BRPOPLPUSH can only block on a single queue, so the queues are polled in
+    /// priority order with RPOPLPUSH until `timeout` seconds have passed.
+    async fn claim_priority_job(&mut self, timeout: u64) -> AgwResult<Option<String>> {
+        let queues = self.client.queue_keys();
+        let deadline = Instant::now() + Duration::from_secs(timeout);
+        loop {
+            for queue in [queues.ready_high, queues.ready, queues.ready_low] {
+                if let Some(job_id_raw) = self.client.rpoplpush(queue, queues.processing).await? {
+                    debug!("Claimed job {job_id_raw} from {queue}");
+                    return Ok(Some(job_id_raw));
+                }
             }
-            None => Ok(None),
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+            tokio::time::sleep(PRIORITY_POLL_INTERVAL).await;
         }
     }
+}
+
+impl Worker {
+    /// Start fetching a job, to be polled until it completes
+    fn start_fetch(&self) -> JobFetch {
+        let fetcher = Arc::clone(&self.fetcher);
+        Box::pin(async move { fetcher.lock_owned().await.fetch_and_prepare_job().await })
+    }
+
+    /// Whether the worker's RSS is over `--max-worker-rss-mb`, logging it if so
+    #[cfg_attr(not(unix), allow(dead_code))]
+    fn memory_limit_exceeded(&self) -> bool {
+        let Some(rss) = self.rss_monitor.and_then(|monitor| monitor.exceeded()) else {
+            return false;
+        };
+        warn!(
+            "Worker RSS {} MiB exceeds --max-worker-rss-mb {}, initiating graceful shutdown",
+            rss / (1024 * 1024),
+            self.config.max_worker_rss_mb.unwrap_or_default()
+        );
+        true
+    }
 
-    /// Send a heartbeat message to AGQ
+    /// Send a heartbeat message to AGQ, counting failures by kind in `agw_heartbeat_failures_total`
     async fn send_heartbeat(&mut self) -> AgwResult<()> {
-        self.client.heartbeat(&self.id).await
+        let result = self.heartbeat_client.heartbeat(&self.id).await;
+        if let Some(kind) = result.as_ref().err().and_then(AgwError::heartbeat_failure) {
+            METRICS.heartbeat_failures_total.inc(&[kind.as_str()]);
+        }
+        result
+    }
+
+    /// Connections for a job about to be spawned
+    fn job_clients(&self) -> JobClients {
+        JobClients {
+            source: self.client.clone(),
+            results: self.result_client.clone(),
+        }
+    }
+
+    /// Execution options for a job about to be spawned
+    fn job_options(&self) -> ExecutionOptions {
+        ExecutionOptions {
+            progress: Some(self.progress.clone()),
+            ..self.config.execution_options()
+        }
+    }
+
+    /// Post the running job's progress to `job:<id>:progress`, if a job is running
+    ///
+    /// Sent on the heartbeat connection right after each heartbeat. A failure
+    /// is only logged: the heartbeat itself went through.
+    async fn post_progress(&mut self) {
+        let Some(snapshot) = self.progress.snapshot() else {
+            return;
+        };
+        let key = self.result_client.result_key(&snapshot.job_id, "progress");
+        let posted = match serde_json::to_string(&snapshot) {
+            Ok(json) => self.heartbeat_client.set(&key, &json).await,
+            Err(e) => Err(AgwError::Worker(format!(
+                "Failed to serialize progress: {e}"
+            ))),
+        };
+        if let Err(e) = posted {
+            warn!("Failed to post progress for job {}: {e}", snapshot.job_id);
+        }
     }
 
     /// Get the worker ID
@@ -387,103 +1205,856 @@ impl Worker {
 
     /// Handle plan execution (extracted to avoid duplication between Unix/non-Unix code paths)
     ///
-    /// This function executes the plan and handles cleanup of the processing queue.
-    /// The `job_id_raw` parameter is the raw job_id string used for cleanup via LREM.
+    /// This function executes the plan, then posts its results on a task tracked
+    /// by `pending_posts` and waits for the post to finish.
+    /// The prepared job's `job_id_raw` is the raw queue entry used for cleanup via LREM.
     async fn handle_plan_execution(
-        job_id: String,
-        plan: Plan,
-        job_id_raw: String,
-        mut client: RespClient,
+        prepared: PreparedJob,
+        mut clients: JobClients,
+        options: ExecutionOptions,
+        history: Arc<JobHistory>,
+        redactor: Arc<Redactor>,
+        stats: Arc<WorkerStats>,
+        pending_posts: PendingPosts,
     ) {
-        const QUEUE_PROCESSING: &str = "queue:processing";
+        let PreparedJob {
+            job,
+            plan,
+            job_id_raw,
+            in_processing,
+        } = prepared;
+        let job_id = job.job_id.clone();
 
-        match executor::execute_plan(&job_id, &plan).await {
-            Ok(result) => {
-                info!(
-                    "Plan {} (job {}) completed: {} tasks executed, success={}",
-                    result.plan_id,
-                    result.job_id,
-                    result.task_results.len(),
-                    result.success
+        // A job needing tools this worker didn't register goes back to the queue
+        // for a worker that has them, or is failed before anything runs
+        let mut rejection = None;
+        if let Some(tools) = &options.registered_tools {
+            let missing = unregistered_commands(&plan, tools);
+            if !missing.is_empty() && options.unregistered_tools == UnregisteredTools::Fail {
+                warn!(
+                    "Job {job_id} needs unregistered tool(s) {}, failing it",
+                    missing.join(", ")
                 );
-
-                // Post result to AGQ (includes partial results if plan failed mid-execution)
-                // Note: result.success == false means some tasks failed, but we still have
-                // partial output from tasks that completed before the failure
-                let status = if result.success {
-                    "completed"
-                } else {
-                    "failed"
-                };
-                if let Err(e) = client
-                    .post_job_result(
-                        &result.job_id,
-                        &result.combined_stdout(),
-                        &result.combined_stderr(),
-                        status,
+                rejection = Some(AgwError::Job {
+                    reason: FailureReason::ValidationError,
+                    message: format!(
+                        "Plan runs tool(s) this worker did not register: {}",
+                        missing.join(", ")
+                    ),
+                });
+            } else if !missing.is_empty() {
+                warn!(
+                    "Job {job_id} needs unregistered tool(s) {}, requeueing it for a capable worker",
+                    missing.join(", ")
+                );
+                let ready = clients.source.queue_keys().ready_for(job.priority);
+                // Nothing has run yet, so handing the job back is safe even at-most-once
+                let requeued = if in_processing {
+                    requeue_abandoned_job(
+                        &mut clients.source,
+                        &mut clients.results,
+                        &job_id,
+                        &job_id_raw,
+                        ready,
                     )
                     .await
-                {
-                    error!("Failed to post results for job {}: {e}", result.job_id);
-                    // Don't remove from processing queue if we couldn't post results
-                    return;
-                }
-
-                // Remove job from processing queue after successful result posting
-                info!("Job completed successfully, removing from processing queue");
-                if let Err(e) = client.lrem(QUEUE_PROCESSING, 1, &job_id_raw).await {
-                    error!(
-                        "Failed to remove job {} from processing queue: {e}",
-                        result.job_id
-                    );
-                    // Job stays in queue:processing for monitoring/retry
+                } else {
+                    clients.source.lpush(ready, &job_id_raw).await.map(|_| true)
+                };
+                match requeued {
+                    Ok(true) => info!("Requeued job {job_id} to {ready}"),
+                    Ok(false) => {}
+                    Err(e) => error!("Failed to requeue job {job_id}: {e}"),
                 }
+                tokio::time::sleep(CAPABILITY_REQUEUE_PAUSE).await;
+                return;
             }
-            Err(e) => {
-                error!("Failed to execute plan {}: {e}", plan.plan_id);
-
-                // Post error to AGQ with empty results
-                // Note: Execution errors occur before any tasks run, so no partial results exist
-                let error_msg = format!("Execution error: {e}");
-                if let Err(post_err) = client
-                    .post_job_result(&job_id, "", &error_msg, "failed")
-                    .await
-                {
-                    error!("Failed to post error for job {}: {post_err}", job_id);
-                    // Don't remove from processing queue if we couldn't post results
-                    return;
-                }
+        }
 
-                // Remove job from processing queue even on execution failure
-                // (we successfully posted the failure results, so job is complete)
-                info!("Job failed but results posted, removing from processing queue");
-                if let Err(e) = client.lrem(QUEUE_PROCESSING, 1, &job_id_raw).await {
-                    error!("Failed to remove job {} from processing queue: {e}", job_id);
-                    // Job stays in queue:processing for monitoring
-                }
+        if let Some(progress) = &options.progress {
+            progress.start(&job_id, plan.tasks.len());
+        }
+        let started = Instant::now();
+        let mut checkpoints = options
+            .checkpoint_tasks
+            .then(|| CheckpointStore::new(clients.source.clone(), &job_id));
+        let execution = match rejection {
+            Some(e) => Err(e),
+            None => {
+                executor::execute_plan_checkpointed(
+                    &job_id,
+                    &plan,
+                    &job.input,
+                    &options,
+                    checkpoints.as_mut(),
+                )
+                .await
             }
+        };
+        if let Some(progress) = &options.progress {
+            progress.finish();
+        }
+        match &execution {
+            Ok(result) => stats.record_job(
+                result.success,
+                result
+                    .task_results
+                    .iter()
+                    .filter(|task| !task.skipped)
+                    .count(),
+            ),
+            Err(_) => stats.record_job(false, 0),
         }
+        history.record(JobSummary {
+            job_id: job_id.clone(),
+            plan_id: plan.plan_id.clone(),
+            status: match &execution {
+                Ok(result) if result.success => "completed",
+                Ok(_) => "failed",
+                Err(_) => "error",
+            }
+            .to_string(),
+            duration_ms: u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+            failed_task: execution.as_ref().ok().and_then(|result| {
+                result
+                    .task_results
+                    .iter()
+                    .find(|task| !task.success)
+                    .map(|task| task.task_number)
+            }),
+        });
+
+        let duration = started.elapsed();
+        let (posted, finished) = tokio::sync::oneshot::channel();
+        let prepared = PreparedJob {
+            job,
+            plan,
+            job_id_raw,
+            in_processing,
+        };
+        pending_posts.spawn(async move {
+            post_execution(clients, prepared, execution, options, redactor, duration).await;
+            let _ = posted.send(());
+        });
+        let _ = finished.await;
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Post a finished job's results and remove it from the processing queue
+///
+/// The job stays in `queue:processing` if any part of its results could not
+/// be posted.
+async fn post_execution(
+    clients: JobClients,
+    prepared: PreparedJob,
+    execution: AgwResult<PlanResult>,
+    options: ExecutionOptions,
+    redactor: Arc<Redactor>,
+    duration: Duration,
+) {
+    let ExecutionOptions {
+        result_sink,
+        manifest_config,
+        webhook,
+        ..
+    } = options;
+    let notify = |job_id: &str, status: &str| {
+        if let Some(webhook) = &webhook {
+            webhook.spawn_notify(JobNotification {
+                job_id: job_id.to_string(),
+                status: status.to_string(),
+                duration_ms: u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+            });
+        }
+    };
+    let PreparedJob {
+        job,
+        plan,
+        job_id_raw,
+        in_processing,
+    } = prepared;
+    let job_id = job.job_id;
+    let JobClients {
+        mut source,
+        mut results,
+    } = clients;
 
-    #[test]
-    fn test_worker_id_generation() {
-        // Test that generated worker IDs follow the pattern
-        let id = format!("agw-{}", Uuid::new_v4());
-        assert!(id.starts_with("agw-"));
-        assert!(id.len() > 4);
+    // Written before the status so a reader that sees the status finds it too
+    if let Err(e) = post_request_id(&mut results, &job_id, job.request_id.as_deref()).await {
+        error!("Failed to post request id for job {job_id}: {e}");
+        // Don't remove from processing queue if we couldn't post results
+        return;
     }
 
-    #[test]
-    fn test_worker_name_generation() {
-        // Test that auto-generated worker names follow the pattern
-        let worker_id = format!("agw-{}", Uuid::new_v4());
-        let short_id = worker_id.chars().take(18).collect::<String>();
-        let name = format!("worker-{}", short_id.replace("agw-", ""));
+    match execution {
+        Ok(result) => {
+            info!(
+                "Plan {} (job {}) completed: {} tasks executed, success={}",
+                result.plan_id,
+                result.job_id,
+                result.task_results.len(),
+                result.success
+            );
+
+            // Post result to AGQ (includes partial results if plan failed mid-execution)
+            // Note: result.success == false means some tasks failed, but we still have
+            // partial output from tasks that completed before the failure
+            let status = if result.success {
+                "completed"
+            } else {
+                "failed"
+            };
+            if let Err(e) =
+                post_failure_reason(&mut results, &job_id, result.failure_reason()).await
+            {
+                error!("Failed to post failure reason for job {job_id}: {e}");
+                // Don't remove from processing queue if we couldn't post results
+                return;
+            }
+            let warnings = result.combined_warnings();
+            if let Err(e) = post_warnings(&mut results, &job_id, &redactor.redact(&warnings)).await
+            {
+                error!("Failed to post warnings for job {job_id}: {e}");
+                // Don't remove from processing queue if we couldn't post results
+                return;
+            }
+            if let Err(e) = post_manifest(&mut results, &plan, &result, manifest_config).await {
+                error!("Failed to post manifest for job {job_id}: {e}");
+                // Don't remove from processing queue if we couldn't post results
+                return;
+            }
+            if let Err(e) = results
+                .post_job_result(
+                    &result.job_id,
+                    &redactor.redact(&result.primary_output(plan.output_mode)),
+                    &redactor.redact(&result.combined_stderr()),
+                    status,
+                )
+                .await
+            {
+                error!("Failed to post results for job {}: {e}", result.job_id);
+                // Don't remove from processing queue if we couldn't post results
+                return;
+            }
+            notify(&result.job_id, status);
+
+            if let Some(sink) = &result_sink {
+                if let Err(e) = sink.emit(&redacted_result(&result, &redactor)) {
+                    error!("Failed to emit result for job {}: {e}", result.job_id);
+                }
+            }
+
+            if result.success {
+                if let Some(follow_up) = &plan.on_success_enqueue {
+                    if let Err(e) =
+                        enqueue_follow_up(&mut source, follow_up, &result, job.chain_depth).await
+                    {
+                        error!(
+                            "Failed to enqueue follow-up plan {} for job {}: {e}",
+                            follow_up.plan_id, result.job_id
+                        );
+                    }
+                }
+            }
+
+            // Remove job from processing queue after successful result posting
+            if !in_processing {
+                return;
+            }
+            info!("Job completed successfully, removing from processing queue");
+            let processing = source.queue_keys().processing;
+            if let Err(e) = source.lrem(processing, 1, &job_id_raw).await {
+                error!(
+                    "Failed to remove job {} from processing queue: {e}",
+                    result.job_id
+                );
+                // Job stays in queue:processing for monitoring/retry
+            }
+        }
+        Err(e) => {
+            error!("Failed to execute plan {}: {e}", plan.plan_id);
+
+            // Post error to AGQ with empty results. `error` rather than `failed`
+            // tells consumers a task could not be started (an infrastructure
+            // problem) as opposed to having run and failed.
+            if let Err(post_err) =
+                post_failure_reason(&mut results, &job_id, e.failure_reason()).await
+            {
+                error!("Failed to post failure reason for job {job_id}: {post_err}");
+                // Don't remove from processing queue if we couldn't post results
+                return;
+            }
+            let error_msg = format!("Execution error: {e}");
+            if let Err(post_err) = results
+                .post_job_result(&job_id, "", &redactor.redact(&error_msg), "error")
+                .await
+            {
+                error!("Failed to post error for job {}: {post_err}", job_id);
+                // Don't remove from processing queue if we couldn't post results
+                return;
+            }
+            notify(&job_id, "error");
+
+            // Remove job from processing queue even on execution failure
+            // (we successfully posted the failure results, so job is complete)
+            if !in_processing {
+                return;
+            }
+            info!("Job failed but results posted, removing from processing queue");
+            let processing = source.queue_keys().processing;
+            if let Err(e) = source.lrem(processing, 1, &job_id_raw).await {
+                error!("Failed to remove job {} from processing queue: {e}", job_id);
+                // Job stays in queue:processing for monitoring
+            }
+        }
+    }
+}
+
+/// Apply the job connection settings from `config` to `client`
+///
+/// # Errors
+///
+/// Returns an error if `--result-key-template` is invalid
+fn configure_job_client(client: &mut RespClient, config: &Config) -> AgwResult<()> {
+    client.set_result_key_template(ResultKeyTemplate::parse(&config.result_key_template)?);
+    client.set_result_chunk_size(config.result_chunk_size);
+    // Only job connections: heartbeats must not queue behind result posts
+    if let Some(max) = config.max_inflight_commands {
+        client.set_max_inflight_commands(max);
+    }
+    if let Some(strategy) = config.retry_backoff {
+        client.set_result_retry_backoff(strategy);
+    }
+    if let Some(nodes) = config.redirect_nodes() {
+        client.enable_cluster_redirects(nodes);
+    }
+    Ok(())
+}
+
+/// Send a bare `PING` every `interval` for the life of the process
+///
+/// Runs on its own connection, separate from the heartbeat, so traffic keeps
+/// flowing to AGQ while the job connection sits in a long blocking fetch.
+/// Failures are only logged: the connection reconnects on the next ping, and
+/// real outages are caught by the heartbeat.
+async fn idle_ping(mut client: RespClient, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        match client.ping().await {
+            Ok(()) => debug!("Idle ping sent"),
+            Err(e) => warn!("Idle ping failed: {e}"),
+        }
+    }
+}
+
+/// Span carried by everything logged while a job executes
+fn job_span(job: &Job) -> Span {
+    let span = info_span!(
+        "job",
+        job_id = %job.job_id,
+        request_id = tracing::field::Empty
+    );
+    if let Some(request_id) = &job.request_id {
+        span.record("request_id", tracing::field::display(request_id));
+    }
+    span
+}
+
+/// Echo a job's external request id into its results (`job:<id>:request_id`)
+async fn post_request_id(
+    client: &mut RespClient,
+    job_id: &str,
+    request_id: Option<&str>,
+) -> AgwResult<()> {
+    if let Some(request_id) = request_id {
+        let key = client.result_key(job_id, "request_id");
+        client.set(&key, request_id).await?;
+    }
+    Ok(())
+}
+
+/// Store why a failed job failed under `job:<id>:failure_reason`
+///
+/// Nothing is written for a job that succeeded, or failed in a way outside the
+/// [`FailureReason`] vocabulary. Called before the status is posted, like the
+/// other result fields.
+async fn post_failure_reason(
+    client: &mut RespClient,
+    job_id: &str,
+    reason: Option<FailureReason>,
+) -> AgwResult<()> {
+    if let Some(reason) = reason {
+        let key = client.result_key(job_id, "failure_reason");
+        client.set(&key, reason.as_str()).await?;
+    }
+    Ok(())
+}
+
+/// Store stderr lines classified as warnings under `job:<id>:warnings`
+///
+/// Nothing is written when there are none. Called before the status is posted,
+/// like the other result fields.
+async fn post_warnings(client: &mut RespClient, job_id: &str, warnings: &str) -> AgwResult<()> {
+    if !warnings.is_empty() {
+        let key = client.result_key(job_id, "warnings");
+        client.set(&key, warnings).await?;
+    }
+    Ok(())
+}
+
+/// Copy of `result` with task output and warnings passed through `redactor`
+fn redacted_result(result: &PlanResult, redactor: &Redactor) -> PlanResult {
+    let task_results = result
+        .task_results
+        .iter()
+        .map(|task| TaskResult {
+            stdout: redactor.redact(&task.stdout).into_owned(),
+            stderr: redactor.redact(&task.stderr).into_owned(),
+            warnings: task
+                .warnings
+                .iter()
+                .map(|warning| redactor.redact(warning).into_owned())
+                .collect(),
+            ..task.clone()
+        })
+        .collect();
+    PlanResult {
+        task_results,
+        ..result.clone()
+    }
+}
+
+/// Store the job's provenance manifest under `job:<id>:manifest`
+///
+/// Called before the status is posted, like the other result fields.
+async fn post_manifest(
+    client: &mut RespClient,
+    plan: &Plan,
+    result: &PlanResult,
+    config: Option<ConfigSnapshot>,
+) -> AgwResult<()> {
+    let manifest = JobManifest::build(plan, result)
+        .with_config(config)
+        .to_json()
+        .map_err(|e| AgwError::Worker(format!("Failed to serialize manifest: {e}")))?;
+    let key = client.result_key(&result.job_id, "manifest");
+    client.set(&key, &manifest).await
+}
+
+/// Parse and validate fetched job metadata
+fn parse_job(job_id_raw: &str, job_json: &str) -> AgwResult<Job> {
+    let job = Job::from_json(job_json).map_err(|e| {
+        AgwError::Worker(format!(
+            "Failed to parse job JSON for '{}': {}",
+            job_id_raw, e
+        ))
+    })?;
+
+    job.validate().map_err(|e| {
+        AgwError::Worker(format!("Job validation failed for '{}': {}", job.job_id, e))
+    })?;
+
+    Ok(job)
+}
+
+/// Parse the JSON stored under a job's `input_ref` key
+fn parse_input_ref(
+    input_ref: &str,
+    raw: Option<String>,
+    max_bytes: usize,
+) -> AgwResult<serde_json::Value> {
+    let raw = raw
+        .ok_or_else(|| AgwError::Worker(format!("Input reference '{input_ref}' does not exist")))?;
+    if raw.len() > max_bytes {
+        return Err(AgwError::Worker(format!(
+            "Input reference '{input_ref}' is {} bytes, exceeding the {max_bytes} byte limit",
+            raw.len()
+        )));
+    }
+    serde_json::from_str(&raw).map_err(|e| {
+        AgwError::Worker(format!(
+            "Input reference '{input_ref}' is not valid JSON: {e}"
+        ))
+    })
+}
+
+/// Poll the fetch in flight (pending forever if there is none)
+async fn poll_fetch(fetch: &mut Option<JobFetch>) -> AgwResult<Option<PreparedJob>> {
+    match fetch {
+        Some(fetch) => fetch.await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Parse and validate a job's plan, then substitute the job's input into it
+///
+/// Input substitution errors are reported for all tasks together.
+fn prepare_plan(job: &Job, plan_json: &str, config: &Config) -> AgwResult<Plan> {
+    let parsed = if config.strict_plan_parsing {
+        Plan::from_json_strict(plan_json)
+    } else {
+        Plan::from_json(plan_json)
+    };
+    let mut plan = parsed.map_err(|e| AgwError::Job {
+        reason: FailureReason::ValidationError,
+        message: format!("Failed to parse plan JSON for '{}': {}", job.plan_id, e),
+    })?;
+
+    let verifier = config.plan_verifier();
+    // Commands are split after validation so a signed plan is verified as it was signed
+    plan.validate_trusted(verifier.as_ref())
+        .and_then(|()| plan.split_command_strings())
+        .and_then(|()| {
+            if config.require_timeouts {
+                plan.require_timeouts()
+            } else {
+                Ok(())
+            }
+        })
+        .map_err(|e| AgwError::Job {
+            reason: FailureReason::ValidationError,
+            message: format!("Plan validation failed for '{}': {}", plan.plan_id, e),
+        })?;
+
+    // Applied after validation so a signed plan is verified as it was signed
+    if let Some(timeout_secs) = config.default_task_timeout_secs {
+        plan.apply_default_timeout(timeout_secs);
+    }
+
+    info!(
+        "Fetched plan {} with {} tasks",
+        plan.plan_id,
+        plan.tasks.len()
+    );
+
+    // Worst case includes the kill grace each timed-out task may use
+    match plan.max_runtime_secs(config.kill_grace_secs) {
+        Ok(Some(secs)) => debug!("Plan {} may run for up to {secs}s", plan.plan_id),
+        Ok(None) => debug!("Plan {} has tasks without a timeout", plan.plan_id),
+        Err(e) => {
+            return Err(AgwError::Job {
+                reason: FailureReason::ValidationError,
+                message: format!("Plan validation failed for '{}': {}", plan.plan_id, e),
+            })
+        }
+    }
+
+    if config.require_declared_inputs {
+        let unused = plan
+            .check_declared_inputs(&job.input)
+            .map_err(|e| AgwError::Job {
+                reason: FailureReason::ValidationError,
+                message: format!("Failed to prepare job '{}': {}", job.job_id, e),
+            })?;
+        if !unused.is_empty() {
+            warn!(
+                "Job {} input has fields plan {} does not use: {}",
+                job.job_id,
+                plan.plan_id,
+                unused.join(", ")
+            );
+        }
+    }
+
+    plan.substitute_input(
+        &job.input,
+        &config.redactor(),
+        &config.substitution_limits(),
+    )
+    .map_err(|e| AgwError::Job {
+        reason: FailureReason::SubstitutionError,
+        message: format!("Failed to prepare job '{}': {}", job.job_id, e),
+    })
+}
+
+/// Enqueue a successful plan's follow-up job, returning its job ID
+///
+/// The follow-up's input is built from the finished tasks' stdout. It is one
+/// deeper in the chain than the job at `chain_depth` that ran the plan, and is
+/// not enqueued past `MAX_FOLLOW_UP_DEPTH`, so plans enqueueing each other in
+/// a cycle stop.
+async fn enqueue_follow_up(
+    client: &mut RespClient,
+    follow_up: &FollowUp,
+    result: &PlanResult,
+    chain_depth: u32,
+) -> AgwResult<String> {
+    if chain_depth >= MAX_FOLLOW_UP_DEPTH {
+        return Err(AgwError::Worker(format!(
+            "Job {} ends a chain of {MAX_FOLLOW_UP_DEPTH} follow-ups, the most allowed",
+            result.job_id
+        )));
+    }
+    let input = follow_up.build_input(|task_number| {
+        result
+            .task_results
+            .iter()
+            .find(|task| task.task_number == task_number)
+            .map(|task| task.stdout.as_str())
+    })?;
+
+    let job = Job {
+        job_id: Uuid::new_v4().to_string(),
+        plan_id: follow_up.plan_id.clone(),
+        input,
+        input_ref: None,
+        request_id: None,
+        priority: JobPriority::Normal,
+        chain_depth: chain_depth + 1,
+        status: "pending".to_string(),
+    };
+    enqueue::push_job(client, &job).await?;
+    info!(
+        "Job {} enqueued follow-up job {} (plan {})",
+        result.job_id, job.job_id, job.plan_id
+    );
+    Ok(job.job_id)
+}
+
+/// Commands `plan` runs that are not among `tools`, in task order without duplicates
+///
+/// Shell tasks are exempt: their command is a script, and whether they may run
+/// at all is decided by `--allow-shell`.
+fn unregistered_commands<'a>(plan: &'a Plan, tools: &HashSet<String>) -> Vec<&'a str> {
+    let mut missing: Vec<&str> = Vec::new();
+    for task in plan.tasks.iter().filter(|task| !task.shell) {
+        if !tools.contains(&task.command) && !missing.contains(&task.command.as_str()) {
+            missing.push(&task.command);
+        }
+    }
+    missing
+}
+
+/// Move an abandoned job from the processing queue back to its ready queue
+///
+/// Returns `Ok(false)` without requeueing if the job already has a terminal
+/// status (its results were posted, so running it again would duplicate work)
+/// or if it is no longer in the processing queue (e.g. a reaper moved it).
+/// The job is removed from processing before being pushed to ready so that at
+/// most one copy is ever requeued.
+async fn requeue_abandoned_job(
+    client: &mut RespClient,
+    results: &mut RespClient,
+    job_id: &str,
+    job_id_raw: &str,
+    ready_queue: &str,
+) -> AgwResult<bool> {
+    let processing = client.queue_keys().processing;
+    let status = results.get(&results.result_key(job_id, "status")).await?;
+    if matches!(status.as_deref(), Some("completed" | "failed" | "error")) {
+        info!(
+            "Job {job_id} already finished ({}), not requeueing",
+            status.unwrap_or_default()
+        );
+        client.lrem(processing, 1, job_id_raw).await?;
+        return Ok(false);
+    }
+
+    if client.lrem(processing, 1, job_id_raw).await? == 0 {
+        warn!("Job {job_id} is no longer in {processing}, not requeueing");
+        return Ok(false);
+    }
+
+    client.lpush(ready_queue, job_id_raw).await?;
+    Ok(true)
+}
+
+/// Compare local wall-clock against AGQ's and warn if they differ by more than `threshold`
+///
+/// Task timeouts use the monotonic clock and are unaffected, but skew confuses
+/// AGQ's lease/TTL logic for any wall-clock timestamps. A server that doesn't
+/// support `TIME` is not an error.
+async fn check_clock_skew(client: &mut RespClient, threshold: Duration) {
+    let server_time = match client.server_time().await {
+        Ok(time) => time,
+        Err(e) => {
+            debug!("Skipping clock skew check: {e}");
+            return;
+        }
+    };
+
+    let local_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    let (skew, local_ahead) = clock_skew(local_time, server_time);
+    if skew > threshold {
+        warn!(
+            "Clock skew of {:.3}s detected (local clock is {} AGQ); \
+             this may confuse lease and TTL handling",
+            skew.as_secs_f64(),
+            if local_ahead { "ahead of" } else { "behind" }
+        );
+    } else {
+        debug!("Clock skew with AGQ: {:.3}s", skew.as_secs_f64());
+    }
+}
+
+/// Probe each tool with `<tool> --version`, returning the versions that could be resolved
+///
+/// Not every tool supports `--version`, so a probe that fails to spawn, exits
+/// non-zero, prints nothing, or runs past `timeout` is logged and skipped.
+/// With a `tool_path`, tools are looked up only in that directory, as tasks are.
+async fn collect_tool_versions(
+    tools: &[String],
+    tool_path: Option<&Path>,
+    timeout: Duration,
+) -> BTreeMap<String, String> {
+    let mut versions = BTreeMap::new();
+    for tool in tools {
+        match probe_tool_version(tool, tool_path, timeout).await {
+            Ok(version) => {
+                debug!("Tool {tool} version: {version}");
+                versions.insert(tool.clone(), version);
+            }
+            Err(reason) => warn!("Skipping version for tool {tool}: {reason}"),
+        }
+    }
+    versions
+}
+
+/// Run `<tool> --version` and return the first non-empty line it prints
+async fn probe_tool_version(
+    tool: &str,
+    tool_path: Option<&Path>,
+    timeout: Duration,
+) -> Result<String, String> {
+    let mut command = Command::new(tool);
+    if let Some(dir) = tool_path {
+        command.env("PATH", dir);
+    }
+    let child = command
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("failed to spawn: {e}"))?;
+
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| format!("timed out after {}s", timeout.as_secs_f64()))?
+        .map_err(|e| format!("failed to wait: {e}"))?;
+
+    if !output.status.success() {
+        return Err(format!("exited with {}", output.status));
+    }
+
+    // Some tools print their version to stderr
+    let version = [&output.stdout, &output.stderr]
+        .into_iter()
+        .find_map(|stream| {
+            String::from_utf8_lossy(stream)
+                .lines()
+                .map(str::trim)
+                .find(|line| !line.is_empty())
+                .map(str::to_string)
+        })
+        .ok_or_else(|| "printed no version".to_string());
+    version
+}
+
+/// Absolute difference between local and server time, and whether local is ahead
+fn clock_skew(local: Duration, server: Duration) -> (Duration, bool) {
+    if local >= server {
+        (local - server, true)
+    } else {
+        (server - local, false)
+    }
+}
+
+/// Host name up to the first dot, for `{hostname}` in `--name-template`
+fn short_hostname() -> Option<String> {
+    let hostname = system_hostname().or_else(|| std::env::var("HOSTNAME").ok())?;
+    let short = hostname.split('.').next().unwrap_or_default();
+    (!short.is_empty()).then(|| short.to_string())
+}
+
+#[cfg(unix)]
+fn system_hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer is valid for its full length; gethostname writes at
+    // most that many bytes
+    if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8(buf[..len].to_vec()).ok()
+}
+
+#[cfg(not(unix))]
+fn system_hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backoff::BackoffStrategy;
+    use crate::resp::QueueKeys;
+
+    const SESSION_KEY: &str = "test-session-key";
+    const QUEUE_READY: &str = QueueKeys::STANDALONE.ready;
+    const QUEUE_READY_HIGH: &str = QueueKeys::STANDALONE.ready_high;
+    const QUEUE_PROCESSING: &str = QueueKeys::STANDALONE.processing;
+
+    /// Worker connected to a mock AGQ, with extra CLI arguments
+    async fn test_worker(mock: &crate::mock_agq::MockAgq, args: &[&str]) -> Worker {
+        use clap::Parser;
+
+        let base = [
+            "agw",
+            "--agq-address",
+            mock.address.as_str(),
+            "--session-key",
+            SESSION_KEY,
+            "--worker-id",
+            "worker-1",
+        ];
+        let config = Config::try_parse_from(base.iter().chain(args)).unwrap();
+        Worker::new(config).await.unwrap()
+    }
+
+    /// Fetch the next job as the main loop does
+    async fn fetch_job(worker: &Worker) -> AgwResult<Option<PreparedJob>> {
+        worker.fetcher.lock().await.fetch_and_prepare_job().await
+    }
+
+    fn prepared_job(job_id: &str, plan: Plan, job_id_raw: &str) -> PreparedJob {
+        PreparedJob {
+            job: Job {
+                job_id: job_id.to_string(),
+                plan_id: plan.plan_id.clone(),
+                input: serde_json::Value::Null,
+                input_ref: None,
+                request_id: None,
+                priority: JobPriority::Normal,
+                chain_depth: 0,
+                status: "pending".to_string(),
+            },
+            plan,
+            job_id_raw: job_id_raw.to_string(),
+            in_processing: true,
+        }
+    }
+
+    #[test]
+    fn test_worker_id_generation() {
+        // Test that generated worker IDs follow the pattern
+        let id = format!("agw-{}", Uuid::new_v4());
+        assert!(id.starts_with("agw-"));
+        assert!(id.len() > 4);
+    }
+
+    #[test]
+    fn test_worker_name_generation() {
+        // Test that auto-generated worker names follow the pattern
+        let worker_id = format!("agw-{}", Uuid::new_v4());
+        let short_id = worker_id.chars().take(18).collect::<String>();
+        let name = format!("worker-{}", short_id.replace("agw-", ""));
 
         assert!(name.starts_with("worker-"));
         assert!(name.len() > 7); // "worker-" + at least some ID chars
@@ -493,6 +2064,55 @@ mod tests {
         assert!(validate_worker_name(&name).is_ok());
     }
 
+    #[tokio::test]
+    async fn test_worker_name_from_template() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(
+            &mock,
+            &[
+                "--name-template",
+                "{role}-{hostname}-{pid}",
+                "--role",
+                "gpu",
+            ],
+        )
+        .await;
+        let expected = format!(
+            "gpu-{}-{}",
+            short_hostname().unwrap_or_else(|| "localhost".to_string()),
+            std::process::id()
+        );
+        assert_eq!(worker.name, expected);
+    }
+
+    #[tokio::test]
+    async fn test_worker_rejects_invalid_templated_name() {
+        use crate::mock_agq::MockAgq;
+
+        use clap::Parser;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let config = Config::try_parse_from([
+            "agw",
+            "--agq-address",
+            mock.address.as_str(),
+            "--session-key",
+            SESSION_KEY,
+            "--name-template",
+            "{role}.{pid}",
+            "--role",
+            "gpu",
+        ])
+        .unwrap();
+        let err = Worker::new(config)
+            .await
+            .err()
+            .expect("invalid name accepted");
+        assert!(matches!(err, AgwError::InvalidConfig(_)), "{err}");
+    }
+
     #[test]
     fn test_worker_id_validation() {
         use crate::config::validate_worker_id;
@@ -505,4 +2125,2087 @@ mod tests {
         assert!(validate_worker_id("worker-1").is_ok());
         assert!(validate_worker_id("test_worker").is_ok());
     }
+
+    #[test]
+    fn test_clock_skew_computation() {
+        let server = Duration::from_secs(1_700_000_000);
+
+        let (skew, local_ahead) = clock_skew(server + Duration::from_millis(1500), server);
+        assert_eq!(skew, Duration::from_millis(1500));
+        assert!(local_ahead);
+
+        let (skew, local_ahead) = clock_skew(server - Duration::from_secs(30), server);
+        assert_eq!(skew, Duration::from_secs(30));
+        assert!(!local_ahead);
+
+        let (skew, _) = clock_skew(server, server);
+        assert_eq!(skew, Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_clock_skew_against_mocked_server_time() {
+        use crate::mock_agq::{MockAgq, Reply};
+
+        let mock = MockAgq::start(None).await;
+        let mut client = RespClient::connect(&mock.address).await.unwrap();
+
+        // Server reports a time two minutes behind the local clock
+        let local = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let server_secs = local.as_secs() - 120;
+        mock.script(
+            "TIME",
+            Reply::Array(vec![
+                Reply::Bulk(server_secs.to_string()),
+                Reply::Bulk("250000".to_string()),
+            ]),
+        );
+
+        let server = client.server_time().await.unwrap();
+        assert_eq!(
+            server,
+            Duration::from_secs(server_secs) + Duration::from_millis(250)
+        );
+
+        let (skew, local_ahead) = clock_skew(local, server);
+        assert!(local_ahead);
+        assert!(skew > Duration::from_secs(118) && skew < Duration::from_secs(121));
+    }
+
+    #[cfg(unix)]
+    fn write_script(dir: &std::path::Path, name: &str, body: &str) -> String {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_collect_tool_versions_skips_failing_probes() {
+        use crate::mock_agq::MockAgq;
+
+        let dir = std::env::temp_dir().join(format!("agw-tools-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let reporting = write_script(&dir, "reporting", "echo\necho 'reporting 1.2.3'");
+        let erroring = write_script(&dir, "erroring", "echo 'unknown flag' >&2\nexit 2");
+        let hanging = write_script(&dir, "hanging", "sleep 5");
+        let missing = dir.join("missing").to_string_lossy().into_owned();
+
+        let tools = vec![reporting.clone(), erroring, hanging, missing];
+        let versions = collect_tool_versions(&tools, None, Duration::from_millis(500)).await;
+
+        assert_eq!(versions.len(), 1);
+        assert_eq!(versions[&reporting], "reporting 1.2.3");
+
+        let mock = MockAgq::start(None).await;
+        let mut client = RespClient::connect(&mock.address).await.unwrap();
+        client
+            .register_tool_versions("worker-1", &versions)
+            .await
+            .unwrap();
+        let stored: BTreeMap<String, String> =
+            serde_json::from_str(&mock.get("worker:worker-1:tool_versions").unwrap()).unwrap();
+        assert_eq!(stored, versions);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_and_result_post_use_distinct_connections() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut worker = test_worker(&mock, &[]).await;
+        worker.send_heartbeat().await.unwrap();
+        worker
+            .client
+            .clone()
+            .post_job_result("job-1", "out", "", "completed")
+            .await
+            .unwrap();
+        worker.send_heartbeat().await.unwrap();
+
+        let heartbeat_connections = mock.connections_for("PING");
+        let result_connections = mock.connections_for("SET");
+        assert_eq!(heartbeat_connections.len(), 2);
+        assert_eq!(heartbeat_connections[0], heartbeat_connections[1]);
+        assert!(!result_connections.is_empty());
+        assert!(result_connections
+            .iter()
+            .all(|c| !heartbeat_connections.contains(c)));
+    }
+
+    #[test]
+    fn test_reconnect_backoff_follows_strategy_up_to_cap() {
+        let mut policy = ReconnectPolicy::new(
+            5,
+            Backoff::new(
+                BackoffStrategy::Exponential,
+                Duration::from_secs(1),
+                Duration::from_secs(5),
+            ),
+        );
+        let delays: Vec<_> = std::iter::from_fn(|| policy.next_delay()).collect();
+        assert_eq!(delays, [1, 2, 4, 5, 5].map(Duration::from_secs).to_vec(),);
+
+        // A success restores the full budget
+        policy.reset();
+        assert_eq!(policy.next_delay(), Some(Duration::from_secs(1)));
+
+        let fibonacci = Backoff::new(
+            BackoffStrategy::Fibonacci,
+            Duration::from_secs(1),
+            Duration::MAX,
+        );
+        let mut policy = ReconnectPolicy::new(5, fibonacci);
+        let delays: Vec<_> = std::iter::from_fn(|| policy.next_delay()).collect();
+        assert_eq!(delays, [1, 1, 2, 3, 5].map(Duration::from_secs).to_vec());
+
+        assert_eq!(
+            ReconnectPolicy::new(0, Backoff::fixed(Duration::MAX)).next_delay(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_worker_gives_up_after_max_reconnect_attempts() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut worker = test_worker(
+            &mock,
+            &[
+                "--max-reconnect-attempts",
+                "2",
+                "--reconnect-backoff-max-secs",
+                "0",
+            ],
+        )
+        .await;
+        mock.drop_on("PING", usize::MAX);
+
+        let err = tokio::time::timeout(Duration::from_secs(10), worker.run_loop())
+            .await
+            .expect("worker kept retrying")
+            .unwrap_err();
+        assert!(matches!(err, AgwError::Connection(_)), "{err}");
+        assert!(err.to_string().contains("after 2 reconnect attempt(s)"));
+        // The first heartbeat plus two reconnect attempts
+        assert!(mock.count("PING") >= 3);
+    }
+
+    #[tokio::test]
+    async fn test_worker_recovers_within_reconnect_attempts() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut worker = test_worker(
+            &mock,
+            &[
+                "--max-reconnect-attempts",
+                "2",
+                "--reconnect-backoff-max-secs",
+                "0",
+            ],
+        )
+        .await;
+        // Fails the first heartbeat (and the client's own retry of it)
+        mock.drop_on("PING", 2);
+
+        assert!(
+            tokio::time::timeout(Duration::from_millis(500), worker.run_loop())
+                .await
+                .is_err(),
+            "worker should still be running"
+        );
+        assert!(mock.count("PING") >= 3);
+        assert_eq!(worker.reconnect.failures, 0);
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_refreshes_progress_of_running_job() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+        use crate::progress::ProgressSnapshot;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut worker = test_worker(&mock, &["--heartbeat-interval", "1"]).await;
+        let plan = Plan {
+            plan_id: "slow".to_string(),
+            tasks: vec![
+                Task {
+                    task_number: 1,
+                    command: "echo".to_string(),
+                    ..Default::default()
+                },
+                Task {
+                    task_number: 2,
+                    command: "sleep".to_string(),
+                    args: vec!["5".to_string()],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        mock.set("plan:slow", &plan.to_json().unwrap());
+        mock.set("job:job-1", r#"{"job_id":"job-1","plan_id":"slow"}"#);
+        mock.push(QUEUE_READY, "job-1");
+
+        let progress = || -> Option<ProgressSnapshot> {
+            serde_json::from_str(&mock.get("job:job-1:progress")?).ok()
+        };
+        let observe = async {
+            let mut first: Option<ProgressSnapshot> = None;
+            loop {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                let Some(snapshot) = progress().filter(|s| s.task_number == Some(2)) else {
+                    continue;
+                };
+                match &first {
+                    None => first = Some(snapshot),
+                    Some(earlier) if snapshot.elapsed_ms > earlier.elapsed_ms => {
+                        return (first.unwrap(), snapshot);
+                    }
+                    Some(_) => {}
+                }
+            }
+        };
+        let (first, refreshed) = tokio::select! {
+            stopped = worker.run_loop() => panic!("worker stopped: {stopped:?}"),
+            observed = tokio::time::timeout(Duration::from_secs(4), observe) => {
+                observed.expect("progress not refreshed by heartbeats")
+            }
+        };
+        assert_eq!(first.total_tasks, 2);
+        assert!(
+            refreshed.elapsed_ms >= first.elapsed_ms + 500,
+            "{first:?} {refreshed:?}"
+        );
+        assert!(mock.get("job:job-1:status").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_during_fetch_keeps_claimed_job() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut worker = test_worker(&mock, &["--heartbeat-interval", "1"]).await;
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "echo".to_string(),
+                args: vec!["done".to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        mock.set("plan:plan-1", &plan.to_json().unwrap());
+        mock.set("job:job-1", r#"{"job_id":"job-1","plan_id":"plan-1"}"#);
+        mock.push(QUEUE_READY, "job-1");
+        // Heartbeats tick while the fetch waits on each GET, after the job
+        // has already moved to processing
+        mock.delay_on("GET", Duration::from_millis(1500));
+
+        let finished = async {
+            while mock.get("job:job-1:status").is_none() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        };
+        tokio::select! {
+            stopped = worker.run_loop() => panic!("worker stopped: {stopped:?}"),
+            finished = tokio::time::timeout(Duration::from_secs(8), finished) => {
+                finished.expect("claimed job never ran");
+            }
+        }
+        assert!(mock.count("PING") >= 3);
+        assert_eq!(mock.get("job:job-1:status").as_deref(), Some("completed"));
+        assert_eq!(mock.get("job:job-1:stdout").as_deref(), Some("done\n"));
+        assert!(mock.list(QUEUE_PROCESSING).is_empty());
+        assert!(mock.list(QUEUE_READY).is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_worker_shuts_down_when_rss_exceeds_limit() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut worker = test_worker(&mock, &["--max-worker-rss-mb", "1"]).await;
+
+        let reason = tokio::time::timeout(Duration::from_secs(5), worker.run_loop())
+            .await
+            .expect("worker ignored its memory limit")
+            .unwrap();
+        assert_eq!(reason, ShutdownReason::MemoryLimit);
+        assert_eq!(reason.to_string(), "memory limit");
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_during_execution_posts_result_and_cleans_up() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut client = RespClient::connect(&mock.address).await.unwrap();
+        client.authenticate(SESSION_KEY).await.unwrap();
+
+        mock.push("queue:ready", "job-raw-1");
+        let job_id_raw = client
+            .brpoplpush("queue:ready", "queue:processing", 1)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            plan_description: None,
+            tasks: vec![Task {
+                task_number: 1,
+                command: "echo".to_string(),
+                args: vec!["hello".to_string()],
+                input_from_task: None,
+                timeout_secs: Some(30),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        // Connection drops while the job is running: the first result write fails
+        mock.drop_on("SET", 1);
+        let history = Arc::new(JobHistory::new(10));
+        Worker::handle_plan_execution(
+            prepared_job("job-1", plan, &job_id_raw),
+            JobClients::shared(client.clone()),
+            ExecutionOptions::default(),
+            Arc::clone(&history),
+            Arc::default(),
+            Arc::default(),
+            PendingPosts::default(),
+        )
+        .await;
+
+        assert_eq!(mock.get("job:job-1:stdout").as_deref(), Some("hello\n"));
+        assert_eq!(mock.get("job:job-1:status").as_deref(), Some("completed"));
+        assert!(mock.list("queue:processing").is_empty());
+
+        let recent = history.recent();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].job_id, "job-1");
+        assert_eq!(recent[0].status, "completed");
+        assert_eq!(recent[0].failed_task, None);
+    }
+
+    #[tokio::test]
+    async fn test_output_mode_shapes_posted_stdout() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::{OutputMode, Task};
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut client = RespClient::connect(&mock.address).await.unwrap();
+        client.authenticate(SESSION_KEY).await.unwrap();
+
+        let echo = |task_number, arg: &str| Task {
+            task_number,
+            command: "echo".to_string(),
+            args: vec![arg.to_string()],
+            ..Default::default()
+        };
+        for (job_id, mode, expected) in [
+            ("job-1", OutputMode::Concatenated, "first\nsecond\n"),
+            ("job-2", OutputMode::LastTaskOnly, "second\n"),
+            (
+                "job-3",
+                OutputMode::PerTask,
+                r#"{"1":"first\n","2":"second\n"}"#,
+            ),
+        ] {
+            let plan = Plan {
+                plan_id: "plan-1".to_string(),
+                tasks: vec![echo(1, "first"), echo(2, "second")],
+                output_mode: mode,
+                ..Default::default()
+            };
+            mock.push(QUEUE_PROCESSING, job_id);
+            Worker::handle_plan_execution(
+                prepared_job(job_id, plan, job_id),
+                JobClients::shared(client.clone()),
+                ExecutionOptions::default(),
+                Arc::new(JobHistory::new(10)),
+                Arc::default(),
+                Arc::default(),
+                PendingPosts::default(),
+            )
+            .await;
+
+            assert_eq!(
+                mock.get(&format!("job:{job_id}:stdout")).as_deref(),
+                Some(expected),
+                "{mode:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_emit_results_writes_json_lines() {
+        use crate::executor::ResultSink;
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+        use crate::test_logs::CapturedLogs;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut client = RespClient::connect(&mock.address).await.unwrap();
+        client.authenticate(SESSION_KEY).await.unwrap();
+
+        let stdout = CapturedLogs::default();
+        let redactor = Arc::new(Redactor::new(vec![regex::Regex::new(r"tok_\w+").unwrap()]));
+        let options = ExecutionOptions {
+            result_sink: Some(ResultSink::new(stdout.clone())),
+            redactor: (*redactor).clone(),
+            ..Default::default()
+        };
+        for (job_id, arg) in [("job-1", "hello"), ("job-2", "tok_secret")] {
+            let plan = Plan {
+                plan_id: "plan-1".to_string(),
+                tasks: vec![Task {
+                    task_number: 1,
+                    command: "echo".to_string(),
+                    args: vec![arg.to_string()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            };
+            mock.push(QUEUE_PROCESSING, job_id);
+            Worker::handle_plan_execution(
+                prepared_job(job_id, plan, job_id),
+                JobClients::shared(client.clone()),
+                options.clone(),
+                Arc::new(JobHistory::new(10)),
+                Arc::clone(&redactor),
+                Arc::default(),
+                PendingPosts::default(),
+            )
+            .await;
+        }
+
+        let output = stdout.contents();
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2, "{output}");
+        assert_eq!(lines[0]["job_id"], "job-1");
+        assert_eq!(lines[0]["plan_id"], "plan-1");
+        assert_eq!(lines[0]["success"], true);
+        assert_eq!(lines[0]["task_results"][0]["stdout"], "hello\n");
+        assert_eq!(lines[1]["task_results"][0]["stdout"], "***\n");
+        assert!(!output.contains("tok_secret"));
+    }
+
+    #[tokio::test]
+    async fn test_idle_ping_sends_pings_at_interval() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut client = RespClient::connect(&mock.address).await.unwrap();
+        client.authenticate(SESSION_KEY).await.unwrap();
+
+        let pinger = tokio::spawn(idle_ping(client, Duration::from_millis(100)));
+        tokio::time::sleep(Duration::from_millis(450)).await;
+        pinger.abort();
+
+        // First ping is immediate, then one per interval
+        let pings = mock.count("PING");
+        assert!((4..=6).contains(&pings), "{pings} pings");
+        let state = mock.state.lock().unwrap();
+        assert!(state
+            .commands
+            .iter()
+            .filter(|c| c[0] == "PING")
+            .all(|c| c.len() == 1));
+    }
+
+    #[tokio::test]
+    async fn test_idle_ping_runs_on_its_own_connection() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let _worker = test_worker(&mock, &["--idle-ping-secs", "1"]).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // Job and heartbeat connections come first
+        assert_eq!(mock.connections_for("PING"), vec![3]);
+    }
+
+    #[tokio::test]
+    async fn test_job_needing_unregistered_tool_is_requeued() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut client = RespClient::connect(&mock.address).await.unwrap();
+        client.authenticate(SESSION_KEY).await.unwrap();
+
+        let task = |task_number, command: &str| Task {
+            task_number,
+            command: command.to_string(),
+            ..Default::default()
+        };
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![task(1, "echo"), task(2, "sort")],
+            ..Default::default()
+        };
+        let options = ExecutionOptions {
+            registered_tools: Some(HashSet::from(["echo".to_string()])),
+            ..Default::default()
+        };
+
+        mock.push(QUEUE_PROCESSING, "job-1");
+        let history = Arc::new(JobHistory::new(10));
+        Worker::handle_plan_execution(
+            prepared_job("job-1", plan, "job-1"),
+            JobClients::shared(client),
+            options,
+            history.clone(),
+            Arc::default(),
+            Arc::default(),
+            PendingPosts::default(),
+        )
+        .await;
+
+        assert_eq!(mock.list(QUEUE_READY), vec!["job-1".to_string()]);
+        assert!(mock.list(QUEUE_PROCESSING).is_empty());
+        assert_eq!(mock.get("job:job-1:status"), None);
+        assert!(history.recent().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_enforce_registered_tools() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "echo".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let run = |args: &'static [&'static str]| {
+            let plan = plan.clone();
+            async move {
+                let mock = MockAgq::start(Some(SESSION_KEY)).await;
+                let worker = test_worker(&mock, args).await;
+                mock.push(QUEUE_PROCESSING, "job-1");
+                Worker::handle_plan_execution(
+                    prepared_job("job-1", plan, "job-1"),
+                    worker.job_clients(),
+                    worker.config.execution_options(),
+                    Arc::clone(&worker.history),
+                    Arc::clone(&worker.redactor),
+                    Arc::clone(&worker.stats),
+                    PendingPosts::default(),
+                )
+                .await;
+                mock
+            }
+        };
+
+        // Advertising other tools alone doesn't stop the plan running
+        let mock = run(&["--tools", "agx-ocr"]).await;
+        assert_eq!(mock.get("job:job-1:status").as_deref(), Some("completed"));
+
+        let mock = run(&["--tools", "agx-ocr", "--enforce-registered-tools", "fail"]).await;
+        assert_eq!(mock.get("job:job-1:status").as_deref(), Some("error"));
+        let error = mock.get("job:job-1:stderr").unwrap();
+        assert!(error.contains("did not register: echo"), "{error}");
+        assert!(mock.list(QUEUE_PROCESSING).is_empty());
+        assert!(mock.list(QUEUE_READY).is_empty());
+
+        let mock = run(&["--tools", "agx-ocr", "--enforce-registered-tools"]).await;
+        assert_eq!(mock.get("job:job-1:status"), None);
+        assert_eq!(mock.list(QUEUE_READY), vec!["job-1".to_string()]);
+
+        let mock = run(&["--tools", "echo", "--enforce-registered-tools", "fail"]).await;
+        assert_eq!(mock.get("job:job-1:status").as_deref(), Some("completed"));
+    }
+
+    #[test]
+    fn test_unregistered_commands_skips_shell_and_duplicates() {
+        use crate::plan::Task;
+
+        let task = |task_number, command: &str, shell| Task {
+            task_number,
+            command: command.to_string(),
+            shell,
+            ..Default::default()
+        };
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![
+                task(1, "sort", false),
+                task(2, "echo hi | wc -l", true),
+                task(3, "uniq", false),
+                task(4, "sort", false),
+                task(5, "echo", false),
+            ],
+            ..Default::default()
+        };
+        let tools = HashSet::from(["echo".to_string()]);
+        assert_eq!(unregistered_commands(&plan, &tools), vec!["sort", "uniq"]);
+    }
+
+    #[tokio::test]
+    async fn test_successful_plan_enqueues_follow_up_job() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut client = RespClient::connect(&mock.address).await.unwrap();
+        client.authenticate(SESSION_KEY).await.unwrap();
+
+        let echo = |task_number, arg: &str| Task {
+            task_number,
+            command: "echo".to_string(),
+            args: vec![arg.to_string()],
+            ..Default::default()
+        };
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![echo(1, "/data/report.csv"), echo(2, "42")],
+            on_success_enqueue: Some(FollowUp {
+                plan_id: "plan-2".to_string(),
+                input: BTreeMap::from([("path".to_string(), 1), ("count".to_string(), 2)]),
+            }),
+            ..Default::default()
+        };
+
+        mock.push(QUEUE_PROCESSING, "job-1");
+        Worker::handle_plan_execution(
+            prepared_job("job-1", plan.clone(), "job-1"),
+            JobClients::shared(client.clone()),
+            ExecutionOptions::default(),
+            Arc::new(JobHistory::new(10)),
+            Arc::default(),
+            Arc::default(),
+            PendingPosts::default(),
+        )
+        .await;
+
+        let ready = mock.list(QUEUE_READY);
+        assert_eq!(ready.len(), 1);
+        let follow_up = Job::from_json(&mock.get(&format!("job:{}", ready[0])).unwrap()).unwrap();
+        assert_eq!(follow_up.plan_id, "plan-2");
+        assert_eq!(follow_up.status, "pending");
+        assert_eq!(follow_up.chain_depth, 1);
+        assert_eq!(
+            follow_up.input,
+            serde_json::json!({"path": "/data/report.csv", "count": "42"})
+        );
+        assert!(mock.list(QUEUE_PROCESSING).is_empty());
+
+        let manifest: JobManifest =
+            serde_json::from_str(&mock.get("job:job-1:manifest").unwrap()).unwrap();
+        assert_eq!(manifest.job_id, "job-1");
+        assert_eq!(
+            manifest
+                .tasks
+                .iter()
+                .map(|entry| (entry.task_number, entry.argv.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, vec!["echo".to_string(), "/data/report.csv".to_string()]),
+                (2, vec!["echo".to_string(), "42".to_string()]),
+            ]
+        );
+
+        // A failed plan does not chain
+        let failing = Plan {
+            tasks: vec![Task {
+                task_number: 1,
+                command: "false".to_string(),
+                ..Default::default()
+            }],
+            ..plan
+        };
+        Worker::handle_plan_execution(
+            prepared_job("job-2", failing, "job-2"),
+            JobClients::shared(client),
+            ExecutionOptions::default(),
+            Arc::new(JobHistory::new(10)),
+            Arc::default(),
+            Arc::default(),
+            PendingPosts::default(),
+        )
+        .await;
+        assert_eq!(mock.get("job:job-2:status").as_deref(), Some("failed"));
+        assert_eq!(mock.list(QUEUE_READY).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_follow_up_chain_stops_at_max_depth() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut client = RespClient::connect(&mock.address).await.unwrap();
+        client.authenticate(SESSION_KEY).await.unwrap();
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "true".to_string(),
+                ..Default::default()
+            }],
+            on_success_enqueue: Some(FollowUp {
+                plan_id: "plan-2".to_string(),
+                input: BTreeMap::new(),
+            }),
+            ..Default::default()
+        };
+
+        let run = |job_id: &'static str, chain_depth| {
+            let mut prepared = prepared_job(job_id, plan.clone(), job_id);
+            prepared.job.chain_depth = chain_depth;
+            mock.push(QUEUE_PROCESSING, job_id);
+            Worker::handle_plan_execution(
+                prepared,
+                JobClients::shared(client.clone()),
+                ExecutionOptions::default(),
+                Arc::new(JobHistory::new(10)),
+                Arc::default(),
+                Arc::default(),
+                PendingPosts::default(),
+            )
+        };
+
+        run("job-1", MAX_FOLLOW_UP_DEPTH - 1).await;
+        let ready = mock.list(QUEUE_READY);
+        assert_eq!(ready.len(), 1);
+        let follow_up = Job::from_json(&mock.get(&format!("job:{}", ready[0])).unwrap()).unwrap();
+        assert_eq!(follow_up.chain_depth, MAX_FOLLOW_UP_DEPTH);
+
+        // The last job in the chain completes but enqueues nothing
+        run("job-2", MAX_FOLLOW_UP_DEPTH).await;
+        assert_eq!(mock.get("job:job-2:status").as_deref(), Some("completed"));
+        assert_eq!(mock.list(QUEUE_READY).len(), 1);
+        assert!(mock.list(QUEUE_PROCESSING).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_posted_results_are_redacted() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(&mock, &["--redact-pattern", r"tok_[A-Za-z0-9]+"]).await;
+
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "sh".to_string(),
+                args: vec![
+                    "-c".to_string(),
+                    "echo 'api_key: tok_Zx81Qa user: alice'; echo 'bad tok_9f' >&2".to_string(),
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        mock.push(QUEUE_PROCESSING, "job-1");
+        Worker::handle_plan_execution(
+            prepared_job("job-1", plan, "job-1"),
+            worker.job_clients(),
+            ExecutionOptions::default(),
+            Arc::clone(&worker.history),
+            Arc::clone(&worker.redactor),
+            Arc::clone(&worker.stats),
+            PendingPosts::default(),
+        )
+        .await;
+
+        assert_eq!(
+            mock.get("job:job-1:stdout").as_deref(),
+            Some("api_key: *** user: alice\n")
+        );
+        assert_eq!(mock.get("job:job-1:stderr").as_deref(), Some("bad ***\n"));
+    }
+
+    #[tokio::test]
+    async fn test_manifest_records_worker_config() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(
+            &mock,
+            &[
+                "--manifest-config",
+                "--strict-plan-parsing",
+                "--kill-grace-secs",
+                "3",
+            ],
+        )
+        .await;
+
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "echo".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        mock.push(QUEUE_PROCESSING, "job-1");
+        Worker::handle_plan_execution(
+            prepared_job("job-1", plan, "job-1"),
+            worker.job_clients(),
+            worker.config.execution_options(),
+            Arc::clone(&worker.history),
+            Arc::clone(&worker.redactor),
+            Arc::clone(&worker.stats),
+            PendingPosts::default(),
+        )
+        .await;
+
+        let raw = mock.get("job:job-1:manifest").unwrap();
+        assert!(!raw.contains(SESSION_KEY), "{raw}");
+        let manifest: JobManifest = serde_json::from_str(&raw).unwrap();
+        let config = manifest.config.unwrap();
+        assert!(config.strict_plan_parsing);
+        assert_eq!(config.kill_grace_secs, 3);
+        assert_eq!(config.agw_version, env!("CARGO_PKG_VERSION"));
+    }
+
+    #[tokio::test]
+    async fn test_webhook_notified_after_results_posted() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+        use crate::webhook::tests::webhook_server;
+        use crate::webhook::{JobNotification, Webhook};
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(&mock, &[]).await;
+        let (address, bodies) = webhook_server(&[200]).await;
+
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "echo".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut options = worker.config.execution_options();
+        options.webhook = Some(Webhook::parse(&format!("http://{address}/done")).unwrap());
+        mock.push(QUEUE_PROCESSING, "job-1");
+        Worker::handle_plan_execution(
+            prepared_job("job-1", plan, "job-1"),
+            worker.job_clients(),
+            options,
+            Arc::clone(&worker.history),
+            Arc::clone(&worker.redactor),
+            Arc::clone(&worker.stats),
+            PendingPosts::default(),
+        )
+        .await;
+        assert_eq!(mock.get("job:job-1:status").as_deref(), Some("completed"));
+
+        // Delivery runs on its own task
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while bodies.lock().unwrap().is_empty() && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 1);
+        let notification: JobNotification = serde_json::from_str(&bodies[0]).unwrap();
+        assert_eq!(notification.job_id, "job-1");
+        assert_eq!(notification.status, "completed");
+        assert!(notification.duration_ms < 5_000, "{notification:?}");
+    }
+
+    #[tokio::test]
+    async fn test_spawn_failure_posts_error_status_distinct_from_failed() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(&mock, &[]).await;
+
+        for (job_id, command) in [
+            ("job-1", "agw-test-command-that-does-not-exist"),
+            ("job-2", "false"),
+        ] {
+            let plan = Plan {
+                plan_id: "plan-1".to_string(),
+                tasks: vec![Task {
+                    task_number: 1,
+                    command: command.to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            };
+            mock.push(QUEUE_PROCESSING, job_id);
+            Worker::handle_plan_execution(
+                prepared_job(job_id, plan, job_id),
+                worker.job_clients(),
+                ExecutionOptions::default(),
+                Arc::clone(&worker.history),
+                Arc::clone(&worker.redactor),
+                Arc::clone(&worker.stats),
+                PendingPosts::default(),
+            )
+            .await;
+        }
+
+        // Could not start: infrastructure problem
+        assert_eq!(mock.get("job:job-1:status").as_deref(), Some("error"));
+        assert!(mock
+            .get("job:job-1:stderr")
+            .unwrap()
+            .starts_with("Execution error: "));
+        // Ran and exited non-zero: task logic problem
+        assert_eq!(mock.get("job:job-2:status").as_deref(), Some("failed"));
+        assert!(mock.list(QUEUE_PROCESSING).is_empty());
+
+        let statuses: Vec<_> = worker
+            .history
+            .recent()
+            .into_iter()
+            .map(|job| (job.job_id, job.status))
+            .collect();
+        assert!(statuses.contains(&("job-1".to_string(), "error".to_string())));
+        assert!(statuses.contains(&("job-2".to_string(), "failed".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_warning_lines_posted_separately_from_stderr() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(
+            &mock,
+            &[
+                "--warning-pattern",
+                "^(WARN|DeprecationWarning)",
+                "--redact-pattern",
+                r"tok_[A-Za-z0-9]+",
+            ],
+        )
+        .await;
+
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "sh".to_string(),
+                args: vec![
+                    "-c".to_string(),
+                    "echo 'WARN: cache miss for tok_9f' >&2; echo 'real problem' >&2; \
+                     echo 'DeprecationWarning: old flag' >&2; echo done"
+                        .to_string(),
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        mock.push(QUEUE_PROCESSING, "job-1");
+        Worker::handle_plan_execution(
+            prepared_job("job-1", plan, "job-1"),
+            worker.job_clients(),
+            worker.config.execution_options(),
+            Arc::clone(&worker.history),
+            Arc::clone(&worker.redactor),
+            Arc::clone(&worker.stats),
+            PendingPosts::default(),
+        )
+        .await;
+
+        assert_eq!(mock.get("job:job-1:status").as_deref(), Some("completed"));
+        assert_eq!(
+            mock.get("job:job-1:warnings").as_deref(),
+            Some("WARN: cache miss for ***\nDeprecationWarning: old flag\n")
+        );
+        assert_eq!(
+            mock.get("job:job-1:stderr").as_deref(),
+            Some("real problem\n")
+        );
+        assert_eq!(mock.get("job:job-1:stdout").as_deref(), Some("done\n"));
+    }
+
+    #[tokio::test]
+    async fn test_request_id_propagated_into_logs_and_results() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+        use crate::test_logs::capture_logs;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(&mock, &[]).await;
+
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "echo".to_string(),
+                args: vec!["hi".to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut prepared = prepared_job("job-1", plan, "job-1");
+        prepared.job.request_id = Some("req-42".to_string());
+
+        let (_guard, logs) = capture_logs();
+        let span = job_span(&prepared.job);
+
+        mock.push(QUEUE_PROCESSING, "job-1");
+        Worker::handle_plan_execution(
+            prepared,
+            worker.job_clients(),
+            ExecutionOptions::default(),
+            Arc::clone(&worker.history),
+            Arc::clone(&worker.redactor),
+            Arc::clone(&worker.stats),
+            PendingPosts::default(),
+        )
+        .instrument(span)
+        .await;
+
+        assert_eq!(mock.get("job:job-1:request_id").as_deref(), Some("req-42"));
+        assert_eq!(mock.get("job:job-1:status").as_deref(), Some("completed"));
+
+        let logs = logs.contents();
+        assert!(
+            logs.lines()
+                .any(|line| line.contains("job{job_id=job-1 request_id=req-42}")
+                    && line.contains("Task 1 completed")),
+            "{logs}"
+        );
+    }
+
+    /// Start a long-running job on `worker` the way the main loop does
+    async fn start_sleeping_job(
+        worker: &mut Worker,
+        mock: &crate::mock_agq::MockAgq,
+    ) -> RunningJob {
+        use crate::plan::Task;
+
+        mock.push(QUEUE_READY, "job-raw-1");
+        let job_id_raw = worker
+            .client
+            .brpoplpush(QUEUE_READY, QUEUE_PROCESSING, 1)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "sleep".to_string(),
+                args: vec!["30".to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let handle = tokio::spawn(Worker::handle_plan_execution(
+            prepared_job("job-1", plan, &job_id_raw),
+            worker.job_clients(),
+            ExecutionOptions::default(),
+            Arc::clone(&worker.history),
+            Arc::clone(&worker.redactor),
+            Arc::clone(&worker.stats),
+            PendingPosts::default(),
+        ));
+
+        RunningJob {
+            handle,
+            job_id: "job-1".to_string(),
+            job_id_raw,
+            priority: JobPriority::Normal,
+            in_processing: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forced_shutdown_requeues_job_to_ready() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut worker = test_worker(&mock, &["--shutdown-timeout", "1"]).await;
+        let running = start_sleeping_job(&mut worker, &mock).await;
+
+        worker.finish_running_job(running).await;
+
+        assert_eq!(mock.list(QUEUE_READY), vec!["job-raw-1"]);
+        assert!(mock.list(QUEUE_PROCESSING).is_empty());
+        // The aborted job never posted results
+        assert_eq!(mock.get("job:job-1:status"), None);
+    }
+
+    #[tokio::test]
+    async fn test_forced_shutdown_awaits_result_post_in_flight() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut worker = test_worker(&mock, &["--shutdown-timeout", "1"]).await;
+        mock.push(QUEUE_PROCESSING, "job-raw-1");
+
+        // A job whose execution finished and whose post outlives the shutdown timeout
+        let pending_posts = worker.pending_posts.clone();
+        let mut client = worker.client.clone();
+        let handle = tokio::spawn(async move {
+            let (posted, finished) = tokio::sync::oneshot::channel();
+            pending_posts.spawn(async move {
+                tokio::time::sleep(Duration::from_millis(1500)).await;
+                client
+                    .post_job_result("job-1", "done\n", "", "completed")
+                    .await
+                    .unwrap();
+                let _ = posted.send(());
+            });
+            let _ = finished.await;
+        });
+        let running = RunningJob {
+            handle,
+            job_id: "job-1".to_string(),
+            job_id_raw: "job-raw-1".to_string(),
+            priority: JobPriority::Normal,
+            in_processing: true,
+        };
+
+        worker.finish_running_job(running).await;
+
+        // The post landed despite the abort, so the job is not run again
+        assert_eq!(mock.get("job:job-1:status").as_deref(), Some("completed"));
+        assert_eq!(mock.get("job:job-1:stdout").as_deref(), Some("done\n"));
+        assert!(mock.list(QUEUE_READY).is_empty());
+        assert!(mock.list(QUEUE_PROCESSING).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_drain_awaits_every_pending_post() {
+        let pending_posts = PendingPosts::default();
+        let landed = Arc::new(AtomicU64::new(0));
+        for delay in [50, 150] {
+            let landed = Arc::clone(&landed);
+            pending_posts.spawn(async move {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                landed.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+
+        pending_posts.drain().await;
+
+        assert_eq!(landed.load(Ordering::SeqCst), 2);
+        // Nothing is left to wait for afterwards
+        pending_posts.drain().await;
+    }
+
+    #[tokio::test]
+    async fn test_forced_shutdown_does_not_requeue_finished_job() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut worker = test_worker(&mock, &[]).await;
+        mock.push(QUEUE_PROCESSING, "job-raw-1");
+        mock.set("job:job-1:status", "completed");
+
+        let requeued = requeue_abandoned_job(
+            &mut worker.client,
+            &mut worker.result_client,
+            "job-1",
+            "job-raw-1",
+            QUEUE_READY,
+        )
+        .await
+        .unwrap();
+
+        assert!(!requeued);
+        assert!(mock.list(QUEUE_READY).is_empty());
+        assert!(mock.list(QUEUE_PROCESSING).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_paused_while_processing_backlog_too_large() {
+        use crate::mock_agq::{MockAgq, Reply};
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(&mock, &["--max-processing-backlog", "2"]).await;
+        mock.push(QUEUE_READY, "job-raw-1");
+        mock.script("LLEN", Reply::Integer(3));
+
+        let fetch = tokio::time::timeout(Duration::from_millis(300), fetch_job(&worker)).await;
+
+        assert!(fetch.is_err(), "fetch should wait while paused");
+        assert!(worker.fetcher.lock().await.backlog_paused);
+        assert_eq!(mock.count("BRPOPLPUSH"), 0);
+        assert_eq!(mock.list(QUEUE_READY), vec!["job-raw-1"]);
+
+        // Backlog drained: fetching resumes
+        mock.script("LLEN", Reply::Integer(2));
+        mock.script("BRPOPLPUSH", Reply::Nil);
+        let fetched = fetch_job(&worker).await.unwrap();
+        assert!(fetched.is_none());
+        assert!(!worker.fetcher.lock().await.backlog_paused);
+        assert_eq!(mock.count("BRPOPLPUSH"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_paused_while_dependency_unhealthy() {
+        use crate::mock_agq::{MockAgq, Reply};
+        use tokio::net::TcpListener;
+
+        // Reserve a port, then close it so the dependency starts out down
+        let address = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let check = format!("tcp://{address}");
+        let worker = test_worker(&mock, &["--dependency-check", &check]).await;
+        mock.push(QUEUE_READY, "job-raw-1");
+
+        let fetch = tokio::time::timeout(Duration::from_millis(300), fetch_job(&worker)).await;
+
+        assert!(
+            fetch.is_err(),
+            "fetch should wait while the dependency is down"
+        );
+        assert!(worker.fetcher.lock().await.dependency_paused);
+        assert_eq!(mock.count("BRPOPLPUSH"), 0);
+        assert_eq!(mock.list(QUEUE_READY), vec!["job-raw-1"]);
+
+        // Dependency back up: fetching resumes
+        let _listener = TcpListener::bind(address).await.unwrap();
+        mock.script("BRPOPLPUSH", Reply::Nil);
+        let fetched = fetch_job(&worker).await.unwrap();
+        assert!(fetched.is_none());
+        assert!(!worker.fetcher.lock().await.dependency_paused);
+        assert_eq!(mock.count("BRPOPLPUSH"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_plan_fails_job_without_stopping_worker() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(&mock, &[]).await;
+
+        let echo_plan = |plan_id: &str, task_count: u32| Plan {
+            plan_id: plan_id.to_string(),
+            tasks: (1..=task_count)
+                .map(|task_number| Task {
+                    task_number,
+                    command: "echo".to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        for (job_id, plan) in [
+            ("job-1", echo_plan("big", 101)),
+            ("job-2", echo_plan("small", 1)),
+        ] {
+            mock.set(&format!("plan:{}", plan.plan_id), &plan.to_json().unwrap());
+            mock.set(
+                &format!("job:{job_id}"),
+                &serde_json::json!({"job_id": job_id, "plan_id": plan.plan_id}).to_string(),
+            );
+        }
+        mock.push(QUEUE_READY, "job-1");
+        mock.push(QUEUE_READY, "job-2");
+
+        let fetched = fetch_job(&worker).await.unwrap();
+        assert!(fetched.is_none());
+        assert_eq!(mock.get("job:job-1:status").as_deref(), Some("failed"));
+        assert!(mock
+            .get("job:job-1:stderr")
+            .unwrap()
+            .contains("exceeds maximum of 100 tasks"));
+        assert!(mock.list(QUEUE_PROCESSING).is_empty());
+
+        // The worker carries on with the next job
+        let prepared = fetch_job(&worker).await.unwrap().unwrap();
+        assert_eq!(prepared.job.job_id, "job-2");
+    }
+
+    #[tokio::test]
+    async fn test_oversized_substitution_fails_job_before_execution() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(&mock, &["--max-substituted-total-bytes", "1024"]).await;
+        let plan = Plan {
+            plan_id: "expand".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "echo".to_string(),
+                args: vec!["{{input.blob}}".to_string(); 4],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        mock.set("plan:expand", &plan.to_json().unwrap());
+        mock.set(
+            "job:job-1",
+            &serde_json::json!({
+                "job_id": "job-1",
+                "plan_id": "expand",
+                "input": {"blob": "x".repeat(512)},
+            })
+            .to_string(),
+        );
+        mock.push(QUEUE_READY, "job-1");
+
+        let fetched = fetch_job(&worker).await.unwrap();
+        assert!(fetched.is_none());
+        assert_eq!(mock.get("job:job-1:status").as_deref(), Some("failed"));
+        let stderr = mock.get("job:job-1:stderr").unwrap();
+        assert!(
+            stderr.contains("2048 bytes, exceeding the maximum of 1024"),
+            "{stderr}"
+        );
+        assert!(mock.get("job:job-1:stdout").unwrap_or_default().is_empty());
+        assert!(mock.list(QUEUE_PROCESSING).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_require_declared_inputs() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+        use crate::test_logs::capture_logs;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(&mock, &["--require-declared-inputs"]).await;
+        let plan = Plan {
+            plan_id: "copy".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "echo".to_string(),
+                args: vec!["{{input.path}}".to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        mock.set("plan:copy", &plan.to_json().unwrap());
+        mock.set(
+            "job:job-1",
+            r#"{"job_id":"job-1","plan_id":"copy","input":{"path":"/tmp","verbose":true}}"#,
+        );
+        mock.set("job:job-2", r#"{"job_id":"job-2","plan_id":"copy"}"#);
+
+        let (_guard, logs) = capture_logs();
+
+        // Extra input fields only warn
+        mock.push(QUEUE_READY, "job-1");
+        let prepared = fetch_job(&worker).await.unwrap().unwrap();
+        assert_eq!(prepared.plan.tasks[0].args, ["/tmp"]);
+        let output = logs.contents();
+        assert!(
+            output.contains("Job job-1 input has fields plan copy does not use: verbose"),
+            "{output}"
+        );
+
+        // Missing input needed by the plan fails the job up front
+        mock.push(QUEUE_READY, "job-2");
+        assert!(fetch_job(&worker).await.unwrap().is_none());
+        assert_eq!(mock.get("job:job-2:status").as_deref(), Some("failed"));
+        let stderr = mock.get("job:job-2:stderr").unwrap();
+        assert!(
+            stderr.contains("Plan uses input variables (path) but the job has no input"),
+            "{stderr}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejected_jobs_post_failure_reason() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(&mock, &[]).await;
+        mock.set(
+            "plan:unsafe",
+            r#"{"plan_id":"unsafe","tasks":[{"task_number":1,"command":"rm;ls"}]}"#,
+        );
+        mock.set(
+            "plan:copy",
+            r#"{"plan_id":"copy","tasks":[{"task_number":1,"command":"ls","args":["{{input.path}}"]}]}"#,
+        );
+        for (job_id, plan_id) in [("job-1", "missing"), ("job-2", "unsafe"), ("job-3", "copy")] {
+            mock.set(
+                &format!("job:{job_id}"),
+                &serde_json::json!({"job_id": job_id, "plan_id": plan_id}).to_string(),
+            );
+            mock.push(QUEUE_READY, job_id);
+        }
+
+        for (job_id, reason) in [
+            ("job-1", "plan_not_found"),
+            ("job-2", "validation_error"),
+            ("job-3", "substitution_error"),
+        ] {
+            assert!(fetch_job(&worker).await.unwrap().is_none());
+            assert_eq!(
+                mock.get(&format!("job:{job_id}:status")).as_deref(),
+                Some("failed")
+            );
+            assert_eq!(
+                mock.get(&format!("job:{job_id}:failure_reason")).as_deref(),
+                Some(reason),
+                "{job_id}"
+            );
+        }
+        assert!(mock.list(QUEUE_PROCESSING).is_empty());
+        assert!(mock
+            .get("job:job-1:stderr")
+            .unwrap()
+            .contains("Plan 'missing' does not exist"));
+
+        let no_exec = test_worker(&mock, &["--no-exec"]).await;
+        mock.set(
+            "job:job-4",
+            r#"{"job_id":"job-4","plan_id":"copy","input":{"path":"/tmp"}}"#,
+        );
+        mock.push(QUEUE_READY, "job-4");
+        assert!(fetch_job(&no_exec).await.unwrap().is_none());
+        assert_eq!(
+            mock.get("job:job-4:failure_reason").as_deref(),
+            Some("cancelled")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_executed_jobs_post_failure_reason() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(&mock, &[]).await;
+        for (job_id, command) in [("job-1", "true"), ("job-2", "false")] {
+            let plan = Plan {
+                plan_id: "plan-1".to_string(),
+                tasks: vec![Task {
+                    task_number: 1,
+                    command: command.to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            };
+            Worker::handle_plan_execution(
+                prepared_job(job_id, plan, job_id),
+                worker.job_clients(),
+                worker.config.execution_options(),
+                Arc::clone(&worker.history),
+                Arc::clone(&worker.redactor),
+                Arc::clone(&worker.stats),
+                PendingPosts::default(),
+            )
+            .await;
+        }
+
+        assert_eq!(mock.get("job:job-1:status").as_deref(), Some("completed"));
+        assert_eq!(mock.get("job:job-1:failure_reason"), None);
+        assert_eq!(mock.get("job:job-2:status").as_deref(), Some("failed"));
+        assert_eq!(
+            mock.get("job:job-2:failure_reason").as_deref(),
+            Some("non_zero_exit")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_strict_plan_parsing_fails_job_with_unknown_field() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        mock.set(
+            "plan:plan-1",
+            r#"{"plan_id":"plan-1","tasks":[{"task_number":1,"command":"echo","timeoutsecs":5}]}"#,
+        );
+        mock.set("job:job-1", r#"{"job_id":"job-1","plan_id":"plan-1"}"#);
+
+        // Lenient (default): the misspelled field is ignored
+        let lenient = test_worker(&mock, &[]).await;
+        mock.push(QUEUE_READY, "job-1");
+        let prepared = fetch_job(&lenient).await.unwrap().unwrap();
+        assert_eq!(prepared.plan.tasks[0].timeout_secs, None);
+
+        let strict = test_worker(&mock, &["--strict-plan-parsing"]).await;
+        mock.push(QUEUE_READY, "job-1");
+        assert!(fetch_job(&strict).await.unwrap().is_none());
+        assert_eq!(mock.get("job:job-1:status").as_deref(), Some("failed"));
+        let stderr = mock.get("job:job-1:stderr").unwrap();
+        assert!(stderr.contains("unknown field `timeoutsecs`"), "{stderr}");
+    }
+
+    #[tokio::test]
+    async fn test_malformed_job_is_discarded_from_processing() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(&mock, &[]).await;
+        mock.set("job:job-1", "not json");
+        mock.push(QUEUE_READY, "job-1");
+
+        let fetched = fetch_job(&worker).await.unwrap();
+        assert!(fetched.is_none());
+        assert!(mock.list(QUEUE_PROCESSING).is_empty());
+        assert_eq!(mock.get("job:job-1:status"), None);
+    }
+
+    #[tokio::test]
+    async fn test_empty_job_id_is_discarded_without_fetching() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(&mock, &[]).await;
+        mock.push(QUEUE_READY, "");
+        mock.push(QUEUE_READY, " \t");
+
+        for _ in 0..2 {
+            let fetched = fetch_job(&worker).await.unwrap();
+            assert!(fetched.is_none());
+        }
+        assert!(mock.list(QUEUE_READY).is_empty());
+        assert!(mock.list(QUEUE_PROCESSING).is_empty());
+        assert_eq!(mock.count("GET"), 0);
+
+        // The worker goes on to the next real job
+        mock.set("job:job-1", r#"{"job_id":"job-1","plan_id":"plan-1"}"#);
+        mock.set(
+            "plan:plan-1",
+            r#"{"plan_id":"plan-1","tasks":[{"task_number":1,"command":"true"}]}"#,
+        );
+        mock.push(QUEUE_READY, "job-1");
+        let fetched = fetch_job(&worker).await.unwrap();
+        assert_eq!(fetched.unwrap().job.job_id, "job-1");
+    }
+
+    #[tokio::test]
+    async fn test_cluster_mode_uses_hash_tagged_queues() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(&mock, &["--cluster"]).await;
+        mock.set("job:job-1", r#"{"job_id":"job-1","plan_id":"plan-1"}"#);
+        mock.set(
+            "plan:plan-1",
+            r#"{"plan_id":"plan-1","tasks":[{"task_number":1,"command":"true"}]}"#,
+        );
+        mock.push(QUEUE_READY, "job-stale");
+        mock.push(QueueKeys::CLUSTER.ready, "job-1");
+
+        let fetched = fetch_job(&worker).await.unwrap();
+        assert_eq!(fetched.unwrap().job.job_id, "job-1");
+        assert_eq!(mock.list(QueueKeys::CLUSTER.processing), ["job-1"]);
+        assert_eq!(mock.list(QUEUE_READY), ["job-stale"]);
+        assert!(mock.list(QUEUE_PROCESSING).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_job_without_metadata_is_discarded_from_processing() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut worker = test_worker(&mock, &["--max-reconnect-attempts", "1"]).await;
+        mock.push(QUEUE_READY, "job-expired");
+
+        let fetched = fetch_job(&worker).await.unwrap();
+        assert!(fetched.is_none());
+        assert!(mock.list(QUEUE_PROCESSING).is_empty());
+        assert_eq!(worker.stats.jobs_failed.load(Ordering::Relaxed), 1);
+
+        // In the run loop it neither strands the job nor counts as a lost connection
+        mock.push(QUEUE_READY, "job-expired");
+        mock.set("job:job-1", r#"{"job_id":"job-1","plan_id":"plan-1"}"#);
+        mock.set(
+            "plan:plan-1",
+            r#"{"plan_id":"plan-1","tasks":[{"task_number":1,"command":"true"}]}"#,
+        );
+        mock.push(QUEUE_READY, "job-1");
+        assert!(
+            tokio::time::timeout(Duration::from_millis(1500), worker.run_loop())
+                .await
+                .is_err(),
+            "worker should still be running"
+        );
+        assert!(mock.list(QUEUE_READY).is_empty());
+        assert!(mock.list(QUEUE_PROCESSING).is_empty());
+        assert_eq!(worker.reconnect.failures, 0);
+        assert_eq!(mock.get("job:job-1:status").as_deref(), Some("completed"));
+    }
+
+    #[tokio::test]
+    async fn test_results_posted_to_result_address() {
+        use crate::mock_agq::MockAgq;
+
+        let source = MockAgq::start(Some(SESSION_KEY)).await;
+        let results = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(&source, &["--result-address", results.address.as_str()]).await;
+        assert_eq!(results.count("AUTH"), 1);
+
+        source.set("job:job-1", r#"{"job_id":"job-1","plan_id":"plan-1"}"#);
+        source.set(
+            "plan:plan-1",
+            r#"{"plan_id":"plan-1","tasks":[{"task_number":1,"command":"echo","args":["hi"]}]}"#,
+        );
+        source.push(QUEUE_READY, "job-1");
+        let prepared = fetch_job(&worker).await.unwrap().unwrap();
+        Worker::handle_plan_execution(
+            prepared,
+            worker.job_clients(),
+            ExecutionOptions::default(),
+            Arc::clone(&worker.history),
+            Arc::clone(&worker.redactor),
+            Arc::clone(&worker.stats),
+            PendingPosts::default(),
+        )
+        .await;
+
+        assert_eq!(results.get("job:job-1:stdout").as_deref(), Some("hi\n"));
+        assert_eq!(
+            results.get("job:job-1:status").as_deref(),
+            Some("completed")
+        );
+        assert_eq!(source.get("job:job-1:status"), None);
+        // Queue bookkeeping stays on the source
+        assert!(source.list(QUEUE_PROCESSING).is_empty());
+        assert_eq!(results.count("BRPOPLPUSH") + results.count("LREM"), 0);
+
+        // A job failed before execution is reported to the result endpoint too
+        source.set(
+            "job:job-2",
+            r#"{"job_id":"job-2","plan_id":"missing-tasks"}"#,
+        );
+        source.set(
+            "plan:missing-tasks",
+            r#"{"plan_id":"missing-tasks","tasks":[]}"#,
+        );
+        source.push(QUEUE_READY, "job-2");
+        assert!(fetch_job(&worker).await.unwrap().is_none());
+        assert_eq!(results.get("job:job-2:status").as_deref(), Some("failed"));
+        assert_eq!(source.get("job:job-2:status"), None);
+        assert!(source.list(QUEUE_PROCESSING).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_report_counts_jobs_and_tasks() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(&mock, &[]).await;
+
+        let task = |task_number: u32, command: &str| Task {
+            task_number,
+            command: command.to_string(),
+            ..Default::default()
+        };
+        let plan = |tasks: Vec<Task>| Plan {
+            plan_id: "plan-1".to_string(),
+            tasks,
+            ..Default::default()
+        };
+        let plans = [
+            plan(vec![task(1, "true")]),
+            plan(vec![task(1, "true"), task(2, "true")]),
+            // Halts after the failed first task
+            plan(vec![task(1, "false"), task(2, "true")]),
+        ];
+        for (n, plan) in plans.into_iter().enumerate() {
+            let job_id = format!("job-{n}");
+            Worker::handle_plan_execution(
+                prepared_job(&job_id, plan, &job_id),
+                worker.job_clients(),
+                ExecutionOptions::default(),
+                Arc::clone(&worker.history),
+                Arc::clone(&worker.redactor),
+                Arc::clone(&worker.stats),
+                PendingPosts::default(),
+            )
+            .await;
+        }
+
+        // Rejected before any task runs
+        mock.set(
+            "job:job-9",
+            r#"{"job_id":"job-9","plan_id":"plan-1","input_ref":"inputs:missing"}"#,
+        );
+        mock.push(QUEUE_READY, "job-9");
+        assert!(fetch_job(&worker).await.unwrap().is_none());
+
+        let report = worker.shutdown_report(ShutdownReason::Signal("SIGTERM"));
+        assert_eq!(report.reason, ShutdownReason::Signal("SIGTERM"));
+        assert_eq!(report.jobs_processed, 4);
+        assert_eq!(report.jobs_succeeded, 2);
+        assert_eq!(report.jobs_failed, 2);
+        assert_eq!(report.tasks_executed, 4);
+        assert!(report.uptime <= worker.started.elapsed());
+    }
+
+    #[tokio::test]
+    async fn test_honor_priority_fetches_high_before_low() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(&mock, &["--honor-priority"]).await;
+
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "echo".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        mock.set("plan:plan-1", &plan.to_json().unwrap());
+        // The low priority job was queued first
+        for (job_id, priority) in [
+            ("job-low", "low"),
+            ("job-normal", "normal"),
+            ("job-high", "high"),
+        ] {
+            mock.set(
+                &format!("job:{job_id}"),
+                &serde_json::json!({"job_id": job_id, "plan_id": "plan-1", "priority": priority})
+                    .to_string(),
+            );
+            mock.push(
+                QueueKeys::STANDALONE.ready_for(priority.parse().unwrap()),
+                job_id,
+            );
+        }
+
+        let mut fetched = Vec::new();
+        for _ in 0..3 {
+            let prepared = fetch_job(&worker).await.unwrap().unwrap();
+            fetched.push(prepared.job.job_id);
+        }
+        assert_eq!(fetched, ["job-high", "job-normal", "job-low"]);
+        assert_eq!(
+            mock.list(QUEUE_PROCESSING),
+            vec!["job-low", "job-normal", "job-high"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forced_shutdown_requeues_to_priority_queue() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let mut worker = test_worker(&mock, &[]).await;
+        mock.push(QUEUE_PROCESSING, "job-raw-1");
+
+        let ready = QueueKeys::STANDALONE.ready_for(JobPriority::High);
+        assert!(requeue_abandoned_job(
+            &mut worker.client,
+            &mut worker.result_client,
+            "job-1",
+            "job-raw-1",
+            ready,
+        )
+        .await
+        .unwrap());
+        assert_eq!(mock.list(QUEUE_READY_HIGH), vec!["job-raw-1"]);
+        assert!(mock.list(QUEUE_READY).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fair_scheduling_fetches_starved_plan_first() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(&mock, &["--fair-schedule-window", "10"]).await;
+
+        for plan_id in ["busy", "quiet"] {
+            let plan = Plan {
+                plan_id: plan_id.to_string(),
+                tasks: vec![Task {
+                    task_number: 1,
+                    command: "echo".to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            };
+            mock.set(&format!("plan:{plan_id}"), &plan.to_json().unwrap());
+        }
+        // Oldest first: two busy jobs queued ahead of a quiet one
+        for (job_id, plan_id) in [("job-1", "busy"), ("job-2", "busy"), ("job-3", "quiet")] {
+            mock.set(
+                &format!("job:{job_id}"),
+                &serde_json::json!({"job_id": job_id, "plan_id": plan_id}).to_string(),
+            );
+            mock.push(QUEUE_READY, job_id);
+        }
+
+        // The busy plan dominated recent fetches
+        for _ in 0..3 {
+            worker
+                .fetcher
+                .lock()
+                .await
+                .scheduler
+                .as_mut()
+                .unwrap()
+                .record("busy");
+        }
+
+        let prepared = fetch_job(&worker).await.unwrap().unwrap();
+        assert_eq!(prepared.job.job_id, "job-3");
+        assert_eq!(mock.list(QUEUE_PROCESSING), vec!["job-3"]);
+        assert_eq!(mock.list(QUEUE_READY), vec!["job-2", "job-1"]);
+        assert_eq!(
+            worker
+                .fetcher
+                .lock()
+                .await
+                .scheduler
+                .as_ref()
+                .unwrap()
+                .recent_count("quiet"),
+            1
+        );
+
+        // Between equally served plans, queue order wins
+        let prepared = fetch_job(&worker).await.unwrap().unwrap();
+        assert_eq!(prepared.job.job_id, "job-1");
+    }
+
+    /// Store a one-task plan without a timeout and queue `jobs` for it
+    fn queue_untimed_plan_jobs(mock: &crate::mock_agq::MockAgq, jobs: &[&str]) {
+        use crate::plan::Task;
+
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "echo".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        mock.set("plan:plan-1", &plan.to_json().unwrap());
+        for job_id in jobs {
+            mock.set(
+                &format!("job:{job_id}"),
+                &serde_json::json!({"job_id": job_id, "plan_id": "plan-1"}).to_string(),
+            );
+            mock.push(QUEUE_READY, job_id);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_default_task_timeout_applied_to_untimed_tasks() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        queue_untimed_plan_jobs(&mock, &["job-1"]);
+        let worker = test_worker(&mock, &["--default-task-timeout-secs", "600"]).await;
+
+        let prepared = fetch_job(&worker).await.unwrap().unwrap();
+        assert_eq!(prepared.plan.tasks[0].timeout_secs, Some(600));
+    }
+
+    #[tokio::test]
+    async fn test_require_timeouts_fails_untimed_plan() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        queue_untimed_plan_jobs(&mock, &["job-1", "job-2"]);
+
+        // Without either option the task stays unbounded
+        let worker = test_worker(&mock, &[]).await;
+        let prepared = fetch_job(&worker).await.unwrap().unwrap();
+        assert_eq!(prepared.plan.tasks[0].timeout_secs, None);
+
+        let worker = test_worker(&mock, &["--require-timeouts"]).await;
+        assert!(fetch_job(&worker).await.unwrap().is_none());
+        assert_eq!(mock.get("job:job-2:status").as_deref(), Some("failed"));
+        let stderr = mock.get("job:job-2:stderr").unwrap();
+        assert!(stderr.contains("Task 1 has no timeout_secs"), "{stderr}");
+    }
+
+    #[tokio::test]
+    async fn test_at_most_once_uses_brpop_without_processing_queue() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(&mock, &["--queue-reliability", "at-most-once"]).await;
+        queue_untimed_plan_jobs(&mock, &["job-1"]);
+        mock.set("job:job-2", "not json");
+        mock.push(QUEUE_READY, "job-2");
+
+        let prepared = fetch_job(&worker).await.unwrap().unwrap();
+        assert_eq!(prepared.job.job_id, "job-1");
+        assert!(!prepared.in_processing);
+        assert_eq!(mock.count("BRPOP"), 1);
+        assert_eq!(mock.count("BRPOPLPUSH"), 0);
+        assert!(mock.list(QUEUE_PROCESSING).is_empty());
+
+        Worker::handle_plan_execution(
+            prepared,
+            worker.job_clients(),
+            ExecutionOptions::default(),
+            Arc::clone(&worker.history),
+            Arc::clone(&worker.redactor),
+            Arc::clone(&worker.stats),
+            PendingPosts::default(),
+        )
+        .await;
+        assert_eq!(mock.get("job:job-1:status").as_deref(), Some("completed"));
+
+        // Discarded jobs skip the cleanup too
+        assert!(fetch_job(&worker).await.unwrap().is_none());
+        assert_eq!(mock.count("LREM"), 0);
+        assert!(mock.list(QUEUE_READY).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_at_most_once_honors_priority_order() {
+        use crate::mock_agq::MockAgq;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(
+            &mock,
+            &["--queue-reliability", "at-most-once", "--honor-priority"],
+        )
+        .await;
+        queue_untimed_plan_jobs(&mock, &["job-normal"]);
+        mock.set(
+            "job:job-high",
+            &serde_json::json!({"job_id": "job-high", "plan_id": "plan-1", "priority": "high"})
+                .to_string(),
+        );
+        mock.push(QUEUE_READY_HIGH, "job-high");
+
+        let prepared = fetch_job(&worker).await.unwrap().unwrap();
+        assert_eq!(prepared.job.job_id, "job-high");
+        let prepared = fetch_job(&worker).await.unwrap().unwrap();
+        assert_eq!(prepared.job.job_id, "job-normal");
+        assert_eq!(mock.count("BRPOP"), 2);
+        assert!(mock.list(QUEUE_PROCESSING).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_no_exec_fails_valid_jobs_without_spawning() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(&mock, &["--no-exec"]).await;
+
+        let marker = std::env::temp_dir().join(format!("agw-no-exec-{}", Uuid::new_v4()));
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "touch".to_string(),
+                args: vec![marker.to_string_lossy().into_owned()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        mock.set("plan:plan-1", &plan.to_json().unwrap());
+        mock.set("plan:bad", r#"{"plan_id":"bad","tasks":[]}"#);
+        for (job_id, plan_id) in [("job-1", "plan-1"), ("job-2", "bad")] {
+            mock.set(
+                &format!("job:{job_id}"),
+                &serde_json::json!({"job_id": job_id, "plan_id": plan_id}).to_string(),
+            );
+            mock.push(QUEUE_READY, job_id);
+        }
+
+        assert!(fetch_job(&worker).await.unwrap().is_none());
+        assert_eq!(mock.get("job:job-1:status").as_deref(), Some("failed"));
+        assert_eq!(
+            mock.get("job:job-1:stderr").as_deref(),
+            Some(NO_EXEC_REASON)
+        );
+        assert!(mock.list(QUEUE_PROCESSING).is_empty());
+        assert_eq!(worker.stats.jobs_failed.load(Ordering::Relaxed), 1);
+
+        // Invalid plans still fail with their validation error
+        assert!(fetch_job(&worker).await.unwrap().is_none());
+        assert_eq!(mock.get("job:job-2:status").as_deref(), Some("failed"));
+        assert_ne!(
+            mock.get("job:job-2:stderr").as_deref(),
+            Some(NO_EXEC_REASON)
+        );
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!marker.exists());
+    }
+
+    #[tokio::test]
+    async fn test_input_ref_resolves_to_job_input() {
+        use crate::mock_agq::MockAgq;
+        use crate::plan::Task;
+
+        let mock = MockAgq::start(Some(SESSION_KEY)).await;
+        let worker = test_worker(&mock, &["--max-input-bytes", "64"]).await;
+
+        let plan = Plan {
+            plan_id: "plan-1".to_string(),
+            tasks: vec![Task {
+                task_number: 1,
+                command: "ls".to_string(),
+                args: vec!["{{input.path}}".to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        mock.set("plan:plan-1", &plan.to_json().unwrap());
+        mock.set("inputs:abc", r#"{"path": "/data"}"#);
+        mock.set(
+            "inputs:big",
+            &format!(r#"{{"path": "{}"}}"#, "x".repeat(64)),
+        );
+        for (job_id, input_ref) in [
+            ("job-1", "inputs:abc"),
+            ("job-2", "inputs:missing"),
+            ("job-3", "inputs:big"),
+        ] {
+            mock.set(
+                &format!("job:{job_id}"),
+                &serde_json::json!({"job_id": job_id, "plan_id": "plan-1", "input_ref": input_ref})
+                    .to_string(),
+            );
+            mock.push(QUEUE_READY, job_id);
+        }
+
+        let prepared = fetch_job(&worker).await.unwrap().unwrap();
+        assert_eq!(prepared.job.input, serde_json::json!({"path": "/data"}));
+        assert_eq!(prepared.plan.tasks[0].args, vec!["/data"]);
+
+        // Missing and oversized references fail just their job
+        for (job_id, reason) in [("job-2", "does not exist"), ("job-3", "byte limit")] {
+            assert!(fetch_job(&worker).await.unwrap().is_none());
+            assert_eq!(
+                mock.get(&format!("job:{job_id}:status")).as_deref(),
+                Some("failed")
+            );
+            let stderr = mock.get(&format!("job:{job_id}:stderr")).unwrap();
+            assert!(stderr.contains(reason), "{stderr}");
+        }
+        assert_eq!(mock.list(QUEUE_PROCESSING), vec!["job-1"]);
+    }
 }
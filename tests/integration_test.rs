@@ -426,7 +426,7 @@ fn test_job_remains_in_processing_on_crash() {
     let job_json = r#"{"job_id":"crash-789","plan_id":"plan-ghi","tasks":[]}"#;
 
     // Job moved to processing queue
-    let processing_queue = vec![job_json];
+    let processing_queue = [job_json];
 
     // Worker crashes before LREM can be called
     let worker_crashed = true;